@@ -17,10 +17,10 @@ use openigtlink_rust::error::Result;
 use openigtlink_rust::io::{ClientBuilder, SyncIgtlClient};
 use openigtlink_rust::protocol::message::IgtlMessage;
 use openigtlink_rust::protocol::types::{
-    CapabilityMessage, GetCapabilityMessage, RtsTDataMessage, StartTDataMessage, StopTDataMessage,
-    TDataMessage,
+    CapabilityMessage, RtsTDataMessage, StartTDataMessage, StopTDataMessage, TDataMessage,
 };
 use std::env;
+use std::time::Duration;
 
 fn main() {
     if let Err(e) = run() {
@@ -105,13 +105,10 @@ fn run() -> Result<()> {
 
 /// Request server capabilities (helper function)
 ///
-/// Sends a GET_CAPABIL query and receives the CAPABILITY response.
+/// Sends a GET_CAPABIL query and waits for the CAPABILITY response.
+/// Unrelated messages received meanwhile are kept for later `receive` calls.
 fn request_capability(client: &mut SyncIgtlClient) -> Result<CapabilityMessage> {
-    let query = GetCapabilityMessage;
-    let msg = IgtlMessage::new(query, "Client")?;
-    client.send(&msg)?;
-
-    let response: IgtlMessage<CapabilityMessage> = client.receive()?;
+    let response = client.get_capability()?;
     Ok(response.content)
 }
 
@@ -129,10 +126,8 @@ fn start_tracking(
         coordinate_name: coordinate_name.to_string(),
     };
 
-    let msg = IgtlMessage::new(start_stream, "Client")?;
-    client.send(&msg)?;
-
-    let response: IgtlMessage<RtsTDataMessage> = client.receive()?;
+    let response: IgtlMessage<RtsTDataMessage> =
+        client.query("Client", start_stream, Duration::from_secs(2))?;
     Ok(response.content)
 }
 
@@ -212,8 +212,7 @@ fn main() -> Result<()> {
             avg_with_crc - avg_without_crc
         );
 
-        if avg_with_crc > 0 {
-            let max_rate_with = 1_000_000 / avg_with_crc;
+        if let Some(max_rate_with) = 1_000_000u128.checked_div(avg_with_crc) {
             let max_rate_without = 1_000_000 / avg_without_crc;
             println!("  Theoretical max rate:");
             println!("    With CRC:    ~{} Hz", max_rate_with);
@@ -1,24 +1,61 @@
 #!/bin/bash
-# Generate self-signed certificates for TLS testing
+# Generate certificates for TLS and mutual TLS testing
 #
-# Usage: ./examples/generate_test_certs.sh
+# Usage: ./examples/generate_test_certs.sh [output_dir]
+#
+# Creates (in examples/certs by default):
+#   ca.pem, ca-key.pem          - test certificate authority
+#   cert.pem, key.pem           - server certificate (CN=localhost, SAN localhost/127.0.0.1)
+#   client.pem, client-key.pem  - client certificate (CN=igtl-client, SAN tracker.local)
 
 set -e
 
-CERT_DIR="examples/certs"
+CERT_DIR="${1:-examples/certs}"
 mkdir -p "$CERT_DIR"
 
 echo "Generating test certificates in $CERT_DIR..."
 
-# Generate private key
-openssl genrsa -out "$CERT_DIR/key.pem" 2048
+# Certificate authority
+openssl req -new -x509 -newkey rsa:2048 -nodes -days 365 \
+    -keyout "$CERT_DIR/ca-key.pem" -out "$CERT_DIR/ca.pem" \
+    -subj "/C=US/O=OpenIGTLink Test/CN=OpenIGTLink Test CA" \
+    -addext "basicConstraints=critical,CA:TRUE" \
+    -addext "keyUsage=critical,keyCertSign,cRLSign" 2>/dev/null
+
+# Issue a certificate signed by the CA: issue <name> <subject> <extensions>
+issue() {
+    local name="$1" subject="$2" extensions="$3"
+    local ext_file="$CERT_DIR/$name.ext"
+
+    openssl req -new -newkey rsa:2048 -nodes \
+        -keyout "$CERT_DIR/$name-key.pem" -out "$CERT_DIR/$name.csr" \
+        -subj "$subject" 2>/dev/null
+    printf '%s\n' "$extensions" > "$ext_file"
+    openssl x509 -req -in "$CERT_DIR/$name.csr" -days 365 \
+        -CA "$CERT_DIR/ca.pem" -CAkey "$CERT_DIR/ca-key.pem" -set_serial "0x$(openssl rand -hex 8)" \
+        -extfile "$ext_file" -out "$CERT_DIR/$name.pem" 2>/dev/null
+    rm -f "$CERT_DIR/$name.csr" "$ext_file"
+}
+
+# Server certificate (kept as cert.pem / key.pem for the TLS examples)
+issue server "/C=US/ST=State/L=City/O=Organization/CN=localhost" \
+"basicConstraints=CA:FALSE
+keyUsage=critical,digitalSignature,keyEncipherment
+extendedKeyUsage=serverAuth
+subjectAltName=DNS:localhost,IP:127.0.0.1"
+mv "$CERT_DIR/server.pem" "$CERT_DIR/cert.pem"
+mv "$CERT_DIR/server-key.pem" "$CERT_DIR/key.pem"
 
-# Generate self-signed certificate (valid for 365 days)
-openssl req -new -x509 -key "$CERT_DIR/key.pem" -out "$CERT_DIR/cert.pem" -days 365 \
-    -subj "/C=US/ST=State/L=City/O=Organization/CN=localhost"
+# Client certificate for mutual TLS
+issue client "/C=US/O=Organization/CN=igtl-client" \
+"basicConstraints=CA:FALSE
+keyUsage=critical,digitalSignature,keyEncipherment
+extendedKeyUsage=clientAuth
+subjectAltName=DNS:tracker.local"
 
-echo "✓ Certificate generated: $CERT_DIR/cert.pem"
-echo "✓ Private key generated: $CERT_DIR/key.pem"
+echo "✓ CA certificate:     $CERT_DIR/ca.pem"
+echo "✓ Server certificate: $CERT_DIR/cert.pem (key: $CERT_DIR/key.pem)"
+echo "✓ Client certificate: $CERT_DIR/client.pem (key: $CERT_DIR/client-key.pem)"
 echo ""
-echo "These are self-signed certificates for testing only."
+echo "These certificates are for testing only."
 echo "Do NOT use in production!"
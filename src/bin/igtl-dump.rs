@@ -0,0 +1,587 @@
+//! igtl-dump: print OpenIGTLink traffic
+//!
+//! Connects to a server or listens for clients over TCP, TLS or UDP and prints every
+//! received message: header fields, CRC status, a summary of the content and v3
+//! metadata, as text or as one JSON object per line.
+//!
+//! # Usage
+//!
+//! ```bash
+//! # Connect to a tracker and show everything
+//! cargo run --features cli --bin igtl-dump -- 10.0.0.2:18944
+//!
+//! # Listen for clients on port 18944, only TRANSFORM and STATUS from "Probe"
+//! cargo run --features cli --bin igtl-dump -- --listen 0.0.0.0:18944 \
+//!     --type TRANSFORM --type STATUS --device Probe
+//!
+//! # TLS client with hex dumps; UDP receiver writing JSON lines and rates every 5 s
+//! cargo run --features cli --bin igtl-dump -- --tls --ca ca.pem --hex tracker.local:18944
+//! cargo run --features cli --bin igtl-dump -- --udp --listen 0.0.0.0:18944 --json --stats 5
+//! ```
+//!
+//! Messages go to stdout; statistics and connection notes go to stderr, so the output
+//! of `--json` can be piped into other tools. Frames with a bad CRC are still decoded
+//! and shown with `crc MISMATCH`. UDP is connectionless, so it is only available with
+//! `--listen`; each datagram holds one complete message, or one fragment of a message
+//! sent with a `FragmentConfig`, which is reassembled before printing.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use serde_json::{json, Map, Value};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+use openigtlink_rust::io::tls::{ClientTlsConfig, ServerTlsConfig};
+use openigtlink_rust::io::{AsyncIgtlStream, FragmentConfig, FragmentReassembler};
+use openigtlink_rust::protocol::inspect::{CrcStatus, FrameInfo};
+
+/// Largest UDP datagram
+const MAX_DATAGRAM: usize = 65_535;
+
+/// Frames waiting to be printed
+const FRAME_QUEUE: usize = 1024;
+
+type Frame = (SocketAddr, Vec<u8>);
+
+fn command() -> Command {
+    Command::new("igtl-dump")
+        .about("Print OpenIGTLink messages received over TCP, TLS or UDP")
+        .arg(
+            Arg::new("addr")
+                .value_name("ADDR")
+                .required_unless_present("listen")
+                .help("Server to connect to, e.g. 127.0.0.1:18944"),
+        )
+        .arg(
+            Arg::new("listen")
+                .long("listen")
+                .short('l')
+                .value_name("ADDR")
+                .conflicts_with("addr")
+                .help("Listen for clients (or datagrams) on ADDR instead of connecting"),
+        )
+        .arg(
+            Arg::new("tls")
+                .long("tls")
+                .action(ArgAction::SetTrue)
+                .conflicts_with("udp")
+                .help("Use TLS"),
+        )
+        .arg(
+            Arg::new("udp")
+                .long("udp")
+                .action(ArgAction::SetTrue)
+                .help("Receive UDP datagrams (with --listen)"),
+        )
+        .arg(
+            Arg::new("ca")
+                .long("ca")
+                .value_name("PEM")
+                .value_parser(value_parser!(PathBuf))
+                .requires("tls")
+                .help("CA to verify the server with (client) or to require client certificates from (server)"),
+        )
+        .arg(
+            Arg::new("cert")
+                .long("cert")
+                .value_name("PEM")
+                .value_parser(value_parser!(PathBuf))
+                .requires_all(["tls", "key"])
+                .help("Certificate chain: server certificate, or client certificate to present"),
+        )
+        .arg(
+            Arg::new("key")
+                .long("key")
+                .value_name("PEM")
+                .value_parser(value_parser!(PathBuf))
+                .requires_all(["tls", "cert"])
+                .help("Private key of --cert"),
+        )
+        .arg(
+            Arg::new("type")
+                .long("type")
+                .short('t')
+                .value_name("TYPE")
+                .action(ArgAction::Append)
+                .help("Show only this message type (repeatable)"),
+        )
+        .arg(
+            Arg::new("device")
+                .long("device")
+                .short('d')
+                .value_name("NAME")
+                .action(ArgAction::Append)
+                .help("Show only messages from this device (repeatable)"),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .action(ArgAction::SetTrue)
+                .help("Print one JSON object per message"),
+        )
+        .arg(
+            Arg::new("hex")
+                .long("hex")
+                .action(ArgAction::SetTrue)
+                .help("Include a hex dump of each frame"),
+        )
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .value_name("SECS")
+                .value_parser(value_parser!(u64).range(1..))
+                .help("Print per-type message rates every SECS seconds"),
+        )
+        .arg(
+            Arg::new("count")
+                .long("count")
+                .short('n')
+                .value_name("N")
+                .value_parser(value_parser!(u64).range(1..))
+                .help("Exit after showing N messages"),
+        )
+}
+
+/// Which messages to show
+struct Filter {
+    types: Vec<String>,
+    devices: Vec<String>,
+}
+
+impl Filter {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        let values = |id: &str| -> Vec<String> {
+            matches
+                .get_many::<String>(id)
+                .map(|values| values.cloned().collect())
+                .unwrap_or_default()
+        };
+        Filter {
+            types: values("type")
+                .into_iter()
+                .map(|t| t.to_ascii_uppercase())
+                .collect(),
+            devices: values("device"),
+        }
+    }
+
+    fn accepts(&self, info: &FrameInfo) -> bool {
+        (self.types.is_empty() || self.types.iter().any(|t| t == info.message_type()))
+            && (self.devices.is_empty() || self.devices.iter().any(|d| d == info.device_name()))
+    }
+}
+
+/// Message counts per type, overall and since the last report
+#[derive(Default)]
+struct RateStats {
+    types: BTreeMap<String, TypeCounters>,
+    crc_errors: u64,
+    window_start: Option<Instant>,
+}
+
+#[derive(Default)]
+struct TypeCounters {
+    total: u64,
+    bytes: u64,
+    window: u64,
+    window_bytes: u64,
+}
+
+impl RateStats {
+    fn record(&mut self, info: &FrameInfo) {
+        self.window_start.get_or_insert_with(Instant::now);
+        let counters = self
+            .types
+            .entry(info.message_type().to_string())
+            .or_default();
+        counters.total += 1;
+        counters.bytes += info.size as u64;
+        counters.window += 1;
+        counters.window_bytes += info.size as u64;
+        if !info.crc.is_valid() {
+            self.crc_errors += 1;
+        }
+    }
+
+    /// Print rates since the last report to stderr and start a new window
+    fn report(&mut self) {
+        let elapsed = self
+            .window_start
+            .replace(Instant::now())
+            .map_or(0.0, |start| start.elapsed().as_secs_f64())
+            .max(f64::EPSILON);
+        let mut out = std::io::stderr().lock();
+        let _ = writeln!(
+            out,
+            "--- {:<14} {:>10} {:>12} {:>10} {:>12}",
+            "type", "total", "total kB", "msg/s", "kB/s"
+        );
+        for (name, counters) in &mut self.types {
+            let _ = writeln!(
+                out,
+                "    {:<14} {:>10} {:>12.1} {:>10.1} {:>12.1}",
+                name,
+                counters.total,
+                counters.bytes as f64 / 1000.0,
+                counters.window as f64 / elapsed,
+                counters.window_bytes as f64 / elapsed / 1000.0
+            );
+            counters.window = 0;
+            counters.window_bytes = 0;
+        }
+        if self.crc_errors > 0 {
+            let _ = writeln!(out, "    CRC mismatches: {}", self.crc_errors);
+        }
+    }
+}
+
+/// Render one inspected frame as text
+fn format_text(peer: SocketAddr, info: &FrameInfo, frame: &[u8], hex: bool) -> String {
+    let crc = match info.crc {
+        CrcStatus::Valid => "crc ok".to_string(),
+        CrcStatus::Mismatch { expected, computed } => {
+            format!("crc MISMATCH (header {expected:#018x}, body {computed:#018x})")
+        }
+    };
+    let mut text = format!(
+        "{:.6} {} {:<12} {:<20} v{} {} B ts {:.6} {}",
+        unix_time(),
+        peer,
+        info.message_type(),
+        format!("\"{}\"", info.device_name()),
+        info.header.version,
+        info.size,
+        info.header.timestamp.to_f64(),
+        crc
+    );
+    let summary = info.summary();
+    if !summary.is_empty() {
+        text += "\n    ";
+        text += &summary;
+    }
+    if let Ok(msg) = &info.message {
+        if let Some(id) = msg.message_id() {
+            text += &format!("\n    message id {id}");
+        }
+        if let Some(metadata) = msg.metadata() {
+            let sorted: BTreeMap<_, _> = metadata.iter().collect();
+            for (key, value) in sorted {
+                text += &format!("\n    {key} = {value}");
+            }
+        }
+    }
+    if hex {
+        text += "\n";
+        text += &hex_dump(frame);
+    }
+    text
+}
+
+/// Render one inspected frame as a JSON object
+fn format_json(peer: SocketAddr, info: &FrameInfo, frame: &[u8], hex: bool) -> Value {
+    let header = &info.header;
+    let mut object = Map::new();
+    object.insert("received".into(), json!(unix_time()));
+    object.insert("peer".into(), json!(peer.to_string()));
+    object.insert("type".into(), json!(info.message_type()));
+    object.insert("device".into(), json!(info.device_name()));
+    object.insert("version".into(), json!(header.version));
+    object.insert("timestamp".into(), json!(header.timestamp.to_f64()));
+    object.insert("body_size".into(), json!(header.body_size));
+    object.insert("size".into(), json!(info.size));
+    object.insert("crc".into(), json!(format!("{:#018x}", header.crc)));
+    object.insert("crc_valid".into(), json!(info.crc.is_valid()));
+    match &info.message {
+        Ok(msg) => {
+            object.insert("summary".into(), json!(info.summary()));
+            if let Some(id) = msg.message_id() {
+                object.insert("message_id".into(), json!(id));
+            }
+            if let Some(metadata) = msg.metadata() {
+                object.insert("metadata".into(), json!(metadata));
+            }
+        }
+        Err(e) => {
+            object.insert("error".into(), json!(e.to_string()));
+        }
+    }
+    if hex {
+        let bytes: String = frame.iter().map(|b| format!("{b:02x}")).collect();
+        object.insert("hex".into(), json!(bytes));
+    }
+    Value::Object(object)
+}
+
+/// Classic offset / hex / ASCII dump, 16 bytes per line
+fn hex_dump(data: &[u8]) -> String {
+    let mut out = String::new();
+    for (line, chunk) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        if line > 0 {
+            out.push('\n');
+        }
+        out += &format!("    {:08x}  {:<47}  |{}|", line * 16, hex.join(" "), ascii);
+    }
+    out
+}
+
+fn unix_time() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0.0, |t| t.as_secs_f64())
+}
+
+/// Forward frames from one stream until it closes
+async fn read_stream<S>(stream: S, peer: SocketAddr, frames: mpsc::Sender<Frame>)
+where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin,
+{
+    let mut stream = AsyncIgtlStream::new(stream);
+    loop {
+        match stream.receive_frame().await {
+            Ok(frame) => {
+                if frames.send((peer, frame)).await.is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                eprintln!("[INFO] {peer} closed: {e}");
+                return;
+            }
+        }
+    }
+}
+
+/// Connect to `addr` and forward its frames
+async fn connect(
+    addr: &str,
+    tls: Option<ClientTlsConfig>,
+    frames: mpsc::Sender<Frame>,
+) -> Result<(), String> {
+    let tcp = TcpStream::connect(addr)
+        .await
+        .map_err(|e| format!("cannot connect to {addr}: {e}"))?;
+    let peer = tcp.peer_addr().map_err(|e| e.to_string())?;
+    eprintln!("[INFO] Connected to {peer}");
+    match tls {
+        None => read_stream(tcp, peer, frames).await,
+        Some(config) => {
+            let config = config.build().map_err(|e| e.to_string())?;
+            let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            let name = ServerName::try_from(host.to_string())
+                .map_err(|e| format!("invalid server name {host}: {e}"))?;
+            let stream = TlsConnector::from(config)
+                .connect(name, tcp)
+                .await
+                .map_err(|e| format!("TLS handshake with {peer} failed: {e}"))?;
+            read_stream(stream, peer, frames).await;
+        }
+    }
+    Ok(())
+}
+
+/// Accept clients on `addr` and forward their frames
+async fn listen(
+    addr: &str,
+    tls: Option<ServerTlsConfig>,
+    frames: mpsc::Sender<Frame>,
+) -> Result<(), String> {
+    let acceptor = match tls {
+        Some(config) => Some(TlsAcceptor::from(Arc::new(
+            config.build().map_err(|e| e.to_string())?,
+        ))),
+        None => None,
+    };
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|e| format!("cannot listen on {addr}: {e}"))?;
+    eprintln!(
+        "[INFO] Listening on {}",
+        listener.local_addr().map_err(|e| e.to_string())?
+    );
+    loop {
+        let (tcp, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                eprintln!("[WARN] accept failed: {e}");
+                tokio::time::sleep(Duration::from_millis(100)).await;
+                continue;
+            }
+        };
+        eprintln!("[INFO] {peer} connected");
+        let frames = frames.clone();
+        let acceptor = acceptor.clone();
+        tokio::spawn(async move {
+            match acceptor {
+                None => read_stream(tcp, peer, frames).await,
+                Some(acceptor) => match acceptor.accept(tcp).await {
+                    Ok(stream) => read_stream(stream, peer, frames).await,
+                    Err(e) => eprintln!("[WARN] TLS handshake with {peer} failed: {e}"),
+                },
+            }
+        });
+    }
+}
+
+/// Receive datagrams on `addr` and forward them as frames
+async fn listen_udp(addr: &str, frames: mpsc::Sender<Frame>) -> Result<(), String> {
+    let socket = UdpSocket::bind(addr)
+        .await
+        .map_err(|e| format!("cannot bind {addr}: {e}"))?;
+    eprintln!(
+        "[INFO] Receiving datagrams on {}",
+        socket.local_addr().map_err(|e| e.to_string())?
+    );
+    let reassembler = FragmentReassembler::new(FragmentConfig::default());
+    let mut buf = vec![0u8; MAX_DATAGRAM];
+    loop {
+        let (n, peer) = socket
+            .recv_from(&mut buf)
+            .await
+            .map_err(|e| format!("receive failed: {e}"))?;
+        let Some(frame) = reassembler.push(&buf[..n], peer) else {
+            continue;
+        };
+        if frames.send((peer, frame)).await.is_err() {
+            return Ok(());
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = command().get_matches();
+    if matches.get_flag("udp") && !matches.contains_id("listen") {
+        command()
+            .error(
+                clap::error::ErrorKind::MissingRequiredArgument,
+                "UDP is connectionless; use --udp with --listen ADDR",
+            )
+            .exit();
+    }
+    let filter = Filter::from_matches(&matches);
+    let json_output = matches.get_flag("json");
+    let hex = matches.get_flag("hex");
+    let limit = matches.get_one::<u64>("count").copied();
+
+    let (frames_tx, mut frames) = mpsc::channel(FRAME_QUEUE);
+    let cert = matches.get_one::<PathBuf>("cert");
+    let key = matches.get_one::<PathBuf>("key");
+    let ca = matches.get_one::<PathBuf>("ca");
+    let tls = matches.get_flag("tls");
+    let source = match matches.get_one::<String>("listen").cloned() {
+        Some(addr) if matches.get_flag("udp") => {
+            tokio::spawn(async move { listen_udp(&addr, frames_tx).await })
+        }
+        Some(addr) => {
+            let config = if tls {
+                let (Some(cert), Some(key)) = (cert, key) else {
+                    eprintln!("[ERROR] --tls with --listen requires --cert and --key");
+                    std::process::exit(2);
+                };
+                let mut config = ServerTlsConfig::new(cert, key);
+                if let Some(ca) = ca {
+                    config = config.with_client_auth(ca);
+                }
+                Some(config)
+            } else {
+                None
+            };
+            tokio::spawn(async move { listen(&addr, config, frames_tx).await })
+        }
+        None => {
+            let addr = matches
+                .get_one::<String>("addr")
+                .cloned()
+                .expect("required without --listen");
+            let config = tls.then(|| {
+                let mut config = ClientTlsConfig::new();
+                if let Some(ca) = ca {
+                    config = config.with_ca(ca);
+                }
+                if let (Some(cert), Some(key)) = (cert, key) {
+                    config = config.with_client_cert(cert, key);
+                }
+                config
+            });
+            tokio::spawn(async move { connect(&addr, config, frames_tx).await })
+        }
+    };
+
+    let mut stats = RateStats::default();
+    let report_every = matches
+        .get_one::<u64>("stats")
+        .map(|&secs| Duration::from_secs(secs));
+    let mut ticker = tokio::time::interval(report_every.unwrap_or(Duration::from_secs(3600)));
+    ticker.tick().await;
+
+    let mut shown = 0;
+    let mut stdout = std::io::stdout().lock();
+    loop {
+        let (peer, frame) = tokio::select! {
+            frame = frames.recv() => match frame {
+                Some(frame) => frame,
+                None => break,
+            },
+            _ = ticker.tick(), if report_every.is_some() => {
+                stats.report();
+                continue;
+            }
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        let info = match FrameInfo::inspect(&frame) {
+            Ok(info) => info,
+            Err(e) => {
+                eprintln!("[WARN] {peer}: invalid frame of {} bytes: {e}", frame.len());
+                continue;
+            }
+        };
+        if !filter.accepts(&info) {
+            continue;
+        }
+        stats.record(&info);
+        let line = if json_output {
+            format_json(peer, &info, &frame, hex).to_string()
+        } else {
+            format_text(peer, &info, &frame, hex)
+        };
+        if writeln!(stdout, "{line}").is_err() {
+            // Output closed, e.g. piped into `head`
+            break;
+        }
+        shown += 1;
+        if limit.is_some_and(|limit| shown >= limit) {
+            break;
+        }
+    }
+    drop(stdout);
+
+    if report_every.is_some() {
+        stats.report();
+    }
+    if source.is_finished() {
+        if let Ok(Err(e)) = source.await {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    }
+}
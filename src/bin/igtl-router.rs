@@ -0,0 +1,286 @@
+//! igtl-router: merge several OpenIGTLink servers into one
+//!
+//! Runs an [`openigtlink_rust::router::Router`] from a JSON configuration file.
+//!
+//! # Usage
+//!
+//! ```bash
+//! cargo run --features cli --bin igtl-router -- router.json
+//! cargo run --features cli --bin igtl-router -- --stats 5 router.json
+//! ```
+//!
+//! # Configuration
+//!
+//! ```json
+//! {
+//!   "listen": "0.0.0.0:18944",
+//!   "forward_unmatched": true,
+//!   "reconnect": { "initial_delay_ms": 100, "max_delay_ms": 5000 },
+//!   "upstreams": [
+//!     { "name": "Tracker", "addr": "10.0.0.2:18944" },
+//!     { "name": "Robot", "addr": "10.0.0.3:18944", "tls": { "ca": "ca.pem" } }
+//!   ],
+//!   "routes": [
+//!     { "name": "probe", "from": "Tracker:Probe", "rename": "ProbeToTracker",
+//!       "convert": ["lps_to_ras", { "scale": 0.001 }] },
+//!     { "name": "no-images", "types": ["IMAGE"], "drop": true }
+//!   ]
+//! }
+//! ```
+//!
+//! Only `upstreams` is required. Set `RUST_LOG` (e.g. `RUST_LOG=debug`) for more
+//! detailed logs. Ctrl-C shuts the router down.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use clap::{value_parser, Arg, Command};
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+use openigtlink_rust::io::reconnect::ReconnectConfig;
+use openigtlink_rust::io::tls::ClientTlsConfig;
+use openigtlink_rust::io::CancellationToken;
+use openigtlink_rust::router::{Conversion, Route, Router, RouterConfig, RouterStats, Upstream};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    listen: Option<String>,
+    forward_unmatched: Option<bool>,
+    downstream_buffer: Option<usize>,
+    reconnect: Option<ReconnectFile>,
+    upstreams: Vec<UpstreamFile>,
+    #[serde(default)]
+    routes: Vec<RouteFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReconnectFile {
+    max_attempts: Option<usize>,
+    initial_delay_ms: Option<u64>,
+    max_delay_ms: Option<u64>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UpstreamFile {
+    name: String,
+    addr: String,
+    tls: Option<TlsFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TlsFile {
+    /// CA certificate to verify the server with; system roots if absent
+    ca: Option<PathBuf>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RouteFile {
+    name: String,
+    from: Option<String>,
+    #[serde(default)]
+    types: Vec<String>,
+    rename: Option<String>,
+    #[serde(default)]
+    convert: Vec<ConversionFile>,
+    #[serde(default)]
+    drop: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConversionFile {
+    Named(String),
+    Scale { scale: f32 },
+}
+
+impl ConversionFile {
+    fn parse(&self) -> Result<Conversion, String> {
+        match self {
+            ConversionFile::Named(name) => match name.as_str() {
+                "ras_to_lps" => Ok(Conversion::RasToLps),
+                "lps_to_ras" => Ok(Conversion::LpsToRas),
+                other => Err(format!(
+                    "unknown conversion \"{other}\" (expected ras_to_lps, lps_to_ras or {{\"scale\": x}})"
+                )),
+            },
+            ConversionFile::Scale { scale } => Ok(Conversion::Scale(*scale)),
+        }
+    }
+}
+
+fn load_config(path: &Path) -> Result<RouterConfig, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| format!("cannot read {}: {e}", path.display()))?;
+    let file: ConfigFile = serde_json::from_str(&text)
+        .map_err(|e| format!("invalid configuration {}: {e}", path.display()))?;
+
+    let mut config = RouterConfig::new();
+    if let Some(listen) = file.listen {
+        config = config.with_listen(listen);
+    }
+    if let Some(forward) = file.forward_unmatched {
+        config = config.with_forward_unmatched(forward);
+    }
+    if let Some(messages) = file.downstream_buffer {
+        if messages == 0 {
+            return Err("downstream_buffer must be at least 1".to_string());
+        }
+        config = config.with_downstream_buffer(messages);
+    }
+    if let Some(reconnect) = file.reconnect {
+        let mut strategy = ReconnectConfig::infinite();
+        strategy.max_attempts = reconnect.max_attempts;
+        if let Some(ms) = reconnect.initial_delay_ms {
+            strategy.initial_delay = Duration::from_millis(ms);
+        }
+        if let Some(ms) = reconnect.max_delay_ms {
+            strategy.max_delay = Duration::from_millis(ms);
+        }
+        config = config.with_reconnect(strategy);
+    }
+    for upstream in file.upstreams {
+        let mut entry = Upstream::new(upstream.name, upstream.addr);
+        if let Some(tls) = upstream.tls {
+            let mut builder = ClientTlsConfig::new();
+            if let Some(ca) = tls.ca {
+                builder = builder.with_ca(ca);
+            }
+            let tls = builder
+                .build()
+                .map_err(|e| format!("upstream \"{}\": {e}", entry.name))?;
+            entry = entry.with_tls(tls);
+        }
+        config = config.with_upstream(entry);
+    }
+    for route in file.routes {
+        let mut entry = Route::new(&route.name).with_types(route.types);
+        if let Some(from) = &route.from {
+            entry = entry.from(from);
+        }
+        if route.drop {
+            if route.rename.is_some() || !route.convert.is_empty() {
+                return Err(format!(
+                    "route \"{}\": drop cannot be combined with rename or convert",
+                    route.name
+                ));
+            }
+            entry = entry.drop();
+        } else {
+            if let Some(rename) = route.rename {
+                entry = entry.rename(rename);
+            }
+            for conversion in &route.convert {
+                let conversion = conversion
+                    .parse()
+                    .map_err(|e| format!("route \"{}\": {e}", route.name))?;
+                entry = entry.convert(conversion);
+            }
+        }
+        config = config.with_route(entry);
+    }
+    Ok(config)
+}
+
+fn print_stats(stats: &RouterStats) {
+    println!(
+        "downstream clients: {}, lagged messages: {}",
+        stats.downstream_clients, stats.downstream_lagged
+    );
+    for upstream in &stats.upstreams {
+        println!(
+            "  upstream {:<16} {:<12} connections {:>4}  received {:>10}  sent {:>8}",
+            upstream.name,
+            if upstream.connected {
+                "connected"
+            } else {
+                "disconnected"
+            },
+            upstream.connections,
+            upstream.received,
+            upstream.sent
+        );
+    }
+    let unmatched = ("(unmatched)".to_string(), stats.unmatched.clone());
+    for (name, route) in stats.routes.iter().chain(std::iter::once(&unmatched)) {
+        println!(
+            "  route    {:<16} matched {:>10}  forwarded {:>10}  bytes {:>12}  errors {:>6}",
+            name, route.matched, route.forwarded, route.bytes, route.errors
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let matches = Command::new("igtl-router")
+        .about("Merge several OpenIGTLink servers into one, with routing rules")
+        .arg(
+            Arg::new("stats")
+                .long("stats")
+                .value_name("SECS")
+                .value_parser(value_parser!(u64).range(1..))
+                .help("Print statistics every SECS seconds"),
+        )
+        .arg(
+            Arg::new("config")
+                .value_name("CONFIG")
+                .value_parser(value_parser!(PathBuf))
+                .required(true)
+                .help("JSON configuration file"),
+        )
+        .get_matches();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+
+    let path = matches.get_one::<PathBuf>("config").expect("required");
+    let config = match load_config(path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(2);
+        }
+    };
+    let router = match Router::bind(config).await {
+        Ok(router) => router,
+        Err(e) => {
+            eprintln!("[ERROR] {e}");
+            std::process::exit(1);
+        }
+    };
+
+    let stop = CancellationToken::new();
+    tokio::spawn({
+        let stop = stop.clone();
+        async move {
+            if tokio::signal::ctrl_c().await.is_ok() {
+                stop.cancel();
+            }
+        }
+    });
+
+    let run = router.run(&stop);
+    tokio::pin!(run);
+    match matches.get_one::<u64>("stats") {
+        Some(&secs) => {
+            let mut ticker = tokio::time::interval(Duration::from_secs(secs));
+            ticker.tick().await;
+            loop {
+                tokio::select! {
+                    _ = &mut run => break,
+                    _ = ticker.tick() => print_stats(&router.stats()),
+                }
+            }
+        }
+        None => run.await,
+    }
+    print_stats(&router.stats());
+}
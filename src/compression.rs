@@ -63,13 +63,14 @@ impl CompressionType {
 }
 
 /// Compression level
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum CompressionLevel {
     /// No compression (level 0)
     None,
     /// Fast compression, lower ratio (level 1)
     Fast,
     /// Default compression (level 6)
+    #[default]
     Default,
     /// Best compression, slower (level 9)
     Best,
@@ -101,12 +102,6 @@ impl CompressionLevel {
     }
 }
 
-impl Default for CompressionLevel {
-    fn default() -> Self {
-        Self::Default
-    }
-}
-
 /// Compress data using the specified algorithm and level
 ///
 /// # Arguments
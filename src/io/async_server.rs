@@ -3,11 +3,24 @@
 //! Provides a non-blocking, async/await-based server for OpenIGTLink communication.
 
 use crate::error::Result;
-use crate::protocol::header::Header;
+use crate::io::common::decode_any_frame;
+use crate::io::events::{ConnectionEvent, EventEmitter};
+use crate::io::limits::{admit_session, ServerLimits, SessionGovernor, SessionTracker};
+use crate::io::liveness::{set_tcp_keepalive, Liveness, LivenessConfig};
+use crate::io::shutdown::{
+    close_session, run_until, shutdown_error, CancellationToken, Interrupted, ShutdownConfig,
+    ShutdownSignal,
+};
+use crate::io::split::{AsyncIgtlReader, AsyncIgtlWriter};
+use crate::io::stream::AsyncIgtlStream;
 use crate::protocol::message::{IgtlMessage, Message};
 use crate::protocol::AnyMessage;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
 use tracing::{debug, info, trace, warn};
 
 /// Asynchronous OpenIGTLink server
@@ -28,6 +41,10 @@ use tracing::{debug, info, trace, warn};
 /// ```
 pub struct AsyncIgtlServer {
     listener: TcpListener,
+    events: EventEmitter,
+    limits: ServerLimits,
+    sessions: Arc<SessionTracker>,
+    shutdown: Arc<ShutdownSignal>,
 }
 
 impl AsyncIgtlServer {
@@ -60,7 +77,13 @@ impl AsyncIgtlServer {
             local_addr = %local_addr,
             "OpenIGTLink server listening (async)"
         );
-        Ok(AsyncIgtlServer { listener })
+        Ok(AsyncIgtlServer {
+            listener,
+            events: EventEmitter::default(),
+            limits: ServerLimits::default(),
+            sessions: Arc::default(),
+            shutdown: Arc::default(),
+        })
     }
 
     /// Accept a new client connection asynchronously
@@ -81,31 +104,130 @@ impl AsyncIgtlServer {
     ///     Ok(())
     /// }
     /// ```
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to accept, or
+    ///   [`ConnectionAborted`](std::io::ErrorKind::ConnectionAborted) once the server
+    ///   is [shut down](Self::shutdown)
     pub async fn accept(&self) -> Result<AsyncIgtlConnection> {
-        trace!("Waiting for client connection (async)");
-        let (stream, addr) = self.listener.accept().await?;
+        self.accept_until(&CancellationToken::new())
+            .await?
+            .ok_or_else(shutdown_error)
+    }
+
+    /// Accept a new client connection unless `token` is cancelled first
+    ///
+    /// Returns `Ok(None)` once `token` is cancelled or the server is
+    /// [shut down](Self::shutdown).
+    pub async fn accept_until(
+        &self,
+        token: &CancellationToken,
+    ) -> Result<Option<AsyncIgtlConnection>> {
+        loop {
+            trace!("Waiting for client connection (async)");
+            let accepted = run_until(self.listener.accept(), &self.shutdown, Some(token)).await;
+            let Ok(accepted) = accepted else {
+                debug!("Accept cancelled (async)");
+                return Ok(None);
+            };
+            let (stream, addr) = accepted?;
+            let Some(session) = admit_session(&self.sessions, &self.limits, &self.events, addr)
+            else {
+                continue;
+            };
+            info!(
+                peer_addr = %addr,
+                "Client connected (async)"
+            );
+            self.events.emit(ConnectionEvent::Connected { peer: addr });
+            return Ok(Some(AsyncIgtlConnection {
+                inner: AsyncIgtlStream::new(stream),
+                peer: addr,
+                events: self.events.connection(Some(addr)),
+                session,
+                shutdown: Arc::clone(&self.shutdown),
+            }));
+        }
+    }
+
+    /// Stop accepting and close all sessions, waiting up to the drain deadline
+    ///
+    /// Pending and later `accept` calls return. Each session sends the STATUS notice
+    /// of `config`, flushes and closes as soon as it is receiving or next sends; see
+    /// [`shutdown`](crate::io::shutdown). Calling this again only waits again.
+    ///
+    /// # Returns
+    ///
+    /// Number of sessions still open when the drain deadline passed
+    pub async fn shutdown(&self, config: ShutdownConfig) -> usize {
+        let drain_timeout = config.drain_timeout;
         info!(
-            peer_addr = %addr,
-            "Client connected (async)"
+            sessions = self.sessions.active(),
+            "Shutting down server (async)"
         );
-        Ok(AsyncIgtlConnection {
-            stream,
-            verify_crc: true,
-        })
+        self.shutdown.begin(config);
+        let _ = tokio::time::timeout(drain_timeout, self.sessions.wait_idle()).await;
+        let remaining = self.sessions.active();
+        if remaining > 0 {
+            warn!(
+                remaining,
+                "Sessions still open after shutdown drain deadline"
+            );
+        }
+        remaining
+    }
+
+    /// Token cancelled when [`shutdown`](Self::shutdown) starts
+    ///
+    /// Pass it to `receive_until` of split connection halves, which do not see the
+    /// shutdown otherwise.
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.token().clone()
+    }
+
+    /// Apply connection and resource limits to sessions accepted from now on
+    ///
+    /// See [`limits`](crate::io::limits).
+    pub fn with_limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits applied to accepted sessions
+    pub fn limits(&self) -> &ServerLimits {
+        &self.limits
+    }
+
+    /// Number of accepted sessions that are still open
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.active()
     }
 
     /// Get the local address this server is bound to
     pub fn local_addr(&self) -> Result<std::net::SocketAddr> {
         Ok(self.listener.local_addr()?)
     }
+
+    /// Subscribe to lifecycle events of all sessions accepted by this server
+    ///
+    /// Reports accepted sessions, rejected connections, sessions closed by the peer and CRC
+    /// errors.
+    /// See [`events`](crate::io::events).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
 }
 
 /// Represents an accepted client connection (async)
 ///
 /// Provides methods to send and receive OpenIGTLink messages asynchronously.
 pub struct AsyncIgtlConnection {
-    stream: TcpStream,
-    verify_crc: bool,
+    inner: AsyncIgtlStream<TcpStream>,
+    peer: SocketAddr,
+    events: EventEmitter,
+    session: SessionGovernor,
+    shutdown: Arc<ShutdownSignal>,
 }
 
 impl AsyncIgtlConnection {
@@ -120,18 +242,12 @@ impl AsyncIgtlConnection {
     /// Disabling CRC verification should only be done in trusted environments
     /// where data corruption is unlikely (e.g., loopback, local network).
     pub fn set_verify_crc(&mut self, verify: bool) {
-        if verify != self.verify_crc {
-            info!(verify = verify, "CRC verification setting changed");
-            if !verify {
-                warn!("CRC verification disabled - use only in trusted environments");
-            }
-        }
-        self.verify_crc = verify;
+        self.inner.set_verify_crc(verify);
     }
 
     /// Get current CRC verification setting
     pub fn verify_crc(&self) -> bool {
-        self.verify_crc
+        self.inner.verify_crc()
     }
 
     /// Send a message to the connected client asynchronously
@@ -165,27 +281,25 @@ impl AsyncIgtlConnection {
     /// }
     /// ```
     pub async fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
-        let data = msg.encode()?;
-        let msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN");
-        let device_name = msg.header.device_name.as_str().unwrap_or("UNKNOWN");
-
-        debug!(
-            msg_type = msg_type,
-            device_name = device_name,
-            size = data.len(),
-            "Sending message to client (async)"
-        );
-
-        self.stream.write_all(&data).await?;
-        self.stream.flush().await?;
-
-        trace!(
-            msg_type = msg_type,
-            bytes_sent = data.len(),
-            "Message sent successfully (async)"
-        );
+        self.check_open().await?;
+        let result = self.inner.send(msg).await;
+        self.events.check_result(&result, Some(self.peer));
+        result
+    }
 
-        Ok(())
+    /// Send a dynamically typed message to the connected client asynchronously
+    ///
+    /// Useful when relaying or replaying messages received with
+    /// [`receive_any`](Self::receive_any).
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network write failed
+    pub async fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        self.check_open().await?;
+        let result = self.inner.send_any(msg).await;
+        self.events.check_result(&result, Some(self.peer));
+        result
     }
 
     /// Receive a message from the connected client asynchronously
@@ -214,55 +328,9 @@ impl AsyncIgtlConnection {
     /// }
     /// ```
     pub async fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
-        trace!("Waiting for message header from client (async)");
-
-        let mut header_buf = vec![0u8; Header::SIZE];
-        self.stream.read_exact(&mut header_buf).await?;
-
-        let header = Header::decode(&header_buf)?;
-
-        let msg_type = header.type_name.as_str().unwrap_or("UNKNOWN");
-        let device_name = header.device_name.as_str().unwrap_or("UNKNOWN");
-
-        debug!(
-            msg_type = msg_type,
-            device_name = device_name,
-            body_size = header.body_size,
-            version = header.version,
-            "Received message header from client (async)"
-        );
-
-        let mut body_buf = vec![0u8; header.body_size as usize];
-        self.stream.read_exact(&mut body_buf).await?;
-
-        trace!(
-            msg_type = msg_type,
-            bytes_read = body_buf.len(),
-            "Message body received from client (async)"
-        );
-
-        let mut full_msg = header_buf;
-        full_msg.extend_from_slice(&body_buf);
-
-        let result = IgtlMessage::decode_with_options(&full_msg, self.verify_crc);
-
-        match &result {
-            Ok(_) => {
-                debug!(
-                    msg_type = msg_type,
-                    device_name = device_name,
-                    "Message decoded successfully (async)"
-                );
-            }
-            Err(e) => {
-                warn!(
-                    msg_type = msg_type,
-                    error = %e,
-                    "Failed to decode message from client (async)"
-                );
-            }
-        }
-
+        let frame = self.read_frame(None).await?.ok_or_else(shutdown_error)?;
+        let result = IgtlMessage::decode_with_options(&frame, self.inner.verify_crc());
+        self.events.check_decode(&result, Some(self.peer));
         result
     }
 
@@ -299,158 +367,202 @@ impl AsyncIgtlConnection {
     /// }
     /// ```
     pub async fn receive_any(&mut self) -> Result<AnyMessage> {
-        trace!("Waiting for any message type from client (async)");
+        let frame = self.read_frame(None).await?.ok_or_else(shutdown_error)?;
+        let result = decode_any_frame(&frame, self.inner.verify_crc());
+        self.events.check_decode(&result, Some(self.peer));
+        result
+    }
 
-        let mut header_buf = vec![0u8; Header::SIZE];
-        self.stream.read_exact(&mut header_buf).await?;
+    /// Send an encoded frame, e.g. one message to many connections
+    pub(crate) async fn send_frame(&mut self, frame: &[u8]) -> Result<()> {
+        self.check_open().await?;
+        let result = self.inner.write_frame(frame).await;
+        self.events.check_result(&result, Some(self.peer));
+        result
+    }
 
-        let header = Header::decode(&header_buf)?;
+    /// Receive a message unless `token` is cancelled first
+    ///
+    /// Returns `Ok(None)` once `token` is cancelled; partially received data is kept, so
+    /// the connection stays usable. Also returns `Ok(None)` after closing the session
+    /// because the server shuts down.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`receive`](Self::receive).
+    pub async fn receive_until<T: Message>(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<IgtlMessage<T>>> {
+        let Some(frame) = self.read_frame(Some(token)).await? else {
+            return Ok(None);
+        };
+        let result = IgtlMessage::decode_with_options(&frame, self.inner.verify_crc());
+        self.events.check_decode(&result, Some(self.peer));
+        result.map(Some)
+    }
 
-        let msg_type = header.type_name.as_str().unwrap_or("UNKNOWN");
-        let device_name = header.device_name.as_str().unwrap_or("UNKNOWN");
+    /// Receive any message type unless `token` is cancelled first
+    ///
+    /// See [`receive_until`](Self::receive_until).
+    pub async fn receive_any_until(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<AnyMessage>> {
+        let Some(frame) = self.read_frame(Some(token)).await? else {
+            return Ok(None);
+        };
+        let result = decode_any_frame(&frame, self.inner.verify_crc());
+        self.events.check_decode(&result, Some(self.peer));
+        result.map(Some)
+    }
 
-        debug!(
-            msg_type = msg_type,
-            device_name = device_name,
-            body_size = header.body_size,
-            version = header.version,
-            "Received message header from client (async)"
-        );
+    /// Flush and close the connection
+    ///
+    /// Later sends and receives fail with [`NotConnected`](std::io::ErrorKind::NotConnected).
+    pub async fn close(&mut self) -> Result<()> {
+        self.session.check_open()?;
+        self.session.close("closed by the server");
+        drop(self.session.take_slot());
+        self.events
+            .disconnected(Some(self.peer), &"closed by the server");
+        info!(peer_addr = %self.peer, "Closing connection (async)");
+        self.inner.close().await
+    }
 
-        let mut body_buf = vec![0u8; header.body_size as usize];
-        self.stream.read_exact(&mut body_buf).await?;
+    /// Read one frame within the session limits, closing the session on a violation
+    /// or a server shutdown
+    ///
+    /// Returns `None` if interrupted by `token` or the shutdown.
+    async fn read_frame(&mut self, token: Option<&CancellationToken>) -> Result<Option<Vec<u8>>> {
+        self.check_open().await?;
+        let shutdown = Arc::clone(&self.shutdown);
+        let read = self.session.read_frame(self.inner.read_frame());
+        let result = match run_until(read, &shutdown, token).await {
+            Ok(result) => result,
+            Err(Interrupted::Cancelled) => return Ok(None),
+            Err(Interrupted::Shutdown) => {
+                self.close_for_shutdown().await;
+                return Ok(None);
+            }
+        };
+        if result.is_err() && self.inner.idle_expired() {
+            self.session
+                .close("no data received within the idle timeout");
+        }
+        if self.session.is_closed() {
+            let _ = self.inner.close().await;
+        }
+        self.events.check_result(&result, Some(self.peer));
+        result.map(Some)
+    }
 
-        trace!(
-            msg_type = msg_type,
-            bytes_read = body_buf.len(),
-            "Message body received from client (async)"
-        );
+    /// Fail if the session is closed, closing it first if the server shuts down
+    async fn check_open(&mut self) -> Result<()> {
+        if self.shutdown.is_shutting_down() && !self.session.is_closed() {
+            self.close_for_shutdown().await;
+            return Err(shutdown_error());
+        }
+        self.session.check_open()
+    }
 
-        let mut full_msg = header_buf;
-        full_msg.extend_from_slice(&body_buf);
+    /// Notify the peer and close the session because the server shuts down
+    async fn close_for_shutdown(&mut self) {
+        self.session.close("server shutting down");
+        if let Err(e) = close_session(&mut self.inner, &self.shutdown.config()).await {
+            debug!(error = %e, peer_addr = %self.peer, "Session did not close cleanly");
+        }
+        self.events.disconnected(Some(self.peer), &shutdown_error());
+        drop(self.session.take_slot());
+    }
 
-        let result = AnyMessage::decode_with_options(&full_msg, self.verify_crc);
+    /// Set the deadline of each `receive` call (`None` waits forever, the default)
+    ///
+    /// An expired receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut). Partially
+    /// received data is kept, so the connection stays usable.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_read_timeout(timeout);
+    }
 
-        match &result {
-            Ok(_) => {
-                debug!(
-                    msg_type = msg_type,
-                    device_name = device_name,
-                    "Message decoded successfully as AnyMessage (async)"
-                );
-            }
-            Err(e) => {
-                warn!(
-                    msg_type = msg_type,
-                    error = %e,
-                    "Failed to decode message from client (async)"
-                );
-            }
-        }
+    /// Set the deadline of each `send` call (`None` waits forever, the default)
+    ///
+    /// An expired send fails with [`TimedOut`](std::io::ErrorKind::TimedOut). The rest of
+    /// the message stays queued and is written before the next one, so framing is kept.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_write_timeout(timeout);
+    }
 
-        result
+    /// Configure keepalive, heartbeats and timeouts for this connection
+    ///
+    /// Heartbeats and the idle timeout are serviced while waiting in `receive`. When the
+    /// idle timeout expires the receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut)
+    /// and the session is closed. See [`liveness`](crate::io::liveness).
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - TCP keepalive could not be configured
+    pub fn set_liveness(&mut self, config: LivenessConfig) -> Result<()> {
+        self.set_tcp_keepalive(config.tcp_keepalive)?;
+        self.inner.set_read_timeout(config.read_timeout);
+        self.inner.set_write_timeout(config.write_timeout);
+        self.inner.set_liveness(Liveness::new(&config));
+        Ok(())
+    }
+
+    /// Enable TCP keepalive probes after `idle` without traffic, or disable them with `None`
+    pub fn set_tcp_keepalive(&self, idle: Option<Duration>) -> Result<()> {
+        set_tcp_keepalive(self.inner.get_ref(), idle)?;
+        debug!(idle = ?idle, "TCP keepalive configured (async)");
+        Ok(())
     }
 
     /// Enable or disable TCP_NODELAY (Nagle's algorithm)
     pub async fn set_nodelay(&self, nodelay: bool) -> Result<()> {
-        self.stream.set_nodelay(nodelay)?;
+        self.inner.get_ref().set_nodelay(nodelay)?;
         debug!(nodelay = nodelay, "TCP_NODELAY configured (async)");
         Ok(())
     }
 
     /// Get the current TCP_NODELAY setting
     pub async fn nodelay(&self) -> Result<bool> {
-        Ok(self.stream.nodelay()?)
+        Ok(self.inner.get_ref().nodelay()?)
     }
 
     /// Get the remote peer address
     pub fn peer_addr(&self) -> Result<std::net::SocketAddr> {
-        Ok(self.stream.peer_addr()?)
+        Ok(self.peer)
     }
 
     /// Split the connection into read and write halves
     ///
-    /// This allows concurrent reading and writing on separate tasks.
+    /// This allows concurrent reading and writing on separate tasks. The reader is a
+    /// [`Stream`](futures::Stream) of messages and the writer a [`Sink`](futures::Sink).
+    ///
+    /// The reader enforces the [`ServerLimits`] of the session, which stays counted
+    /// while the reader is alive. After a violation the reader fails with
+    /// [`ConnectionAborted`](std::io::ErrorKind::ConnectionAborted) (its stream ends)
+    /// and the session no longer counts; drop both halves to close the connection.
+    /// Heartbeats configured with [`set_liveness`](Self::set_liveness) stop.
     pub fn into_split(self) -> (AsyncIgtlConnectionReader, AsyncIgtlConnectionWriter) {
-        let (reader, writer) = self.stream.into_split();
-        (
-            AsyncIgtlConnectionReader {
-                reader,
-                verify_crc: self.verify_crc,
-            },
-            AsyncIgtlConnectionWriter { writer },
-        )
+        let (mut reader, mut writer) = self.inner.split_with(TcpStream::into_split);
+        reader.set_session(self.session);
+        reader.set_events(self.events.clone(), Some(self.peer));
+        writer.set_events(self.events, Some(self.peer));
+        (reader, writer)
     }
 }
 
 /// Read half of an async OpenIGTLink connection
-pub struct AsyncIgtlConnectionReader {
-    reader: tokio::net::tcp::OwnedReadHalf,
-    verify_crc: bool,
-}
-
-impl AsyncIgtlConnectionReader {
-    /// Receive a message from the read half
-    pub async fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
-        trace!("Waiting for message header (async connection reader)");
-
-        let mut header_buf = vec![0u8; Header::SIZE];
-        self.reader.read_exact(&mut header_buf).await?;
-
-        let header = Header::decode(&header_buf)?;
-
-        let msg_type = header.type_name.as_str().unwrap_or("UNKNOWN");
-
-        debug!(
-            msg_type = msg_type,
-            body_size = header.body_size,
-            "Received message header (async connection reader)"
-        );
-
-        let mut body_buf = vec![0u8; header.body_size as usize];
-        self.reader.read_exact(&mut body_buf).await?;
-
-        let mut full_msg = header_buf;
-        full_msg.extend_from_slice(&body_buf);
-
-        IgtlMessage::decode_with_options(&full_msg, self.verify_crc)
-    }
-}
+pub type AsyncIgtlConnectionReader = AsyncIgtlReader<OwnedReadHalf>;
 
 /// Write half of an async OpenIGTLink connection
-pub struct AsyncIgtlConnectionWriter {
-    writer: tokio::net::tcp::OwnedWriteHalf,
-}
-
-impl AsyncIgtlConnectionWriter {
-    /// Send a message to the write half
-    pub async fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
-        let data = msg.encode()?;
-        let msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN");
-
-        debug!(
-            msg_type = msg_type,
-            size = data.len(),
-            "Sending message (async connection writer)"
-        );
-
-        self.writer.write_all(&data).await?;
-        self.writer.flush().await?;
-
-        trace!(
-            msg_type = msg_type,
-            bytes_sent = data.len(),
-            "Message sent (async connection writer)"
-        );
-
-        Ok(())
-    }
-}
+pub type AsyncIgtlConnectionWriter = AsyncIgtlWriter<OwnedWriteHalf>;
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::protocol::types::StatusMessage;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use tokio::time::Duration;
 
     #[tokio::test]
@@ -539,4 +651,355 @@ mod tests {
         let response: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
         assert_eq!(response.content.status_string, "Echo test");
     }
+
+    #[tokio::test]
+    async fn test_split_reader_enforces_limits() {
+        use futures::StreamExt;
+
+        let server = AsyncIgtlServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_limits(ServerLimits::new().with_idle_timeout(Duration::from_millis(100)));
+        let addr = server.local_addr().unwrap();
+        let mut client = AsyncIgtlStream::new(TcpStream::connect(addr).await.unwrap());
+        let (mut reader, _writer) = server.accept().await.unwrap().into_split();
+
+        let msg = IgtlMessage::new(StatusMessage::ok("once"), "Client").unwrap();
+        client.send(&msg).await.unwrap();
+        let received: IgtlMessage<StatusMessage> = reader.receive().await.unwrap();
+        assert_eq!(received.content.status_string, "once");
+        assert_eq!(server.active_sessions(), 1);
+
+        // Silent for longer than the idle timeout
+        let err = reader.next().await.unwrap().unwrap_err();
+        assert!(matches!(err, crate::error::IgtlError::Io(ref e)
+            if e.kind() == std::io::ErrorKind::ConnectionAborted));
+        assert!(reader.next().await.is_none());
+        assert_eq!(server.active_sessions(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout_keeps_partial_message() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut conn = server.accept().await.unwrap();
+        conn.set_read_timeout(Some(Duration::from_millis(50)));
+        conn.set_tcp_keepalive(Some(Duration::from_secs(10)))
+            .unwrap();
+
+        let data = IgtlMessage::new(StatusMessage::ok("split"), "Client")
+            .unwrap()
+            .encode()
+            .unwrap();
+        stream.write_all(&data[..30]).await.unwrap();
+
+        match conn.receive::<StatusMessage>().await {
+            Err(crate::error::IgtlError::Io(e)) => {
+                assert_eq!(e.kind(), std::io::ErrorKind::TimedOut)
+            }
+            other => panic!("expected timeout, got {:?}", other.is_ok()),
+        }
+
+        stream.write_all(&data[30..]).await.unwrap();
+        let msg: IgtlMessage<StatusMessage> = conn.receive().await.unwrap();
+        assert_eq!(msg.content.status_string, "split");
+    }
+
+    #[tokio::test]
+    async fn test_liveness_closes_session_of_silent_client() {
+        use crate::io::liveness::HeartbeatConfig;
+
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let client = TcpStream::connect(addr).await.unwrap();
+        let mut conn = server.accept().await.unwrap();
+        conn.set_liveness(
+            LivenessConfig::new()
+                .with_heartbeat(HeartbeatConfig::get_status(Duration::from_millis(20)))
+                .with_idle_timeout(Duration::from_millis(150)),
+        )
+        .unwrap();
+
+        // Answers three heartbeats, then hangs
+        let peer = tokio::spawn(async move {
+            let mut client = AsyncIgtlStream::new(client);
+            for _ in 0..3 {
+                let query = client.receive_any().await.unwrap();
+                assert_eq!(query.message_type(), "GET_STATUS");
+                let mut reply =
+                    IgtlMessage::new(StatusMessage::ok("alive"), query.device_name().unwrap())
+                        .unwrap();
+                reply.set_message_id(query.message_id().unwrap());
+                client.send(&reply).await.unwrap();
+            }
+            client
+        });
+
+        let started = tokio::time::Instant::now();
+        assert_eq!(
+            io_kind(conn.receive_any().await),
+            std::io::ErrorKind::TimedOut
+        );
+        // The replies were consumed and kept the session alive
+        assert!(started.elapsed() >= Duration::from_millis(200));
+        assert_eq!(
+            io_kind(conn.receive_any().await),
+            std::io::ErrorKind::NotConnected
+        );
+
+        let mut client = peer.await.unwrap();
+        loop {
+            match client.receive_any().await {
+                Ok(msg) => assert_eq!(msg.message_type(), "GET_STATUS"),
+                Err(e) => {
+                    assert_eq!(io_kind::<()>(Err(e)), std::io::ErrorKind::UnexpectedEof);
+                    break;
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_events() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let mut events = server.subscribe_events();
+
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let client_addr = stream.local_addr().unwrap();
+        let mut conn = server.accept().await.unwrap();
+        assert_eq!(
+            events.recv().await.unwrap(),
+            ConnectionEvent::Connected { peer: client_addr }
+        );
+
+        drop(stream);
+        assert!(conn.receive_any().await.is_err());
+        assert!(conn.receive_any().await.is_err());
+        match events.recv().await.unwrap() {
+            ConnectionEvent::Disconnected { peer, .. } => assert_eq!(peer, Some(client_addr)),
+            other => panic!("unexpected event: {other}"),
+        }
+        // Reported once per connection, also when the connection is dropped
+        drop(conn);
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_split_and_closed_sessions_report_disconnect() {
+        use futures::StreamExt;
+
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let mut events = server.subscribe_events();
+
+        // Peer closes a split connection
+        let stream = TcpStream::connect(addr).await.unwrap();
+        let (mut reader, writer) = server.accept().await.unwrap().into_split();
+        drop(stream);
+        assert!(reader.next().await.is_none());
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ConnectionEvent::Connected { .. }
+        ));
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ConnectionEvent::Disconnected { .. }
+        ));
+        drop((reader, writer));
+
+        // Server closes the session
+        let _stream = TcpStream::connect(addr).await.unwrap();
+        let mut conn = server.accept().await.unwrap();
+        conn.close().await.unwrap();
+        drop(conn);
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ConnectionEvent::Connected { .. }
+        ));
+        match events.recv().await.unwrap() {
+            ConnectionEvent::Disconnected { reason, .. } => {
+                assert_eq!(reason, "closed by the server")
+            }
+            other => panic!("unexpected event: {other}"),
+        }
+        assert!(events.try_recv().is_err());
+    }
+
+    fn io_kind<T>(result: Result<T>) -> std::io::ErrorKind {
+        match result {
+            Err(crate::error::IgtlError::Io(e)) => e.kind(),
+            Err(e) => panic!("unexpected error: {e}"),
+            Ok(_) => panic!("expected an error"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_limit_rejects_extra_clients() {
+        let limits = ServerLimits::new().with_max_sessions(1);
+        let server = AsyncIgtlServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_limits(limits);
+        let addr = server.local_addr().unwrap();
+        let mut events = server.subscribe_events();
+
+        let _first = TcpStream::connect(addr).await.unwrap();
+        let conn = server.accept().await.unwrap();
+        events.recv().await.unwrap();
+        assert_eq!(server.active_sessions(), 1);
+
+        let mut second = TcpStream::connect(addr).await.unwrap();
+        let second_addr = second.local_addr().unwrap();
+        let server = std::sync::Arc::new(server);
+        let accepting = tokio::spawn({
+            let server = std::sync::Arc::clone(&server);
+            async move { server.accept().await.unwrap() }
+        });
+
+        match events.recv().await.unwrap() {
+            ConnectionEvent::Rejected { peer, reason } => {
+                assert_eq!(peer, second_addr);
+                assert!(reason.contains("session limit"));
+            }
+            other => panic!("unexpected event: {other}"),
+        }
+        let mut buf = [0u8; 1];
+        assert_eq!(second.read(&mut buf).await.unwrap(), 0);
+
+        // Closing the first session frees its slot
+        drop(conn);
+        let third = TcpStream::connect(addr).await.unwrap();
+        let conn = accepting.await.unwrap();
+        assert_eq!(conn.peer_addr().unwrap(), third.local_addr().unwrap());
+        assert_eq!(server.active_sessions(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_first_message_deadline_closes_silent_session() {
+        let limits = ServerLimits::new().with_first_message_timeout(Duration::from_millis(50));
+        let server = AsyncIgtlServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_limits(limits);
+        let addr = server.local_addr().unwrap();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut conn = server.accept().await.unwrap();
+
+        assert_eq!(
+            io_kind(conn.receive_any().await),
+            std::io::ErrorKind::ConnectionAborted
+        );
+        assert_eq!(
+            io_kind(conn.receive_any().await),
+            std::io::ErrorKind::NotConnected
+        );
+        let mut buf = [0u8; 1];
+        assert_eq!(stream.read(&mut buf).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_flooding_client_is_closed_without_affecting_others() {
+        let limits = ServerLimits::new().with_max_messages_per_sec(5);
+        let server = AsyncIgtlServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_limits(limits);
+        let addr = server.local_addr().unwrap();
+        let data = IgtlMessage::new(StatusMessage::ok("flood"), "Client")
+            .unwrap()
+            .encode()
+            .unwrap();
+
+        let mut flooder = TcpStream::connect(addr).await.unwrap();
+        let mut flooded = server.accept().await.unwrap();
+        let mut polite = TcpStream::connect(addr).await.unwrap();
+        let mut served = server.accept().await.unwrap();
+
+        for _ in 0..20 {
+            flooder.write_all(&data).await.unwrap();
+        }
+        let mut received = 0;
+        let err = loop {
+            match flooded.receive_any().await {
+                Ok(_) => received += 1,
+                Err(e) => break e,
+            }
+        };
+        // The burst allowance plus whatever refilled while receiving
+        assert!((5..20).contains(&received), "received {received}");
+        assert!(err.to_string().contains("messages/s"));
+
+        polite.write_all(&data).await.unwrap();
+        let msg: IgtlMessage<StatusMessage> = served.receive().await.unwrap();
+        assert_eq!(msg.content.status_string, "flood");
+    }
+
+    #[tokio::test]
+    async fn test_receive_until_cancel_keeps_connection_usable() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let mut conn = server.accept().await.unwrap();
+        let data = IgtlMessage::new(StatusMessage::ok("after cancel"), "Client")
+            .unwrap()
+            .encode()
+            .unwrap();
+        stream.write_all(&data[..30]).await.unwrap();
+
+        let token = CancellationToken::new();
+        let receiving = conn.receive_until::<StatusMessage>(&token);
+        let cancel = async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            token.cancel();
+        };
+        let (received, ()) = tokio::join!(receiving, cancel);
+        assert!(received.unwrap().is_none());
+
+        stream.write_all(&data[30..]).await.unwrap();
+        let msg: IgtlMessage<StatusMessage> = conn.receive().await.unwrap();
+        assert_eq!(msg.content.status_string, "after cancel");
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_notifies_and_closes_sessions() {
+        use crate::io::shutdown::STATUS_SHUT_DOWN;
+
+        let server = Arc::new(AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap());
+        let addr = server.local_addr().unwrap();
+        let mut events = server.subscribe_events();
+
+        let mut client = AsyncIgtlStream::new(TcpStream::connect(addr).await.unwrap());
+        let mut conn = server.accept().await.unwrap();
+        events.recv().await.unwrap();
+        let session = tokio::spawn(async move { conn.receive_any().await });
+        let accepting = tokio::spawn({
+            let server = Arc::clone(&server);
+            async move { server.accept_until(&CancellationToken::new()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(20)).await;
+
+        let config = ShutdownConfig::new().with_drain_timeout(Duration::from_secs(2));
+        assert_eq!(server.shutdown(config).await, 0);
+        assert_eq!(server.active_sessions(), 0);
+        assert!(accepting.await.unwrap().unwrap().is_none());
+        assert_eq!(
+            io_kind(session.await.unwrap()),
+            std::io::ErrorKind::ConnectionAborted
+        );
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ConnectionEvent::Disconnected { .. }
+        ));
+
+        let notice: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(notice.content.code, STATUS_SHUT_DOWN);
+        assert_eq!(notice.content.status_string, "Shutdown in progress");
+        assert!(client.receive_any().await.is_err());
+        assert!(server.accept().await.is_err());
+    }
 }
@@ -0,0 +1,451 @@
+//! Asynchronous UDP-based OpenIGTLink communication
+//!
+//! Tokio counterparts of [`UdpClient`](crate::io::UdpClient) and
+//! [`UdpServer`](crate::io::UdpServer) with the same API shape. All methods take `&self`,
+//! so an endpoint can be shared between tasks (e.g. behind an `Arc`).
+//!
+//! Receives are cancel-safe: wrapping them in `tokio::time::timeout` or `tokio::select!`
+//! never loses a datagram that has not been returned yet.
+//!
+//! # Example: Tracking Relay
+//!
+//! ```no_run
+//! use openigtlink_rust::io::{AsyncUdpClient, AsyncUdpServer};
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let server = AsyncUdpServer::bind("0.0.0.0:18944").await?;
+//! let forward = AsyncUdpClient::bind("0.0.0.0:0").await?;
+//!
+//! loop {
+//!     let (msg, _sender) = server.receive_any().await?;
+//!     if let openigtlink_rust::protocol::AnyMessage::Transform(transform) = msg {
+//!         forward.send_to(&transform, "192.168.1.100:18944").await?;
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+use tokio::net::{ToSocketAddrs, UdpSocket};
+use tracing::{info, warn};
+
+use crate::error::Result;
+use crate::io::udp::{decode_any_datagram, DatagramLayers, MAX_UDP_DATAGRAM_SIZE};
+use crate::io::udp_fragment::{FragmentConfig, FragmentStats, Fragmentation};
+use crate::io::udp_sequence::{SequenceConfig, SequenceSource, SequenceStats, Sequencing};
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::AnyMessage;
+
+/// Asynchronous UDP client for sending/receiving OpenIGTLink messages
+///
+/// # Examples
+///
+/// ```no_run
+/// use openigtlink_rust::io::AsyncUdpClient;
+/// use openigtlink_rust::protocol::types::TransformMessage;
+/// use openigtlink_rust::protocol::message::IgtlMessage;
+///
+/// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+/// let client = AsyncUdpClient::bind("0.0.0.0:0").await?;
+/// let msg = IgtlMessage::new(TransformMessage::identity(), "Tool")?;
+/// client.send_to(&msg, "192.168.1.100:18944").await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncUdpClient {
+    socket: UdpSocket,
+    verify_crc: bool,
+    layers: DatagramLayers,
+}
+
+impl AsyncUdpClient {
+    /// Bind to a local address
+    ///
+    /// # Arguments
+    ///
+    /// * `local_addr` - Local address to bind (use "0.0.0.0:0" for any available port)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind socket (address in use, permission denied, etc.)
+    pub async fn bind(local_addr: &str) -> Result<Self> {
+        let socket = UdpSocket::bind(local_addr).await?;
+        Ok(AsyncUdpClient {
+            socket,
+            verify_crc: true,
+            layers: DatagramLayers::default(),
+        })
+    }
+
+    /// Send a message to a remote address
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - Message to send
+    /// * `target` - Target address (e.g., "127.0.0.1:18944")
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network transmission failed
+    /// - [`IgtlError::BodyTooLarge`](crate::error::IgtlError::BodyTooLarge) - Message exceeds UDP MTU (65507 bytes)
+    pub async fn send_to<T: Message>(
+        &self,
+        msg: &IgtlMessage<T>,
+        target: impl ToSocketAddrs,
+    ) -> Result<()> {
+        for datagram in self.layers.encode(msg)? {
+            self.socket.send_to(&datagram, &target).await?;
+        }
+        Ok(())
+    }
+
+    /// Receive a message
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (message, sender_address)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read failed
+    /// - [`IgtlError::InvalidHeader`](crate::error::IgtlError::InvalidHeader) - Malformed header
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption detected
+    pub async fn receive_from<T: Message>(&self) -> Result<(IgtlMessage<T>, SocketAddr)> {
+        let (data, src) = recv_frame(&self.socket, &self.layers).await?;
+        let msg = IgtlMessage::decode_with_options(&data, self.verify_crc)?;
+        Ok((msg, src))
+    }
+
+    /// Receive a message of any type
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (message, sender_address)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read failed
+    /// - [`IgtlError::InvalidSize`](crate::error::IgtlError::InvalidSize) - Datagram shorter than a header
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption detected
+    pub async fn receive_any_from(&self) -> Result<(AnyMessage, SocketAddr)> {
+        let (data, src) = recv_frame(&self.socket, &self.layers).await?;
+        let msg = decode_any_datagram(&data, self.verify_crc)?;
+        Ok((msg, src))
+    }
+
+    /// Enable or disable CRC verification for received messages
+    ///
+    /// # Arguments
+    ///
+    /// * `verify` - true to enable CRC verification (default), false to disable
+    pub fn set_verify_crc(&mut self, verify: bool) {
+        log_verify_crc(self.verify_crc, verify);
+        self.verify_crc = verify;
+    }
+
+    /// Get current CRC verification setting
+    pub fn verify_crc(&self) -> bool {
+        self.verify_crc
+    }
+
+    /// Get local socket address
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to get socket address
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Enable fragmentation of messages larger than one datagram
+    ///
+    /// See [`udp_fragment`](crate::io::udp_fragment) for the wire format.
+    pub fn with_fragmentation(mut self, config: FragmentConfig) -> Self {
+        self.layers.fragmentation = Some(Fragmentation::new(config));
+        self
+    }
+
+    /// Fragmentation statistics (None if fragmentation is disabled)
+    pub fn fragment_stats(&self) -> Option<FragmentStats> {
+        self.layers.fragmentation.as_ref().map(Fragmentation::stats)
+    }
+
+    /// Stamp sent messages with per-device sequence numbers and track received ones
+    ///
+    /// See [`udp_sequence`](crate::io::udp_sequence).
+    pub fn with_sequencing(mut self, config: SequenceConfig) -> Self {
+        self.layers.sequencing = Some(Sequencing::new(config));
+        self
+    }
+
+    /// Per-source receive statistics (None if sequencing is disabled)
+    pub fn sequence_stats(&self) -> Option<HashMap<SequenceSource, SequenceStats>> {
+        self.layers.sequencing.as_ref().map(Sequencing::stats)
+    }
+}
+
+/// Asynchronous UDP server for receiving OpenIGTLink messages
+///
+/// # Examples
+///
+/// ```no_run
+/// use openigtlink_rust::io::AsyncUdpServer;
+/// use openigtlink_rust::protocol::types::TransformMessage;
+/// use openigtlink_rust::protocol::message::IgtlMessage;
+///
+/// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+/// let server = AsyncUdpServer::bind("0.0.0.0:18944").await?;
+///
+/// // Echo back
+/// let (msg, sender) = server.receive::<TransformMessage>().await?;
+/// let response = IgtlMessage::new(msg.content, "Server")?;
+/// server.send_to(&response, sender).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct AsyncUdpServer {
+    socket: UdpSocket,
+    verify_crc: bool,
+    layers: DatagramLayers,
+}
+
+impl AsyncUdpServer {
+    /// Bind server to an address
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Address to bind (e.g., "0.0.0.0:18944")
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind (port in use, permission denied, etc.)
+    pub async fn bind(addr: &str) -> Result<Self> {
+        let socket = UdpSocket::bind(addr).await?;
+        Ok(AsyncUdpServer {
+            socket,
+            verify_crc: true,
+            layers: DatagramLayers::default(),
+        })
+    }
+
+    /// Receive a message
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (message, sender_address)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read failed
+    /// - [`IgtlError::InvalidHeader`](crate::error::IgtlError::InvalidHeader) - Malformed header
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption
+    pub async fn receive<T: Message>(&self) -> Result<(IgtlMessage<T>, SocketAddr)> {
+        let (data, src) = recv_frame(&self.socket, &self.layers).await?;
+        let msg = IgtlMessage::decode_with_options(&data, self.verify_crc)?;
+        Ok((msg, src))
+    }
+
+    /// Receive a message of any type
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (message, sender_address)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read failed
+    /// - [`IgtlError::InvalidSize`](crate::error::IgtlError::InvalidSize) - Datagram shorter than a header
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption
+    pub async fn receive_any(&self) -> Result<(AnyMessage, SocketAddr)> {
+        let (data, src) = recv_frame(&self.socket, &self.layers).await?;
+        let msg = decode_any_datagram(&data, self.verify_crc)?;
+        Ok((msg, src))
+    }
+
+    /// Send a response to a specific address
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - Message to send
+    /// * `target` - Target socket address
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network transmission failed
+    /// - [`IgtlError::BodyTooLarge`](crate::error::IgtlError::BodyTooLarge) - Message exceeds UDP MTU
+    pub async fn send_to<T: Message>(
+        &self,
+        msg: &IgtlMessage<T>,
+        target: SocketAddr,
+    ) -> Result<()> {
+        for datagram in self.layers.encode(msg)? {
+            self.socket.send_to(&datagram, &target).await?;
+        }
+        Ok(())
+    }
+
+    /// Enable or disable CRC verification for received messages
+    ///
+    /// # Arguments
+    ///
+    /// * `verify` - true to enable CRC verification (default), false to disable
+    pub fn set_verify_crc(&mut self, verify: bool) {
+        log_verify_crc(self.verify_crc, verify);
+        self.verify_crc = verify;
+    }
+
+    /// Get current CRC verification setting
+    pub fn verify_crc(&self) -> bool {
+        self.verify_crc
+    }
+
+    /// Get local socket address
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to get socket address
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Enable fragmentation of messages larger than one datagram
+    ///
+    /// See [`udp_fragment`](crate::io::udp_fragment) for the wire format.
+    pub fn with_fragmentation(mut self, config: FragmentConfig) -> Self {
+        self.layers.fragmentation = Some(Fragmentation::new(config));
+        self
+    }
+
+    /// Fragmentation statistics (None if fragmentation is disabled)
+    pub fn fragment_stats(&self) -> Option<FragmentStats> {
+        self.layers.fragmentation.as_ref().map(Fragmentation::stats)
+    }
+
+    /// Stamp sent messages with per-device sequence numbers and track received ones
+    ///
+    /// See [`udp_sequence`](crate::io::udp_sequence).
+    pub fn with_sequencing(mut self, config: SequenceConfig) -> Self {
+        self.layers.sequencing = Some(Sequencing::new(config));
+        self
+    }
+
+    /// Per-source receive statistics (None if sequencing is disabled)
+    pub fn sequence_stats(&self) -> Option<HashMap<SequenceSource, SequenceStats>> {
+        self.layers.sequencing.as_ref().map(Sequencing::stats)
+    }
+}
+
+/// Receive datagrams until a complete message frame is available (cancel-safe)
+///
+/// Fragments received before a cancellation stay in the reassembly state.
+async fn recv_frame(socket: &UdpSocket, layers: &DatagramLayers) -> Result<(Vec<u8>, SocketAddr)> {
+    let mut buf = vec![0u8; MAX_UDP_DATAGRAM_SIZE];
+    loop {
+        let (size, src) = socket.recv_from(&mut buf).await?;
+        if let Some(frame) = layers.receive(&buf[..size], src) {
+            return Ok((frame, src));
+        }
+    }
+}
+
+fn log_verify_crc(current: bool, verify: bool) {
+    if verify != current {
+        info!(verify = verify, "CRC verification setting changed");
+        if !verify {
+            warn!("CRC verification disabled - use only in trusted environments");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IgtlError;
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_send_receive() {
+        let server = AsyncUdpServer::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let client = AsyncUdpClient::bind("127.0.0.1:0").await.unwrap();
+
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Tracker").unwrap();
+        client.send_to(&msg, server_addr).await.unwrap();
+        let (received, sender) = server.receive::<TransformMessage>().await.unwrap();
+        assert_eq!(received.header.device_name.as_str().unwrap(), "Tracker");
+        assert_eq!(sender, client.local_addr().unwrap());
+
+        // Echo back as any message
+        let reply = IgtlMessage::new(StatusMessage::ok("ack"), "Server").unwrap();
+        server.send_to(&reply, sender).await.unwrap();
+        let (received, sender) = client.receive_any_from().await.unwrap();
+        assert_eq!(sender, server_addr);
+        match received {
+            AnyMessage::Status(status) => assert_eq!(status.content.status_string, "ack"),
+            other => panic!("unexpected message: {}", other.message_type()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_receive_is_cancel_safe() {
+        let server = AsyncUdpServer::bind("127.0.0.1:0").await.unwrap();
+        let client = AsyncUdpClient::bind("127.0.0.1:0").await.unwrap();
+
+        let timed_out = tokio::time::timeout(Duration::from_millis(50), server.receive_any()).await;
+        assert!(timed_out.is_err());
+
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        client
+            .send_to(&msg, server.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (received, _) = server.receive_any().await.unwrap();
+        assert!(matches!(received, AnyMessage::Transform(_)));
+    }
+
+    #[tokio::test]
+    async fn test_fragmented_message() {
+        use crate::protocol::types::{ImageMessage, ImageScalarType};
+
+        let config = FragmentConfig::new().with_max_datagram_size(8192);
+        let server = AsyncUdpServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_fragmentation(config.clone());
+        let client = AsyncUdpClient::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_fragmentation(config);
+
+        let image =
+            ImageMessage::new(ImageScalarType::Uint16, [200, 200, 1], vec![7u8; 80_000]).unwrap();
+        let msg = IgtlMessage::new(image, "CT").unwrap();
+        client
+            .send_to(&msg, server.local_addr().unwrap())
+            .await
+            .unwrap();
+
+        let (received, _) = server.receive_any().await.unwrap();
+        match received {
+            AnyMessage::Image(image) => assert_eq!(image.content.size, [200, 200, 1]),
+            other => panic!("unexpected message: {}", other.message_type()),
+        }
+        assert_eq!(server.fragment_stats().unwrap().messages_reassembled, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_truncated_datagram() {
+        let server = AsyncUdpServer::bind("127.0.0.1:0").await.unwrap();
+        let raw = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        raw.send_to(&[0u8; 10], server.local_addr().unwrap())
+            .await
+            .unwrap();
+        assert!(matches!(
+            server.receive_any().await,
+            Err(IgtlError::InvalidSize { actual: 10, .. })
+        ));
+    }
+}
@@ -25,14 +25,25 @@
 //! ClientBuilder<Unspecified, Unspecified>
 //!   ├─ .tcp(addr)  → ClientBuilder<TcpConfigured, Unspecified>
 //!   │   ├─ .sync()       → ClientBuilder<TcpConfigured, SyncMode>
-//!   │   │   └─ .build()  → Result<SyncIgtlClient>
+//!   │   │   ├─ .with_tls(config) → self
+//!   │   │   └─ .build()          → Result<SyncIgtlClient>
 //!   │   └─ .async_mode() → ClientBuilder<TcpConfigured, AsyncMode>
 //!   │       ├─ .with_tls(config)      → self
 //!   │       ├─ .with_reconnect(cfg)   → self
 //!   │       ├─ .verify_crc(bool)      → self
 //!   │       └─ .build()               → Result<UnifiedAsyncClient>
-//!   └─ .udp(addr)  → ClientBuilder<UdpConfigured, Unspecified>
-//!       └─ .build() → Result<UdpClient>
+//!   ├─ .udp(addr)  → ClientBuilder<UdpConfigured, SyncMode>
+//!   │   ├─ .with_fragmentation(cfg) → self
+//!   │   ├─ .with_sequencing(cfg)    → self
+//!   │   ├─ .build()      → Result<UdpClient>
+//!   │   └─ .async_mode() → ClientBuilder<UdpConfigured, AsyncMode>
+//!   │       └─ .build()  → Result<AsyncUdpClient>
+//!   ├─ .websocket(url) → ClientBuilder<WebSocketConfigured, AsyncMode>
+//!   │   ├─ .with_tls(config) → self   (wss:// URLs)
+//!   │   └─ .build()          → Result<WebSocketIgtlClient>
+//!   └─ .unix(path) → ClientBuilder<UnixConfigured, Unspecified>   (Unix only)
+//!       ├─ .sync()       → .build() → Result<UnixIgtlClient>
+//!       └─ .async_mode() → .build() → Result<AsyncUnixIgtlClient>
 //! ```
 //!
 //! Invalid state transitions result in **compile errors**, not runtime errors!
@@ -145,6 +156,25 @@
 //! # Ok::<(), openigtlink_rust::error::IgtlError>(())
 //! ```
 //!
+//! Inside a Tokio runtime, `.udp(addr).async_mode()` builds an
+//! [`AsyncUdpClient`](crate::io::AsyncUdpClient) instead.
+//!
+//! ## Unix Domain Socket Clients
+//!
+//! ```no_run
+//! # #[cfg(unix)]
+//! # fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! use openigtlink_rust::io::builder::ClientBuilder;
+//!
+//! // Local IPC without the TCP stack
+//! let client = ClientBuilder::new()
+//!     .unix("/run/igtl.sock")
+//!     .sync()
+//!     .build()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
 //! ## Compile-Time Error Prevention
 //!
 //! The following code will **not compile**:
@@ -163,13 +193,23 @@
 //! TCP-based TLS as it's the standard for OpenIGTLink secure communications.
 
 use crate::error::Result;
+use crate::io::events::{ConnectionEvent, EventEmitter};
+use crate::io::liveness::LivenessConfig;
 use crate::io::reconnect::ReconnectConfig;
+use crate::io::session::OfflineQueueConfig;
 use crate::io::sync_client::SyncTcpClient;
-use crate::io::unified_async_client::UnifiedAsyncClient;
+use crate::io::udp_fragment::FragmentConfig;
+use crate::io::udp_sequence::SequenceConfig;
+use crate::io::unified_async_client::{ConnectionParams, UnifiedAsyncClient};
 use crate::io::unified_client::{AsyncIgtlClient, SyncIgtlClient};
-use crate::io::UdpClient;
+use crate::io::{AsyncUdpClient, UdpClient, WebSocketIgtlClient};
+#[cfg(unix)]
+use crate::io::{AsyncUnixIgtlClient, UnixIgtlClient};
 use std::marker::PhantomData;
+#[cfg(unix)]
+use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::sync::broadcast;
 use tokio_rustls::rustls;
 
 // ============================================================================
@@ -191,11 +231,29 @@ pub struct TcpConfigured {
 
 /// UDP protocol configured state
 ///
-/// Contains the server address for UDP communication.
-/// Note: UDP only supports synchronous mode.
+/// Contains the local bind address for UDP communication.
+/// UDP starts in synchronous mode; see [`ClientBuilder::async_mode`].
 #[allow(dead_code)]
 pub struct UdpConfigured {
     pub(crate) addr: String,
+    pub(crate) fragmentation: Option<FragmentConfig>,
+    pub(crate) sequencing: Option<SequenceConfig>,
+}
+
+/// WebSocket configured state
+///
+/// Contains the `ws://` or `wss://` URL of the server. WebSocket clients are always
+/// asynchronous.
+pub struct WebSocketConfigured {
+    pub(crate) url: String,
+}
+
+/// Unix domain socket configured state
+///
+/// Contains the path of the server's socket file.
+#[cfg(unix)]
+pub struct UnixConfigured {
+    pub(crate) path: PathBuf,
 }
 
 /// Synchronous (blocking) mode state
@@ -214,7 +272,8 @@ pub struct AsyncMode;
 /// can be constructed.
 ///
 /// # Type Parameters
-/// * `Protocol` - Protocol state (Unspecified, TcpConfigured, UdpConfigured)
+/// * `Protocol` - Protocol state (Unspecified, TcpConfigured, UdpConfigured,
+///   WebSocketConfigured, UnixConfigured)
 /// * `Mode` - Mode state (Unspecified, SyncMode, AsyncMode)
 ///
 /// # Examples
@@ -233,6 +292,9 @@ pub struct ClientBuilder<Protocol = Unspecified, Mode = Unspecified> {
     mode: PhantomData<Mode>,
     tls_config: Option<Arc<rustls::ClientConfig>>,
     reconnect_config: Option<ReconnectConfig>,
+    liveness_config: Option<LivenessConfig>,
+    offline_queue: Option<OfflineQueueConfig>,
+    events: Option<broadcast::Sender<ConnectionEvent>>,
     verify_crc: bool,
 }
 
@@ -258,6 +320,9 @@ impl ClientBuilder<Unspecified, Unspecified> {
             mode: PhantomData,
             tls_config: None,
             reconnect_config: None,
+            liveness_config: None,
+            offline_queue: None,
+            events: None,
             verify_crc: true,
         }
     }
@@ -293,14 +358,16 @@ impl ClientBuilder<Unspecified, Unspecified> {
             mode: PhantomData,
             tls_config: self.tls_config,
             reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
             verify_crc: self.verify_crc,
         }
     }
 
     /// Select UDP protocol
     ///
-    /// Note: UDP automatically sets mode to SyncMode as UDP only supports
-    /// synchronous operation.
+    /// Note: UDP defaults to SyncMode; call `.async_mode()` for a Tokio-based client.
     ///
     /// # Arguments
     /// * `addr` - Server address (e.g., "127.0.0.1:18944")
@@ -315,10 +382,76 @@ impl ClientBuilder<Unspecified, Unspecified> {
     /// ```
     pub fn udp(self, addr: impl Into<String>) -> ClientBuilder<UdpConfigured, SyncMode> {
         ClientBuilder {
-            protocol: UdpConfigured { addr: addr.into() },
+            protocol: UdpConfigured {
+                addr: addr.into(),
+                fragmentation: None,
+                sequencing: None,
+            },
             mode: PhantomData,
             tls_config: self.tls_config,
             reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
+            verify_crc: self.verify_crc,
+        }
+    }
+
+    /// Select a WebSocket transport
+    ///
+    /// Note: WebSocket clients are always asynchronous. `wss://` URLs need
+    /// `with_tls`.
+    ///
+    /// # Arguments
+    /// * `url` - Server URL (e.g., "ws://127.0.0.1:18945/igtl")
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    ///
+    /// let builder = ClientBuilder::new()
+    ///     .websocket("ws://127.0.0.1:18945/igtl");
+    /// ```
+    pub fn websocket(
+        self,
+        url: impl Into<String>,
+    ) -> ClientBuilder<WebSocketConfigured, AsyncMode> {
+        ClientBuilder {
+            protocol: WebSocketConfigured { url: url.into() },
+            mode: PhantomData,
+            tls_config: self.tls_config,
+            reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
+            verify_crc: self.verify_crc,
+        }
+    }
+
+    /// Select a Unix domain socket transport
+    ///
+    /// # Arguments
+    /// * `path` - Path of the server's socket file (e.g., "/run/igtl.sock")
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    ///
+    /// let builder = ClientBuilder::new()
+    ///     .unix("/run/igtl.sock");
+    /// ```
+    #[cfg(unix)]
+    pub fn unix(self, path: impl Into<PathBuf>) -> ClientBuilder<UnixConfigured, Unspecified> {
+        ClientBuilder {
+            protocol: UnixConfigured { path: path.into() },
+            mode: PhantomData,
+            tls_config: self.tls_config,
+            reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
             verify_crc: self.verify_crc,
         }
     }
@@ -346,6 +479,9 @@ impl ClientBuilder<TcpConfigured, Unspecified> {
             mode: PhantomData,
             tls_config: self.tls_config,
             reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
             verify_crc: self.verify_crc,
         }
     }
@@ -367,6 +503,9 @@ impl ClientBuilder<TcpConfigured, Unspecified> {
             mode: PhantomData,
             tls_config: self.tls_config,
             reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
             verify_crc: self.verify_crc,
         }
     }
@@ -377,6 +516,35 @@ impl ClientBuilder<TcpConfigured, Unspecified> {
 // ============================================================================
 
 impl ClientBuilder<TcpConfigured, SyncMode> {
+    /// Configure TLS encryption
+    ///
+    /// The handshake is performed with blocking I/O inside [`build`](Self::build).
+    ///
+    /// # Arguments
+    /// * `config` - TLS client configuration
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    /// use std::sync::Arc;
+    ///
+    /// let tls_config = rustls::ClientConfig::builder()
+    ///     .with_root_certificates(rustls::RootCertStore::empty())
+    ///     .with_no_client_auth();
+    ///
+    /// let client = ClientBuilder::new()
+    ///     .tcp("127.0.0.1:18944")
+    ///     .sync()
+    ///     .with_tls(Arc::new(tls_config))
+    ///     .build()?;
+    /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
+    /// ```
+    pub fn with_tls(mut self, config: Arc<rustls::ClientConfig>) -> Self {
+        self.tls_config = Some(config);
+        self
+    }
+
     /// Build a synchronous TCP client
     ///
     /// # Errors
@@ -395,7 +563,16 @@ impl ClientBuilder<TcpConfigured, SyncMode> {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn build(self) -> Result<SyncIgtlClient> {
-        let mut client = SyncTcpClient::connect(&self.protocol.addr)?;
+        let events = self.events.map(EventEmitter::new).unwrap_or_default();
+        if let Some(tls_config) = self.tls_config {
+            let (hostname, port) = parse_addr(&self.protocol.addr)?;
+            let mut client =
+                SyncTcpClient::connect_tls_with_events(&hostname, port, tls_config, events)?;
+            client.set_verify_crc(self.verify_crc);
+            return Ok(SyncIgtlClient::Tls(client));
+        }
+
+        let mut client = SyncTcpClient::connect_with_events(&self.protocol.addr, events)?;
         client.set_verify_crc(self.verify_crc);
         Ok(SyncIgtlClient::TcpSync(client))
     }
@@ -462,6 +639,72 @@ impl ClientBuilder<TcpConfigured, AsyncMode> {
         self
     }
 
+    /// Configure TCP keepalive, heartbeats and read/write/idle timeouts
+    ///
+    /// Combined with [`with_reconnect`](Self::with_reconnect), an expired idle timeout
+    /// reconnects the client. See [`liveness`](crate::io::liveness) for details.
+    ///
+    /// # Arguments
+    /// * `config` - Liveness configuration
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    /// use openigtlink_rust::io::liveness::{HeartbeatConfig, LivenessConfig};
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+    /// let client = ClientBuilder::new()
+    ///     .tcp("127.0.0.1:18944")
+    ///     .async_mode()
+    ///     .with_liveness(
+    ///         LivenessConfig::new()
+    ///             .with_heartbeat(HeartbeatConfig::get_status(Duration::from_secs(1)))
+    ///             .with_idle_timeout(Duration::from_secs(3)),
+    ///     )
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_liveness(mut self, config: LivenessConfig) -> Self {
+        self.liveness_config = Some(config);
+        self
+    }
+
+    /// Queue messages sent while disconnected and deliver them after reconnecting
+    ///
+    /// Requires [`with_reconnect`](Self::with_reconnect). Streams started with STT_*
+    /// messages are restarted after reconnecting regardless of this setting.
+    ///
+    /// # Arguments
+    /// * `config` - Queue capacity, drop policy and time-to-live
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    /// use openigtlink_rust::io::reconnect::ReconnectConfig;
+    /// use openigtlink_rust::io::session::OfflineQueueConfig;
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+    /// let client = ClientBuilder::new()
+    ///     .tcp("127.0.0.1:18944")
+    ///     .async_mode()
+    ///     .with_reconnect(ReconnectConfig::default())
+    ///     .with_offline_queue(OfflineQueueConfig::new(64).with_ttl(Duration::from_secs(1)))
+    ///     .build()
+    ///     .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_offline_queue(mut self, config: OfflineQueueConfig) -> Self {
+        self.offline_queue = Some(config);
+        self
+    }
+
     /// Build an asynchronous TCP client
     ///
     /// Creates the appropriate client variant based on configured options:
@@ -493,20 +736,32 @@ impl ClientBuilder<TcpConfigured, AsyncMode> {
         let addr = self.protocol.addr;
 
         // Create base client (with or without TLS)
-        let mut client = if let Some(tls_config) = self.tls_config {
+        let params = if let Some(tls_config) = self.tls_config {
             // TLS connection
             let (hostname, port) = parse_addr(&addr)?;
-            UnifiedAsyncClient::connect_with_tls(&hostname, port, tls_config).await?
+            ConnectionParams::tls(&hostname, port, tls_config)
         } else {
             // Plain TCP connection
-            UnifiedAsyncClient::connect(&addr).await?
+            ConnectionParams::plain(&addr)
         };
+        let events = self.events.map(EventEmitter::new).unwrap_or_default();
+        let mut client = UnifiedAsyncClient::connect_with_params(params, events).await?;
 
         // Add reconnection if configured
         if let Some(reconnect_config) = self.reconnect_config {
             client = client.with_reconnect(reconnect_config);
         }
 
+        // Add offline queue if configured
+        if let Some(queue_config) = self.offline_queue {
+            client = client.with_offline_queue(queue_config);
+        }
+
+        // Add keepalive, heartbeats and timeouts if configured
+        if let Some(liveness_config) = self.liveness_config {
+            client = client.with_liveness(liveness_config);
+        }
+
         // Set CRC verification
         client.set_verify_crc(self.verify_crc);
 
@@ -518,6 +773,35 @@ impl ClientBuilder<TcpConfigured, AsyncMode> {
 // Common Configuration Methods
 // ============================================================================
 
+impl<Mode> ClientBuilder<TcpConfigured, Mode> {
+    /// Publish connection lifecycle events on `sender`
+    ///
+    /// Unlike subscribing on the built client, this also reports the initial
+    /// connection. See [`events`](crate::io::events).
+    ///
+    /// # Arguments
+    /// * `sender` - Broadcast channel the events are sent on
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    ///
+    /// let (events_tx, mut events) = tokio::sync::broadcast::channel(16);
+    /// let client = ClientBuilder::new()
+    ///     .tcp("127.0.0.1:18944")
+    ///     .sync()
+    ///     .with_events(events_tx)
+    ///     .build()?;
+    /// println!("{}", events.try_recv().unwrap());
+    /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
+    /// ```
+    pub fn with_events(mut self, sender: broadcast::Sender<ConnectionEvent>) -> Self {
+        self.events = Some(sender);
+        self
+    }
+}
+
 impl<Protocol, Mode> ClientBuilder<Protocol, Mode> {
     /// Enable or disable CRC verification for received messages
     ///
@@ -613,7 +897,236 @@ impl ClientBuilder<UdpConfigured, SyncMode> {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn build(self) -> Result<UdpClient> {
-        UdpClient::bind(&self.protocol.addr)
+        let mut client = UdpClient::bind(&self.protocol.addr)?;
+        if let Some(config) = self.protocol.fragmentation {
+            client = client.with_fragmentation(config);
+        }
+        if let Some(config) = self.protocol.sequencing {
+            client = client.with_sequencing(config);
+        }
+        Ok(client)
+    }
+}
+
+impl<Mode> ClientBuilder<UdpConfigured, Mode> {
+    /// Enable fragmentation of messages larger than one datagram
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    /// use openigtlink_rust::io::FragmentConfig;
+    ///
+    /// let client = ClientBuilder::new()
+    ///     .udp("0.0.0.0:0")
+    ///     .with_fragmentation(FragmentConfig::default())
+    ///     .build()?;
+    /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
+    /// ```
+    pub fn with_fragmentation(mut self, config: FragmentConfig) -> Self {
+        self.protocol.fragmentation = Some(config);
+        self
+    }
+
+    /// Stamp sent messages with sequence numbers and track received ones
+    pub fn with_sequencing(mut self, config: SequenceConfig) -> Self {
+        self.protocol.sequencing = Some(config);
+        self
+    }
+}
+
+impl ClientBuilder<UdpConfigured, SyncMode> {
+    /// Select asynchronous (Tokio) mode for UDP
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    ///
+    /// let builder = ClientBuilder::new()
+    ///     .udp("0.0.0.0:0")
+    ///     .async_mode();
+    /// ```
+    pub fn async_mode(self) -> ClientBuilder<UdpConfigured, AsyncMode> {
+        ClientBuilder {
+            protocol: self.protocol,
+            mode: PhantomData,
+            tls_config: self.tls_config,
+            reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
+            verify_crc: self.verify_crc,
+        }
+    }
+}
+
+impl ClientBuilder<UdpConfigured, AsyncMode> {
+    /// Build an asynchronous UDP client
+    ///
+    /// # Errors
+    ///
+    /// Returns error if binding to local address fails
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    /// use openigtlink_rust::protocol::types::TransformMessage;
+    /// use openigtlink_rust::protocol::message::IgtlMessage;
+    ///
+    /// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+    /// let client = ClientBuilder::new()
+    ///     .udp("0.0.0.0:0")
+    ///     .async_mode()
+    ///     .build()
+    ///     .await?;
+    ///
+    /// let msg = IgtlMessage::new(TransformMessage::identity(), "Device")?;
+    /// client.send_to(&msg, "127.0.0.1:18944").await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build(self) -> Result<AsyncUdpClient> {
+        let mut client = AsyncUdpClient::bind(&self.protocol.addr).await?;
+        client.set_verify_crc(self.verify_crc);
+        if let Some(config) = self.protocol.fragmentation {
+            client = client.with_fragmentation(config);
+        }
+        if let Some(config) = self.protocol.sequencing {
+            client = client.with_sequencing(config);
+        }
+        Ok(client)
+    }
+}
+
+// ============================================================================
+// WebSocket Configuration and Build
+// ============================================================================
+
+impl ClientBuilder<WebSocketConfigured, AsyncMode> {
+    /// Configure TLS for `wss://` URLs
+    pub fn with_tls(mut self, config: Arc<rustls::ClientConfig>) -> Self {
+        self.tls_config = Some(config);
+        self
+    }
+
+    /// Publish connection lifecycle events on `sender`
+    pub fn with_events(mut self, sender: broadcast::Sender<ConnectionEvent>) -> Self {
+        self.events = Some(sender);
+        self
+    }
+
+    /// Connect a WebSocket client
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the URL is not `ws://` or `wss://`, a `wss://` URL has no TLS
+    /// configuration, or the connection or a handshake fails
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    ///
+    /// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+    /// let mut client = ClientBuilder::new()
+    ///     .websocket("ws://127.0.0.1:18945/igtl")
+    ///     .build()
+    ///     .await?;
+    /// let msg = client.receive_any().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build(self) -> Result<WebSocketIgtlClient> {
+        let events = self.events.map(EventEmitter::new).unwrap_or_default();
+        let mut client =
+            WebSocketIgtlClient::open(&self.protocol.url, self.tls_config, events).await?;
+        client.set_verify_crc(self.verify_crc);
+        Ok(client)
+    }
+}
+
+// ============================================================================
+// Unix Domain Socket Configuration and Build
+// ============================================================================
+
+#[cfg(unix)]
+impl ClientBuilder<UnixConfigured, Unspecified> {
+    /// Select synchronous (blocking) mode
+    pub fn sync(self) -> ClientBuilder<UnixConfigured, SyncMode> {
+        ClientBuilder {
+            protocol: self.protocol,
+            mode: PhantomData,
+            tls_config: self.tls_config,
+            reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
+            verify_crc: self.verify_crc,
+        }
+    }
+
+    /// Select asynchronous (Tokio) mode
+    pub fn async_mode(self) -> ClientBuilder<UnixConfigured, AsyncMode> {
+        ClientBuilder {
+            protocol: self.protocol,
+            mode: PhantomData,
+            tls_config: self.tls_config,
+            reconnect_config: self.reconnect_config,
+            liveness_config: self.liveness_config,
+            offline_queue: self.offline_queue,
+            events: self.events,
+            verify_crc: self.verify_crc,
+        }
+    }
+}
+
+#[cfg(unix)]
+impl ClientBuilder<UnixConfigured, SyncMode> {
+    /// Connect a synchronous Unix domain socket client
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the socket file does not exist or refuses the connection
+    pub fn build(self) -> Result<UnixIgtlClient> {
+        let mut client = UnixIgtlClient::connect(&self.protocol.path)?;
+        client.set_verify_crc(self.verify_crc);
+        Ok(client)
+    }
+}
+
+#[cfg(unix)]
+impl ClientBuilder<UnixConfigured, AsyncMode> {
+    /// Connect an asynchronous Unix domain socket client
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the socket file does not exist or refuses the connection
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    /// use openigtlink_rust::protocol::types::StatusMessage;
+    /// use openigtlink_rust::protocol::message::IgtlMessage;
+    ///
+    /// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+    /// let mut client = ClientBuilder::new()
+    ///     .unix("/run/igtl.sock")
+    ///     .async_mode()
+    ///     .build()
+    ///     .await?;
+    ///
+    /// let msg = IgtlMessage::new(StatusMessage::ok("Ready"), "Device")?;
+    /// client.send(&msg).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn build(self) -> Result<AsyncUnixIgtlClient> {
+        let mut client = AsyncUnixIgtlClient::connect(&self.protocol.path).await?;
+        client.set_verify_crc(self.verify_crc);
+        Ok(client)
     }
 }
 
@@ -661,8 +1174,18 @@ mod tests {
         let builder = ClientBuilder::new().tcp("127.0.0.1:18944");
         let _async_builder = builder.async_mode();
 
-        // UDP automatically sets sync mode
+        // UDP defaults to sync mode
         let _udp_builder = ClientBuilder::new().udp("127.0.0.1:18944");
+        let _udp_async_builder = ClientBuilder::new().udp("127.0.0.1:18944").async_mode();
+
+        // WebSocket is async only
+        let _ws_builder = ClientBuilder::new().websocket("ws://127.0.0.1:18945");
+
+        #[cfg(unix)]
+        {
+            let _unix_sync_builder = ClientBuilder::new().unix("/run/igtl.sock").sync();
+            let _unix_async_builder = ClientBuilder::new().unix("/run/igtl.sock").async_mode();
+        }
     }
 
     #[test]
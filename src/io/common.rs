@@ -3,8 +3,17 @@
 //! This module provides shared encode/decode logic used across different client implementations
 //! to reduce code duplication and improve maintainability.
 
-use crate::error::Result;
+use std::io::Read;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use crate::error::{IgtlError, Result};
+use crate::protocol::any_message::AnyMessage;
+use crate::protocol::extended_header::ExtendedHeader;
+use crate::protocol::factory::MessageFactory;
+use crate::protocol::header::Header;
 use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::types::GetStatusMessage;
 use tracing::{debug, trace, warn};
 
 /// Encode a message to bytes
@@ -104,3 +113,235 @@ pub(crate) fn decode_message<T: Message>(data: &[u8], verify_crc: bool) -> Resul
 
     result
 }
+
+/// Default time to wait for the reply to a query
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Size of the chunks read from the socket into a receive buffer
+pub(crate) const READ_CHUNK_SIZE: usize = 8192;
+
+/// Split the first complete message frame (header + body) off a receive buffer
+///
+/// Returns `Ok(None)` while the buffer holds less than one full frame, so reads can
+/// be interrupted (by a timeout or cancellation) without losing partial data.
+pub(crate) fn take_frame(buf: &mut Vec<u8>) -> Result<Option<Vec<u8>>> {
+    if buf.len() < Header::SIZE {
+        return Ok(None);
+    }
+
+    let header = Header::decode(&buf[..Header::SIZE])?;
+    let frame_len = Header::SIZE + header.body_size as usize;
+    if buf.len() < frame_len {
+        return Ok(None);
+    }
+
+    let rest = buf.split_off(frame_len);
+    Ok(Some(std::mem::replace(buf, rest)))
+}
+
+/// Read one complete frame from a blocking transport through a receive buffer
+///
+/// A read timeout is reported as [`TimedOut`](std::io::ErrorKind::TimedOut) and keeps
+/// partial data in `rx_buf`. A closed connection is reported as
+/// [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof).
+pub(crate) fn read_frame_blocking<R: Read>(
+    reader: &mut R,
+    rx_buf: &mut Vec<u8>,
+) -> Result<Vec<u8>> {
+    let mut chunk = [0u8; READ_CHUNK_SIZE];
+    loop {
+        if let Some(frame) = take_frame(rx_buf)? {
+            return Ok(frame);
+        }
+        match reader.read(&mut chunk) {
+            Ok(0) => {
+                return Err(IgtlError::Io(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "Connection closed by peer",
+                )))
+            }
+            Ok(n) => rx_buf.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                ) =>
+            {
+                return Err(timed_out("message"))
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Decode a complete frame into an [`AnyMessage`]
+pub(crate) fn decode_any_frame(frame: &[u8], verify_crc: bool) -> Result<AnyMessage> {
+    let header = Header::decode(&frame[..Header::SIZE])?;
+    let factory = MessageFactory::new();
+    let result = factory.decode_any(&header, &frame[Header::SIZE..], verify_crc);
+
+    match &result {
+        Ok(msg) => {
+            trace!(
+                msg_type = msg.message_type(),
+                device_name = msg.device_name().unwrap_or("UNKNOWN"),
+                "Message decoded successfully"
+            );
+        }
+        Err(e) => {
+            warn!(error = %e, "Failed to decode message");
+        }
+    }
+
+    result
+}
+
+/// Error returned when a query or deadline expires
+pub(crate) fn timed_out(what: &str) -> IgtlError {
+    IgtlError::Io(std::io::Error::new(
+        std::io::ErrorKind::TimedOut,
+        format!("Timed out waiting for {}", what),
+    ))
+}
+
+/// Decides whether a received frame is the reply to an outstanding query
+///
+/// A frame matches when its type is the expected reply type, its device name equals
+/// the queried device (any device if the query used an empty name), and, when both
+/// the query and the reply carry a v3 message ID, the IDs are equal. Replies without an
+/// ID (the specification does not require echoing it, and v2 peers cannot) are matched
+/// by type and device only.
+pub(crate) struct ReplyMatcher {
+    type_name: String,
+    device_name: String,
+    message_id: Option<u32>,
+}
+
+impl ReplyMatcher {
+    /// Matcher for replies of type `Resp` to a query sent to `device_name`
+    pub(crate) fn new<Resp: Message>(device_name: &str, message_id: Option<u32>) -> Self {
+        let type_name = Resp::message_type();
+        ReplyMatcher {
+            type_name: type_name[..type_name.len().min(12)].to_string(),
+            device_name: device_name.to_string(),
+            message_id,
+        }
+    }
+
+    /// Human-readable description of the awaited reply, for errors and logs
+    pub(crate) fn describe(&self) -> String {
+        if self.device_name.is_empty() {
+            format!("{} reply", self.type_name)
+        } else {
+            format!("{} reply from '{}'", self.type_name, self.device_name)
+        }
+    }
+
+    /// Check whether a complete frame is the awaited reply
+    pub(crate) fn matches(&self, frame: &[u8]) -> bool {
+        let Ok(header) = Header::decode(&frame[..Header::SIZE]) else {
+            return false;
+        };
+        if header.type_name.as_str().ok() != Some(self.type_name.as_str()) {
+            return false;
+        }
+        if !self.device_name.is_empty()
+            && header.device_name.as_str().ok() != Some(self.device_name.as_str())
+        {
+            return false;
+        }
+
+        match (self.message_id, frame_message_id(&header, frame)) {
+            (Some(expected), Some(actual)) => expected == actual,
+            _ => true,
+        }
+    }
+}
+
+/// Build a GET_STATUS query for `device_name`
+///
+/// STATUS messages are also sent unsolicited (e.g. shutdown notices), so a query without
+/// a device name gets a v3 message ID: a server echoing it is told apart from STATUS
+/// messages carrying other IDs. Without an echo the first STATUS without an ID is taken
+/// as the reply, which may be an unsolicited one that arrived first.
+pub(crate) fn status_query(device_name: &str) -> Result<IgtlMessage<GetStatusMessage>> {
+    static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+
+    let mut query = IgtlMessage::new(GetStatusMessage, device_name)?;
+    if device_name.is_empty() {
+        query.set_message_id(NEXT_ID.fetch_add(1, Ordering::Relaxed));
+    }
+    Ok(query)
+}
+
+/// Read the v3 message ID of a frame without decoding its content
+pub(crate) fn frame_message_id(header: &Header, frame: &[u8]) -> Option<u32> {
+    if header.version < 3 {
+        return None;
+    }
+    ExtendedHeader::decode(&frame[Header::SIZE..])
+        .ok()
+        .map(|ext| ext.get_message_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::types::{CapabilityMessage, StatusMessage, TransformMessage};
+
+    fn frame<T: Message>(content: T, device: &str, message_id: Option<u32>) -> Vec<u8> {
+        let mut msg = IgtlMessage::new(content, device).unwrap();
+        if let Some(id) = message_id {
+            msg.set_message_id(id);
+        }
+        msg.encode().unwrap()
+    }
+
+    #[test]
+    fn test_take_frame_waits_for_complete_frame() {
+        let encoded = frame(TransformMessage::identity(), "Needle", None);
+        let mut buf = encoded[..40].to_vec();
+        assert!(take_frame(&mut buf).unwrap().is_none());
+
+        buf.extend_from_slice(&encoded[40..]);
+        buf.extend_from_slice(&encoded[..10]);
+        assert_eq!(take_frame(&mut buf).unwrap().unwrap(), encoded);
+        assert_eq!(buf, encoded[..10]);
+    }
+
+    #[test]
+    fn test_reply_matcher_type_and_device() {
+        let matcher = ReplyMatcher::new::<TransformMessage>("Needle", None);
+        assert!(matcher.matches(&frame(TransformMessage::identity(), "Needle", None)));
+        assert!(!matcher.matches(&frame(TransformMessage::identity(), "Probe", None)));
+        assert!(!matcher.matches(&frame(CapabilityMessage::empty(), "Needle", None)));
+
+        let any_device = ReplyMatcher::new::<TransformMessage>("", None);
+        assert!(any_device.matches(&frame(TransformMessage::identity(), "Probe", None)));
+    }
+
+    #[test]
+    fn test_reply_matcher_message_id() {
+        let matcher = ReplyMatcher::new::<TransformMessage>("Needle", Some(7));
+        assert!(matcher.matches(&frame(TransformMessage::identity(), "Needle", Some(7))));
+        assert!(!matcher.matches(&frame(TransformMessage::identity(), "Needle", Some(8))));
+        // Replies without an ID (v2 servers) are correlated by type and device only
+        assert!(matcher.matches(&frame(TransformMessage::identity(), "Needle", None)));
+    }
+
+    #[test]
+    fn test_status_query_without_device_prefers_message_id() {
+        let query = status_query("").unwrap();
+        let id = query.get_message_id().unwrap();
+        let matcher = ReplyMatcher::new::<StatusMessage>("", Some(id));
+
+        assert!(matcher.matches(&frame(StatusMessage::ok("Ready"), "Server", Some(id))));
+        assert!(!matcher.matches(&frame(StatusMessage::ok("Bye"), "Server", Some(id + 1))));
+        // Servers that do not echo the ID are matched by type only
+        assert!(matcher.matches(&frame(StatusMessage::ok("Ready"), "Server", None)));
+
+        let named = status_query("Robot").unwrap();
+        assert_eq!(named.get_message_id(), None);
+    }
+}
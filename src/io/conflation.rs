@@ -0,0 +1,630 @@
+//! Per-device rate limiting and conflation of tracking data
+//!
+//! Trackers often produce poses much faster than consumers need them. Sending every
+//! update wastes bandwidth, and a consumer that falls behind ends up processing stale
+//! poses. This module handles both sides:
+//!
+//! - **Send side** - [`ConflatingSender`] limits the rate of configured message types
+//!   per (type, device) pair. Updates arriving faster than the rate replace the pending
+//!   one, so only the latest value is sent at each tick. Other types pass through
+//!   immediately.
+//! - **Receive side** - [`latest_by_device`](crate::io::AsyncIgtlReader::latest_by_device)
+//!   turns a reader into a [`LatestByDevice`] stream that, when the consumer falls
+//!   behind, drops queued tracking messages superseded by a newer message from the same
+//!   device
+//!
+//! Conflation changes the relative order of messages of different devices or types;
+//! the order of messages of one (type, device) pair is kept.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::conflation::{ConflatingSender, ConflationConfig};
+//! use openigtlink_rust::io::ClientBuilder;
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::TransformMessage;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let client = ClientBuilder::new()
+//!     .tcp("127.0.0.1:18944")
+//!     .async_mode()
+//!     .build()
+//!     .await?;
+//! let (_reader, writer) = client.into_split()?;
+//!
+//! // 500 Hz in, at most 60 Hz per tool out
+//! let config = ConflationConfig::new().with_rate("TRANSFORM", 60.0);
+//! let (sender, task) = ConflatingSender::spawn(writer, config);
+//! for _ in 0..500 {
+//!     let msg = IgtlMessage::new(TransformMessage::identity(), "Tool")?;
+//!     sender.send(&msg).await?;
+//! }
+//! drop(sender);
+//! task.finish().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::Stream;
+use tokio::io::AsyncWrite;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+use tracing::{debug, trace, warn};
+
+use crate::error::{IgtlError, Result};
+use crate::io::scheduler::Priority;
+use crate::io::shutdown::{drain_task, join_task, CancellationToken};
+use crate::io::split::AsyncIgtlWriter;
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::AnyMessage;
+
+/// Number of messages a [`ConflatingSender`] buffers before `send` waits
+const CHANNEL_CAPACITY: usize = 256;
+
+/// Most frames a [`LatestByDevice`] stream reads ahead in one poll
+const MAX_READ_AHEAD: usize = 1024;
+
+/// Most messages that cannot be conflated (and errors) a [`LatestByDevice`] stream
+/// queues before it stops reading ahead
+const MAX_QUEUED_UNCONFLATED: usize = 64;
+
+/// Send rates of a [`ConflatingSender`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConflationConfig {
+    /// Maximum messages per second per device, keyed by message type
+    pub rates: HashMap<String, f64>,
+}
+
+impl ConflationConfig {
+    /// Create a configuration that limits no message type
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send at most `hz` messages per second per device for `message_type`
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hz` is not positive.
+    pub fn with_rate(mut self, message_type: impl Into<String>, hz: f64) -> Self {
+        assert!(hz > 0.0, "conflation rate must be positive");
+        self.rates.insert(message_type.into(), hz);
+        self
+    }
+
+    /// Minimum time between two messages of `message_type` from one device
+    pub fn interval(&self, message_type: &str) -> Option<Duration> {
+        self.rates
+            .get(message_type)
+            .map(|hz| Duration::from_secs_f64(1.0 / hz))
+    }
+}
+
+/// Counters of a [`ConflatingSender`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConflationStats {
+    /// Messages written to the transport
+    pub sent: u64,
+    /// Messages replaced by a newer one before they were sent
+    pub conflated: u64,
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    sent: AtomicU64,
+    conflated: AtomicU64,
+}
+
+/// An encoded message handed to the writer task
+struct Outgoing {
+    message_type: String,
+    device_name: String,
+    frame: Vec<u8>,
+}
+
+/// Sending side of a rate-limited, conflating writer
+///
+/// Cheap to clone; all clones feed the same writer task. The task finishes once every
+/// clone is dropped and the pending updates are sent. See the [module documentation](self).
+#[derive(Clone)]
+pub struct ConflatingSender {
+    tx: mpsc::Sender<Outgoing>,
+    counters: Arc<Counters>,
+}
+
+impl ConflatingSender {
+    /// Start a writer task for `writer` applying the rates of `config`
+    pub fn spawn<W>(
+        writer: AsyncIgtlWriter<W>,
+        config: ConflationConfig,
+    ) -> (Self, ConflationTask<W>)
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel(CHANNEL_CAPACITY);
+        let counters = Arc::new(Counters::default());
+        let stop = CancellationToken::new();
+        let handle = tokio::spawn(write_loop(
+            writer,
+            rx,
+            config,
+            Arc::clone(&counters),
+            stop.clone(),
+        ));
+        (
+            ConflatingSender { tx, counters },
+            ConflationTask { handle, stop },
+        )
+    }
+
+    /// Send a message, or hold it until its next tick if its type is rate limited
+    ///
+    /// Returns once the writer task took the message, not when it is written.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - The writer task stopped after a
+    ///   write error ([`BrokenPipe`](std::io::ErrorKind::BrokenPipe))
+    pub async fn send<T: Message>(&self, msg: &IgtlMessage<T>) -> Result<()> {
+        let outgoing = Outgoing {
+            message_type: T::message_type().to_string(),
+            device_name: msg.header.device_name.as_str()?.to_string(),
+            frame: msg.encode()?,
+        };
+        self.enqueue(outgoing).await
+    }
+
+    /// Send a dynamically typed message, see [`send`](Self::send)
+    pub async fn send_any(&self, msg: &AnyMessage) -> Result<()> {
+        let outgoing = Outgoing {
+            message_type: msg.message_type().to_string(),
+            device_name: msg.device_name()?.to_string(),
+            frame: msg.encode()?,
+        };
+        self.enqueue(outgoing).await
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> ConflationStats {
+        ConflationStats {
+            sent: self.counters.sent.load(Ordering::Relaxed),
+            conflated: self.counters.conflated.load(Ordering::Relaxed),
+        }
+    }
+
+    async fn enqueue(&self, outgoing: Outgoing) -> Result<()> {
+        self.tx.send(outgoing).await.map_err(|_| {
+            IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "conflating sender stopped after a write error",
+            ))
+        })
+    }
+}
+
+/// Writer task of a [`ConflatingSender`]
+pub struct ConflationTask<W> {
+    handle: JoinHandle<Result<AsyncIgtlWriter<W>>>,
+    stop: CancellationToken,
+}
+
+impl<W> ConflationTask<W> {
+    /// Wait until all senders are dropped and pending updates are sent
+    ///
+    /// Returns the writer for further use.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - A write failed; pending updates
+    ///   were discarded
+    pub async fn finish(mut self) -> Result<AsyncIgtlWriter<W>> {
+        join_task(&mut self.handle).await
+    }
+
+    /// Stop accepting messages and send the pending updates now, waiting up to `deadline`
+    ///
+    /// Held updates are sent without waiting for their tick; sends on any sender fail
+    /// from now on.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - A write failed, or
+    ///   [`TimedOut`](std::io::ErrorKind::TimedOut) if the updates were not sent in time;
+    ///   the task is stopped and the rest discarded
+    pub async fn shutdown(self, deadline: Duration) -> Result<AsyncIgtlWriter<W>> {
+        drain_task(self.handle, &self.stop, deadline).await
+    }
+
+    /// Stop the writer task, discarding pending updates
+    pub fn abort(&self) {
+        self.handle.abort();
+    }
+}
+
+/// Latest update of one (type, device) pair waiting for its tick
+struct Pending {
+    frame: Vec<u8>,
+    due: Instant,
+}
+
+struct Conflator<W> {
+    writer: AsyncIgtlWriter<W>,
+    config: ConflationConfig,
+    counters: Arc<Counters>,
+    pending: HashMap<(String, String), Pending>,
+    last_sent: HashMap<(String, String), Instant>,
+    /// Send held updates at once instead of at their tick
+    draining: bool,
+}
+
+impl<W: AsyncWrite + Unpin> Conflator<W> {
+    async fn write(&mut self, frame: Vec<u8>) -> Result<()> {
+        self.writer.write_frame(frame).await?;
+        self.counters.sent.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn handle(&mut self, outgoing: Outgoing) -> Result<()> {
+        let Some(interval) = self.config.interval(&outgoing.message_type) else {
+            return self.write(outgoing.frame).await;
+        };
+        let key = (outgoing.message_type, outgoing.device_name);
+        if let Some(pending) = self.pending.get_mut(&key) {
+            trace!(msg_type = %key.0, device = %key.1, "Conflating update");
+            pending.frame = outgoing.frame;
+            self.counters.conflated.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        let now = Instant::now();
+        match self.last_sent.get(&key) {
+            Some(&last) if last + interval > now => {
+                let due = if self.draining { now } else { last + interval };
+                let pending = Pending {
+                    frame: outgoing.frame,
+                    due,
+                };
+                self.pending.insert(key, pending);
+                Ok(())
+            }
+            _ => {
+                self.last_sent.insert(key, now);
+                self.write(outgoing.frame).await
+            }
+        }
+    }
+
+    /// Send every pending update whose tick has come, oldest tick first
+    async fn flush_due(&mut self) -> Result<()> {
+        let now = Instant::now();
+        let mut due: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, pending)| pending.due <= now)
+            .map(|(key, pending)| (pending.due, key.clone()))
+            .collect();
+        due.sort();
+        for (_, key) in due {
+            let pending = self.pending.remove(&key).expect("pending update");
+            self.last_sent.insert(key, now);
+            self.write(pending.frame).await?;
+        }
+        Ok(())
+    }
+
+    /// Make every held update due now
+    fn start_draining(&mut self) {
+        debug!("Draining conflating sender");
+        self.draining = true;
+        let now = Instant::now();
+        for pending in self.pending.values_mut() {
+            pending.due = now;
+        }
+    }
+
+    fn next_due(&self) -> Option<Instant> {
+        self.pending.values().map(|pending| pending.due).min()
+    }
+}
+
+async fn write_loop<W>(
+    writer: AsyncIgtlWriter<W>,
+    mut rx: mpsc::Receiver<Outgoing>,
+    config: ConflationConfig,
+    counters: Arc<Counters>,
+    stop: CancellationToken,
+) -> Result<AsyncIgtlWriter<W>>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut conflator = Conflator {
+        writer,
+        config,
+        counters,
+        pending: HashMap::new(),
+        last_sent: HashMap::new(),
+        draining: false,
+    };
+    let mut open = true;
+    let result = loop {
+        let next_due = conflator.next_due();
+        if !open && next_due.is_none() {
+            break Ok(());
+        }
+        let step = tokio::select! {
+            biased;
+            _ = stop.cancelled(), if !conflator.draining => {
+                rx.close();
+                conflator.start_draining();
+                Ok(())
+            }
+            _ = tokio::time::sleep_until(next_due.unwrap_or_else(Instant::now)), if next_due.is_some() => {
+                conflator.flush_due().await
+            }
+            outgoing = rx.recv(), if open => match outgoing {
+                Some(outgoing) => conflator.handle(outgoing).await,
+                None => {
+                    open = false;
+                    Ok(())
+                }
+            },
+        };
+        if let Err(e) = step {
+            break Err(e);
+        }
+    };
+    match result {
+        Ok(()) => {
+            debug!("Conflating sender finished");
+            Ok(conflator.writer)
+        }
+        Err(e) => {
+            warn!(error = %e, "Conflated write failed");
+            Err(e)
+        }
+    }
+}
+
+/// Stream adapter dropping superseded tracking messages
+///
+/// Created by [`AsyncIgtlReader::latest_by_device`](crate::io::AsyncIgtlReader::latest_by_device).
+/// Each poll first reads the messages already available from the transport. A queued
+/// tracking message (TRANSFORM, POSITION, TDATA, ...; see [`Priority::classify`]) is
+/// replaced in place when a newer message of the same type and device arrives; all
+/// other messages and errors are kept in order. Reading ahead stops while 64 of those
+/// are queued, so a burst of images or commands is not buffered without bound.
+pub struct LatestByDevice<S> {
+    inner: S,
+    queue: VecDeque<(u64, Result<AnyMessage>)>,
+    /// Sequence number of the queue slot holding each (type, device) pair's tracking message
+    latest: HashMap<(String, String), u64>,
+    next_seq: u64,
+    /// Queued messages that are not tracking slots, including errors
+    unconflated: usize,
+    discarded: u64,
+    done: bool,
+}
+
+impl<S> LatestByDevice<S>
+where
+    S: Stream<Item = Result<AnyMessage>> + Unpin,
+{
+    pub(crate) fn new(inner: S) -> Self {
+        LatestByDevice {
+            inner,
+            queue: VecDeque::new(),
+            latest: HashMap::new(),
+            next_seq: 0,
+            unconflated: 0,
+            discarded: 0,
+            done: false,
+        }
+    }
+
+    /// Number of stale messages dropped so far
+    pub fn discarded(&self) -> u64 {
+        self.discarded
+    }
+
+    /// Get a reference to the wrapped stream
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwrap the stream, dropping queued messages
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn push(&mut self, item: Result<AnyMessage>) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        let key = match &item {
+            Ok(msg) if Priority::classify(msg.message_type()) == Priority::Tracking => msg
+                .device_name()
+                .ok()
+                .map(|device| (msg.message_type().to_string(), device.to_string())),
+            _ => None,
+        };
+        if let Some(key) = key {
+            if let Some(&slot) = self.latest.get(&key) {
+                // Queue slots keep their sequence number, so they stay sorted
+                if let Ok(index) = self.queue.binary_search_by_key(&slot, |(s, _)| *s) {
+                    self.queue[index].1 = item;
+                    self.discarded += 1;
+                    return;
+                }
+            }
+            self.latest.insert(key, seq);
+        } else {
+            self.unconflated += 1;
+        }
+        self.queue.push_back((seq, item));
+    }
+
+    fn pop(&mut self) -> Option<Result<AnyMessage>> {
+        let (seq, item) = self.queue.pop_front()?;
+        let key = match &item {
+            Ok(msg) => msg
+                .device_name()
+                .ok()
+                .map(|device| (msg.message_type().to_string(), device.to_string())),
+            Err(_) => None,
+        };
+        match key {
+            Some(key) if self.latest.get(&key) == Some(&seq) => {
+                self.latest.remove(&key);
+            }
+            _ => self.unconflated -= 1,
+        }
+        Some(item)
+    }
+}
+
+impl<S> Stream for LatestByDevice<S>
+where
+    S: Stream<Item = Result<AnyMessage>> + Unpin,
+{
+    type Item = Result<AnyMessage>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mut read = 0;
+        while !this.done && read < MAX_READ_AHEAD && this.unconflated < MAX_QUEUED_UNCONFLATED {
+            match Pin::new(&mut this.inner).poll_next(cx) {
+                Poll::Ready(Some(item)) => {
+                    this.push(item);
+                    read += 1;
+                }
+                Poll::Ready(None) => this.done = true,
+                Poll::Pending => break,
+            }
+        }
+        match this.pop() {
+            Some(item) => Poll::Ready(Some(item)),
+            None if this.done => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::split::AsyncIgtlReader;
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+    use futures::StreamExt;
+
+    fn pose(device: &str, x: f32) -> IgtlMessage<TransformMessage> {
+        let mut transform = TransformMessage::identity();
+        transform.matrix[0][3] = x;
+        IgtlMessage::new(transform, device).unwrap()
+    }
+
+    fn x_of(msg: &AnyMessage) -> f32 {
+        msg.as_transform().unwrap().content.matrix[0][3]
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_conflation_sends_latest_value_per_tick() {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let config = ConflationConfig::new().with_rate("TRANSFORM", 10.0);
+        let (sender, task) = ConflatingSender::spawn(AsyncIgtlWriter::new(client), config);
+
+        // 50 updates per device within one 100 ms tick
+        for i in 0..50 {
+            sender.send(&pose("ToolA", i as f32)).await.unwrap();
+            sender.send(&pose("ToolB", -(i as f32))).await.unwrap();
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        let status = IgtlMessage::new(StatusMessage::ok("unlimited"), "Server").unwrap();
+        sender.send(&status).await.unwrap();
+
+        let mut reader = AsyncIgtlReader::new(server, true);
+        let mut a = Vec::new();
+        let mut b = Vec::new();
+        for _ in 0..5 {
+            let msg = reader.receive_any().await.unwrap();
+            match (msg.message_type(), msg.device_name().unwrap()) {
+                ("TRANSFORM", "ToolA") => a.push(x_of(&msg)),
+                ("TRANSFORM", "ToolB") => b.push(x_of(&msg)),
+                ("STATUS", _) => {}
+                other => panic!("unexpected message {other:?}"),
+            }
+        }
+        // The first update goes out at once, the last one at the next tick
+        assert_eq!(a, vec![0.0, 49.0]);
+        assert_eq!(b, vec![0.0, -49.0]);
+
+        // Counters are updated right after each write completes
+        while sender.stats().sent < 5 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(sender.stats().conflated, 96);
+        drop(sender);
+        task.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_latest_by_device_drops_stale_poses() {
+        let mut backlog = Vec::new();
+        for i in 0..10 {
+            backlog.push(Ok(AnyMessage::Transform(pose("ToolA", i as f32))));
+            if i == 4 {
+                let status = IgtlMessage::new(StatusMessage::ok("mid"), "Server").unwrap();
+                backlog.push(Ok(AnyMessage::Status(status)));
+            }
+            backlog.push(Ok(AnyMessage::Transform(pose("ToolB", i as f32))));
+        }
+
+        let mut stream = LatestByDevice::new(futures::stream::iter(backlog));
+        let mut received = Vec::new();
+        while let Some(msg) = stream.next().await {
+            let msg = msg.unwrap();
+            received.push((
+                msg.device_name().unwrap().to_string(),
+                msg.message_type().to_string(),
+            ));
+            if msg.message_type() == "TRANSFORM" {
+                assert_eq!(x_of(&msg), 9.0);
+            }
+        }
+        assert_eq!(
+            received,
+            vec![
+                ("ToolA".to_string(), "TRANSFORM".to_string()),
+                ("ToolB".to_string(), "TRANSFORM".to_string()),
+                ("Server".to_string(), "STATUS".to_string()),
+            ]
+        );
+        assert_eq!(stream.discarded(), 18);
+    }
+
+    #[tokio::test]
+    async fn test_latest_by_device_bounds_unconflated_backlog() {
+        let polled = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&polled);
+        let statuses = futures::stream::iter(0..10_000).map(move |i| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            let status = IgtlMessage::new(StatusMessage::ok(&i.to_string()), "Server").unwrap();
+            Ok(AnyMessage::Status(status))
+        });
+
+        let mut stream = LatestByDevice::new(statuses);
+        for i in 0..3 {
+            let msg = stream.next().await.unwrap().unwrap();
+            assert_eq!(
+                msg.as_status().unwrap().content.status_string,
+                i.to_string()
+            );
+        }
+        assert!(polled.load(Ordering::Relaxed) <= (MAX_QUEUED_UNCONFLATED + 3) as u64);
+    }
+}
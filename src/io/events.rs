@@ -0,0 +1,350 @@
+//! Connection lifecycle events
+//!
+//! Clients and servers publish [`ConnectionEvent`]s on a [`tokio::sync::broadcast`]
+//! channel, so a UI can show a live connection indicator or raise an alarm when the
+//! navigation link drops without polling [`is_connected`](crate::io::AsyncIgtlClient::is_connected).
+//!
+//! - Clients: [`subscribe_events`](crate::io::AsyncIgtlClient::subscribe_events), or pass a
+//!   sender with [`ClientBuilder::with_events`](crate::io::builder::ClientBuilder::with_events) to also
+//!   observe the initial connection
+//! - Servers: [`subscribe_events`](crate::io::AsyncIgtlServer::subscribe_events) reports
+//!   events of all accepted sessions
+//!
+//! Events are delivered on a best-effort basis: nothing is buffered while there are no
+//! subscribers, and slow subscribers receive
+//! [`RecvError::Lagged`](tokio::sync::broadcast::error::RecvError::Lagged).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::builder::ClientBuilder;
+//! use openigtlink_rust::io::events::ConnectionEvent;
+//! use openigtlink_rust::io::reconnect::ReconnectConfig;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let (events_tx, mut events) = tokio::sync::broadcast::channel(64);
+//! let client = ClientBuilder::new()
+//!     .tcp("127.0.0.1:18944")
+//!     .async_mode()
+//!     .with_reconnect(ReconnectConfig::infinite())
+//!     .with_events(events_tx)
+//!     .build()
+//!     .await?;
+//!
+//! tokio::spawn(async move {
+//!     while let Ok(event) = events.recv().await {
+//!         match event {
+//!             ConnectionEvent::Connected { peer } => println!("Link up ({})", peer),
+//!             ConnectionEvent::Disconnected { reason, .. } => eprintln!("Link down: {}", reason),
+//!             ConnectionEvent::GaveUp { .. } => eprintln!("Navigation link lost"),
+//!             _ => {}
+//!         }
+//!     }
+//! });
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::broadcast;
+use tracing::trace;
+
+use crate::error::{IgtlError, Result};
+
+/// Default capacity of event channels created by clients and servers
+pub const DEFAULT_EVENT_CAPACITY: usize = 64;
+
+/// Connection lifecycle event
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionEvent {
+    /// A connection attempt to `addr` started
+    Connecting {
+        /// Address being connected to
+        addr: String,
+    },
+    /// The connection is established (client) or a session was accepted (server)
+    Connected {
+        /// Remote address
+        peer: SocketAddr,
+    },
+    /// A server closed a new connection right away because a session limit was reached
+    ///
+    /// See [`ServerLimits`](crate::io::limits::ServerLimits).
+    Rejected {
+        /// Remote address
+        peer: SocketAddr,
+        /// Limit that was reached
+        reason: String,
+    },
+    /// The connection was closed or declared dead
+    ///
+    /// Reported once per connection: when the peer closes it, a read or write fails, the
+    /// server closes the session, or the connection (all halves of a split connection)
+    /// is dropped. A reconnecting client reports it once per lost connection.
+    Disconnected {
+        /// Remote address, if it was known
+        peer: Option<SocketAddr>,
+        /// Why the connection ended
+        reason: String,
+    },
+    /// A reconnection attempt is about to be made after `delay`
+    ReconnectAttempt {
+        /// Attempt number, starting at 1
+        attempt: usize,
+        /// Backoff delay before the attempt
+        delay: Duration,
+    },
+    /// Reconnection was abandoned after the maximum number of attempts
+    GaveUp {
+        /// Number of failed attempts
+        attempts: usize,
+    },
+    /// The TLS handshake failed
+    TlsHandshakeFailed {
+        /// Remote address, if it was known
+        peer: Option<SocketAddr>,
+        /// Handshake error
+        error: String,
+    },
+    /// A received message failed CRC verification
+    CrcError {
+        /// Remote address, if it was known
+        peer: Option<SocketAddr>,
+        /// CRC carried in the header
+        expected: u64,
+        /// CRC calculated from the received body
+        actual: u64,
+    },
+}
+
+impl fmt::Display for ConnectionEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConnectionEvent::Connecting { addr } => write!(f, "connecting to {}", addr),
+            ConnectionEvent::Connected { peer } => write!(f, "connected to {}", peer),
+            ConnectionEvent::Rejected { peer, reason } => {
+                write!(f, "rejected {}: {}", peer, reason)
+            }
+            ConnectionEvent::Disconnected { reason, .. } => write!(f, "disconnected: {}", reason),
+            ConnectionEvent::ReconnectAttempt { attempt, delay } => {
+                write!(f, "reconnect attempt {} in {:?}", attempt, delay)
+            }
+            ConnectionEvent::GaveUp { attempts } => {
+                write!(f, "gave up after {} reconnect attempts", attempts)
+            }
+            ConnectionEvent::TlsHandshakeFailed { error, .. } => {
+                write!(f, "TLS handshake failed: {}", error)
+            }
+            ConnectionEvent::CrcError {
+                expected, actual, ..
+            } => write!(
+                f,
+                "CRC mismatch (expected {:#018x}, got {:#018x})",
+                expected, actual
+            ),
+        }
+    }
+}
+
+/// Sending side of an event channel shared by a client or server and its sessions
+///
+/// An emitter created with [`connection`](Self::connection) belongs to one connection:
+/// it and its clones (e.g. the halves of a split connection) report
+/// [`Disconnected`](ConnectionEvent::Disconnected) at most once, and when the last of
+/// them is dropped without having reported it.
+#[derive(Debug, Clone)]
+pub(crate) struct EventEmitter {
+    tx: broadcast::Sender<ConnectionEvent>,
+    connection: Option<Arc<ConnectionState>>,
+}
+
+/// Disconnection state shared by the emitters of one connection
+#[derive(Debug)]
+struct ConnectionState {
+    tx: broadcast::Sender<ConnectionEvent>,
+    peer: Option<SocketAddr>,
+    ended: AtomicBool,
+}
+
+impl Drop for ConnectionState {
+    fn drop(&mut self) {
+        if !self.ended.swap(true, Ordering::AcqRel) {
+            let _ = self.tx.send(ConnectionEvent::Disconnected {
+                peer: self.peer,
+                reason: "connection dropped".to_string(),
+            });
+        }
+    }
+}
+
+impl Default for EventEmitter {
+    fn default() -> Self {
+        Self::new(broadcast::channel(DEFAULT_EVENT_CAPACITY).0)
+    }
+}
+
+impl EventEmitter {
+    pub(crate) fn new(tx: broadcast::Sender<ConnectionEvent>) -> Self {
+        Self {
+            tx,
+            connection: None,
+        }
+    }
+
+    /// Emitter for a new connection to `peer` on the same channel
+    pub(crate) fn connection(&self, peer: Option<SocketAddr>) -> Self {
+        Self {
+            tx: self.tx.clone(),
+            connection: Some(Arc::new(ConnectionState {
+                tx: self.tx.clone(),
+                peer,
+                ended: AtomicBool::new(false),
+            })),
+        }
+    }
+
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.tx.subscribe()
+    }
+
+    pub(crate) fn emit(&self, event: ConnectionEvent) {
+        trace!(event = %event, "Connection event");
+        // No subscribers is not an error
+        let _ = self.tx.send(event);
+    }
+
+    /// Report an ended connection, once per connection emitter
+    pub(crate) fn disconnected(&self, peer: Option<SocketAddr>, reason: &dyn fmt::Display) {
+        if let Some(connection) = &self.connection {
+            if connection.ended.swap(true, Ordering::AcqRel) {
+                return;
+            }
+        }
+        self.emit(ConnectionEvent::Disconnected {
+            peer,
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Report a CRC failure contained in a decode result
+    pub(crate) fn check_decode<T>(&self, result: &Result<T>, peer: Option<SocketAddr>) {
+        if let Err(IgtlError::CrcMismatch { expected, actual }) = result {
+            self.emit(ConnectionEvent::CrcError {
+                peer,
+                expected: *expected,
+                actual: *actual,
+            });
+        }
+    }
+
+    /// Report CRC errors and lost connections contained in an operation result
+    ///
+    /// Timeouts and decode errors other than CRC mismatches leave the connection usable
+    /// and are not reported.
+    pub(crate) fn check_result<T>(&self, result: &Result<T>, peer: Option<SocketAddr>) {
+        match result {
+            Err(IgtlError::CrcMismatch { .. }) => self.check_decode(result, peer),
+            Err(e @ IgtlError::Io(io)) if is_connection_lost(io.kind()) => {
+                self.disconnected(peer, e)
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether an I/O error kind means the connection is gone
+fn is_connection_lost(kind: std::io::ErrorKind) -> bool {
+    use std::io::ErrorKind::*;
+    matches!(
+        kind,
+        UnexpectedEof | ConnectionReset | ConnectionAborted | BrokenPipe | NotConnected
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_emitter_reports_crc_and_disconnect() {
+        let emitter = EventEmitter::default();
+        let mut rx = emitter.subscribe();
+        let peer: SocketAddr = "127.0.0.1:18944".parse().unwrap();
+
+        let crc: Result<()> = Err(IgtlError::CrcMismatch {
+            expected: 1,
+            actual: 2,
+        });
+        emitter.check_result(&crc, Some(peer));
+        let eof: Result<()> = Err(IgtlError::Io(std::io::Error::new(
+            std::io::ErrorKind::UnexpectedEof,
+            "closed",
+        )));
+        emitter.check_result(&eof, Some(peer));
+        let timeout: Result<()> = Err(IgtlError::Io(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "slow",
+        )));
+        emitter.check_result(&timeout, Some(peer));
+
+        assert_eq!(
+            rx.recv().await.unwrap(),
+            ConnectionEvent::CrcError {
+                peer: Some(peer),
+                expected: 1,
+                actual: 2
+            }
+        );
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            ConnectionEvent::Disconnected { peer: Some(p), .. } if p == peer
+        ));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_connection_reports_disconnect_once() {
+        let emitter = EventEmitter::default();
+        let mut rx = emitter.subscribe();
+        let peer: SocketAddr = "127.0.0.1:18944".parse().unwrap();
+        let eof = || -> Result<()> {
+            Err(IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "closed",
+            )))
+        };
+
+        let connection = emitter.connection(Some(peer));
+        let half = connection.clone();
+        connection.check_result(&eof(), Some(peer));
+        half.check_result(&eof(), Some(peer));
+        drop((connection, half));
+        let expected = eof().unwrap_err().to_string();
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            ConnectionEvent::Disconnected { reason, .. } if reason == expected
+        ));
+        assert!(rx.try_recv().is_err());
+
+        // Dropped without an error: reported once the last clone is gone
+        let connection = emitter.connection(Some(peer));
+        let half = connection.clone();
+        drop(connection);
+        assert!(rx.try_recv().is_err());
+        drop(half);
+        assert!(matches!(
+            rx.recv().await.unwrap(),
+            ConnectionEvent::Disconnected { reason, .. } if reason == "connection dropped"
+        ));
+    }
+
+    #[test]
+    fn test_emit_without_subscribers() {
+        EventEmitter::default().emit(ConnectionEvent::GaveUp { attempts: 3 });
+    }
+}
@@ -0,0 +1,82 @@
+//! Concurrent connection handshakes of the TLS and WebSocket servers
+//!
+//! A client that connects and then stalls or sends garbage must not keep `accept` from
+//! serving other clients. [`Handshakes`] accepts TCP connections and runs their
+//! handshakes side by side, each within the deadline of its
+//! [`SessionGovernor`](crate::io::limits::SessionGovernor).
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::time::Instant;
+
+use futures::stream::FuturesUnordered;
+use futures::StreamExt;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::Mutex;
+
+/// A handshake in progress, yielding `None` if it failed (already logged and reported)
+pub(crate) type Handshake<T> = Pin<Box<dyn Future<Output = Option<T>> + Send>>;
+
+/// Handshakes in progress on connections accepted from one listener
+pub(crate) struct Handshakes<T> {
+    pending: Mutex<FuturesUnordered<Handshake<T>>>,
+}
+
+impl<T> Default for Handshakes<T> {
+    fn default() -> Self {
+        Handshakes {
+            pending: Mutex::new(FuturesUnordered::new()),
+        }
+    }
+}
+
+impl<T> Handshakes<T> {
+    /// Accept connections from `listener` until a handshake completes
+    ///
+    /// `start` begins the handshake of an accepted connection, or returns `None` to drop
+    /// it. Cancel-safe: handshakes in progress continue on the next call.
+    pub(crate) async fn next<F>(&self, listener: &TcpListener, mut start: F) -> std::io::Result<T>
+    where
+        F: FnMut(TcpStream, SocketAddr) -> Option<Handshake<T>>,
+    {
+        let mut pending = self.pending.lock().await;
+        loop {
+            tokio::select! {
+                biased;
+                Some(done) = pending.next(), if !pending.is_empty() => {
+                    if let Some(connection) = done {
+                        return Ok(connection);
+                    }
+                }
+                accepted = listener.accept() => {
+                    let (stream, peer) = accepted?;
+                    if let Some(handshake) = start(stream, peer) {
+                        pending.push(handshake);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Abort all handshakes in progress, e.g. on shutdown
+    pub(crate) async fn clear(&self) {
+        self.pending.lock().await.clear();
+    }
+}
+
+/// Run `handshake`, failing with [`TimedOut`](std::io::ErrorKind::TimedOut) at `deadline`
+pub(crate) async fn with_deadline<F, T, E>(deadline: Instant, handshake: F) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: From<std::io::Error>,
+{
+    match tokio::time::timeout_at(deadline.into(), handshake).await {
+        Ok(result) => result,
+        Err(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "handshake not completed before the deadline",
+        )
+        .into()),
+    }
+}
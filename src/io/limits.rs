@@ -0,0 +1,478 @@
+//! Server connection limits and per-session resource governance
+//!
+//! By default servers accept any number of sessions and wait forever for their
+//! messages. [`ServerLimits`] protects a server from misbehaving or malicious clients:
+//!
+//! - **Session limits** - maximum number of concurrent sessions, in total and per
+//!   client IP address. Connections above a limit are closed right after accept and
+//!   reported as [`ConnectionEvent::Rejected`](crate::io::ConnectionEvent::Rejected);
+//!   `accept` keeps waiting for the next client.
+//! - **First-message deadline** - the TLS or WebSocket handshake and the first message
+//!   must complete within this time after connecting. Without it, handshakes alone are
+//!   limited to [`DEFAULT_HANDSHAKE_TIMEOUT`]; a handshake that fails or times out is
+//!   logged and `accept` keeps waiting for the next client.
+//! - **Idle eviction** - a session that sends nothing for this long is closed
+//! - **Inbound rate limits** - messages and bytes per second, averaged with a burst
+//!   allowance of one second
+//!
+//! A session violating a limit is closed: the pending `receive` fails with
+//! [`ConnectionAborted`](std::io::ErrorKind::ConnectionAborted) carrying the reason,
+//! which is also logged and reported as
+//! [`ConnectionEvent::Disconnected`](crate::io::ConnectionEvent::Disconnected). Later
+//! calls fail with [`NotConnected`](std::io::ErrorKind::NotConnected).
+//!
+//! Limits are checked when the application receives, so a flooding client cannot make
+//! the server read faster than it wants to.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::limits::ServerLimits;
+//! use openigtlink_rust::io::AsyncIgtlServer;
+//! use std::time::Duration;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let limits = ServerLimits::new()
+//!     .with_max_sessions(16)
+//!     .with_max_sessions_per_ip(2)
+//!     .with_first_message_timeout(Duration::from_secs(5))
+//!     .with_idle_timeout(Duration::from_secs(30))
+//!     .with_max_messages_per_sec(500)
+//!     .with_max_bytes_per_sec(50_000_000);
+//!
+//! let server = AsyncIgtlServer::bind("0.0.0.0:18944")
+//!     .await?
+//!     .with_limits(limits);
+//! loop {
+//!     let mut conn = server.accept().await?;
+//!     tokio::spawn(async move {
+//!         while let Ok(msg) = conn.receive_any().await {
+//!             // handle msg
+//!         }
+//!     });
+//! }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::Notify;
+use tracing::warn;
+
+use crate::error::{IgtlError, Result};
+use crate::io::events::{ConnectionEvent, EventEmitter};
+
+/// Time allowed for a TLS or WebSocket handshake if no first-message deadline is set
+pub const DEFAULT_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection and resource limits of a server
+///
+/// Every limit is disabled by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerLimits {
+    /// Maximum number of concurrent sessions
+    pub max_sessions: Option<usize>,
+    /// Maximum number of concurrent sessions from one IP address
+    pub max_sessions_per_ip: Option<usize>,
+    /// Time allowed after connecting for the TLS or WebSocket handshake and the first
+    /// message
+    pub first_message_timeout: Option<Duration>,
+    /// Time without received messages after which a session is closed
+    pub idle_timeout: Option<Duration>,
+    /// Maximum average number of received messages per second
+    pub max_messages_per_sec: Option<u32>,
+    /// Maximum average number of received bytes per second
+    pub max_bytes_per_sec: Option<u64>,
+}
+
+impl ServerLimits {
+    /// Create limits with every limit disabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limit the number of concurrent sessions
+    pub fn with_max_sessions(mut self, sessions: usize) -> Self {
+        self.max_sessions = Some(sessions);
+        self
+    }
+
+    /// Limit the number of concurrent sessions from one IP address
+    pub fn with_max_sessions_per_ip(mut self, sessions: usize) -> Self {
+        self.max_sessions_per_ip = Some(sessions);
+        self
+    }
+
+    /// Require the TLS or WebSocket handshake and the first message within `timeout` of
+    /// connecting
+    pub fn with_first_message_timeout(mut self, timeout: Duration) -> Self {
+        self.first_message_timeout = Some(timeout);
+        self
+    }
+
+    /// Close sessions that send nothing for `timeout`
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Limit the average number of received messages per second
+    pub fn with_max_messages_per_sec(mut self, messages: u32) -> Self {
+        self.max_messages_per_sec = Some(messages);
+        self
+    }
+
+    /// Limit the average number of received bytes per second
+    pub fn with_max_bytes_per_sec(mut self, bytes: u64) -> Self {
+        self.max_bytes_per_sec = Some(bytes);
+        self
+    }
+}
+
+/// Session counts of one server
+#[derive(Debug, Default)]
+pub(crate) struct SessionTracker {
+    state: Mutex<TrackerState>,
+    /// Notified whenever a session closes
+    closed: Notify,
+}
+
+#[derive(Debug, Default)]
+struct TrackerState {
+    total: usize,
+    per_ip: HashMap<IpAddr, usize>,
+}
+
+impl SessionTracker {
+    /// Number of open sessions
+    pub(crate) fn active(&self) -> usize {
+        self.state.lock().unwrap().total
+    }
+
+    /// Wait until no session is open
+    pub(crate) async fn wait_idle(&self) {
+        loop {
+            let closed = self.closed.notified();
+            if self.active() == 0 {
+                return;
+            }
+            closed.await;
+        }
+    }
+
+    /// Admit a new session from `peer`, or return why it is rejected
+    pub(crate) fn admit(
+        self: &Arc<Self>,
+        peer: SocketAddr,
+        limits: &ServerLimits,
+    ) -> std::result::Result<SessionGovernor, String> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(max) = limits.max_sessions {
+            if state.total >= max {
+                return Err(format!("session limit reached ({} sessions)", max));
+            }
+        }
+        let from_ip = state.per_ip.get(&peer.ip()).copied().unwrap_or(0);
+        if let Some(max) = limits.max_sessions_per_ip {
+            if from_ip >= max {
+                return Err(format!(
+                    "per-IP session limit reached ({} sessions from {})",
+                    max,
+                    peer.ip()
+                ));
+            }
+        }
+        state.total += 1;
+        state.per_ip.insert(peer.ip(), from_ip + 1);
+        drop(state);
+
+        let slot = SessionSlot {
+            tracker: Arc::clone(self),
+            ip: peer.ip(),
+        };
+        Ok(SessionGovernor::new(slot, peer, limits))
+    }
+}
+
+/// Admit a session from `peer`, or close and report the connection if a limit is reached
+pub(crate) fn admit_session(
+    sessions: &Arc<SessionTracker>,
+    limits: &ServerLimits,
+    events: &EventEmitter,
+    peer: SocketAddr,
+) -> Option<SessionGovernor> {
+    match sessions.admit(peer, limits) {
+        Ok(session) => Some(session),
+        Err(reason) => {
+            warn!(peer_addr = %peer, reason = %reason, "Connection rejected");
+            events.emit(ConnectionEvent::Rejected { peer, reason });
+            None
+        }
+    }
+}
+
+/// A counted session, released when dropped
+#[derive(Debug)]
+pub(crate) struct SessionSlot {
+    tracker: Arc<SessionTracker>,
+    ip: IpAddr,
+}
+
+impl Drop for SessionSlot {
+    fn drop(&mut self) {
+        let mut state = self.tracker.state.lock().unwrap();
+        state.total -= 1;
+        if let Some(count) = state.per_ip.get_mut(&self.ip) {
+            *count -= 1;
+            if *count == 0 {
+                state.per_ip.remove(&self.ip);
+            }
+        }
+        drop(state);
+        self.tracker.closed.notify_waiters();
+    }
+}
+
+/// Token bucket holding up to one second of `rate`
+#[derive(Debug)]
+struct RateBucket {
+    rate: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateBucket {
+    fn new(rate: f64, now: Instant) -> Self {
+        RateBucket {
+            rate,
+            tokens: rate,
+            updated: now,
+        }
+    }
+
+    /// Take `amount` tokens, failing if there are not enough
+    ///
+    /// A message larger than the bucket is accepted when the bucket is full and borrows
+    /// from the following seconds.
+    fn take(&mut self, amount: f64, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        self.updated = now;
+        if self.tokens < amount.min(self.rate) {
+            return false;
+        }
+        self.tokens -= amount;
+        true
+    }
+}
+
+/// Limit enforcement for one accepted session
+#[derive(Debug)]
+pub(crate) struct SessionGovernor {
+    /// Keeps the session counted until dropped
+    slot: Option<SessionSlot>,
+    peer: SocketAddr,
+    first_message_timeout: Option<Duration>,
+    idle_timeout: Option<Duration>,
+    connected_at: Instant,
+    last_rx: Option<Instant>,
+    messages: Option<RateBucket>,
+    bytes: Option<RateBucket>,
+    /// Why the session was closed
+    closed: Option<String>,
+}
+
+impl SessionGovernor {
+    fn new(slot: SessionSlot, peer: SocketAddr, limits: &ServerLimits) -> Self {
+        let now = Instant::now();
+        SessionGovernor {
+            slot: Some(slot),
+            peer,
+            first_message_timeout: limits.first_message_timeout,
+            idle_timeout: limits.idle_timeout,
+            connected_at: now,
+            last_rx: None,
+            messages: limits
+                .max_messages_per_sec
+                .map(|rate| RateBucket::new(rate as f64, now)),
+            bytes: limits
+                .max_bytes_per_sec
+                .map(|rate| RateBucket::new(rate as f64, now)),
+            closed: None,
+        }
+    }
+
+    /// Hand over the session slot, e.g. to the halves of a split connection
+    pub(crate) fn take_slot(&mut self) -> Option<SessionSlot> {
+        self.slot.take()
+    }
+
+    /// Fail if the session was closed
+    pub(crate) fn check_open(&self) -> Result<()> {
+        match &self.closed {
+            Some(reason) => Err(IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                format!("session closed: {}", reason),
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Mark the session closed for `reason`, e.g. a server shutdown
+    pub(crate) fn close(&mut self, reason: &str) {
+        self.closed.get_or_insert_with(|| reason.to_string());
+    }
+
+    /// Whether the session was closed
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.is_some()
+    }
+
+    /// Time by which the next message must arrive
+    pub(crate) fn deadline(&self) -> Option<Instant> {
+        match self.last_rx {
+            None => self.first_message_timeout.map(|t| self.connected_at + t),
+            Some(last_rx) => self.idle_timeout.map(|t| last_rx + t),
+        }
+    }
+
+    /// Time by which the TLS or WebSocket handshake must complete
+    pub(crate) fn handshake_deadline(&self) -> Instant {
+        self.connected_at
+            + self
+                .first_message_timeout
+                .unwrap_or(DEFAULT_HANDSHAKE_TIMEOUT)
+    }
+
+    /// Close the session because the deadline passed
+    pub(crate) fn expire(&mut self) -> IgtlError {
+        let reason = match self.last_rx {
+            None => format!(
+                "no message within {:?} of connecting",
+                self.first_message_timeout.unwrap_or_default()
+            ),
+            Some(_) => format!("idle for {:?}", self.idle_timeout.unwrap_or_default()),
+        };
+        self.violate(reason)
+    }
+
+    /// Account for a received frame, closing the session if it exceeds a rate limit
+    pub(crate) fn check_frame(&mut self, frame: Vec<u8>) -> Result<Vec<u8>> {
+        let now = Instant::now();
+        self.last_rx = Some(now);
+        if let Some(bucket) = &mut self.messages {
+            if !bucket.take(1.0, now) {
+                let reason = format!("inbound message rate above {} messages/s", bucket.rate);
+                return Err(self.violate(reason));
+            }
+        }
+        if let Some(bucket) = &mut self.bytes {
+            if !bucket.take(frame.len() as f64, now) {
+                let reason = format!("inbound data rate above {} bytes/s", bucket.rate);
+                return Err(self.violate(reason));
+            }
+        }
+        Ok(frame)
+    }
+
+    /// Read one frame with `read`, enforcing the deadline and rate limits
+    pub(crate) async fn read_frame<F>(&mut self, read: F) -> Result<Vec<u8>>
+    where
+        F: std::future::Future<Output = Result<Vec<u8>>>,
+    {
+        let frame = match self.deadline() {
+            Some(deadline) => match tokio::time::timeout_at(deadline.into(), read).await {
+                Ok(frame) => frame?,
+                Err(_) => return Err(self.expire()),
+            },
+            None => read.await?,
+        };
+        self.check_frame(frame)
+    }
+
+    fn violate(&mut self, reason: String) -> IgtlError {
+        warn!(peer_addr = %self.peer, reason = %reason, "Closing session: limit violated");
+        let error = IgtlError::Io(std::io::Error::new(
+            std::io::ErrorKind::ConnectionAborted,
+            reason.clone(),
+        ));
+        self.closed = Some(reason);
+        error
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(ip: &str, port: u16) -> SocketAddr {
+        SocketAddr::new(ip.parse().unwrap(), port)
+    }
+
+    #[test]
+    fn test_session_and_per_ip_limits() {
+        let tracker = Arc::new(SessionTracker::default());
+        let limits = ServerLimits::new()
+            .with_max_sessions(3)
+            .with_max_sessions_per_ip(2);
+
+        let a1 = tracker.admit(peer("10.0.0.1", 1), &limits).unwrap();
+        let _a2 = tracker.admit(peer("10.0.0.1", 2), &limits).unwrap();
+        let err = tracker.admit(peer("10.0.0.1", 3), &limits).unwrap_err();
+        assert!(err.contains("per-IP"));
+
+        let _b1 = tracker.admit(peer("10.0.0.2", 1), &limits).unwrap();
+        let err = tracker.admit(peer("10.0.0.3", 1), &limits).unwrap_err();
+        assert!(err.contains("session limit reached"));
+        assert_eq!(tracker.active(), 3);
+
+        drop(a1);
+        assert_eq!(tracker.active(), 2);
+        tracker.admit(peer("10.0.0.1", 4), &limits).unwrap();
+    }
+
+    #[test]
+    fn test_message_rate_limit_closes_session() {
+        let tracker = Arc::new(SessionTracker::default());
+        let limits = ServerLimits::new().with_max_messages_per_sec(10);
+        let mut session = tracker.admit(peer("10.0.0.1", 1), &limits).unwrap();
+
+        let accepted = (0..100)
+            .take_while(|_| session.check_frame(vec![0; 60]).is_ok())
+            .count();
+        assert_eq!(accepted, 10);
+        assert!(session.is_closed());
+        let err = session.check_open().unwrap_err();
+        assert!(
+            matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::NotConnected)
+        );
+    }
+
+    #[test]
+    fn test_byte_rate_limit_allows_one_large_message() {
+        let tracker = Arc::new(SessionTracker::default());
+        let limits = ServerLimits::new().with_max_bytes_per_sec(1000);
+        let mut session = tracker.admit(peer("10.0.0.1", 1), &limits).unwrap();
+
+        assert!(session.check_frame(vec![0; 5000]).is_ok());
+        let err = session.check_frame(vec![0; 60]).unwrap_err();
+        assert!(err.to_string().contains("bytes/s"));
+    }
+
+    #[test]
+    fn test_deadline_switches_from_first_message_to_idle() {
+        let tracker = Arc::new(SessionTracker::default());
+        let limits = ServerLimits::new()
+            .with_first_message_timeout(Duration::from_secs(1))
+            .with_idle_timeout(Duration::from_secs(10));
+        let mut session = tracker.admit(peer("10.0.0.1", 1), &limits).unwrap();
+
+        let first = session.deadline().unwrap();
+        assert!(first <= Instant::now() + Duration::from_secs(1));
+        session.check_frame(vec![0; 60]).unwrap();
+        assert!(session.deadline().unwrap() > Instant::now() + Duration::from_secs(9));
+    }
+}
@@ -0,0 +1,428 @@
+//! Connection liveness: TCP keepalive, heartbeats and timeouts
+//!
+//! A half-open TCP connection (peer powered off, cable pulled, NAT entry expired) never
+//! delivers an error on its own, so a client blocked in `receive` can hang forever.
+//! [`LivenessConfig`] bundles the mechanisms used to detect such connections:
+//!
+//! - **TCP keepalive** - the OS probes an idle connection and fails it if the peer is gone
+//! - **Heartbeats** - STATUS or GET_STATUS messages sent when nothing was sent for a while
+//! - **Read / write timeouts** - per-operation deadlines; the connection stays usable
+//! - **Idle timeout** - the connection is declared dead when nothing was received for too
+//!   long, triggering reconnection if enabled
+//!
+//! GET_STATUS heartbeats make the peer answer with a STATUS message, so together with an
+//! idle timeout they detect a dead peer even when it normally sends nothing. Each heartbeat
+//! carries a v3 message ID; replies echoing an outstanding ID are consumed and never
+//! returned from `receive`. Replies without the ID (e.g. from v2 peers) are delivered like
+//! any other STATUS message, but still keep the idle timer alive.
+//!
+//! Clients take a [`LivenessConfig`] through
+//! [`ClientBuilder::with_liveness`](crate::io::builder::ClientBuilder); server connections
+//! through `set_liveness`, e.g.
+//! [`AsyncIgtlConnection::set_liveness`](crate::io::AsyncIgtlConnection::set_liveness).
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::builder::ClientBuilder;
+//! use openigtlink_rust::io::liveness::{HeartbeatConfig, LivenessConfig};
+//! use openigtlink_rust::io::reconnect::ReconnectConfig;
+//! use std::time::Duration;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let liveness = LivenessConfig::new()
+//!     .with_tcp_keepalive(Duration::from_secs(10))
+//!     .with_heartbeat(HeartbeatConfig::get_status(Duration::from_secs(1)))
+//!     .with_idle_timeout(Duration::from_secs(3))
+//!     .with_write_timeout(Duration::from_secs(2));
+//!
+//! let mut client = ClientBuilder::new()
+//!     .tcp("127.0.0.1:18944")
+//!     .async_mode()
+//!     .with_liveness(liveness)
+//!     .with_reconnect(ReconnectConfig::infinite())
+//!     .build()
+//!     .await?;
+//!
+//! // Reconnects transparently if the tracker stops answering heartbeats
+//! let msg = client.receive_any().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::future::Future;
+use std::time::Duration;
+
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+use crate::error::{IgtlError, Result};
+use crate::io::common::frame_message_id;
+use crate::protocol::header::Header;
+use crate::protocol::message::IgtlMessage;
+use crate::protocol::types::{GetStatusMessage, StatusMessage};
+
+/// Default device name of heartbeat messages
+pub const DEFAULT_HEARTBEAT_DEVICE: &str = "Heartbeat";
+
+/// GET_STATUS heartbeats remembered while waiting for their reply
+///
+/// Peers that do not echo message IDs never answer a heartbeat by ID, so the oldest
+/// entries are forgotten.
+const MAX_OUTSTANDING_HEARTBEATS: usize = 16;
+
+/// Message used as heartbeat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeartbeatKind {
+    /// One-way STATUS (OK) message; detects dead peers through failing writes
+    Status,
+    /// GET_STATUS round trip; the STATUS reply keeps the idle timer alive
+    GetStatus,
+}
+
+/// Application-level heartbeat configuration
+///
+/// A heartbeat is sent whenever nothing has been sent for `interval`.
+#[derive(Debug, Clone)]
+pub struct HeartbeatConfig {
+    /// Time without outgoing traffic after which a heartbeat is sent
+    pub interval: Duration,
+    /// Message used as heartbeat
+    pub kind: HeartbeatKind,
+    /// Device name of heartbeat messages
+    pub device_name: String,
+}
+
+impl HeartbeatConfig {
+    /// STATUS heartbeat sent every `interval`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use openigtlink_rust::io::liveness::{HeartbeatConfig, HeartbeatKind};
+    /// use std::time::Duration;
+    ///
+    /// let config = HeartbeatConfig::status(Duration::from_secs(1));
+    /// assert_eq!(config.kind, HeartbeatKind::Status);
+    /// ```
+    pub fn status(interval: Duration) -> Self {
+        Self {
+            interval,
+            kind: HeartbeatKind::Status,
+            device_name: DEFAULT_HEARTBEAT_DEVICE.to_string(),
+        }
+    }
+
+    /// GET_STATUS heartbeat sent every `interval`
+    pub fn get_status(interval: Duration) -> Self {
+        Self {
+            interval,
+            kind: HeartbeatKind::GetStatus,
+            device_name: DEFAULT_HEARTBEAT_DEVICE.to_string(),
+        }
+    }
+
+    /// Use a custom device name for heartbeat messages
+    pub fn with_device_name(mut self, device_name: impl Into<String>) -> Self {
+        self.device_name = device_name.into();
+        self
+    }
+
+    /// Encode one heartbeat message carrying `message_id`
+    pub(crate) fn encode(&self, message_id: u32) -> Result<Vec<u8>> {
+        match self.kind {
+            HeartbeatKind::Status => {
+                IgtlMessage::new(StatusMessage::ok("Heartbeat"), &self.device_name)?
+                    .encode_with_message_id(message_id)
+            }
+            HeartbeatKind::GetStatus => IgtlMessage::new(GetStatusMessage, &self.device_name)?
+                .encode_with_message_id(message_id),
+        }
+    }
+
+    /// Message ID of a received frame if it is a STATUS reply to a GET_STATUS heartbeat
+    pub(crate) fn reply_id(&self, frame: &[u8]) -> Option<u32> {
+        if self.kind != HeartbeatKind::GetStatus {
+            return None;
+        }
+        let header = Header::decode(&frame[..Header::SIZE.min(frame.len())]).ok()?;
+        if header.type_name.as_str().ok() != Some("STATUS")
+            || header.device_name.as_str().ok() != Some(self.device_name.as_str())
+        {
+            return None;
+        }
+        frame_message_id(&header, frame)
+    }
+}
+
+/// Liveness settings for async connections
+///
+/// All mechanisms are disabled by default.
+#[derive(Debug, Clone, Default)]
+pub struct LivenessConfig {
+    /// Idle time before the OS starts sending TCP keepalive probes
+    pub tcp_keepalive: Option<Duration>,
+    /// Application-level heartbeat
+    pub heartbeat: Option<HeartbeatConfig>,
+    /// Deadline of each `receive` call; the connection stays usable after it expires
+    pub read_timeout: Option<Duration>,
+    /// Deadline of each `send` call; the connection is closed after it expires
+    pub write_timeout: Option<Duration>,
+    /// Time without received data after which the connection is declared dead
+    pub idle_timeout: Option<Duration>,
+}
+
+impl LivenessConfig {
+    /// Create a config with all mechanisms disabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable TCP keepalive probes after `idle` without traffic
+    pub fn with_tcp_keepalive(mut self, idle: Duration) -> Self {
+        self.tcp_keepalive = Some(idle);
+        self
+    }
+
+    /// Enable application-level heartbeats
+    pub fn with_heartbeat(mut self, heartbeat: HeartbeatConfig) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+
+    /// Set the deadline of each `receive` call
+    pub fn with_read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Set the deadline of each `send` call
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Declare the connection dead after `timeout` without received data
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use openigtlink_rust::io::liveness::{HeartbeatConfig, LivenessConfig};
+    /// use std::time::Duration;
+    ///
+    /// // Three missed heartbeat replies mean the peer is gone
+    /// let config = LivenessConfig::new()
+    ///     .with_heartbeat(HeartbeatConfig::get_status(Duration::from_secs(1)))
+    ///     .with_idle_timeout(Duration::from_secs(3));
+    /// assert_eq!(config.idle_timeout, Some(Duration::from_secs(3)));
+    /// ```
+    pub fn with_idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = Some(timeout);
+        self
+    }
+}
+
+/// Heartbeat and idle-timeout state of one connection
+///
+/// Serviced by [`AsyncIgtlStream`](crate::io::AsyncIgtlStream) while it waits for data.
+#[derive(Debug)]
+pub(crate) struct Liveness {
+    heartbeat: Option<HeartbeatConfig>,
+    idle_timeout: Option<Duration>,
+    /// Time data was last received
+    last_rx: Instant,
+    /// Time data was last sent
+    last_tx: Instant,
+    /// Message IDs of GET_STATUS heartbeats whose reply has not arrived yet
+    outstanding: VecDeque<u32>,
+    next_id: u32,
+}
+
+impl Default for Liveness {
+    fn default() -> Self {
+        Self::new(&LivenessConfig::default())
+    }
+}
+
+impl Liveness {
+    /// Heartbeat and idle-timeout part of `config`
+    pub(crate) fn new(config: &LivenessConfig) -> Self {
+        let now = Instant::now();
+        Liveness {
+            heartbeat: config.heartbeat.clone(),
+            idle_timeout: config.idle_timeout,
+            last_rx: now,
+            last_tx: now,
+            outstanding: VecDeque::new(),
+            next_id: 1,
+        }
+    }
+
+    /// Record received data
+    pub(crate) fn received(&mut self) {
+        self.last_rx = Instant::now();
+    }
+
+    /// Record sent data
+    pub(crate) fn sent(&mut self) {
+        self.last_tx = Instant::now();
+    }
+
+    /// Time at which the connection is declared dead, if nothing arrives before
+    pub(crate) fn idle_deadline(&self) -> Option<Instant> {
+        self.idle_timeout.map(|t| self.last_rx + t)
+    }
+
+    /// Time at which the next heartbeat is due, if nothing is sent before
+    pub(crate) fn heartbeat_due(&self) -> Option<Instant> {
+        self.heartbeat.as_ref().map(|hb| self.last_tx + hb.interval)
+    }
+
+    /// Encode the next heartbeat and count it as sent
+    pub(crate) fn next_heartbeat(&mut self) -> Result<Vec<u8>> {
+        let Some(heartbeat) = &self.heartbeat else {
+            return Ok(Vec::new());
+        };
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+        let data = heartbeat.encode(id)?;
+        if heartbeat.kind == HeartbeatKind::GetStatus {
+            if self.outstanding.len() == MAX_OUTSTANDING_HEARTBEATS {
+                self.outstanding.pop_front();
+            }
+            self.outstanding.push_back(id);
+        }
+        self.last_tx = Instant::now();
+        Ok(data)
+    }
+
+    /// Consume `frame` if it answers an outstanding heartbeat
+    pub(crate) fn take_reply(&mut self, frame: &[u8]) -> bool {
+        let Some(id) = self.heartbeat.as_ref().and_then(|hb| hb.reply_id(frame)) else {
+            return false;
+        };
+        match self.outstanding.iter().position(|&o| o == id) {
+            Some(index) => {
+                self.outstanding.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Error reported when the idle timeout expires
+    pub(crate) fn idle_error(&self) -> IgtlError {
+        IgtlError::Io(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            format!(
+                "No data received for {:?}, connection considered dead",
+                self.last_rx.elapsed()
+            ),
+        ))
+    }
+}
+
+/// Enable (`Some(idle)`) or disable (`None`) TCP keepalive on a socket
+pub(crate) fn set_tcp_keepalive(stream: &TcpStream, idle: Option<Duration>) -> Result<()> {
+    let socket = SockRef::from(stream);
+    match idle {
+        Some(idle) => socket.set_tcp_keepalive(&TcpKeepalive::new().with_time(idle))?,
+        None => socket.set_keepalive(false)?,
+    }
+    Ok(())
+}
+
+/// Run `fut` with an optional deadline, failing with `TimedOut` when it expires
+pub(crate) async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    what: &str,
+    fut: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, fut)
+            .await
+            .map_err(|_| crate::io::common::timed_out(what))?,
+        None => fut.await,
+    }
+}
+
+/// Sleep until `deadline`, or forever if there is none
+pub(crate) async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::types::TransformMessage;
+
+    fn status_reply(device: &str, message_id: Option<u32>) -> Vec<u8> {
+        let mut msg = IgtlMessage::new(StatusMessage::ok("alive"), device).unwrap();
+        if let Some(id) = message_id {
+            msg.set_message_id(id);
+        }
+        msg.encode().unwrap()
+    }
+
+    #[test]
+    fn test_heartbeat_reply_detection() {
+        let config = LivenessConfig::new()
+            .with_heartbeat(HeartbeatConfig::get_status(Duration::from_secs(1)));
+        let mut liveness = Liveness::new(&config);
+        let heartbeat = liveness.next_heartbeat().unwrap();
+        let header = Header::decode(&heartbeat[..Header::SIZE]).unwrap();
+        let id = frame_message_id(&header, &heartbeat).unwrap();
+
+        // Application statuses from the heartbeat device are not replies
+        assert!(!liveness.take_reply(&status_reply(DEFAULT_HEARTBEAT_DEVICE, None)));
+        assert!(!liveness.take_reply(&status_reply(DEFAULT_HEARTBEAT_DEVICE, Some(id + 1))));
+        assert!(!liveness.take_reply(&status_reply("Tracker", Some(id))));
+        let other_type = IgtlMessage::new(TransformMessage::identity(), DEFAULT_HEARTBEAT_DEVICE)
+            .unwrap()
+            .encode_with_message_id(id)
+            .unwrap();
+        assert!(!liveness.take_reply(&other_type));
+
+        let reply = status_reply(DEFAULT_HEARTBEAT_DEVICE, Some(id));
+        assert!(liveness.take_reply(&reply));
+        // Each heartbeat is answered once
+        assert!(!liveness.take_reply(&reply));
+        assert!(HeartbeatConfig::status(Duration::from_secs(1))
+            .reply_id(&reply)
+            .is_none());
+    }
+
+    #[test]
+    fn test_heartbeat_encoding() {
+        let frame = HeartbeatConfig::get_status(Duration::from_secs(1))
+            .with_device_name("Probe")
+            .encode(7)
+            .unwrap();
+        let header = Header::decode(&frame[..Header::SIZE]).unwrap();
+        assert_eq!(header.type_name.as_str().unwrap(), "GET_STATUS");
+        assert_eq!(header.device_name.as_str().unwrap(), "Probe");
+        assert_eq!(frame_message_id(&header, &frame), Some(7));
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        let err = with_timeout(Some(Duration::from_millis(10)), "nothing", async {
+            std::future::pending::<Result<()>>().await
+        })
+        .await
+        .unwrap_err();
+        match err {
+            crate::error::IgtlError::Io(e) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+            other => panic!("unexpected error: {other}"),
+        }
+        assert_eq!(
+            with_timeout(None, "value", async { Ok(7) }).await.unwrap(),
+            7
+        );
+    }
+}
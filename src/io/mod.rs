@@ -1,7 +1,7 @@
 //! Network I/O module for OpenIGTLink communication
 //!
 //! Provides client and server implementations for OpenIGTLink connections
-//! over both TCP (reliable) and UDP (low-latency) transports.
+//! over TCP (reliable), UDP (low-latency), WebSocket and Unix domain socket transports.
 //!
 //! # Client Creation
 //!
@@ -22,27 +22,78 @@
 //! ```
 
 pub mod async_server;
+pub mod async_udp;
 pub mod builder;
 mod common;
+pub mod conflation;
+pub mod events;
+mod handshake;
+pub mod limits;
+pub mod liveness;
+pub mod multicast;
 pub mod reconnect;
+pub mod repository;
+pub mod scheduler;
 pub mod server;
+pub mod session;
+pub mod shutdown;
+pub mod split;
+pub mod stream;
+pub mod streaming;
 mod sync_client;
+mod sync_stream;
+pub mod tls;
 pub mod tls_server;
 pub mod udp;
+pub mod udp_fragment;
+pub mod udp_sequence;
 pub mod unified_async_client;
 pub mod unified_client;
+#[cfg(unix)]
+pub mod unix;
+pub mod websocket;
 
 // Client builder API (recommended)
 pub use builder::ClientBuilder;
+pub use common::DEFAULT_QUERY_TIMEOUT;
+pub use conflation::{ConflatingSender, ConflationConfig};
+pub use events::ConnectionEvent;
+pub use limits::ServerLimits;
+pub use liveness::{HeartbeatConfig, LivenessConfig};
 pub use reconnect::ReconnectConfig;
+pub use scheduler::{OutgoingScheduler, Priority};
+pub use session::{DropPolicy, OfflineQueueConfig};
+pub use shutdown::{CancellationToken, ShutdownConfig};
+pub use unified_async_client::{AsyncClientReader, AsyncClientWriter};
 pub use unified_client::{AsyncIgtlClient, SyncIgtlClient};
 
 // Server APIs
 pub use async_server::{
     AsyncIgtlConnection, AsyncIgtlConnectionReader, AsyncIgtlConnectionWriter, AsyncIgtlServer,
 };
-pub use server::{IgtlConnection, IgtlServer};
+pub use repository::DeviceRepository;
+pub use server::{IgtlConnection, IgtlServer, SyncTlsIgtlServer};
+pub use split::{AsyncIgtlReader, AsyncIgtlWriter};
+pub use stream::{AsyncIgtlStream, IgtlReader, IgtlStream, IgtlWriter};
+pub use streaming::{DataSource, StreamRequest, StreamingEngine, StreamingSession};
+pub use tls::{
+    CertificatePin, ClientTlsConfig, PeerCertificate, ReloadableCertResolver, ServerTlsConfig,
+};
 pub use tls_server::{TlsIgtlConnection, TlsIgtlServer};
 
 // UDP
+pub use async_udp::{AsyncUdpClient, AsyncUdpServer};
+pub use multicast::{MulticastConfig, MulticastReceiver, MulticastSender};
 pub use udp::{UdpClient, UdpServer};
+pub use udp_fragment::{FragmentConfig, FragmentReassembler, FragmentStats};
+pub use udp_sequence::{SequenceConfig, SequenceSource, SequenceStats};
+
+// WebSocket
+pub use websocket::{WebSocketIgtlClient, WebSocketIgtlConnection, WebSocketIgtlServer};
+
+// Unix domain sockets
+#[cfg(unix)]
+pub use unix::{
+    AsyncUnixIgtlClient, AsyncUnixIgtlConnection, AsyncUnixIgtlServer, PeerCredentials,
+    UnixIgtlClient, UnixIgtlConnection, UnixIgtlServer,
+};
@@ -0,0 +1,473 @@
+//! UDP multicast distribution of OpenIGTLink messages
+//!
+//! One sender publishes to an IPv4 multicast group and any number of receivers (e.g.
+//! navigation consoles) join the group, without a TCP stream per client. Like plain
+//! [UDP](crate::io::udp) there is no delivery guarantee, so multicast suits high-rate
+//! tracking data rather than commands.
+//!
+//! # Example: Tracker Host and Console
+//!
+//! ```no_run
+//! use openigtlink_rust::io::{MulticastConfig, MulticastReceiver, MulticastSender};
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::TransformMessage;
+//! use std::net::Ipv4Addr;
+//!
+//! let config = MulticastConfig::new(Ipv4Addr::new(239, 255, 0, 1), 18944).with_ttl(4);
+//!
+//! // Tracker host
+//! let sender = MulticastSender::new(&config)?;
+//! let msg = IgtlMessage::new(TransformMessage::identity(), "Probe")?;
+//! sender.send(&msg)?;
+//!
+//! // Navigation console, only interested in the probe
+//! let mut receiver = MulticastReceiver::join(&config)?;
+//! receiver.set_device_filter(["Probe"]);
+//! let (msg, sender_addr) = receiver.receive_any()?;
+//! # Ok::<(), openigtlink_rust::error::IgtlError>(())
+//! ```
+
+use std::collections::HashSet;
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tracing::{debug, info};
+
+use crate::error::{IgtlError, Result};
+use crate::io::udp::{
+    check_datagram_size, decode_any_datagram, encode_datagram, MAX_UDP_DATAGRAM_SIZE,
+};
+use crate::protocol::header::Header;
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::AnyMessage;
+
+/// Multicast group and socket settings shared by senders and receivers
+///
+/// # Examples
+///
+/// ```
+/// use openigtlink_rust::io::MulticastConfig;
+/// use std::net::Ipv4Addr;
+///
+/// // Loopback-only group, e.g. for tests on a single host
+/// let config = MulticastConfig::new(Ipv4Addr::new(239, 255, 42, 1), 18944)
+///     .with_interface(Ipv4Addr::LOCALHOST)
+///     .with_ttl(0);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MulticastConfig {
+    /// Multicast group address (224.0.0.0/4)
+    pub group: Ipv4Addr,
+    /// UDP port of the group
+    pub port: u16,
+    /// Local interface used to send and join (default `0.0.0.0`, chosen by the OS)
+    pub interface: Ipv4Addr,
+    /// Time-to-live of sent datagrams, i.e. number of router hops (default 1, local subnet)
+    pub ttl: u32,
+    /// Deliver sent datagrams to receivers on the sending host (default true)
+    pub loopback: bool,
+}
+
+impl MulticastConfig {
+    /// Create a configuration for `group:port` with default socket settings
+    pub fn new(group: Ipv4Addr, port: u16) -> Self {
+        Self {
+            group,
+            port,
+            interface: Ipv4Addr::UNSPECIFIED,
+            ttl: 1,
+            loopback: true,
+        }
+    }
+
+    /// Set the local interface used to send and join
+    pub fn with_interface(mut self, interface: Ipv4Addr) -> Self {
+        self.interface = interface;
+        self
+    }
+
+    /// Set the time-to-live of sent datagrams
+    pub fn with_ttl(mut self, ttl: u32) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Enable or disable delivery to receivers on the sending host
+    pub fn with_loopback(mut self, loopback: bool) -> Self {
+        self.loopback = loopback;
+        self
+    }
+
+    /// Group socket address
+    pub fn group_addr(&self) -> SocketAddr {
+        SocketAddr::V4(SocketAddrV4::new(self.group, self.port))
+    }
+}
+
+/// Publishes messages to a multicast group
+pub struct MulticastSender {
+    socket: UdpSocket,
+    group: SocketAddr,
+}
+
+impl MulticastSender {
+    /// Create a sender for the configured group
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] with [`InvalidInput`](std::io::ErrorKind::InvalidInput) - `group`
+    ///   is not a multicast address
+    /// - [`IgtlError::Io`] - Failed to bind or configure the socket
+    pub fn new(config: &MulticastConfig) -> Result<Self> {
+        check_group(config.group)?;
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_multicast_ttl_v4(config.ttl)?;
+        socket.set_multicast_loop_v4(config.loopback)?;
+        if !config.interface.is_unspecified() {
+            socket.set_multicast_if_v4(&config.interface)?;
+        }
+        let bind_addr = SocketAddr::V4(SocketAddrV4::new(config.interface, 0));
+        socket.bind(&bind_addr.into())?;
+        info!(
+            group = %config.group_addr(),
+            ttl = config.ttl,
+            interface = %config.interface,
+            "Multicast sender ready"
+        );
+        Ok(MulticastSender {
+            socket: socket.into(),
+            group: config.group_addr(),
+        })
+    }
+
+    /// Publish a message to the group
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Network transmission failed
+    /// - [`IgtlError::BodyTooLarge`] - Message exceeds UDP MTU (65507 bytes)
+    pub fn send<T: Message>(&self, msg: &IgtlMessage<T>) -> Result<()> {
+        let data = encode_datagram(msg)?;
+        self.socket.send_to(&data, self.group)?;
+        Ok(())
+    }
+
+    /// Publish a message of any type to the group
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Network transmission failed
+    /// - [`IgtlError::BodyTooLarge`] - Message exceeds UDP MTU (65507 bytes)
+    pub fn send_any(&self, msg: &AnyMessage) -> Result<()> {
+        let data = check_datagram_size(msg.encode()?)?;
+        self.socket.send_to(&data, self.group)?;
+        Ok(())
+    }
+
+    /// Group socket address messages are sent to
+    pub fn group_addr(&self) -> SocketAddr {
+        self.group
+    }
+
+    /// Get local socket address
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Failed to get socket address
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+}
+
+/// Receives messages published to one or more multicast groups
+///
+/// The socket is bound with `SO_REUSEADDR`, so several receivers on the same host can
+/// listen on the same group port.
+pub struct MulticastReceiver {
+    socket: UdpSocket,
+    interface: Ipv4Addr,
+    groups: Vec<Ipv4Addr>,
+    device_filter: Option<HashSet<String>>,
+}
+
+impl MulticastReceiver {
+    /// Bind to the configured port and join the configured group
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] with [`InvalidInput`](std::io::ErrorKind::InvalidInput) - `group`
+    ///   is not a multicast address
+    /// - [`IgtlError::Io`] - Failed to bind the socket or join the group
+    pub fn join(config: &MulticastConfig) -> Result<Self> {
+        check_group(config.group)?;
+        let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+        socket.set_reuse_address(true)?;
+        let bind_addr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, config.port));
+        socket.bind(&bind_addr.into())?;
+
+        let mut receiver = MulticastReceiver {
+            socket: socket.into(),
+            interface: config.interface,
+            groups: Vec::new(),
+            device_filter: None,
+        };
+        receiver.join_group(config.group)?;
+        Ok(receiver)
+    }
+
+    /// Join an additional group on the same port
+    ///
+    /// Joining a group that was already joined has no effect.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - `group` is not a multicast address, or joining failed
+    pub fn join_group(&mut self, group: Ipv4Addr) -> Result<()> {
+        check_group(group)?;
+        if self.groups.contains(&group) {
+            return Ok(());
+        }
+        self.socket.join_multicast_v4(&group, &self.interface)?;
+        self.groups.push(group);
+        info!(group = %group, interface = %self.interface, "Joined multicast group");
+        Ok(())
+    }
+
+    /// Leave a group
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Leaving failed (e.g. the group was not joined)
+    pub fn leave_group(&mut self, group: Ipv4Addr) -> Result<()> {
+        self.socket.leave_multicast_v4(&group, &self.interface)?;
+        self.groups.retain(|g| *g != group);
+        info!(group = %group, "Left multicast group");
+        Ok(())
+    }
+
+    /// Groups currently joined
+    pub fn groups(&self) -> &[Ipv4Addr] {
+        &self.groups
+    }
+
+    /// Only return messages from the given device names
+    ///
+    /// Other messages are dropped before their body is decoded.
+    pub fn set_device_filter<I, S>(&mut self, device_names: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.device_filter = Some(device_names.into_iter().map(Into::into).collect());
+    }
+
+    /// Return messages from all devices again
+    pub fn clear_device_filter(&mut self) {
+        self.device_filter = None;
+    }
+
+    /// Receive a message (blocking)
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (message, sender_address)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Network read failed or timeout
+    /// - [`IgtlError::InvalidHeader`] - Malformed header
+    /// - [`IgtlError::CrcMismatch`] - Data corruption detected
+    pub fn receive<T: Message>(&self) -> Result<(IgtlMessage<T>, SocketAddr)> {
+        let (data, src) = self.recv_datagram()?;
+        let msg = IgtlMessage::decode(&data)?;
+        Ok((msg, src))
+    }
+
+    /// Receive a message of any type (blocking)
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (message, sender_address)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Network read failed or timeout
+    /// - [`IgtlError::InvalidSize`] - Datagram shorter than a header
+    /// - [`IgtlError::CrcMismatch`] - Data corruption detected
+    pub fn receive_any(&self) -> Result<(AnyMessage, SocketAddr)> {
+        let (data, src) = self.recv_datagram()?;
+        let msg = decode_any_datagram(&data, true)?;
+        Ok((msg, src))
+    }
+
+    /// Set read timeout
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Timeout duration (None for blocking forever)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Failed to set socket option
+    pub fn set_read_timeout(&self, timeout: Option<Duration>) -> Result<()> {
+        self.socket.set_read_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Get local socket address
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Failed to get socket address
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    /// Receive the next datagram that passes the device filter
+    fn recv_datagram(&self) -> Result<(Vec<u8>, SocketAddr)> {
+        let mut buf = vec![0u8; MAX_UDP_DATAGRAM_SIZE];
+        loop {
+            let (size, src) = self.socket.recv_from(&mut buf)?;
+            if self.accepts(&buf[..size]) {
+                return Ok((buf[..size].to_vec(), src));
+            }
+        }
+    }
+
+    fn accepts(&self, datagram: &[u8]) -> bool {
+        let Some(filter) = &self.device_filter else {
+            return true;
+        };
+        // Malformed datagrams are passed on so that decoding reports the error
+        let Some(header) = datagram
+            .get(..Header::SIZE)
+            .and_then(|h| Header::decode(h).ok())
+        else {
+            return true;
+        };
+        let accepted = header
+            .device_name
+            .as_str()
+            .is_ok_and(|name| filter.contains(name));
+        if !accepted {
+            debug!(device_name = ?header.device_name.as_str(), "Filtered multicast message");
+        }
+        accepted
+    }
+}
+
+fn check_group(group: Ipv4Addr) -> Result<()> {
+    if group.is_multicast() {
+        Ok(())
+    } else {
+        Err(IgtlError::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Not a multicast address: {}", group),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_os = "linux")]
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+
+    // Joining a group on the loopback interface is only reliable on Linux, so the tests
+    // exchanging datagrams run there only
+    #[cfg(target_os = "linux")]
+    const GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 42, 99);
+
+    /// Loopback-only receiver on an ephemeral port, and the matching config
+    #[cfg(target_os = "linux")]
+    fn loopback_receiver() -> (MulticastReceiver, MulticastConfig) {
+        let config = MulticastConfig::new(GROUP, 0).with_interface(Ipv4Addr::LOCALHOST);
+        let receiver = MulticastReceiver::join(&config).unwrap();
+        receiver
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let port = receiver.local_addr().unwrap().port();
+        (receiver, MulticastConfig { port, ..config })
+    }
+
+    #[cfg(target_os = "linux")]
+    fn transform(device: &str) -> IgtlMessage<TransformMessage> {
+        IgtlMessage::new(TransformMessage::identity(), device).unwrap()
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_publish_to_several_receivers() {
+        let (first, config) = loopback_receiver();
+        let second = MulticastReceiver::join(&config).unwrap();
+        second
+            .set_read_timeout(Some(Duration::from_secs(2)))
+            .unwrap();
+        let sender = MulticastSender::new(&config).unwrap();
+        assert_eq!(sender.group_addr(), config.group_addr());
+
+        sender.send(&transform("Probe")).unwrap();
+        for receiver in [&first, &second] {
+            let (msg, src) = receiver.receive_any().unwrap();
+            assert!(matches!(msg, AnyMessage::Transform(_)));
+            assert_eq!(src, sender.local_addr().unwrap());
+        }
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_device_filter() {
+        let (mut receiver, config) = loopback_receiver();
+        receiver.set_device_filter(["Probe"]);
+        let sender = MulticastSender::new(&config).unwrap();
+
+        sender.send(&transform("Reference")).unwrap();
+        sender
+            .send_any(&AnyMessage::Status(
+                IgtlMessage::new(StatusMessage::ok("tracking"), "Probe").unwrap(),
+            ))
+            .unwrap();
+        let (msg, _) = receiver.receive::<StatusMessage>().unwrap();
+        assert_eq!(msg.header.device_name.as_str().unwrap(), "Probe");
+
+        receiver.clear_device_filter();
+        sender.send(&transform("Reference")).unwrap();
+        let (msg, _) = receiver.receive_any().unwrap();
+        assert_eq!(msg.device_name().unwrap(), "Reference");
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_join_and_leave_groups() {
+        let (mut receiver, config) = loopback_receiver();
+        let other = Ipv4Addr::new(239, 255, 42, 100);
+        receiver.join_group(other).unwrap();
+        receiver.join_group(other).unwrap();
+        assert_eq!(receiver.groups(), &[GROUP, other]);
+
+        let other_sender = MulticastSender::new(&MulticastConfig {
+            group: other,
+            ..config.clone()
+        })
+        .unwrap();
+        other_sender.send(&transform("Other")).unwrap();
+        let (msg, _) = receiver.receive_any().unwrap();
+        assert_eq!(msg.device_name().unwrap(), "Other");
+
+        receiver.leave_group(other).unwrap();
+        assert_eq!(receiver.groups(), &[GROUP]);
+        receiver
+            .set_read_timeout(Some(Duration::from_millis(200)))
+            .unwrap();
+        other_sender.send(&transform("Other")).unwrap();
+        assert!(receiver.receive_any().is_err());
+    }
+
+    #[test]
+    fn test_rejects_non_multicast_group() {
+        let config = MulticastConfig::new(Ipv4Addr::LOCALHOST, 18944);
+        assert!(MulticastSender::new(&config).is_err());
+        assert!(MulticastReceiver::join(&config).is_err());
+    }
+}
@@ -0,0 +1,685 @@
+//! Latest-value device repository answering GET_* queries
+//!
+//! OpenIGTLink clients such as 3D Slicer's OpenIGTLinkIF poll servers with
+//! `GET_TRANSFOR`, `GET_IMAGE`, `GET_POINT`, `GET_IMGMETA`, `GET_LBMETA` and
+//! `GET_CAPABIL`, expecting the current data for the requested device in return.
+//!
+//! [`DeviceRepository`] keeps the most recent message per (type, device) and turns
+//! those queries into replies:
+//!
+//! - `GET_<TYPE>` returns the stored message for the device, or every stored
+//!   message of that type when the device name is empty
+//! - `GET_IMGMETA` / `GET_LBMETA` return IMGMETA / LBMETA listings synthesized
+//!   from the stored images and label maps
+//! - `GET_CAPABIL` returns a CAPABILITY message built from what can be served
+//! - Queries for data that is not available are answered with a `STATUS`
+//!   message carrying [`STATUS_NOT_FOUND`]
+//!
+//! Replies to v3 queries carry the query's `message_id` so clients can correlate them.
+//!
+//! The repository is cheap to clone and shared between connections. It can be
+//! attached to a [`StreamingEngine`](crate::io::StreamingEngine) with
+//! [`with_repository`](crate::io::StreamingEngine::with_repository) and is itself a
+//! [`DataSource`], streaming the latest stored value.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::{DeviceRepository, IgtlServer};
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::TransformMessage;
+//! use openigtlink_rust::protocol::AnyMessage;
+//!
+//! # fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let repository = DeviceRepository::new();
+//! repository.store(AnyMessage::Transform(IgtlMessage::new(
+//!     TransformMessage::identity(),
+//!     "Needle",
+//! )?))?;
+//!
+//! let server = IgtlServer::bind("0.0.0.0:18944")?;
+//! let mut conn = server.accept()?;
+//! loop {
+//!     let msg = conn.receive_any()?;
+//!     if let Some(replies) = repository.respond(&msg)? {
+//!         for reply in &replies {
+//!             conn.send_any(reply)?;
+//!         }
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+
+use tracing::{debug, trace};
+
+use crate::error::Result;
+use crate::io::streaming::{DataSource, StreamRequest};
+use crate::protocol::message::IgtlMessage;
+use crate::protocol::types::{
+    CapabilityMessage, ImageMessage, ImageMetaElement, ImgMetaMessage, LabelMetaElement,
+    LbMetaMessage, StatusMessage,
+};
+use crate::protocol::AnyMessage;
+
+/// STATUS code sent when a query asks for data the repository does not hold
+pub const STATUS_NOT_FOUND: u16 = 4;
+
+/// Maximum length of a type name on the wire
+const TYPE_NAME_LEN: usize = 12;
+
+/// Truncate a type name to the 12 bytes that fit in the header (e.g. "GET_TRANSFOR")
+fn wire_type_name(name: &str) -> &str {
+    &name[..name.len().min(TYPE_NAME_LEN)]
+}
+
+/// Standard data types whose `GET_*` name is truncated on the wire
+const LONG_TYPE_NAMES: &[&str] = &[
+    "TRANSFORM",
+    "CAPABILITY",
+    "TRAJECTORY",
+    "COLORTABLE",
+    "VIDEOMETA",
+];
+
+/// Full data type name of a `GET_*` wire name that matches no stored type
+fn full_type_name(query_type: &str) -> String {
+    LONG_TYPE_NAMES
+        .iter()
+        .find(|ty| wire_type_name(&format!("GET_{}", ty)) == query_type)
+        .map_or_else(
+            || query_type["GET_".len()..].to_string(),
+            |ty| ty.to_string(),
+        )
+}
+
+/// Latest stored message for one (type, device) pair
+#[derive(Debug)]
+struct Entry {
+    message: AnyMessage,
+    sequence: u64,
+}
+
+#[derive(Debug, Default)]
+struct RepositoryState {
+    entries: HashMap<(String, String), Entry>,
+    label_maps: HashSet<String>,
+    image_meta: HashMap<String, ImageMetaElement>,
+    extra_capabilities: BTreeSet<String>,
+    sequence: u64,
+}
+
+impl RepositoryState {
+    fn insert(&mut self, message_type: String, device_name: String, message: AnyMessage) {
+        self.sequence += 1;
+        let entry = Entry {
+            message,
+            sequence: self.sequence,
+        };
+        self.entries.insert((message_type, device_name), entry);
+    }
+
+    /// Stored messages of one type, ordered by device name
+    fn entries_of<'a>(&'a self, message_type: &str) -> Vec<(&'a str, &'a Entry)> {
+        let mut entries: Vec<_> = self
+            .entries
+            .iter()
+            .filter(|((ty, _), _)| ty == message_type)
+            .map(|((_, device), entry)| (device.as_str(), entry))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(b.0));
+        entries
+    }
+
+    /// Find the stored type a `GET_*` wire name refers to
+    fn queried_type(&self, query_type: &str) -> Option<String> {
+        self.entries
+            .keys()
+            .map(|(ty, _)| ty)
+            .find(|ty| wire_type_name(&format!("GET_{}", ty)) == query_type)
+            .cloned()
+    }
+}
+
+/// What a GET_* message asks for
+enum Query {
+    Capability,
+    ImgMeta,
+    LbMeta,
+    Data(String),
+}
+
+/// Shared store of the latest message per (type, device)
+///
+/// See the [module documentation](self) for the query semantics.
+#[derive(Debug, Clone, Default)]
+pub struct DeviceRepository {
+    state: Arc<RwLock<RepositoryState>>,
+}
+
+impl DeviceRepository {
+    /// Create an empty repository
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store a message, replacing the previous one with the same type and device
+    ///
+    /// Storing an IMAGE under a device previously registered as a label map turns it
+    /// back into a regular image.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::InvalidHeader`](crate::error::IgtlError::InvalidHeader) - Device name is not valid UTF-8
+    pub fn store(&self, message: AnyMessage) -> Result<()> {
+        let message_type = message.message_type().to_string();
+        let device_name = message.device_name()?.to_string();
+
+        trace!(
+            msg_type = %message_type,
+            device_name = %device_name,
+            "Storing latest message"
+        );
+
+        let mut state = self.state.write().unwrap();
+        if matches!(message, AnyMessage::Image(_)) {
+            state.label_maps.remove(&device_name);
+        }
+        state.insert(message_type, device_name, message);
+        Ok(())
+    }
+
+    /// Store a label map image
+    ///
+    /// The image is served to `GET_IMAGE` like any other image, but listed in
+    /// LBMETA instead of IMGMETA.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::InvalidHeader`](crate::error::IgtlError::InvalidHeader) - Device name is not valid UTF-8
+    pub fn store_label_map(&self, message: IgtlMessage<ImageMessage>) -> Result<()> {
+        let device_name = message.header.device_name.as_str()?.to_string();
+
+        let mut state = self.state.write().unwrap();
+        state.label_maps.insert(device_name.clone());
+        state.insert("IMAGE".to_string(), device_name, AnyMessage::Image(message));
+        Ok(())
+    }
+
+    /// Provide descriptive metadata (modality, patient, ...) for an image
+    ///
+    /// The element is matched to a stored image by its `id`, which must equal the
+    /// image's device name. Size and scalar type are always taken from the image itself.
+    pub fn set_image_meta(&self, element: ImageMetaElement) {
+        let mut state = self.state.write().unwrap();
+        state.image_meta.insert(element.id.clone(), element);
+    }
+
+    /// Advertise an additional message type in CAPABILITY replies (e.g. "STT_TDATA")
+    pub fn add_capability(&self, message_type: &str) {
+        let mut state = self.state.write().unwrap();
+        state
+            .extra_capabilities
+            .insert(wire_type_name(message_type).to_string());
+    }
+
+    /// Get a copy of the stored message for a type and device
+    pub fn get(&self, message_type: &str, device_name: &str) -> Option<AnyMessage> {
+        let state = self.state.read().unwrap();
+        state
+            .entries
+            .get(&(message_type.to_string(), device_name.to_string()))
+            .map(|entry| entry.message.clone())
+    }
+
+    /// Get a copy of the most recently stored message of a type, whatever its device
+    pub fn latest(&self, message_type: &str) -> Option<AnyMessage> {
+        let state = self.state.read().unwrap();
+        state
+            .entries
+            .iter()
+            .filter(|((ty, _), _)| ty == message_type)
+            .max_by_key(|(_, entry)| entry.sequence)
+            .map(|(_, entry)| entry.message.clone())
+    }
+
+    /// Remove the stored message for a type and device
+    pub fn remove(&self, message_type: &str, device_name: &str) -> Option<AnyMessage> {
+        let mut state = self.state.write().unwrap();
+        let removed = state
+            .entries
+            .remove(&(message_type.to_string(), device_name.to_string()));
+        if message_type == "IMAGE" {
+            state.label_maps.remove(device_name);
+        }
+        removed.map(|entry| entry.message)
+    }
+
+    /// Remove all stored messages and image metadata
+    ///
+    /// Capabilities added with [`add_capability`](Self::add_capability) are kept.
+    pub fn clear(&self) {
+        let mut state = self.state.write().unwrap();
+        state.entries.clear();
+        state.label_maps.clear();
+        state.image_meta.clear();
+    }
+
+    /// Device names with a stored message of the given type, sorted
+    pub fn device_names(&self, message_type: &str) -> Vec<String> {
+        let state = self.state.read().unwrap();
+        state
+            .entries_of(message_type)
+            .into_iter()
+            .map(|(device, _)| device.to_string())
+            .collect()
+    }
+
+    /// Number of stored (type, device) entries
+    pub fn len(&self) -> usize {
+        self.state.read().unwrap().entries.len()
+    }
+
+    /// Check whether the repository holds no messages
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Message types this repository can currently serve, as sent in CAPABILITY
+    ///
+    /// Includes every stored type with its `GET_*` query, the metadata queries when
+    /// images or label maps are stored, and any extra capabilities.
+    pub fn capabilities(&self) -> Vec<String> {
+        let state = self.state.read().unwrap();
+        let mut types: BTreeSet<String> = state.extra_capabilities.clone();
+        types.insert("GET_CAPABIL".to_string());
+
+        for (message_type, device_name) in state.entries.keys() {
+            types.insert(message_type.clone());
+            types.insert(wire_type_name(&format!("GET_{}", message_type)).to_string());
+            if message_type == "IMAGE" {
+                if state.label_maps.contains(device_name) {
+                    types.insert("GET_LBMETA".to_string());
+                    types.insert("LBMETA".to_string());
+                } else {
+                    types.insert("GET_IMGMETA".to_string());
+                    types.insert("IMGMETA".to_string());
+                }
+            }
+        }
+
+        types.into_iter().collect()
+    }
+
+    /// Build the IMGMETA listing of all stored (non label map) images
+    pub fn image_meta(&self) -> ImgMetaMessage {
+        let state = self.state.read().unwrap();
+        let mut meta = ImgMetaMessage::empty();
+
+        for (device_name, entry) in state.entries_of("IMAGE") {
+            if state.label_maps.contains(device_name) {
+                continue;
+            }
+            let AnyMessage::Image(image) = &entry.message else {
+                continue;
+            };
+            let element = match state.image_meta.get(device_name) {
+                Some(element) => element.clone(),
+                None => ImageMetaElement::new(device_name, device_name, "")
+                    .with_timestamp(image.header.timestamp.to_u64()),
+            };
+            meta.add_image(
+                element
+                    .with_size(image.content.size)
+                    .with_scalar_type(image.content.scalar_type as u8),
+            );
+        }
+
+        meta
+    }
+
+    /// Build the LBMETA listing of all stored label maps
+    pub fn label_meta(&self) -> LbMetaMessage {
+        let state = self.state.read().unwrap();
+        let mut meta = LbMetaMessage::empty();
+
+        for (device_name, entry) in state.entries_of("IMAGE") {
+            if !state.label_maps.contains(device_name) {
+                continue;
+            }
+            let AnyMessage::Image(image) = &entry.message else {
+                continue;
+            };
+            meta.add_label(
+                LabelMetaElement::new(device_name, device_name, 0).with_size(image.content.size),
+            );
+        }
+
+        meta
+    }
+
+    /// Build the replies to a query message
+    ///
+    /// # Returns
+    /// `None` if `query` is not a `GET_*` message, otherwise the messages to send back
+    /// (never empty: missing data is reported with a `STATUS` message)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::InvalidHeader`](crate::error::IgtlError::InvalidHeader) - Query header is not valid UTF-8
+    pub fn respond(&self, query: &AnyMessage) -> Result<Option<Vec<AnyMessage>>> {
+        let query_type = query.header().type_name.as_str()?;
+        if !query_type.starts_with("GET_") {
+            return Ok(None);
+        }
+        let device_name = query.device_name()?;
+
+        let target = match query_type {
+            "GET_CAPABIL" => Query::Capability,
+            "GET_IMGMETA" => Query::ImgMeta,
+            "GET_LBMETA" => Query::LbMeta,
+            _ => match self.state.read().unwrap().queried_type(query_type) {
+                Some(message_type) => Query::Data(message_type),
+                None => Query::Data(full_type_name(query_type)),
+            },
+        };
+
+        let mut replies = match target {
+            Query::Capability => vec![AnyMessage::Capability(IgtlMessage::new(
+                CapabilityMessage::new(self.capabilities()),
+                device_name,
+            )?)],
+            Query::ImgMeta => vec![AnyMessage::ImgMeta(IgtlMessage::new(
+                self.image_meta(),
+                device_name,
+            )?)],
+            Query::LbMeta => vec![AnyMessage::LbMeta(IgtlMessage::new(
+                self.label_meta(),
+                device_name,
+            )?)],
+            Query::Data(message_type) => {
+                let state = self.state.read().unwrap();
+                let found: Vec<AnyMessage> = if device_name.is_empty() {
+                    state
+                        .entries_of(&message_type)
+                        .into_iter()
+                        .map(|(_, entry)| entry.message.clone())
+                        .collect()
+                } else {
+                    state
+                        .entries
+                        .get(&(message_type.clone(), device_name.to_string()))
+                        .map(|entry| entry.message.clone())
+                        .into_iter()
+                        .collect()
+                };
+                drop(state);
+
+                if found.is_empty() {
+                    debug!(
+                        msg_type = query_type,
+                        device_name = device_name,
+                        "Query for unavailable data"
+                    );
+                    vec![not_found(&message_type, device_name)?]
+                } else {
+                    found
+                }
+            }
+        };
+
+        if let Some(message_id) = query.message_id() {
+            for reply in &mut replies {
+                reply.set_message_id(message_id);
+            }
+        }
+
+        debug!(
+            msg_type = query_type,
+            device_name = device_name,
+            replies = replies.len(),
+            "Answered query from repository"
+        );
+
+        Ok(Some(replies))
+    }
+}
+
+/// STATUS reply for a query that matched no stored data
+fn not_found(message_type: &str, device_name: &str) -> Result<AnyMessage> {
+    let status = StatusMessage {
+        code: STATUS_NOT_FOUND,
+        subcode: 0,
+        error_name: "NOT_FOUND".to_string(),
+        status_string: if device_name.is_empty() {
+            format!("No {} data available", message_type)
+        } else {
+            format!("No {} data for device '{}'", message_type, device_name)
+        },
+    };
+    Ok(AnyMessage::Status(IgtlMessage::new(status, device_name)?))
+}
+
+impl DataSource for DeviceRepository {
+    /// Stream the latest stored value for the requested device
+    ///
+    /// An empty device name streams the most recently stored message of the type.
+    fn next_message(&self, request: &StreamRequest) -> Result<Option<AnyMessage>> {
+        if request.device_name.is_empty() {
+            Ok(self.latest(&request.message_type))
+        } else {
+            Ok(self.get(&request.message_type, &request.device_name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::types::{
+        GetCapabilityMessage, GetImageMessage, GetImgMetaMessage, GetLbMetaMessage,
+        GetPointMessage, GetTransformMessage, ImageScalarType, PointElement, PointMessage,
+        TransformMessage,
+    };
+
+    fn transform(device: &str) -> AnyMessage {
+        AnyMessage::Transform(IgtlMessage::new(TransformMessage::identity(), device).unwrap())
+    }
+
+    fn image(device: &str, size: [u16; 3]) -> IgtlMessage<ImageMessage> {
+        let len = size.iter().map(|&s| s as usize).product();
+        let content = ImageMessage::new(ImageScalarType::Uint8, size, vec![0; len]).unwrap();
+        IgtlMessage::new(content, device).unwrap()
+    }
+
+    fn query<T: crate::protocol::message::Message>(content: T, device: &str) -> IgtlMessage<T> {
+        IgtlMessage::new(content, device).unwrap()
+    }
+
+    #[test]
+    fn test_store_replaces_latest() {
+        let repo = DeviceRepository::new();
+        repo.store(transform("Needle")).unwrap();
+        repo.store(transform("Needle")).unwrap();
+        repo.store(transform("Probe")).unwrap();
+
+        assert_eq!(repo.len(), 2);
+        assert_eq!(repo.device_names("TRANSFORM"), vec!["Needle", "Probe"]);
+        assert_eq!(
+            repo.latest("TRANSFORM").unwrap().device_name().unwrap(),
+            "Probe"
+        );
+        assert!(repo.remove("TRANSFORM", "Probe").is_some());
+        assert!(repo.get("TRANSFORM", "Probe").is_none());
+    }
+
+    #[test]
+    fn test_get_transform_by_device_and_all() {
+        let repo = DeviceRepository::new();
+        repo.store(transform("Needle")).unwrap();
+        repo.store(transform("Probe")).unwrap();
+
+        let q = AnyMessage::GetTransform(query(GetTransformMessage, "Probe"));
+        let replies = repo.respond(&q).unwrap().unwrap();
+        assert_eq!(replies.len(), 1);
+        assert_eq!(replies[0].message_type(), "TRANSFORM");
+        assert_eq!(replies[0].device_name().unwrap(), "Probe");
+
+        let q = AnyMessage::GetTransform(query(GetTransformMessage, ""));
+        let replies = repo.respond(&q).unwrap().unwrap();
+        let devices: Vec<_> = replies.iter().map(|r| r.device_name().unwrap()).collect();
+        assert_eq!(devices, vec!["Needle", "Probe"]);
+    }
+
+    #[test]
+    fn test_missing_data_replies_not_found() {
+        let repo = DeviceRepository::new();
+        let point = PointMessage::new(vec![PointElement::new("Tip", "Tool", [0.0, 0.0, 0.0])]);
+        repo.store(AnyMessage::Point(query(point, "Fiducials")))
+            .unwrap();
+
+        let q = AnyMessage::GetPoint(query(GetPointMessage, "Other"));
+        let replies = repo.respond(&q).unwrap().unwrap();
+        assert_eq!(replies.len(), 1);
+        let status = replies[0].as_status().unwrap();
+        assert_eq!(status.content.code, STATUS_NOT_FOUND);
+        assert_eq!(status.header.device_name.as_str().unwrap(), "Other");
+
+        // The truncated wire name is reported with the full type name
+        let q = AnyMessage::GetTransform(query(GetTransformMessage, "Needle"));
+        let replies = repo.respond(&q).unwrap().unwrap();
+        let status = replies[0].as_status().unwrap();
+        assert_eq!(
+            status.content.status_string,
+            "No TRANSFORM data for device 'Needle'"
+        );
+    }
+
+    #[test]
+    fn test_non_query_is_ignored() {
+        let repo = DeviceRepository::new();
+        assert!(repo.respond(&transform("Needle")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_unknown_get_type_matches_stored_type() {
+        let repo = DeviceRepository::new();
+        let status = StatusMessage::ok("Ready");
+        repo.store(AnyMessage::Status(query(status, "Robot")))
+            .unwrap();
+
+        // GET_STATUS decoded without a typed variant still resolves to STATUS
+        let mut header = query(crate::protocol::types::GetStatusMessage, "Robot").header;
+        header.type_name = crate::protocol::header::TypeName::new("GET_STATUS").unwrap();
+        let q = AnyMessage::Unknown {
+            header,
+            body: Vec::new(),
+        };
+        let replies = repo.respond(&q).unwrap().unwrap();
+        assert_eq!(replies[0].message_type(), "STATUS");
+        assert_eq!(replies[0].as_status().unwrap().content.code, 1);
+    }
+
+    #[test]
+    fn test_image_and_label_meta_listings() {
+        let repo = DeviceRepository::new();
+        repo.store(AnyMessage::Image(image("CT", [4, 4, 2])))
+            .unwrap();
+        repo.store_label_map(image("Segmentation", [4, 4, 2]))
+            .unwrap();
+        repo.set_image_meta(ImageMetaElement::new("Head CT", "CT", "CT").with_patient("Doe", "42"));
+
+        let replies = repo
+            .respond(&AnyMessage::GetImgMeta(query(GetImgMetaMessage, "")))
+            .unwrap()
+            .unwrap();
+        let AnyMessage::ImgMeta(meta) = &replies[0] else {
+            panic!("expected IMGMETA, got {}", replies[0].message_type());
+        };
+        assert_eq!(meta.content.images.len(), 1);
+        let element = &meta.content.images[0];
+        assert_eq!(element.id, "CT");
+        assert_eq!(element.name, "Head CT");
+        assert_eq!(element.patient_id, "42");
+        assert_eq!(element.size, [4, 4, 2]);
+        assert_eq!(element.scalar_type, 3);
+
+        let replies = repo
+            .respond(&AnyMessage::GetLbMeta(query(GetLbMetaMessage, "")))
+            .unwrap()
+            .unwrap();
+        let AnyMessage::LbMeta(meta) = &replies[0] else {
+            panic!("expected LBMETA, got {}", replies[0].message_type());
+        };
+        assert_eq!(meta.content.labels.len(), 1);
+        assert_eq!(meta.content.labels[0].id, "Segmentation");
+
+        // Label maps are still served as images
+        let replies = repo
+            .respond(&AnyMessage::GetImage(query(
+                GetImageMessage,
+                "Segmentation",
+            )))
+            .unwrap()
+            .unwrap();
+        assert_eq!(replies[0].message_type(), "IMAGE");
+    }
+
+    #[test]
+    fn test_capability_reflects_contents() {
+        let repo = DeviceRepository::new();
+        repo.store(transform("Needle")).unwrap();
+        repo.store(AnyMessage::Image(image("CT", [2, 2, 1])))
+            .unwrap();
+        repo.add_capability("STT_TDATA");
+
+        let replies = repo
+            .respond(&AnyMessage::GetCapability(query(GetCapabilityMessage, "")))
+            .unwrap()
+            .unwrap();
+        let types = &replies[0].as_capability().unwrap().content.types;
+        for expected in [
+            "GET_CAPABIL",
+            "GET_IMAGE",
+            "GET_IMGMETA",
+            "GET_TRANSFOR",
+            "IMAGE",
+            "IMGMETA",
+            "STT_TDATA",
+            "TRANSFORM",
+        ] {
+            assert!(types.iter().any(|t| t == expected), "missing {}", expected);
+        }
+        assert!(!types.iter().any(|t| t == "GET_LBMETA"));
+    }
+
+    #[test]
+    fn test_reply_carries_query_message_id() {
+        let repo = DeviceRepository::new();
+        repo.store(transform("Needle")).unwrap();
+
+        let mut q = query(GetTransformMessage, "Needle");
+        q.set_message_id(77);
+        let replies = repo.respond(&AnyMessage::GetTransform(q)).unwrap().unwrap();
+        assert_eq!(replies[0].message_id(), Some(77));
+        // The stored copy is left untouched
+        assert_eq!(repo.get("TRANSFORM", "Needle").unwrap().message_id(), None);
+    }
+
+    #[test]
+    fn test_repository_as_data_source() {
+        let repo = DeviceRepository::new();
+        repo.store(transform("Needle")).unwrap();
+
+        let request = StreamRequest {
+            message_type: "TRANSFORM".to_string(),
+            device_name: String::new(),
+            resolution: std::time::Duration::from_millis(50),
+            coordinate_name: String::new(),
+        };
+        let msg = repo.next_message(&request).unwrap().unwrap();
+        assert_eq!(msg.device_name().unwrap(), "Needle");
+    }
+}
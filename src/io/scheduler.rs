@@ -0,0 +1,704 @@
+//! Prioritized outgoing message scheduler
+//!
+//! A connection writes messages in the order they are sent, so a TRANSFORM sent right
+//! after a few large IMAGEs waits until all of them are flushed. [`OutgoingScheduler`]
+//! queues outgoing messages per [`Priority`] class and a writer task always sends the
+//! most urgent queued message next:
+//!
+//! - [`Priority::Control`] - status replies, commands and query/streaming control
+//! - [`Priority::Tracking`] - poses and sensor readings (TRANSFORM, TDATA, ...)
+//! - [`Priority::Bulk`] - everything else (IMAGE, VIDEO, NDARRAY, POLYDATA, ...)
+//!
+//! Messages of the same class keep their order. The class is derived from the message
+//! type ([`Priority::classify`]) or given explicitly with
+//! [`send_with_priority`](OutgoingScheduler::send_with_priority).
+//!
+//! OpenIGTLink frames cannot be interleaved on the wire, so the scheduler switches
+//! classes only between complete messages: a bulk message that is already being
+//! written is finished first. The wait of an urgent message is therefore bounded by
+//! one bulk message instead of the whole backlog.
+//!
+//! With [`with_chunk_size`](OutgoingScheduler::with_chunk_size), IMAGEs queued with
+//! [`send_image`](OutgoingScheduler::send_image) or `send_any` are sent as several
+//! sub-volume IMAGE messages (SUBVOL_OFFSET/SUBVOL_SIZE), slabs of slices or bands of
+//! rows, and urgent messages go out between them. This is off by default: receivers
+//! have to assemble the sub-volumes themselves, which the receivers and
+//! [`DeviceRepository`](crate::io::repository::DeviceRepository) of this crate do not
+//! do.
+//!
+//! [`SchedulerMetrics`] reports how long messages of each class waited in the queue.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::scheduler::OutgoingScheduler;
+//! use openigtlink_rust::io::AsyncIgtlServer;
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::{ImageMessage, ImageScalarType, TransformMessage};
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let server = AsyncIgtlServer::bind("0.0.0.0:18944").await?;
+//! let conn = server.accept().await?;
+//! let (_reader, writer) = conn.into_split();
+//! let (scheduler, task) = OutgoingScheduler::spawn(writer);
+//!
+//! let voxels = vec![0; 512 * 512 * 400];
+//! let volume = ImageMessage::new(ImageScalarType::Uint8, [512, 512, 400], voxels)?;
+//! scheduler.send(&IgtlMessage::new(volume, "CT")?).await?;
+//! // Sent as soon as the volume is on the wire, ahead of any further queued images
+//! scheduler.send(&IgtlMessage::new(TransformMessage::identity(), "Tool")?).await?;
+//!
+//! println!("{:?}", scheduler.metrics().tracking.mean_queue_time());
+//! drop(scheduler);
+//! task.finish().await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::io::AsyncWrite;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tracing::{debug, trace, warn};
+
+use crate::error::{IgtlError, Result};
+use crate::io::shutdown::{drain_task, join_task, CancellationToken};
+use crate::io::split::AsyncIgtlWriter;
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::types::ImageMessage;
+use crate::protocol::AnyMessage;
+
+/// Default number of queued messages per priority class
+pub const DEFAULT_QUEUE_CAPACITY: usize = 256;
+
+/// Priority class of an outgoing message, most urgent first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Priority {
+    /// Status replies, commands and query/streaming control messages
+    Control,
+    /// Poses and sensor readings
+    Tracking,
+    /// Large data such as images, video and meshes
+    Bulk,
+}
+
+impl Priority {
+    /// All classes, most urgent first
+    pub const ALL: [Priority; 3] = [Priority::Control, Priority::Tracking, Priority::Bulk];
+
+    /// Default class of a message type
+    ///
+    /// `GET_*`, `STT_*`, `STP_*` and `RTS_*` messages, STATUS, STRING, COMMAND and
+    /// CAPABILITY are control messages; TRANSFORM, POSITION, QTRANS, TDATA, QTDATA,
+    /// POINT and SENSOR are tracking messages; all other types are bulk.
+    pub fn classify(message_type: &str) -> Self {
+        const CONTROL_PREFIXES: [&str; 4] = ["GET_", "STT_", "STP_", "RTS_"];
+        match message_type {
+            "STATUS" | "STRING" | "COMMAND" | "CAPABILITY" => Priority::Control,
+            "TRANSFORM" | "POSITION" | "QTRANS" | "TDATA" | "QTDATA" | "POINT" | "SENSOR" => {
+                Priority::Tracking
+            }
+            t if CONTROL_PREFIXES.iter().any(|p| t.starts_with(p)) => Priority::Control,
+            _ => Priority::Bulk,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Queue statistics of one priority class
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClassMetrics {
+    /// Messages written to the transport, counting each sub-volume of a split IMAGE
+    pub sent: u64,
+    /// Bytes written to the transport
+    pub bytes: u64,
+    /// Messages waiting in the queue
+    pub queued: usize,
+    /// Sum of the time written messages waited before their write started
+    pub total_queue_time: Duration,
+    /// Longest time a written message waited before its write started
+    pub max_queue_time: Duration,
+}
+
+impl ClassMetrics {
+    /// Average time a written message waited before its write started
+    pub fn mean_queue_time(&self) -> Duration {
+        if self.sent == 0 {
+            Duration::ZERO
+        } else {
+            self.total_queue_time / self.sent as u32
+        }
+    }
+
+    fn record(&mut self, bytes: usize, waited: Duration) {
+        self.sent += 1;
+        self.bytes += bytes as u64;
+        self.total_queue_time += waited;
+        self.max_queue_time = self.max_queue_time.max(waited);
+    }
+}
+
+/// Queue statistics of an [`OutgoingScheduler`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SchedulerMetrics {
+    /// Control messages
+    pub control: ClassMetrics,
+    /// Tracking messages
+    pub tracking: ClassMetrics,
+    /// Bulk messages
+    pub bulk: ClassMetrics,
+}
+
+impl SchedulerMetrics {
+    /// Statistics of one class
+    pub fn class(&self, priority: Priority) -> &ClassMetrics {
+        match priority {
+            Priority::Control => &self.control,
+            Priority::Tracking => &self.tracking,
+            Priority::Bulk => &self.bulk,
+        }
+    }
+
+    fn class_mut(&mut self, priority: Priority) -> &mut ClassMetrics {
+        match priority {
+            Priority::Control => &mut self.control,
+            Priority::Tracking => &mut self.tracking,
+            Priority::Bulk => &mut self.bulk,
+        }
+    }
+}
+
+/// An encoded message waiting to be written
+struct Queued {
+    frame: Vec<u8>,
+    enqueued: Instant,
+}
+
+/// Sending side of a prioritized outgoing queue
+///
+/// Cheap to clone; all clones feed the same writer task. The task finishes once every
+/// clone is dropped and the queues are drained. See the [module documentation](self).
+#[derive(Clone)]
+pub struct OutgoingScheduler {
+    queues: [mpsc::Sender<Queued>; 3],
+    metrics: Arc<Mutex<SchedulerMetrics>>,
+    chunk_size: usize,
+}
+
+impl OutgoingScheduler {
+    /// Start a writer task for `writer` with [`DEFAULT_QUEUE_CAPACITY`] messages per class
+    pub fn spawn<W>(writer: AsyncIgtlWriter<W>) -> (Self, SchedulerTask<W>)
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        Self::spawn_with_capacity(writer, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Start a writer task for `writer` queueing up to `capacity` messages per class
+    ///
+    /// Sending to a full class waits until the writer makes room.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn spawn_with_capacity<W>(
+        writer: AsyncIgtlWriter<W>,
+        capacity: usize,
+    ) -> (Self, SchedulerTask<W>)
+    where
+        W: AsyncWrite + Unpin + Send + 'static,
+    {
+        let (control_tx, control_rx) = mpsc::channel(capacity);
+        let (tracking_tx, tracking_rx) = mpsc::channel(capacity);
+        let (bulk_tx, bulk_rx) = mpsc::channel(capacity);
+        let metrics = Arc::new(Mutex::new(SchedulerMetrics::default()));
+        let stop = CancellationToken::new();
+        let handle = tokio::spawn(write_loop(
+            writer,
+            [control_rx, tracking_rx, bulk_rx],
+            Arc::clone(&metrics),
+            stop.clone(),
+        ));
+        let scheduler = OutgoingScheduler {
+            queues: [control_tx, tracking_tx, bulk_tx],
+            metrics,
+            chunk_size: usize::MAX,
+        };
+        (scheduler, SchedulerTask { handle, stop })
+    }
+
+    /// Split IMAGE messages with more than `max_bytes` of image data into sub-volumes
+    ///
+    /// Applies to [`send_image`](Self::send_image) and `send_any` on this scheduler and
+    /// clones made from it afterwards. Each sub-volume holds at least one row. Only
+    /// enable it if the receivers assemble sub-volume IMAGE messages; by default
+    /// (`usize::MAX`) every image is sent in one message.
+    pub fn with_chunk_size(mut self, max_bytes: usize) -> Self {
+        self.chunk_size = max_bytes;
+        self
+    }
+
+    /// Queue a message in the class of its type
+    ///
+    /// Returns once the message is queued, not when it is written.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - The writer task stopped after a
+    ///   write error ([`BrokenPipe`](std::io::ErrorKind::BrokenPipe))
+    pub async fn send<T: Message>(&self, msg: &IgtlMessage<T>) -> Result<()> {
+        self.send_with_priority(msg, Priority::classify(T::message_type()))
+            .await
+    }
+
+    /// Queue a message in the given class
+    pub async fn send_with_priority<T: Message>(
+        &self,
+        msg: &IgtlMessage<T>,
+        priority: Priority,
+    ) -> Result<()> {
+        self.enqueue(T::message_type(), msg.encode()?, priority)
+            .await
+    }
+
+    /// Queue an IMAGE, split into sub-volumes as set by [`with_chunk_size`](Self::with_chunk_size)
+    pub async fn send_image(&self, msg: &IgtlMessage<ImageMessage>) -> Result<()> {
+        self.enqueue_image(msg, Priority::classify(ImageMessage::message_type()))
+            .await
+    }
+
+    /// Queue a dynamically typed message in the class of its type
+    ///
+    /// IMAGEs are split like with [`send_image`](Self::send_image).
+    pub async fn send_any(&self, msg: &AnyMessage) -> Result<()> {
+        let priority = Priority::classify(msg.message_type());
+        self.send_any_with_priority(msg, priority).await
+    }
+
+    /// Queue a dynamically typed message in the given class
+    pub async fn send_any_with_priority(&self, msg: &AnyMessage, priority: Priority) -> Result<()> {
+        if let AnyMessage::Image(image) = msg {
+            return self.enqueue_image(image, priority).await;
+        }
+        self.enqueue(msg.message_type(), msg.encode()?, priority)
+            .await
+    }
+
+    /// Queue an IMAGE as sub-volumes of at most `chunk_size` bytes of image data
+    async fn enqueue_image(
+        &self,
+        msg: &IgtlMessage<ImageMessage>,
+        priority: Priority,
+    ) -> Result<()> {
+        let chunks = chunk_boxes(&msg.content, self.chunk_size);
+        if chunks.len() <= 1 {
+            return self.enqueue("IMAGE", msg.encode()?, priority).await;
+        }
+        trace!(chunks = chunks.len(), "Splitting IMAGE into sub-volumes");
+        for (offset, size) in chunks {
+            let chunk = IgtlMessage {
+                header: msg.header.clone(),
+                extended_header: msg.extended_header.clone(),
+                content: msg.content.subvolume(offset, size)?,
+                metadata: msg.metadata.clone(),
+            };
+            self.enqueue("IMAGE", chunk.encode()?, priority).await?;
+        }
+        Ok(())
+    }
+
+    /// Current queue statistics
+    pub fn metrics(&self) -> SchedulerMetrics {
+        let mut metrics = self.metrics.lock().unwrap().clone();
+        for priority in Priority::ALL {
+            let queue = &self.queues[priority.index()];
+            metrics.class_mut(priority).queued = queue.max_capacity() - queue.capacity();
+        }
+        metrics
+    }
+
+    async fn enqueue(&self, message_type: &str, frame: Vec<u8>, priority: Priority) -> Result<()> {
+        trace!(
+            msg_type = message_type,
+            size = frame.len(),
+            priority = ?priority,
+            "Queueing message"
+        );
+        let queued = Queued {
+            frame,
+            enqueued: Instant::now(),
+        };
+        self.queues[priority.index()]
+            .send(queued)
+            .await
+            .map_err(|_| {
+                IgtlError::Io(std::io::Error::new(
+                    std::io::ErrorKind::BrokenPipe,
+                    "outgoing scheduler stopped after a write error",
+                ))
+            })
+    }
+}
+
+/// Sub-volumes (offset, size) of `image` with at most `max_bytes` of data each
+///
+/// Whole slices are grouped into slabs; a slice larger than `max_bytes` is cut into
+/// bands of rows instead, at least one row each.
+fn chunk_boxes(image: &ImageMessage, max_bytes: usize) -> Vec<([u16; 3], [u16; 3])> {
+    let [x, y, z] = image.subvolume_offset;
+    let [columns, rows, slices] = image.subvolume_size;
+    let row_bytes = columns as usize * image.voxel_size();
+    let slice_bytes = row_bytes * rows as usize;
+    if slice_bytes * slices as usize <= max_bytes || slice_bytes == 0 {
+        return vec![(image.subvolume_offset, image.subvolume_size)];
+    }
+
+    let mut chunks = Vec::new();
+    if slice_bytes <= max_bytes {
+        let step = (max_bytes / slice_bytes).min(slices as usize) as u16;
+        for k in (0..slices).step_by(step as usize) {
+            let count = step.min(slices - k);
+            chunks.push(([x, y, z + k], [columns, rows, count]));
+        }
+    } else {
+        let step = (max_bytes / row_bytes).clamp(1, rows as usize) as u16;
+        for k in 0..slices {
+            for j in (0..rows).step_by(step as usize) {
+                let count = step.min(rows - j);
+                chunks.push(([x, y + j, z + k], [columns, count, 1]));
+            }
+        }
+    }
+    chunks
+}
+
+/// Writer task of an [`OutgoingScheduler`]
+pub struct SchedulerTask<W> {
+    handle: JoinHandle<Result<AsyncIgtlWriter<W>>>,
+    stop: CancellationToken,
+}
+
+impl<W> SchedulerTask<W> {
+    /// Wait until all schedulers are dropped and the queues are drained
+    ///
+    /// Returns the writer for further use.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - A write failed; queued messages
+    ///   were discarded
+    pub async fn finish(mut self) -> Result<AsyncIgtlWriter<W>> {
+        join_task(&mut self.handle).await
+    }
+
+    /// Stop accepting messages and send the queued ones, waiting up to `deadline`
+    ///
+    /// Sends on any scheduler fail from now on. Returns the writer once the queues are
+    /// empty, e.g. to [`shutdown`](AsyncIgtlWriter::shutdown) it.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - A write failed, or
+    ///   [`TimedOut`](std::io::ErrorKind::TimedOut) if the queues were not drained in
+    ///   time; the task is stopped and the remaining messages discarded
+    pub async fn shutdown(self, deadline: Duration) -> Result<AsyncIgtlWriter<W>> {
+        drain_task(self.handle, &self.stop, deadline).await
+    }
+
+    /// Stop the writer task, discarding queued messages
+    pub fn abort(&self) {
+        self.handle.abort();
+    }
+}
+
+async fn write_loop<W>(
+    mut writer: AsyncIgtlWriter<W>,
+    [mut control, mut tracking, mut bulk]: [mpsc::Receiver<Queued>; 3],
+    metrics: Arc<Mutex<SchedulerMetrics>>,
+    stop: CancellationToken,
+) -> Result<AsyncIgtlWriter<W>>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut open = [true; 3];
+    let mut draining = false;
+    while open.contains(&true) {
+        let (priority, queued) = tokio::select! {
+            biased;
+            _ = stop.cancelled(), if !draining => {
+                debug!("Draining outgoing scheduler");
+                draining = true;
+                control.close();
+                tracking.close();
+                bulk.close();
+                continue;
+            }
+            queued = control.recv(), if open[0] => (Priority::Control, queued),
+            queued = tracking.recv(), if open[1] => (Priority::Tracking, queued),
+            queued = bulk.recv(), if open[2] => (Priority::Bulk, queued),
+        };
+        // A class is done once all schedulers are dropped (or it was closed) and it is empty
+        let Some(queued) = queued else {
+            open[priority.index()] = false;
+            continue;
+        };
+        let waited = queued.enqueued.elapsed();
+        let size = queued.frame.len();
+        if let Err(e) = writer.write_frame(queued.frame).await {
+            warn!(error = %e, priority = ?priority, "Scheduled write failed");
+            return Err(e);
+        }
+        metrics
+            .lock()
+            .unwrap()
+            .class_mut(priority)
+            .record(size, waited);
+    }
+    debug!("Outgoing scheduler finished");
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::split::AsyncIgtlReader;
+    use crate::protocol::types::{ImageMessage, ImageScalarType, StatusMessage, TransformMessage};
+
+    fn image() -> IgtlMessage<ImageMessage> {
+        let data = vec![0u8; 64 * 64 * 16];
+        let image = ImageMessage::new(ImageScalarType::Uint8, [64, 64, 16], data).unwrap();
+        IgtlMessage::new(image, "Volume").unwrap()
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(Priority::classify("STATUS"), Priority::Control);
+        assert_eq!(Priority::classify("GET_TRANSFORM"), Priority::Control);
+        assert_eq!(Priority::classify("RTS_TDATA"), Priority::Control);
+        assert_eq!(Priority::classify("TRANSFORM"), Priority::Tracking);
+        assert_eq!(Priority::classify("TDATA"), Priority::Tracking);
+        assert_eq!(Priority::classify("IMAGE"), Priority::Bulk);
+        assert_eq!(Priority::classify("NDARRAY"), Priority::Bulk);
+    }
+
+    #[tokio::test]
+    async fn test_urgent_messages_overtake_queued_bulk() {
+        // A small pipe keeps the writer busy with the first image
+        let (client, server) = tokio::io::duplex(4096);
+        let (scheduler, task) = OutgoingScheduler::spawn(AsyncIgtlWriter::new(client));
+
+        for _ in 0..3 {
+            scheduler.send(&image()).await.unwrap();
+        }
+        let pose = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        scheduler.send(&pose).await.unwrap();
+        let status = IgtlMessage::new(StatusMessage::ok("ready"), "Server").unwrap();
+        scheduler.send(&status).await.unwrap();
+        drop(scheduler);
+
+        let mut reader = AsyncIgtlReader::new(server, true);
+        let mut order = Vec::new();
+        for _ in 0..5 {
+            order.push(
+                reader
+                    .receive_any()
+                    .await
+                    .unwrap()
+                    .message_type()
+                    .to_string(),
+            );
+        }
+        let position = |t: &str| order.iter().position(|o| o == t).unwrap();
+        // At most the image already being written goes first
+        assert!(position("STATUS") <= 1, "{order:?}");
+        assert_eq!(position("TRANSFORM"), position("STATUS") + 1, "{order:?}");
+        assert_eq!(order.iter().filter(|t| *t == "IMAGE").count(), 3);
+        task.finish().await.unwrap();
+    }
+
+    #[test]
+    fn test_chunk_boxes() {
+        let image = |size: [u16; 3]| {
+            let len = size.iter().map(|&s| s as usize).product::<usize>() * 2;
+            ImageMessage::new(ImageScalarType::Uint16, size, vec![0; len]).unwrap()
+        };
+        // Slabs of whole slices; the last one is shorter
+        assert_eq!(
+            chunk_boxes(&image([8, 8, 5]), 256),
+            [
+                ([0, 0, 0], [8, 8, 2]),
+                ([0, 0, 2], [8, 8, 2]),
+                ([0, 0, 4], [8, 8, 1])
+            ]
+        );
+        // Bands of rows when one slice is too large, within the carried sub-volume
+        let part = image([8, 8, 5]).subvolume([0, 4, 3], [8, 3, 2]).unwrap();
+        assert_eq!(
+            chunk_boxes(&part, 40),
+            [
+                ([0, 4, 3], [8, 2, 1]),
+                ([0, 6, 3], [8, 1, 1]),
+                ([0, 4, 4], [8, 2, 1]),
+                ([0, 6, 4], [8, 1, 1])
+            ]
+        );
+        assert_eq!(chunk_boxes(&part, 1)[0], ([0, 4, 3], [8, 1, 1]));
+        assert_eq!(chunk_boxes(&part, 96), [([0, 4, 3], [8, 3, 2])]);
+    }
+
+    #[tokio::test]
+    async fn test_tracking_interleaves_with_image_chunks() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (scheduler, task) = OutgoingScheduler::spawn(AsyncIgtlWriter::new(client));
+        let scheduler = scheduler.with_chunk_size(64 * 1024);
+
+        let data: Vec<u8> = (0..256 * 256 * 16).map(|i| i as u8).collect();
+        let volume = ImageMessage::new(ImageScalarType::Uint8, [256, 256, 16], data).unwrap();
+        let volume = IgtlMessage::new(volume, "Volume").unwrap();
+        let started = Instant::now();
+        scheduler.send_image(&volume).await.unwrap();
+        let pose = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        scheduler.send(&pose).await.unwrap();
+        drop(scheduler);
+
+        // A slow reader: the image takes at least 16 reads of 5 ms to arrive
+        let mut reader = AsyncIgtlReader::new(server, true);
+        let mut reassembled = vec![0u8; volume.content.data.len()];
+        let mut chunks = 0;
+        let mut pose_latency = None;
+        while chunks < 16 {
+            match reader.receive_any().await.unwrap() {
+                AnyMessage::Image(image) => {
+                    let image = image.content;
+                    assert_eq!(image.size, [256, 256, 16]);
+                    assert_eq!(image.subvolume_size, [256, 256, 1]);
+                    let start = image.subvolume_offset[2] as usize * 256 * 256;
+                    reassembled[start..start + image.data.len()].copy_from_slice(&image.data);
+                    chunks += 1;
+                }
+                AnyMessage::Transform(_) => {
+                    assert!(chunks <= 1, "pose waited for {chunks} chunks");
+                    pose_latency = Some(started.elapsed());
+                }
+                other => panic!("unexpected {}", other.message_type()),
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let image_time = started.elapsed();
+        let pose_latency = pose_latency.expect("pose not received");
+        assert!(
+            pose_latency < image_time / 2,
+            "{pose_latency:?} vs {image_time:?}"
+        );
+        assert_eq!(reassembled, volume.content.data);
+        task.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_images_are_not_split_by_default() {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let (scheduler, task) = OutgoingScheduler::spawn(AsyncIgtlWriter::new(client));
+        scheduler.send_image(&image()).await.unwrap();
+        scheduler
+            .with_chunk_size(1024)
+            .send_any(&AnyMessage::Image(image()))
+            .await
+            .unwrap();
+
+        let mut reader = AsyncIgtlReader::new(server, true);
+        let AnyMessage::Image(whole) = reader.receive_any().await.unwrap() else {
+            panic!("expected an IMAGE");
+        };
+        assert!(!whole.content.is_subvolume());
+        // 64 x 64 x 16 voxels in bands of 16 rows
+        let AnyMessage::Image(part) = reader.receive_any().await.unwrap() else {
+            panic!("expected an IMAGE");
+        };
+        assert_eq!(part.content.subvolume_size, [64, 16, 1]);
+        task.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_metrics_per_class() {
+        let (client, server) = tokio::io::duplex(1 << 20);
+        let (scheduler, task) = OutgoingScheduler::spawn(AsyncIgtlWriter::new(client));
+
+        scheduler.send(&image()).await.unwrap();
+        for _ in 0..2 {
+            let status = IgtlMessage::new(StatusMessage::ok("ok"), "Server").unwrap();
+            scheduler.send(&status).await.unwrap();
+        }
+        let handle = scheduler.clone();
+        drop(scheduler);
+        let mut reader = AsyncIgtlReader::new(server, true);
+        for _ in 0..3 {
+            reader.receive_any().await.unwrap();
+        }
+        // Metrics are recorded right after each write completes
+        while handle.metrics().control.sent < 2 {
+            tokio::task::yield_now().await;
+        }
+
+        let metrics = handle.metrics();
+        assert_eq!(metrics.control.sent, 2);
+        assert_eq!(metrics.bulk.sent, 1);
+        assert_eq!(metrics.tracking, ClassMetrics::default());
+        assert!(metrics.bulk.bytes > 64 * 64 * 16);
+        assert!(metrics.control.max_queue_time >= metrics.control.mean_queue_time());
+        drop(handle);
+        task.finish().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_write_error_stops_scheduler() {
+        let (client, server) = tokio::io::duplex(1024);
+        drop(server);
+        let (scheduler, task) = OutgoingScheduler::spawn(AsyncIgtlWriter::new(client));
+
+        let status = IgtlMessage::new(StatusMessage::ok("lost"), "Server").unwrap();
+        scheduler.send(&status).await.unwrap();
+        let err = loop {
+            tokio::task::yield_now().await;
+            if let Err(e) = scheduler.send(&status).await {
+                break e;
+            }
+        };
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::BrokenPipe));
+        assert!(task.finish().await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_queues_within_deadline() {
+        let (client, server) = tokio::io::duplex(4096);
+        let (scheduler, task) = OutgoingScheduler::spawn(AsyncIgtlWriter::new(client));
+        for _ in 0..3 {
+            scheduler.send(&image()).await.unwrap();
+        }
+
+        let reading = tokio::spawn(async move {
+            let mut reader = AsyncIgtlReader::new(server, true);
+            for _ in 0..3 {
+                reader.receive_any().await.unwrap();
+            }
+            reader
+        });
+        // The scheduler is still alive; shutdown does not wait for it to be dropped
+        task.shutdown(Duration::from_secs(5)).await.unwrap();
+        reading.await.unwrap();
+        assert!(scheduler.send(&image()).await.is_err());
+
+        // Nobody reads this pipe, so the drain deadline passes
+        let (client, _server) = tokio::io::duplex(4096);
+        let (scheduler, task) = OutgoingScheduler::spawn(AsyncIgtlWriter::new(client));
+        scheduler.send(&image()).await.unwrap();
+        match task.shutdown(Duration::from_millis(50)).await {
+            Err(IgtlError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+            other => panic!("expected timeout, got {:?}", other.is_ok()),
+        }
+    }
+}
@@ -1,14 +1,23 @@
 //! Synchronous OpenIGTLink server implementation
 //!
-//! Provides a simple blocking TCP server for OpenIGTLink communication.
+//! Provides a simple blocking TCP server for OpenIGTLink communication, and
+//! [`SyncTlsIgtlServer`], its TLS-encrypted counterpart.
 
-use std::io::{Read, Write};
-use std::net::{TcpListener, TcpStream};
+use std::net::{Shutdown, SocketAddr, TcpListener};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
+use tokio::sync::broadcast;
+use tokio_rustls::rustls;
 use tracing::{debug, info, trace, warn};
 
-use crate::error::Result;
-use crate::protocol::header::Header;
+use crate::error::{IgtlError, Result};
+use crate::io::common::decode_any_frame;
+use crate::io::events::{ConnectionEvent, EventEmitter};
+use crate::io::limits::{admit_session, ServerLimits, SessionGovernor, SessionTracker};
+use crate::io::stream::IgtlStream;
+use crate::io::sync_stream::SyncStream;
+use crate::io::tls::{peer_certificate, PeerCertificate, ServerTlsConfig};
 use crate::protocol::message::{IgtlMessage, Message};
 use crate::protocol::AnyMessage;
 
@@ -17,6 +26,9 @@ use crate::protocol::AnyMessage;
 /// Uses blocking I/O with `std::net::TcpListener` for simple, synchronous server implementation.
 pub struct IgtlServer {
     listener: TcpListener,
+    events: EventEmitter,
+    limits: ServerLimits,
+    sessions: Arc<SessionTracker>,
 }
 
 impl IgtlServer {
@@ -46,7 +58,12 @@ impl IgtlServer {
             local_addr = %local_addr,
             "OpenIGTLink server listening"
         );
-        Ok(IgtlServer { listener })
+        Ok(IgtlServer {
+            listener,
+            events: EventEmitter::default(),
+            limits: ServerLimits::default(),
+            sessions: Arc::default(),
+        })
     }
 
     /// Accept a new client connection
@@ -67,33 +84,257 @@ impl IgtlServer {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn accept(&self) -> Result<IgtlConnection> {
-        trace!("Waiting for client connection");
-        let (stream, addr) = self.listener.accept()?;
+        loop {
+            trace!("Waiting for client connection");
+            let (stream, addr) = self.listener.accept()?;
+            let Some(session) = admit_session(&self.sessions, &self.limits, &self.events, addr)
+            else {
+                continue;
+            };
+            info!(
+                peer_addr = %addr,
+                "Client connected"
+            );
+            self.events.emit(ConnectionEvent::Connected { peer: addr });
+            return Ok(IgtlConnection::new(
+                SyncStream::Plain(stream),
+                None,
+                addr,
+                self.events.connection(Some(addr)),
+                session,
+            ));
+        }
+    }
+
+    /// Apply connection and resource limits to sessions accepted from now on
+    ///
+    /// See [`limits`](crate::io::limits).
+    pub fn with_limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits applied to accepted sessions
+    pub fn limits(&self) -> &ServerLimits {
+        &self.limits
+    }
+
+    /// Number of accepted sessions that are still open
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.active()
+    }
+
+    /// Get the local address this server is bound to
+    pub fn local_addr(&self) -> Result<std::net::SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Subscribe to lifecycle events of all sessions accepted by this server
+    ///
+    /// Also reports rejected connections. See [`events`](crate::io::events).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+}
+
+/// TLS-encrypted synchronous OpenIGTLink server
+///
+/// Blocking counterpart of [`TlsIgtlServer`](crate::io::TlsIgtlServer). Accepted connections are regular
+/// [`IgtlConnection`]s; the TLS handshake completes inside [`accept`](Self::accept).
+///
+/// # Examples
+///
+/// ```no_run
+/// use openigtlink_rust::io::SyncTlsIgtlServer;
+///
+/// let server = SyncTlsIgtlServer::bind("0.0.0.0:18944", "cert.pem", "key.pem")?;
+/// let mut conn = server.accept()?;
+/// let msg = conn.receive_any()?;
+/// # Ok::<(), openigtlink_rust::error::IgtlError>(())
+/// ```
+pub struct SyncTlsIgtlServer {
+    listener: TcpListener,
+    config: Arc<rustls::ServerConfig>,
+    events: EventEmitter,
+    limits: ServerLimits,
+    sessions: Arc<SessionTracker>,
+}
+
+impl SyncTlsIgtlServer {
+    /// Bind to a local address with TLS using certificate files
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Local address to bind (e.g., "0.0.0.0:18944")
+    /// * `cert_path` - Path to PEM-encoded certificate file
+    /// * `key_path` - Path to PEM-encoded private key file
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind or load certificates
+    pub fn bind(addr: &str, cert_path: &str, key_path: &str) -> Result<Self> {
         info!(
-            peer_addr = %addr,
-            "Client connected"
+            addr = addr,
+            cert = cert_path,
+            key = key_path,
+            "Binding TLS-enabled OpenIGTLink server"
         );
-        Ok(IgtlConnection {
-            stream,
-            verify_crc: true, // Default: verify CRC
+
+        let config = ServerTlsConfig::new(cert_path, key_path).build()?;
+        Self::bind_with_config(addr, config)
+    }
+
+    /// Bind with custom TLS configuration
+    ///
+    /// Allows advanced configuration like client authentication, cipher suites, etc.
+    /// See [`ServerTlsConfig`] for requiring client certificates.
+    pub fn bind_with_config(addr: &str, config: rustls::ServerConfig) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        info!(
+            local_addr = %local_addr,
+            "TLS server listening"
+        );
+        Ok(SyncTlsIgtlServer {
+            listener,
+            config: Arc::new(config),
+            events: EventEmitter::default(),
+            limits: ServerLimits::default(),
+            sessions: Arc::default(),
         })
     }
 
+    /// Accept a new TLS client connection
+    ///
+    /// Blocks until a client connects and the TLS handshake completes. Handshakes run
+    /// one at a time within the first-message deadline of the [`ServerLimits`], or
+    /// [`DEFAULT_HANDSHAKE_TIMEOUT`](crate::io::limits::DEFAULT_HANDSHAKE_TIMEOUT) if none
+    /// is set; failed or timed out handshakes are logged, reported as
+    /// [`ConnectionEvent::TlsHandshakeFailed`] and skipped.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to accept connection
+    pub fn accept(&self) -> Result<IgtlConnection> {
+        loop {
+            trace!("Waiting for TLS client connection");
+            let (stream, addr) = self.listener.accept()?;
+            let Some(session) = admit_session(&self.sessions, &self.limits, &self.events, addr)
+            else {
+                continue;
+            };
+            debug!(peer_addr = %addr, "TCP connection accepted, starting TLS handshake");
+
+            // The first-message deadline, or the default handshake timeout, bounds
+            // each read and write of the handshake. A zero socket timeout is rejected
+            // by the standard library.
+            let timeout = remaining(session.handshake_deadline()).max(Duration::from_millis(1));
+            stream.set_read_timeout(Some(timeout))?;
+            stream.set_write_timeout(Some(timeout))?;
+            let stream = match SyncStream::tls_server(stream, self.config.clone()) {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!(error = %e, peer_addr = %addr, "TLS handshake failed");
+                    self.events.emit(ConnectionEvent::TlsHandshakeFailed {
+                        peer: Some(addr),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            stream.tcp().set_read_timeout(None)?;
+            stream.tcp().set_write_timeout(None)?;
+
+            let peer_certificate = match &stream {
+                SyncStream::TlsServer(tls) => peer_certificate(tls.conn.peer_certificates()),
+                _ => None,
+            };
+            info!(
+                peer_addr = %addr,
+                peer_subject = peer_certificate.as_ref().map(|c| c.subject()),
+                "TLS client connected"
+            );
+            self.events.emit(ConnectionEvent::Connected { peer: addr });
+            return Ok(IgtlConnection::new(
+                stream,
+                peer_certificate,
+                addr,
+                self.events.connection(Some(addr)),
+                session,
+            ));
+        }
+    }
+
+    /// Apply connection and resource limits to sessions accepted from now on
+    ///
+    /// The first-message deadline also bounds the TLS handshake, which is otherwise
+    /// limited to [`DEFAULT_HANDSHAKE_TIMEOUT`](crate::io::limits::DEFAULT_HANDSHAKE_TIMEOUT).
+    /// See [`limits`](crate::io::limits).
+    pub fn with_limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits applied to accepted sessions
+    pub fn limits(&self) -> &ServerLimits {
+        &self.limits
+    }
+
+    /// Number of accepted sessions that are still open
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.active()
+    }
+
     /// Get the local address this server is bound to
     pub fn local_addr(&self) -> Result<std::net::SocketAddr> {
         Ok(self.listener.local_addr()?)
     }
+
+    /// Subscribe to lifecycle events of all sessions accepted by this server
+    ///
+    /// Also reports rejected connections and failed TLS handshakes. See
+    /// [`events`](crate::io::events).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+}
+
+/// Time left until `deadline`
+fn remaining(deadline: Instant) -> Duration {
+    deadline.saturating_duration_since(Instant::now())
 }
 
 /// Represents an accepted client connection
 ///
 /// Provides methods to send and receive OpenIGTLink messages over the connection.
 pub struct IgtlConnection {
-    stream: TcpStream,
-    verify_crc: bool,
+    inner: IgtlStream<SyncStream>,
+    peer_certificate: Option<PeerCertificate>,
+    peer: SocketAddr,
+    events: EventEmitter,
+    session: SessionGovernor,
+    /// Read timeout requested by the application
+    read_timeout: Option<Duration>,
 }
 
 impl IgtlConnection {
+    fn new(
+        stream: SyncStream,
+        peer_certificate: Option<PeerCertificate>,
+        peer: SocketAddr,
+        events: EventEmitter,
+        session: SessionGovernor,
+    ) -> Self {
+        IgtlConnection {
+            inner: IgtlStream::new(stream),
+            peer_certificate,
+            peer,
+            events,
+            session,
+            read_timeout: None,
+        }
+    }
+
     /// Enable or disable CRC verification for received messages
     ///
     /// # Arguments
@@ -105,13 +346,7 @@ impl IgtlConnection {
     /// Disabling CRC verification should only be done in trusted environments
     /// where data corruption is unlikely (e.g., loopback, local network).
     pub fn set_verify_crc(&mut self, verify: bool) {
-        if verify != self.verify_crc {
-            info!(verify = verify, "CRC verification setting changed");
-            if !verify {
-                warn!("CRC verification disabled - use only in trusted environments");
-            }
-        }
-        self.verify_crc = verify;
+        self.inner.set_verify_crc(verify);
     }
 
     /// Get current CRC verification setting
@@ -120,7 +355,7 @@ impl IgtlConnection {
     ///
     /// true if CRC verification is enabled, false otherwise
     pub fn verify_crc(&self) -> bool {
-        self.verify_crc
+        self.inner.verify_crc()
     }
 
     /// Send a message to the connected client
@@ -150,27 +385,25 @@ impl IgtlConnection {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
-        let data = msg.encode()?;
-        let msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN");
-        let device_name = msg.header.device_name.as_str().unwrap_or("UNKNOWN");
-
-        debug!(
-            msg_type = msg_type,
-            device_name = device_name,
-            size = data.len(),
-            "Sending message to client"
-        );
-
-        self.stream.write_all(&data)?;
-        self.stream.flush()?;
-
-        trace!(
-            msg_type = msg_type,
-            bytes_sent = data.len(),
-            "Message sent successfully"
-        );
+        self.session.check_open()?;
+        let result = self.inner.send(msg);
+        self.events.check_result(&result, Some(self.peer));
+        result
+    }
 
-        Ok(())
+    /// Send a dynamically typed message to the connected client
+    ///
+    /// Useful when relaying or replaying messages received with
+    /// [`receive_any`](Self::receive_any).
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network write failed
+    pub fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        self.session.check_open()?;
+        let result = self.inner.send_any(msg);
+        self.events.check_result(&result, Some(self.peer));
+        result
     }
 
     /// Receive a message from the connected client
@@ -199,58 +432,9 @@ impl IgtlConnection {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
-        trace!("Waiting for message header from client");
-
-        // Read header (58 bytes)
-        let mut header_buf = vec![0u8; Header::SIZE];
-        self.stream.read_exact(&mut header_buf)?;
-
-        let header = Header::decode(&header_buf)?;
-
-        let msg_type = header.type_name.as_str().unwrap_or("UNKNOWN");
-        let device_name = header.device_name.as_str().unwrap_or("UNKNOWN");
-
-        debug!(
-            msg_type = msg_type,
-            device_name = device_name,
-            body_size = header.body_size,
-            version = header.version,
-            "Received message header from client"
-        );
-
-        // Read body
-        let mut body_buf = vec![0u8; header.body_size as usize];
-        self.stream.read_exact(&mut body_buf)?;
-
-        trace!(
-            msg_type = msg_type,
-            bytes_read = body_buf.len(),
-            "Message body received from client"
-        );
-
-        // Decode full message with CRC verification setting
-        let mut full_msg = header_buf;
-        full_msg.extend_from_slice(&body_buf);
-
-        let result = IgtlMessage::decode_with_options(&full_msg, self.verify_crc);
-
-        match &result {
-            Ok(_) => {
-                debug!(
-                    msg_type = msg_type,
-                    device_name = device_name,
-                    "Message decoded successfully"
-                );
-            }
-            Err(e) => {
-                warn!(
-                    msg_type = msg_type,
-                    error = %e,
-                    "Failed to decode message from client"
-                );
-            }
-        }
-
+        let frame = self.read_frame()?;
+        let result = IgtlMessage::decode_with_options(&frame, self.inner.verify_crc());
+        self.events.check_decode(&result, Some(self.peer));
         result
     }
 
@@ -283,58 +467,9 @@ impl IgtlConnection {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn receive_any(&mut self) -> Result<AnyMessage> {
-        trace!("Waiting for any message type from client");
-
-        // Read header (58 bytes)
-        let mut header_buf = vec![0u8; Header::SIZE];
-        self.stream.read_exact(&mut header_buf)?;
-
-        let header = Header::decode(&header_buf)?;
-
-        let msg_type = header.type_name.as_str().unwrap_or("UNKNOWN");
-        let device_name = header.device_name.as_str().unwrap_or("UNKNOWN");
-
-        debug!(
-            msg_type = msg_type,
-            device_name = device_name,
-            body_size = header.body_size,
-            version = header.version,
-            "Received message header from client"
-        );
-
-        // Read body
-        let mut body_buf = vec![0u8; header.body_size as usize];
-        self.stream.read_exact(&mut body_buf)?;
-
-        trace!(
-            msg_type = msg_type,
-            bytes_read = body_buf.len(),
-            "Message body received from client"
-        );
-
-        // Decode full message with CRC verification setting
-        let mut full_msg = header_buf;
-        full_msg.extend_from_slice(&body_buf);
-
-        let result = AnyMessage::decode_with_options(&full_msg, self.verify_crc);
-
-        match &result {
-            Ok(_) => {
-                debug!(
-                    msg_type = msg_type,
-                    device_name = device_name,
-                    "Message decoded successfully as AnyMessage"
-                );
-            }
-            Err(e) => {
-                warn!(
-                    msg_type = msg_type,
-                    error = %e,
-                    "Failed to decode message from client"
-                );
-            }
-        }
-
+        let frame = self.read_frame()?;
+        let result = decode_any_frame(&frame, self.inner.verify_crc());
+        self.events.check_decode(&result, Some(self.peer));
         result
     }
 
@@ -343,18 +478,66 @@ impl IgtlConnection {
     /// # Arguments
     ///
     /// * `timeout` - Timeout duration (None for infinite)
+    ///
+    /// An expired receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut). Partially
+    /// received data is kept, so the connection stays usable.
     pub fn set_read_timeout(&mut self, timeout: Option<std::time::Duration>) -> Result<()> {
-        self.stream.set_read_timeout(timeout)?;
+        self.inner.get_ref().tcp().set_read_timeout(timeout)?;
+        self.read_timeout = timeout;
         Ok(())
     }
 
+    /// Read one frame within the session limits, closing the session on a violation
+    fn read_frame(&mut self) -> Result<Vec<u8>> {
+        self.session.check_open()?;
+        let result = match self.session.deadline() {
+            Some(deadline) => self.read_frame_until(deadline),
+            None => self
+                .inner
+                .read_frame()
+                .and_then(|frame| self.session.check_frame(frame)),
+        };
+        if self.session.is_closed() {
+            let _ = self.inner.get_ref().tcp().shutdown(Shutdown::Both);
+        }
+        self.events.check_result(&result, Some(self.peer));
+        result
+    }
+
+    /// Read one frame, expiring the session if nothing arrives before `deadline`
+    fn read_frame_until(&mut self, deadline: Instant) -> Result<Vec<u8>> {
+        let remaining = remaining(deadline);
+        if remaining.is_zero() {
+            return Err(self.session.expire());
+        }
+        let timeout = match self.read_timeout {
+            Some(timeout) if timeout < remaining => timeout,
+            _ => remaining,
+        };
+        self.inner.get_ref().tcp().set_read_timeout(Some(timeout))?;
+        let result = self.inner.read_frame();
+        self.inner
+            .get_ref()
+            .tcp()
+            .set_read_timeout(self.read_timeout)?;
+        match result {
+            Ok(frame) => self.session.check_frame(frame),
+            Err(IgtlError::Io(e))
+                if e.kind() == std::io::ErrorKind::TimedOut && Instant::now() >= deadline =>
+            {
+                Err(self.session.expire())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
     /// Set write timeout for the underlying TCP stream
     ///
     /// # Arguments
     ///
     /// * `timeout` - Timeout duration (None for infinite)
     pub fn set_write_timeout(&mut self, timeout: Option<std::time::Duration>) -> Result<()> {
-        self.stream.set_write_timeout(timeout)?;
+        self.inner.get_ref().tcp().set_write_timeout(timeout)?;
         Ok(())
     }
 
@@ -362,7 +545,7 @@ impl IgtlConnection {
     ///
     /// Wrapper around [`std::net::TcpStream::set_nodelay`].
     pub fn set_nodelay(&self, nodelay: bool) -> Result<()> {
-        self.stream.set_nodelay(nodelay)?;
+        self.inner.get_ref().tcp().set_nodelay(nodelay)?;
         Ok(())
     }
 
@@ -372,7 +555,7 @@ impl IgtlConnection {
         {
             use std::os::fd::AsRawFd;
 
-            let fd = self.stream.as_raw_fd();
+            let fd = self.inner.get_ref().tcp().as_raw_fd();
             let size = size as libc::c_int;
 
             unsafe {
@@ -398,7 +581,7 @@ impl IgtlConnection {
             const SOL_SOCKET: libc::c_int = 0xffff;
             const SO_RCVBUF: libc::c_int = 0x1002;
 
-            let socket = self.stream.as_raw_socket();
+            let socket = self.inner.get_ref().tcp().as_raw_socket();
             let size = size as libc::c_int;
 
             unsafe {
@@ -425,7 +608,7 @@ impl IgtlConnection {
         {
             use std::os::fd::AsRawFd;
 
-            let fd = self.stream.as_raw_fd();
+            let fd = self.inner.get_ref().tcp().as_raw_fd();
             let size = size as libc::c_int;
 
             unsafe {
@@ -451,7 +634,7 @@ impl IgtlConnection {
             const SOL_SOCKET: libc::c_int = 0xffff;
             const SO_SNDBUF: libc::c_int = 0x1001;
 
-            let socket = self.stream.as_raw_socket();
+            let socket = self.inner.get_ref().tcp().as_raw_socket();
             let size = size as libc::c_int;
 
             unsafe {
@@ -474,17 +657,63 @@ impl IgtlConnection {
 
     /// Get the current TCP_NODELAY setting
     pub fn nodelay(&self) -> Result<bool> {
-        Ok(self.stream.nodelay()?)
+        Ok(self.inner.get_ref().tcp().nodelay()?)
     }
 
     /// Get the remote peer address
     pub fn peer_addr(&self) -> Result<std::net::SocketAddr> {
-        Ok(self.stream.peer_addr()?)
+        Ok(self.peer)
+    }
+
+    /// Verified certificate presented by the client
+    ///
+    /// Only available on connections accepted by a [`SyncTlsIgtlServer`] that requires
+    /// client certificates (see [`ServerTlsConfig::with_client_auth`]).
+    pub fn peer_certificate(&self) -> Option<&PeerCertificate> {
+        self.peer_certificate.as_ref()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    // Integration tests require multi-threaded setup
-    // See examples/ for end-to-end testing
+    use super::*;
+    use crate::protocol::types::StatusMessage;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_idle_session_is_evicted() {
+        let limits = ServerLimits::new().with_idle_timeout(Duration::from_millis(100));
+        let server = IgtlServer::bind("127.0.0.1:0").unwrap().with_limits(limits);
+        let mut stream = std::net::TcpStream::connect(server.local_addr().unwrap()).unwrap();
+        let mut conn = server.accept().unwrap();
+        // A shorter application timeout still reports plain timeouts
+        conn.set_read_timeout(Some(Duration::from_millis(20)))
+            .unwrap();
+
+        let data = IgtlMessage::new(StatusMessage::ok("hello"), "Client")
+            .unwrap()
+            .encode()
+            .unwrap();
+        stream.write_all(&data).unwrap();
+        conn.receive::<StatusMessage>().unwrap();
+
+        let kind = |result: Result<AnyMessage>| match result {
+            Err(IgtlError::Io(e)) => e.kind(),
+            _ => panic!("expected an I/O error"),
+        };
+        assert_eq!(kind(conn.receive_any()), std::io::ErrorKind::TimedOut);
+        let started = Instant::now();
+        let kind = loop {
+            match kind(conn.receive_any()) {
+                std::io::ErrorKind::TimedOut => continue,
+                kind => break kind,
+            }
+        };
+        assert_eq!(kind, std::io::ErrorKind::ConnectionAborted);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(stream.read(&mut [0u8; 1]).unwrap(), 0);
+        assert_eq!(server.active_sessions(), 1);
+        drop(conn);
+        assert_eq!(server.active_sessions(), 0);
+    }
 }
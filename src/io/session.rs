@@ -0,0 +1,311 @@
+//! Session state kept across automatic reconnections
+//!
+//! A reconnecting [`UnifiedAsyncClient`](crate::io::unified_async_client::UnifiedAsyncClient)
+//! restores the parts of a session that the server forgets when the TCP connection drops:
+//!
+//! - **Active streams** - every STT_* message sent is recorded (and forgotten again on the
+//!   matching STP_*) and replayed after reconnecting, so tracking and image streams resume
+//!   without application code
+//! - **Offline queue** - with [`OfflineQueueConfig`], messages sent while disconnected are
+//!   buffered and delivered after reconnecting, subject to a capacity, a [`DropPolicy`] and
+//!   a time-to-live
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::builder::ClientBuilder;
+//! use openigtlink_rust::io::reconnect::ReconnectConfig;
+//! use openigtlink_rust::io::session::{DropPolicy, OfflineQueueConfig};
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::StartTDataMessage;
+//! use std::time::Duration;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let mut client = ClientBuilder::new()
+//!     .tcp("127.0.0.1:18944")
+//!     .async_mode()
+//!     .with_reconnect(ReconnectConfig::infinite())
+//!     .with_offline_queue(
+//!         OfflineQueueConfig::new(100)
+//!             .with_drop_policy(DropPolicy::DropOldest)
+//!             .with_ttl(Duration::from_secs(2)),
+//!     )
+//!     .build()
+//!     .await?;
+//!
+//! // Restarted automatically after every reconnection
+//! let start = IgtlMessage::new(StartTDataMessage::new(50, "RAS"), "Tracker")?;
+//! client.send(&start).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::error::{IgtlError, Result};
+
+/// What to do when a message is sent while the offline queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DropPolicy {
+    /// Discard the oldest queued message to make room (keeps the freshest data)
+    #[default]
+    DropOldest,
+    /// Discard the message being sent
+    DropNewest,
+    /// Fail the send with an error
+    Reject,
+}
+
+/// Offline queue configuration
+///
+/// The queue is only used by clients with reconnection enabled.
+#[derive(Debug, Clone)]
+pub struct OfflineQueueConfig {
+    /// Maximum number of queued messages
+    pub capacity: usize,
+    /// Behavior when the queue is full
+    pub drop_policy: DropPolicy,
+    /// Maximum age of a queued message when it is delivered (None = no limit)
+    pub ttl: Option<Duration>,
+}
+
+impl OfflineQueueConfig {
+    /// Queue up to `capacity` messages, dropping the oldest when full
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use openigtlink_rust::io::session::{DropPolicy, OfflineQueueConfig};
+    ///
+    /// let config = OfflineQueueConfig::new(32);
+    /// assert_eq!(config.capacity, 32);
+    /// assert_eq!(config.drop_policy, DropPolicy::DropOldest);
+    /// assert_eq!(config.ttl, None);
+    /// ```
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            drop_policy: DropPolicy::default(),
+            ttl: None,
+        }
+    }
+
+    /// Set the behavior when the queue is full
+    pub fn with_drop_policy(mut self, policy: DropPolicy) -> Self {
+        self.drop_policy = policy;
+        self
+    }
+
+    /// Discard queued messages older than `ttl` instead of delivering them
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+}
+
+/// Bounded queue of encoded messages waiting for a connection
+#[derive(Debug)]
+pub(crate) struct OfflineQueue {
+    config: OfflineQueueConfig,
+    frames: VecDeque<(Instant, Vec<u8>)>,
+    dropped: u64,
+}
+
+impl OfflineQueue {
+    pub(crate) fn new(config: OfflineQueueConfig) -> Self {
+        Self {
+            config,
+            frames: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Queue an encoded message, applying the TTL and drop policy
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] with [`WouldBlock`](std::io::ErrorKind::WouldBlock) - Queue full
+    ///   and the policy is [`DropPolicy::Reject`]
+    pub(crate) fn push(&mut self, frame: Vec<u8>) -> Result<()> {
+        self.expire();
+        if self.frames.len() >= self.config.capacity {
+            match self.config.drop_policy {
+                DropPolicy::DropOldest => {
+                    if self.frames.pop_front().is_none() {
+                        // Zero capacity: nothing to make room in
+                        self.dropped += 1;
+                        return Ok(());
+                    }
+                }
+                DropPolicy::DropNewest => {
+                    self.dropped += 1;
+                    return Ok(());
+                }
+                DropPolicy::Reject => {
+                    return Err(IgtlError::Io(std::io::Error::new(
+                        std::io::ErrorKind::WouldBlock,
+                        "Disconnected and offline queue is full",
+                    )));
+                }
+            }
+            self.dropped += 1;
+        }
+        self.frames.push_back((Instant::now(), frame));
+        Ok(())
+    }
+
+    /// Oldest message that has not expired yet
+    pub(crate) fn front(&mut self) -> Option<&[u8]> {
+        self.expire();
+        self.frames.front().map(|(_, frame)| frame.as_slice())
+    }
+
+    /// Remove the oldest message after it was delivered
+    pub(crate) fn pop(&mut self) {
+        self.frames.pop_front();
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Messages discarded because the queue was full or they expired
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    fn expire(&mut self) {
+        let Some(ttl) = self.config.ttl else {
+            return;
+        };
+        while let Some((queued_at, _)) = self.frames.front() {
+            if queued_at.elapsed() <= ttl {
+                break;
+            }
+            self.frames.pop_front();
+            self.dropped += 1;
+        }
+    }
+}
+
+/// STT_* requests to replay after reconnecting, keyed by streamed type and device
+#[derive(Debug, Default)]
+pub(crate) struct StreamRegistry {
+    /// (type suffix after "STT_", device name, encoded STT_* message)
+    streams: Vec<(String, String, Vec<u8>)>,
+}
+
+impl StreamRegistry {
+    /// Track a sent message: STT_* starts a stream, STP_* stops it
+    pub(crate) fn record(&mut self, msg_type: &str, device_name: &str, frame: &[u8]) {
+        if let Some(kind) = msg_type.strip_prefix("STT_") {
+            // A repeated request replaces the previous one (e.g. new resolution)
+            self.streams
+                .retain(|(k, d, _)| k != kind || d != device_name);
+            self.streams
+                .push((kind.to_string(), device_name.to_string(), frame.to_vec()));
+        } else if let Some(kind) = msg_type.strip_prefix("STP_") {
+            self.remove(kind, device_name);
+        }
+    }
+
+    /// Whether a message type is handled by stream replay rather than the offline queue
+    pub(crate) fn is_stream_control(msg_type: &str) -> bool {
+        msg_type.starts_with("STT_") || msg_type.starts_with("STP_")
+    }
+
+    /// Encoded STT_* messages in the order they were sent
+    pub(crate) fn frames(&self) -> impl Iterator<Item = &[u8]> {
+        self.streams.iter().map(|(_, _, frame)| frame.as_slice())
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.streams.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.streams.is_empty()
+    }
+
+    /// Active streams as (STT_* type name, device name)
+    pub(crate) fn list(&self) -> Vec<(String, String)> {
+        self.streams
+            .iter()
+            .map(|(kind, device, _)| (format!("STT_{}", kind), device.clone()))
+            .collect()
+    }
+
+    fn remove(&mut self, kind: &str, device_name: &str) {
+        // An empty device name on STP_* stops the stream for every device
+        self.streams
+            .retain(|(k, d, _)| k != kind || !(device_name.is_empty() || d == device_name));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_registry_start_stop() {
+        let mut registry = StreamRegistry::default();
+        registry.record("STT_TDATA", "Tracker", b"a");
+        registry.record("STT_IMAGE", "US", b"b");
+        registry.record("STT_TDATA", "Tracker", b"c");
+        registry.record("TRANSFORM", "Tracker", b"x");
+
+        assert_eq!(
+            registry.list(),
+            vec![
+                ("STT_IMAGE".to_string(), "US".to_string()),
+                ("STT_TDATA".to_string(), "Tracker".to_string()),
+            ]
+        );
+        assert_eq!(registry.frames().collect::<Vec<_>>(), vec![b"b", b"c"]);
+
+        registry.record("STP_TDATA", "Tracker", b"");
+        registry.record("STP_IMAGE", "", b"");
+        assert!(registry.list().is_empty());
+    }
+
+    #[test]
+    fn test_offline_queue_drop_policies() {
+        let mut oldest = OfflineQueue::new(OfflineQueueConfig::new(2));
+        for frame in [b"1", b"2", b"3"] {
+            oldest.push(frame.to_vec()).unwrap();
+        }
+        assert_eq!(oldest.front(), Some(&b"2"[..]));
+        assert_eq!(oldest.dropped(), 1);
+
+        let mut newest =
+            OfflineQueue::new(OfflineQueueConfig::new(2).with_drop_policy(DropPolicy::DropNewest));
+        for frame in [b"1", b"2", b"3"] {
+            newest.push(frame.to_vec()).unwrap();
+        }
+        assert_eq!(newest.front(), Some(&b"1"[..]));
+        assert_eq!(newest.len(), 2);
+
+        let mut reject =
+            OfflineQueue::new(OfflineQueueConfig::new(1).with_drop_policy(DropPolicy::Reject));
+        reject.push(b"1".to_vec()).unwrap();
+        assert!(reject.push(b"2".to_vec()).is_err());
+        assert_eq!(reject.dropped(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_offline_queue_ttl() {
+        let mut queue =
+            OfflineQueue::new(OfflineQueueConfig::new(10).with_ttl(Duration::from_millis(20)));
+        queue.push(b"old".to_vec()).unwrap();
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        queue.push(b"new".to_vec()).unwrap();
+
+        assert_eq!(queue.front(), Some(&b"new"[..]));
+        queue.pop();
+        assert_eq!(queue.front(), None);
+        assert_eq!(queue.dropped(), 1);
+    }
+}
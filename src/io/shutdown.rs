@@ -0,0 +1,365 @@
+//! Graceful shutdown and cancellation
+//!
+//! Aborting a task that is blocked in `accept()` or `receive()` works, but gives the
+//! peer no notice and can cut an outgoing message mid-frame. This module provides the
+//! orderly alternative:
+//!
+//! - [`CancellationToken`] - a cloneable stop signal. `accept_until` and
+//!   `receive_until` on servers, connections, split readers and clients return
+//!   `Ok(None)` once it is cancelled. Receives are cancel-safe, so partially received
+//!   data stays buffered and the connection stays usable.
+//! - `shutdown()` on the async TCP, TLS and WebSocket servers stops accepting and asks
+//!   every session to close: a session notices the shutdown in its next (or current)
+//!   `receive` or `send`, sends the optional STATUS notice of the [`ShutdownConfig`],
+//!   flushes and closes the connection. `shutdown()` waits for the sessions up to the
+//!   drain deadline.
+//! - `close()` on connections and clients flushes and shuts the connection down; TLS
+//!   connections send a `close_notify` alert first, so the peer can tell an orderly
+//!   close from a truncation.
+//!
+//! Split connection halves do not see the server shutdown by themselves; pass
+//! [`shutdown_token`](crate::io::AsyncIgtlServer::shutdown_token) to their
+//! `receive_until`. Outgoing queues of an
+//! [`OutgoingScheduler`](crate::io::scheduler::OutgoingScheduler) or
+//! [`ConflatingSender`](crate::io::conflation::ConflatingSender) are drained with
+//! their tasks' `shutdown(deadline)`.
+//!
+//! The blocking [`IgtlServer`](crate::io::IgtlServer) and
+//! [`SyncTlsIgtlServer`](crate::io::SyncTlsIgtlServer) have no `shutdown()`: a thread
+//! blocked in their `accept()` or `receive()` cannot be woken portably. Give their
+//! connections a read timeout so receiving threads can check a stop flag and drop the
+//! connection, or use the async servers.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::shutdown::{CancellationToken, ShutdownConfig};
+//! use openigtlink_rust::io::AsyncIgtlServer;
+//! use std::sync::Arc;
+//! use std::time::Duration;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let server = Arc::new(AsyncIgtlServer::bind("0.0.0.0:18944").await?);
+//! let stop = CancellationToken::new();
+//!
+//! let accept_loop = tokio::spawn({
+//!     let server = Arc::clone(&server);
+//!     let stop = stop.clone();
+//!     async move {
+//!         while let Ok(Some(mut conn)) = server.accept_until(&stop).await {
+//!             tokio::spawn(async move {
+//!                 while let Ok(msg) = conn.receive_any().await {
+//!                     // handle msg
+//!                 }
+//!             });
+//!         }
+//!     }
+//! });
+//!
+//! tokio::signal::ctrl_c().await?;
+//! stop.cancel();
+//! let remaining = server
+//!     .shutdown(ShutdownConfig::new().with_drain_timeout(Duration::from_secs(2)))
+//!     .await;
+//! println!("{remaining} sessions did not close in time");
+//! accept_loop.await.ok();
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+use crate::error::{IgtlError, Result};
+use crate::io::stream::AsyncIgtlStream;
+use crate::protocol::extended_header::ExtendedHeader;
+use crate::protocol::message::IgtlMessage;
+use crate::protocol::types::StatusMessage;
+
+/// OpenIGTLink status code "shut down in progress"
+pub const STATUS_SHUT_DOWN: u16 = 19;
+
+/// Device name of the STATUS notice sent to peers on shutdown
+pub const SHUTDOWN_DEVICE_NAME: &str = "Server";
+
+/// Default time `shutdown()` waits for sessions to close
+pub const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Cloneable signal to stop accepting or receiving
+///
+/// All clones share the same state; cancelling one cancels all of them. Cancellation
+/// cannot be undone.
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    state: Arc<watch::Sender<bool>>,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled
+    pub fn new() -> Self {
+        CancellationToken {
+            state: Arc::new(watch::Sender::new(false)),
+        }
+    }
+
+    /// Cancel the token, waking every task waiting on it
+    pub fn cancel(&self) {
+        self.state.send_replace(true);
+    }
+
+    /// Whether the token was cancelled
+    pub fn is_cancelled(&self) -> bool {
+        *self.state.borrow()
+    }
+
+    /// Wait until the token is cancelled
+    pub async fn cancelled(&self) {
+        let mut rx = self.state.subscribe();
+        // The sender lives as long as `self`, so this only returns once cancelled
+        let _ = rx.wait_for(|cancelled| *cancelled).await;
+    }
+}
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How a server shuts its sessions down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownConfig {
+    /// Text of the STATUS message sent to every peer before closing, if any
+    /// (default "Shutdown in progress")
+    pub notice: Option<String>,
+    /// Time to wait for sessions to send the notice, flush and close (default 5 s)
+    pub drain_timeout: Duration,
+}
+
+impl ShutdownConfig {
+    /// Create the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send a STATUS message with this text to every peer before closing
+    pub fn with_notice(mut self, notice: impl Into<String>) -> Self {
+        self.notice = Some(notice.into());
+        self
+    }
+
+    /// Close sessions without a STATUS notice
+    pub fn without_notice(mut self) -> Self {
+        self.notice = None;
+        self
+    }
+
+    /// Set the time to wait for sessions to close
+    pub fn with_drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = timeout;
+        self
+    }
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        ShutdownConfig {
+            notice: Some("Shutdown in progress".to_string()),
+            drain_timeout: DEFAULT_DRAIN_TIMEOUT,
+        }
+    }
+}
+
+/// Shutdown state shared by a server and its sessions
+#[derive(Debug, Default)]
+pub(crate) struct ShutdownSignal {
+    token: CancellationToken,
+    config: OnceLock<ShutdownConfig>,
+}
+
+impl ShutdownSignal {
+    /// Start shutting down; only the first configuration is used
+    pub(crate) fn begin(&self, config: ShutdownConfig) {
+        let _ = self.config.set(config);
+        self.token.cancel();
+    }
+
+    pub(crate) fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    pub(crate) fn is_shutting_down(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    pub(crate) fn config(&self) -> ShutdownConfig {
+        self.config.get().cloned().unwrap_or_default()
+    }
+}
+
+/// Why a wait was interrupted
+pub(crate) enum Interrupted {
+    /// The server is shutting down
+    Shutdown,
+    /// The caller's token was cancelled
+    Cancelled,
+}
+
+/// Run `future` unless the server shuts down or `token` is cancelled first
+pub(crate) async fn run_until<F: Future>(
+    future: F,
+    signal: &ShutdownSignal,
+    token: Option<&CancellationToken>,
+) -> std::result::Result<F::Output, Interrupted> {
+    let cancelled = async {
+        match token {
+            Some(token) => token.cancelled().await,
+            None => std::future::pending().await,
+        }
+    };
+    tokio::select! {
+        biased;
+        _ = signal.token().cancelled() => Err(Interrupted::Shutdown),
+        _ = cancelled => Err(Interrupted::Cancelled),
+        output = future => Ok(output),
+    }
+}
+
+/// Run `future` unless `token` is cancelled first
+pub(crate) async fn unless_cancelled<F: Future>(
+    future: F,
+    token: &CancellationToken,
+) -> Option<F::Output> {
+    tokio::select! {
+        biased;
+        _ = token.cancelled() => None,
+        output = future => Some(output),
+    }
+}
+
+/// Error returned by session operations interrupted by a server shutdown
+pub(crate) fn shutdown_error() -> IgtlError {
+    IgtlError::Io(std::io::Error::new(
+        std::io::ErrorKind::ConnectionAborted,
+        "server shutting down",
+    ))
+}
+
+/// STATUS message announcing the shutdown, if `config` has a notice
+pub(crate) fn shutdown_notice(
+    config: &ShutdownConfig,
+) -> Result<Option<IgtlMessage<StatusMessage>>> {
+    let Some(notice) = &config.notice else {
+        return Ok(None);
+    };
+    let status = StatusMessage {
+        code: STATUS_SHUT_DOWN,
+        ..StatusMessage::ok(notice)
+    };
+    let mut msg = IgtlMessage::new(status, SHUTDOWN_DEVICE_NAME)?;
+    // Without an extended header, a status code of 12 or more in the first body
+    // bytes would be taken for an extended header size by receivers
+    msg.set_extended_header_struct(ExtendedHeader::new());
+    Ok(Some(msg))
+}
+
+/// Run `close` within the drain deadline of `config`
+pub(crate) async fn within_drain_deadline<F>(config: &ShutdownConfig, close: F) -> Result<()>
+where
+    F: Future<Output = Result<()>>,
+{
+    match tokio::time::timeout(config.drain_timeout, close).await {
+        Ok(result) => {
+            debug!("Session closed for shutdown");
+            result
+        }
+        Err(_) => Err(IgtlError::Io(std::io::Error::new(
+            std::io::ErrorKind::TimedOut,
+            "session did not close before the drain deadline",
+        ))),
+    }
+}
+
+/// Send the shutdown notice and close `stream` within the drain deadline
+pub(crate) async fn close_session<S>(
+    stream: &mut AsyncIgtlStream<S>,
+    config: &ShutdownConfig,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    within_drain_deadline(config, async {
+        if let Some(msg) = shutdown_notice(config)? {
+            stream.send(&msg).await?;
+        }
+        stream.close().await
+    })
+    .await
+}
+
+/// Wait for a writer task, resuming its panic if it panicked
+pub(crate) async fn join_task<T>(handle: &mut JoinHandle<Result<T>>) -> Result<T> {
+    match handle.await {
+        Ok(result) => result,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(IgtlError::Io(std::io::Error::other(e))),
+    }
+}
+
+/// Ask a writer task to drain its queue via `stop` and wait up to `deadline`
+///
+/// The task is aborted if the deadline passes, discarding whatever is still queued.
+pub(crate) async fn drain_task<T>(
+    mut handle: JoinHandle<Result<T>>,
+    stop: &CancellationToken,
+    deadline: Duration,
+) -> Result<T> {
+    stop.cancel();
+    match tokio::time::timeout(deadline, join_task(&mut handle)).await {
+        Ok(result) => result,
+        Err(_) => {
+            handle.abort();
+            Err(IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::TimedOut,
+                "queued messages not sent before the drain deadline",
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_token_wakes_all_clones() {
+        let token = CancellationToken::new();
+        let waiter = tokio::spawn({
+            let token = token.clone();
+            async move { token.cancelled().await }
+        });
+        assert!(!token.is_cancelled());
+        token.clone().cancel();
+        waiter.await.unwrap();
+        assert!(token.is_cancelled());
+        // Waiting on a cancelled token returns at once
+        token.cancelled().await;
+    }
+
+    #[tokio::test]
+    async fn test_unless_cancelled() {
+        let token = CancellationToken::new();
+        assert_eq!(unless_cancelled(async { 1 }, &token).await, Some(1));
+        token.cancel();
+        assert_eq!(
+            unless_cancelled(std::future::pending::<()>(), &token).await,
+            None
+        );
+    }
+}
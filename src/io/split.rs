@@ -0,0 +1,593 @@
+//! Read and write halves of async OpenIGTLink connections
+//!
+//! Splitting a connection lets one task block in [`receive_any`](AsyncIgtlReader::receive_any)
+//! while another sends commands. Both halves are generic over the underlying
+//! transport and are used for server connections
+//! ([`AsyncIgtlConnection::into_split`](crate::io::AsyncIgtlConnection::into_split)) as well
+//! as plain and TLS clients
+//! ([`UnifiedAsyncClient::into_split`](crate::io::unified_async_client::UnifiedAsyncClient::into_split)).
+//!
+//! - [`AsyncIgtlReader`] implements [`futures::Stream`] with
+//!   `Item = Result<AnyMessage>`, so it works with `StreamExt` combinators and `select!`
+//! - [`AsyncIgtlWriter`] implements [`futures::Sink<AnyMessage>`], applying backpressure
+//!   until the previous message has been written to the transport
+//!
+//! Both halves are cancel-safe: dropping a pending `receive_*` or `send_*` future never
+//! loses message framing. Partially read data stays buffered for the next read, and
+//! partially written data is completed before the next message.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use futures::{SinkExt, StreamExt};
+//! use openigtlink_rust::io::builder::ClientBuilder;
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::StatusMessage;
+//! use openigtlink_rust::protocol::AnyMessage;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let client = ClientBuilder::new()
+//!     .tcp("127.0.0.1:18944")
+//!     .async_mode()
+//!     .build()
+//!     .await?;
+//! let (mut reader, mut writer) = client.into_split()?;
+//!
+//! tokio::spawn(async move {
+//!     while let Some(msg) = reader.next().await {
+//!         match msg {
+//!             Ok(msg) => println!("Received {}", msg.message_type()),
+//!             Err(e) => eprintln!("Receive error: {}", e),
+//!         }
+//!     }
+//! });
+//!
+//! let status = IgtlMessage::new(StatusMessage::ok("Ready"), "Client")?;
+//! writer.send(&status).await?;
+//!
+//! // The writer is also a `Sink<AnyMessage>`
+//! let status = IgtlMessage::new(StatusMessage::ok("Busy"), "Client")?;
+//! SinkExt::send(&mut writer, AnyMessage::Status(status)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+
+use futures::{Sink, Stream};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep_until, Sleep};
+use tracing::{debug, trace};
+
+use crate::error::{IgtlError, Result};
+use crate::io::common::{decode_any_frame, take_frame, READ_CHUNK_SIZE};
+use crate::io::conflation::LatestByDevice;
+use crate::io::events::EventEmitter;
+use crate::io::limits::SessionGovernor;
+use crate::io::liveness::with_timeout;
+use crate::io::shutdown::{unless_cancelled, CancellationToken};
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::AnyMessage;
+
+/// Error returned when the peer closes the connection
+fn connection_closed() -> IgtlError {
+    IgtlError::Io(std::io::Error::new(
+        std::io::ErrorKind::UnexpectedEof,
+        "Connection closed by peer",
+    ))
+}
+
+/// Read half of an async OpenIGTLink connection
+///
+/// See the [module documentation](self) for an example.
+pub struct AsyncIgtlReader<R> {
+    reader: R,
+    verify_crc: bool,
+    /// Bytes read that do not yet form a complete message
+    rx_buf: Vec<u8>,
+    /// Complete messages received before the split (e.g. buffered by a query)
+    pending: VecDeque<Vec<u8>>,
+    eof: bool,
+    read_timeout: Option<Duration>,
+    /// Limits of a server session, which stays counted while the reader is alive
+    session: Option<SessionGovernor>,
+    /// Fires at the session's first-message or idle deadline
+    deadline_timer: Option<Pin<Box<Sleep>>>,
+    /// Reports the end of the connection
+    events: Option<(EventEmitter, Option<SocketAddr>)>,
+}
+
+impl<R: AsyncRead + Unpin> AsyncIgtlReader<R> {
+    /// Wrap a readable transport
+    pub fn new(reader: R, verify_crc: bool) -> Self {
+        Self::with_buffered(reader, verify_crc, Vec::new(), VecDeque::new())
+    }
+
+    /// Wrap a transport together with data already read from it
+    pub(crate) fn with_buffered(
+        reader: R,
+        verify_crc: bool,
+        rx_buf: Vec<u8>,
+        pending: VecDeque<Vec<u8>>,
+    ) -> Self {
+        AsyncIgtlReader {
+            reader,
+            verify_crc,
+            rx_buf,
+            pending,
+            eof: false,
+            read_timeout: None,
+            session: None,
+            deadline_timer: None,
+            events: None,
+        }
+    }
+
+    /// Enable or disable CRC verification for received messages
+    pub fn set_verify_crc(&mut self, verify: bool) {
+        self.verify_crc = verify;
+    }
+
+    /// Get current CRC verification setting
+    pub fn verify_crc(&self) -> bool {
+        self.verify_crc
+    }
+
+    /// Set the deadline of each `receive` call (`None` waits forever, the default)
+    ///
+    /// An expired receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut) and keeps
+    /// partially received data. The [`Stream`] implementation is not affected; wrap it
+    /// with `tokio::time::timeout` instead.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    /// Enforce the limits of a server session, keeping it counted until this reader
+    /// is dropped or a limit is violated
+    pub(crate) fn set_session(&mut self, session: SessionGovernor) {
+        self.session = Some(session);
+    }
+
+    /// Report the end of the connection to `peer` on `events`
+    pub(crate) fn set_events(&mut self, events: EventEmitter, peer: Option<SocketAddr>) {
+        self.events = Some((events, peer));
+    }
+
+    /// Get a reference to the underlying transport
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Turn the reader into a stream that skips stale tracking messages
+    ///
+    /// When the consumer falls behind, a queued TRANSFORM, TDATA, ... is dropped as soon
+    /// as a newer message of the same type arrives from the same device. See
+    /// [`conflation`](crate::io::conflation).
+    pub fn latest_by_device(self) -> LatestByDevice<Self> {
+        LatestByDevice::new(self)
+    }
+
+    /// Receive a message from the read half
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read failed, or
+    ///   [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) if the peer closed the connection
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption detected
+    pub async fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
+        let frame = self.next_frame().await?;
+        IgtlMessage::decode_with_options(&frame, self.verify_crc)
+    }
+
+    /// Receive any message type dynamically from the read half
+    ///
+    /// # Errors
+    ///
+    /// Same as [`receive`](Self::receive).
+    pub async fn receive_any(&mut self) -> Result<AnyMessage> {
+        let frame = self.next_frame().await?;
+        decode_any_frame(&frame, self.verify_crc)
+    }
+
+    /// Receive a message unless `token` is cancelled first
+    ///
+    /// Returns `Ok(None)` once `token` is cancelled. Partially received data is kept, so
+    /// the reader stays usable. Pass
+    /// [`shutdown_token`](crate::io::AsyncIgtlServer::shutdown_token) to stop when the
+    /// server shuts down.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`receive`](Self::receive).
+    pub async fn receive_until<T: Message>(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<IgtlMessage<T>>> {
+        unless_cancelled(self.receive(), token).await.transpose()
+    }
+
+    /// Receive any message type unless `token` is cancelled first
+    ///
+    /// See [`receive_until`](Self::receive_until).
+    pub async fn receive_any_until(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<AnyMessage>> {
+        unless_cancelled(self.receive_any(), token)
+            .await
+            .transpose()
+    }
+
+    async fn next_frame(&mut self) -> Result<Vec<u8>> {
+        let frame = with_timeout(
+            self.read_timeout,
+            "message",
+            poll_fn(|cx| self.poll_frame(cx)),
+        )
+        .await?;
+        frame.ok_or_else(connection_closed)
+    }
+
+    /// Poll for the next frame, reporting a lost or closed connection
+    fn poll_frame(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Vec<u8>>>> {
+        let polled = ready!(self.poll_session_frame(cx));
+        if let Some((events, peer)) = &self.events {
+            match &polled {
+                Ok(None) => events.disconnected(*peer, &connection_closed()),
+                Err(_) => events.check_result(&polled, *peer),
+                Ok(Some(_)) => {}
+            }
+        }
+        Poll::Ready(polled)
+    }
+
+    /// Poll for the next frame within the session limits, closing the session on a
+    /// violation
+    fn poll_session_frame(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Vec<u8>>>> {
+        if let Some(session) = &self.session {
+            session.check_open()?;
+        }
+        let polled = self.poll_raw_frame(cx);
+        let Some(session) = &mut self.session else {
+            return polled;
+        };
+        let result = match polled {
+            Poll::Ready(Ok(Some(frame))) => session.check_frame(frame).map(Some),
+            Poll::Pending => {
+                let Some(deadline) = session.deadline() else {
+                    return Poll::Pending;
+                };
+                let deadline = tokio::time::Instant::from_std(deadline);
+                let timer = self
+                    .deadline_timer
+                    .get_or_insert_with(|| Box::pin(sleep_until(deadline)));
+                if timer.deadline() != deadline {
+                    timer.as_mut().reset(deadline);
+                }
+                ready!(timer.as_mut().poll(cx));
+                Err(session.expire())
+            }
+            Poll::Ready(other) => other,
+        };
+        if session.is_closed() {
+            drop(session.take_slot());
+        }
+        Poll::Ready(result)
+    }
+
+    /// Poll for the next complete frame; `Ok(None)` on a clean end of stream
+    fn poll_raw_frame(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<Vec<u8>>>> {
+        if let Some(frame) = self.pending.pop_front() {
+            return Poll::Ready(Ok(Some(frame)));
+        }
+
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        loop {
+            if let Some(frame) = take_frame(&mut self.rx_buf)? {
+                trace!(size = frame.len(), "Message received (async reader)");
+                return Poll::Ready(Ok(Some(frame)));
+            }
+            if self.eof {
+                return Poll::Ready(Ok(None));
+            }
+
+            let mut buf = ReadBuf::new(&mut chunk);
+            ready!(Pin::new(&mut self.reader).poll_read(cx, &mut buf))?;
+            if buf.filled().is_empty() {
+                self.eof = true;
+                if self.rx_buf.is_empty() {
+                    debug!("Peer closed connection (async reader)");
+                    return Poll::Ready(Ok(None));
+                }
+                self.rx_buf.clear();
+                return Poll::Ready(Err(connection_closed()));
+            }
+            self.rx_buf.extend_from_slice(buf.filled());
+        }
+    }
+}
+
+impl<R: AsyncRead + Unpin> Stream for AsyncIgtlReader<R> {
+    type Item = Result<AnyMessage>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        // The violation was reported by the previous item
+        if this.session.as_ref().is_some_and(|s| s.is_closed()) {
+            return Poll::Ready(None);
+        }
+        match ready!(this.poll_frame(cx)) {
+            Ok(Some(frame)) => Poll::Ready(Some(decode_any_frame(&frame, this.verify_crc))),
+            Ok(None) => Poll::Ready(None),
+            Err(e) => Poll::Ready(Some(Err(e))),
+        }
+    }
+}
+
+/// Write half of an async OpenIGTLink connection
+///
+/// See the [module documentation](self) for an example.
+pub struct AsyncIgtlWriter<W> {
+    writer: W,
+    /// Encoded bytes not yet accepted by the transport
+    tx_buf: Vec<u8>,
+    tx_pos: usize,
+    write_timeout: Option<Duration>,
+    /// Reports the end of the connection
+    events: Option<(EventEmitter, Option<SocketAddr>)>,
+}
+
+impl<W: AsyncWrite + Unpin> AsyncIgtlWriter<W> {
+    /// Wrap a writable transport
+    pub fn new(writer: W) -> Self {
+        Self::with_buffered(writer, Vec::new())
+    }
+
+    /// Wrap a transport together with bytes still to be written to it
+    pub(crate) fn with_buffered(writer: W, tx_buf: Vec<u8>) -> Self {
+        AsyncIgtlWriter {
+            writer,
+            tx_buf,
+            tx_pos: 0,
+            write_timeout: None,
+            events: None,
+        }
+    }
+
+    /// Set the deadline of each `send` call (`None` waits forever, the default)
+    ///
+    /// An expired send fails with [`TimedOut`](std::io::ErrorKind::TimedOut). The rest of
+    /// the message stays queued and is written before the next one, so framing is kept.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout;
+    }
+
+    /// Get a reference to the underlying transport
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    /// Report the end of the connection to `peer` on `events`
+    pub(crate) fn set_events(&mut self, events: EventEmitter, peer: Option<SocketAddr>) {
+        self.events = Some((events, peer));
+    }
+
+    /// Send a message to the write half
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network write failed
+    pub async fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
+        let data = msg.encode()?;
+        debug!(
+            msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN"),
+            size = data.len(),
+            "Sending message (async writer)"
+        );
+        self.write_frame(data).await
+    }
+
+    /// Send a dynamically typed message to the write half
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network write failed
+    pub async fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        let data = msg.encode()?;
+        debug!(
+            msg_type = msg.message_type(),
+            size = data.len(),
+            "Sending message (async writer)"
+        );
+        self.write_frame(data).await
+    }
+
+    /// Flush and shut down the write direction of the transport
+    pub async fn shutdown(&mut self) -> Result<()> {
+        poll_fn(|cx| self.poll_close_inner(cx)).await
+    }
+
+    /// Write one encoded frame, completing any frame left by a cancelled send first
+    pub(crate) async fn write_frame(&mut self, data: Vec<u8>) -> Result<()> {
+        let timeout = self.write_timeout;
+        let result = with_timeout(timeout, "message write", async {
+            // Complete any frame left over from a cancelled send first
+            poll_fn(|cx| self.poll_write_buf(cx)).await?;
+            self.queue(data);
+            poll_fn(|cx| self.poll_flush_inner(cx)).await
+        })
+        .await;
+        self.check_result(&result);
+        result?;
+        trace!("Message sent (async writer)");
+        Ok(())
+    }
+
+    /// Report a lost connection contained in a write result
+    fn check_result<T>(&self, result: &Result<T>) {
+        if let Some((events, peer)) = &self.events {
+            events.check_result(result, *peer);
+        }
+    }
+
+    fn queue(&mut self, data: Vec<u8>) {
+        if self.tx_pos == self.tx_buf.len() {
+            self.tx_buf = data;
+            self.tx_pos = 0;
+        } else {
+            self.tx_buf.extend_from_slice(&data);
+        }
+    }
+
+    /// Write buffered bytes until the buffer is empty
+    fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        while self.tx_pos < self.tx_buf.len() {
+            let n = ready!(Pin::new(&mut self.writer).poll_write(cx, &self.tx_buf[self.tx_pos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(IgtlError::Io(std::io::Error::new(
+                    std::io::ErrorKind::WriteZero,
+                    "Failed to write message to transport",
+                ))));
+            }
+            self.tx_pos += n;
+        }
+        self.tx_buf.clear();
+        self.tx_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_flush_inner(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_write_buf(cx))?;
+        ready!(Pin::new(&mut self.writer).poll_flush(cx))?;
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close_inner(&mut self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        ready!(self.poll_flush_inner(cx))?;
+        ready!(Pin::new(&mut self.writer).poll_shutdown(cx))?;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<W: AsyncWrite + Unpin> Sink<AnyMessage> for AsyncIgtlWriter<W> {
+    type Error = IgtlError;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        let result = ready!(this.poll_write_buf(cx));
+        this.check_result(&result);
+        Poll::Ready(result)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: AnyMessage) -> Result<()> {
+        let data = item.encode()?;
+        trace!(
+            msg_type = item.message_type(),
+            size = data.len(),
+            "Queueing message (async writer sink)"
+        );
+        self.get_mut().queue(data);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        let result = ready!(this.poll_flush_inner(cx));
+        this.check_result(&result);
+        Poll::Ready(result)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let this = self.get_mut();
+        let result = ready!(this.poll_close_inner(cx));
+        this.check_result(&result);
+        Poll::Ready(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+    use futures::{SinkExt, StreamExt};
+
+    fn status(text: &str) -> AnyMessage {
+        AnyMessage::Status(IgtlMessage::new(StatusMessage::ok(text), "Device").unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_sink_to_stream_roundtrip() {
+        let (a, b) = tokio::io::duplex(64);
+        let mut writer = AsyncIgtlWriter::new(a);
+        let mut reader = AsyncIgtlReader::new(b, true);
+
+        let send = async {
+            for i in 0..5 {
+                writer.feed(status(&format!("msg {}", i))).await.unwrap();
+            }
+            writer.close().await.unwrap();
+        };
+        let recv = async {
+            let messages: Vec<_> = (&mut reader).collect().await;
+            messages
+        };
+        let ((), messages) = tokio::join!(send, recv);
+
+        assert_eq!(messages.len(), 5);
+        for (i, msg) in messages.into_iter().enumerate() {
+            let msg = msg.unwrap();
+            assert_eq!(
+                msg.as_status().unwrap().content.status_string,
+                format!("msg {}", i)
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_truncated_stream_reports_error() {
+        let encoded = IgtlMessage::new(TransformMessage::identity(), "Device")
+            .unwrap()
+            .encode()
+            .unwrap();
+        let (mut a, b) = tokio::io::duplex(1024);
+        tokio::io::AsyncWriteExt::write_all(&mut a, &encoded[..30])
+            .await
+            .unwrap();
+        drop(a);
+
+        let mut reader = AsyncIgtlReader::new(b, true);
+        assert!(matches!(reader.next().await, Some(Err(IgtlError::Io(_)))));
+        assert!(reader.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_receive_after_cancelled_read_keeps_framing() {
+        let encoded = IgtlMessage::new(TransformMessage::identity(), "Device")
+            .unwrap()
+            .encode()
+            .unwrap();
+        let (mut a, b) = tokio::io::duplex(1024);
+        let mut reader = AsyncIgtlReader::new(b, true);
+
+        tokio::io::AsyncWriteExt::write_all(&mut a, &encoded[..70])
+            .await
+            .unwrap();
+        let cancelled = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            reader.receive::<TransformMessage>(),
+        )
+        .await;
+        assert!(cancelled.is_err());
+
+        tokio::io::AsyncWriteExt::write_all(&mut a, &encoded[70..])
+            .await
+            .unwrap();
+        let msg: IgtlMessage<TransformMessage> = reader.receive().await.unwrap();
+        assert_eq!(msg.header.device_name.as_str().unwrap(), "Device");
+    }
+}
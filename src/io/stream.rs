@@ -0,0 +1,724 @@
+//! OpenIGTLink framing over any byte stream
+//!
+//! [`IgtlStream`] (blocking, over [`Read`] + [`Write`]) and [`AsyncIgtlStream`] (Tokio,
+//! over [`AsyncRead`] + [`AsyncWrite`]) provide `send`, `send_any`, `receive`,
+//! `receive_any` and splitting for any transport: serial links, SSH tunnels, pipes,
+//! `tokio::io::duplex` in tests, and so on. The TCP, TLS and Unix domain socket
+//! connections of this crate are built on them.
+//!
+//! # Examples
+//!
+//! ```
+//! use openigtlink_rust::io::AsyncIgtlStream;
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::StatusMessage;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let (a, b) = tokio::io::duplex(64 * 1024);
+//! let mut left = AsyncIgtlStream::new(a);
+//! let mut right = AsyncIgtlStream::new(b);
+//!
+//! left.send(&IgtlMessage::new(StatusMessage::ok("Hello"), "Left")?).await?;
+//! let msg: IgtlMessage<StatusMessage> = right.receive().await?;
+//! assert_eq!(msg.content.status_string, "Hello");
+//! # Ok(())
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadHalf, WriteHalf};
+use tracing::{debug, info, trace, warn};
+
+use crate::error::{IgtlError, Result};
+use crate::io::common::{decode_any_frame, read_frame_blocking, take_frame, READ_CHUNK_SIZE};
+use crate::io::liveness::{sleep_until, with_timeout, Liveness};
+use crate::io::split::{AsyncIgtlReader, AsyncIgtlWriter};
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::AnyMessage;
+
+/// Log a change of the CRC verification setting
+fn log_verify_crc(old: bool, new: bool) {
+    if old != new {
+        info!(verify = new, "CRC verification setting changed");
+        if !new {
+            warn!("CRC verification disabled - use only in trusted environments");
+        }
+    }
+}
+
+// ============================================================================
+// Blocking
+// ============================================================================
+
+/// Blocking OpenIGTLink connection over any [`Read`] + [`Write`] stream
+///
+/// Read timeouts are configured on the stream itself (e.g.
+/// [`TcpStream::set_read_timeout`](std::net::TcpStream::set_read_timeout)); an expired
+/// receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut) and keeps partially
+/// received data for the next call.
+pub struct IgtlStream<S> {
+    stream: S,
+    verify_crc: bool,
+    /// Bytes read that do not yet form a complete message
+    rx_buf: Vec<u8>,
+}
+
+impl<S: Read + Write> IgtlStream<S> {
+    /// Wrap a stream, with CRC verification enabled
+    pub fn new(stream: S) -> Self {
+        IgtlStream {
+            stream,
+            verify_crc: true,
+            rx_buf: Vec::new(),
+        }
+    }
+
+    /// Enable or disable CRC verification for received messages
+    pub fn set_verify_crc(&mut self, verify: bool) {
+        log_verify_crc(self.verify_crc, verify);
+        self.verify_crc = verify;
+    }
+
+    /// Get current CRC verification setting
+    pub fn verify_crc(&self) -> bool {
+        self.verify_crc
+    }
+
+    /// Send a message
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Stream write failed
+    pub fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
+        let data = msg.encode()?;
+        debug!(
+            msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN"),
+            size = data.len(),
+            "Sending message"
+        );
+        self.write_frame(&data)
+    }
+
+    /// Send a dynamically typed message
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Stream write failed
+    pub fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        let data = msg.encode()?;
+        debug!(
+            msg_type = msg.message_type(),
+            size = data.len(),
+            "Sending message"
+        );
+        self.write_frame(&data)
+    }
+
+    /// Receive a message of a known type
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Stream read failed,
+    ///   [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) if the peer closed the
+    ///   stream, or [`TimedOut`](std::io::ErrorKind::TimedOut) if the read timeout expired
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption detected
+    pub fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
+        let frame = self.read_frame()?;
+        IgtlMessage::decode_with_options(&frame, self.verify_crc)
+    }
+
+    /// Receive any message type dynamically
+    ///
+    /// # Errors
+    ///
+    /// Same as [`receive`](Self::receive).
+    pub fn receive_any(&mut self) -> Result<AnyMessage> {
+        let frame = self.read_frame()?;
+        decode_any_frame(&frame, self.verify_crc)
+    }
+
+    /// Read one complete frame
+    pub(crate) fn read_frame(&mut self) -> Result<Vec<u8>> {
+        let frame = read_frame_blocking(&mut self.stream, &mut self.rx_buf)?;
+        trace!(size = frame.len(), "Message received");
+        Ok(frame)
+    }
+
+    /// Write and flush one complete frame
+    pub(crate) fn write_frame(&mut self, data: &[u8]) -> Result<()> {
+        self.stream.write_all(data)?;
+        self.stream.flush()?;
+        trace!(bytes_sent = data.len(), "Message sent");
+        Ok(())
+    }
+}
+
+impl<S> IgtlStream<S> {
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream
+    ///
+    /// Reading from the stream directly breaks message framing.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Unwrap the stream, discarding data received but not yet returned as a message
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+impl<S> IgtlStream<S>
+where
+    for<'a> &'a S: Read + Write,
+{
+    /// Split into a reader and a writer that can be used from different threads
+    ///
+    /// Available for streams that can be read and written through a shared reference,
+    /// such as [`TcpStream`](std::net::TcpStream) and Unix domain sockets.
+    pub fn split(self) -> (IgtlReader<S>, IgtlWriter<S>) {
+        let stream = Arc::new(self.stream);
+        (
+            IgtlReader {
+                stream: stream.clone(),
+                verify_crc: self.verify_crc,
+                rx_buf: self.rx_buf,
+            },
+            IgtlWriter { stream },
+        )
+    }
+}
+
+/// Read half of a split [`IgtlStream`]
+pub struct IgtlReader<S> {
+    stream: Arc<S>,
+    verify_crc: bool,
+    rx_buf: Vec<u8>,
+}
+
+impl<S> IgtlReader<S>
+where
+    for<'a> &'a S: Read,
+{
+    /// Enable or disable CRC verification for received messages
+    pub fn set_verify_crc(&mut self, verify: bool) {
+        self.verify_crc = verify;
+    }
+
+    /// Receive a message of a known type
+    ///
+    /// # Errors
+    ///
+    /// Same as [`IgtlStream::receive`].
+    pub fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
+        let frame = read_frame_blocking(&mut &*self.stream, &mut self.rx_buf)?;
+        IgtlMessage::decode_with_options(&frame, self.verify_crc)
+    }
+
+    /// Receive any message type dynamically
+    ///
+    /// # Errors
+    ///
+    /// Same as [`IgtlStream::receive`].
+    pub fn receive_any(&mut self) -> Result<AnyMessage> {
+        let frame = read_frame_blocking(&mut &*self.stream, &mut self.rx_buf)?;
+        decode_any_frame(&frame, self.verify_crc)
+    }
+
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+}
+
+/// Write half of a split [`IgtlStream`]
+pub struct IgtlWriter<S> {
+    stream: Arc<S>,
+}
+
+impl<S> IgtlWriter<S>
+where
+    for<'a> &'a S: Write,
+{
+    /// Send a message
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Stream write failed
+    pub fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
+        self.write_frame(&msg.encode()?)
+    }
+
+    /// Send a dynamically typed message
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Stream write failed
+    pub fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        self.write_frame(&msg.encode()?)
+    }
+
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    fn write_frame(&mut self, data: &[u8]) -> Result<()> {
+        let mut stream = &*self.stream;
+        stream.write_all(data)?;
+        stream.flush()?;
+        Ok(())
+    }
+}
+
+// ============================================================================
+// Asynchronous
+// ============================================================================
+
+/// Async OpenIGTLink connection over any [`AsyncRead`] + [`AsyncWrite`] stream
+///
+/// Sends and receives are cancel-safe: partially received data stays buffered when a
+/// receive future is dropped or times out, and the rest of a partially written message is
+/// written before the next one.
+pub struct AsyncIgtlStream<S> {
+    stream: S,
+    verify_crc: bool,
+    /// Bytes read that do not yet form a complete message
+    rx_buf: Vec<u8>,
+    /// Encoded bytes not yet accepted by the stream
+    tx_buf: Vec<u8>,
+    tx_pos: usize,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    /// Heartbeats and idle timeout serviced while waiting for data
+    liveness: Liveness,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> AsyncIgtlStream<S> {
+    /// Wrap a stream, with CRC verification enabled and no timeouts
+    pub fn new(stream: S) -> Self {
+        AsyncIgtlStream {
+            stream,
+            verify_crc: true,
+            rx_buf: Vec::new(),
+            tx_buf: Vec::new(),
+            tx_pos: 0,
+            read_timeout: None,
+            write_timeout: None,
+            liveness: Liveness::default(),
+        }
+    }
+
+    /// Enable or disable CRC verification for received messages
+    pub fn set_verify_crc(&mut self, verify: bool) {
+        log_verify_crc(self.verify_crc, verify);
+        self.verify_crc = verify;
+    }
+
+    /// Get current CRC verification setting
+    pub fn verify_crc(&self) -> bool {
+        self.verify_crc
+    }
+
+    /// Set the deadline of each `receive` call (`None` waits forever, the default)
+    ///
+    /// An expired receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut). Partially
+    /// received data is kept, so the stream stays usable.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    /// Set the deadline of each `send` call (`None` waits forever, the default)
+    ///
+    /// An expired send fails with [`TimedOut`](std::io::ErrorKind::TimedOut). The rest of
+    /// the message stays queued and is written before the next one, so framing is kept.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout;
+    }
+
+    /// Send heartbeats and enforce the idle timeout while waiting for data
+    ///
+    /// Read and write timeouts and TCP keepalive are configured separately.
+    pub(crate) fn set_liveness(&mut self, liveness: Liveness) {
+        self.liveness = liveness;
+    }
+
+    /// Whether the idle timeout has expired, i.e. the peer is considered dead
+    pub(crate) fn idle_expired(&self) -> bool {
+        self.liveness
+            .idle_deadline()
+            .is_some_and(|deadline| deadline <= tokio::time::Instant::now())
+    }
+
+    /// Send a message
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Stream write failed, or
+    ///   [`TimedOut`](std::io::ErrorKind::TimedOut) if the write timeout expired
+    pub async fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
+        let data = msg.encode()?;
+        debug!(
+            msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN"),
+            size = data.len(),
+            "Sending message (async)"
+        );
+        self.write_frame(&data).await
+    }
+
+    /// Send a dynamically typed message
+    ///
+    /// # Errors
+    ///
+    /// Same as [`send`](Self::send).
+    pub async fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        let data = msg.encode()?;
+        debug!(
+            msg_type = msg.message_type(),
+            size = data.len(),
+            "Sending message (async)"
+        );
+        self.write_frame(&data).await
+    }
+
+    /// Receive a message of a known type
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Stream read failed,
+    ///   [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) if the peer closed the
+    ///   stream, or [`TimedOut`](std::io::ErrorKind::TimedOut) if the read timeout expired
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption detected
+    pub async fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
+        let frame = self.read_frame().await?;
+        IgtlMessage::decode_with_options(&frame, self.verify_crc)
+    }
+
+    /// Receive any message type dynamically
+    ///
+    /// # Errors
+    ///
+    /// Same as [`receive`](Self::receive).
+    pub async fn receive_any(&mut self) -> Result<AnyMessage> {
+        let frame = self.read_frame().await?;
+        decode_any_frame(&frame, self.verify_crc)
+    }
+
+    /// Receive one complete frame (header and body) without decoding it
+    ///
+    /// The CRC is not checked; see [`FrameInfo`](crate::protocol::inspect::FrameInfo)
+    /// to inspect the frame.
+    ///
+    /// # Errors
+    ///
+    /// Same as [`receive`](Self::receive), without CRC errors.
+    pub async fn receive_frame(&mut self) -> Result<Vec<u8>> {
+        self.read_frame().await
+    }
+
+    /// Flush and shut down the write direction, honouring the write timeout
+    ///
+    /// TLS streams send a `close_notify` alert first, so the peer sees an orderly close.
+    pub async fn close(&mut self) -> Result<()> {
+        with_timeout(self.write_timeout, "close", async {
+            self.flush_tx().await?;
+            self.stream.shutdown().await?;
+            Ok(())
+        })
+        .await?;
+        trace!("Stream closed (async)");
+        Ok(())
+    }
+
+    /// Read one complete frame, honouring the read timeout
+    ///
+    /// Sends due heartbeats, consumes heartbeat replies and enforces the idle timeout.
+    /// Cancel-safe: received data stays in the receive buffer and queued heartbeats in
+    /// the send buffer if the future is dropped.
+    pub(crate) async fn read_frame(&mut self) -> Result<Vec<u8>> {
+        let frame = with_timeout(self.read_timeout, "message", self.next_frame()).await?;
+        trace!(size = frame.len(), "Message received (async)");
+        Ok(frame)
+    }
+
+    async fn next_frame(&mut self) -> Result<Vec<u8>> {
+        let mut chunk = [0u8; READ_CHUNK_SIZE];
+        loop {
+            if let Some(frame) = take_frame(&mut self.rx_buf)? {
+                if self.liveness.take_reply(&frame) {
+                    trace!("Heartbeat reply received");
+                    continue;
+                }
+                return Ok(frame);
+            }
+            if self.tx_pos < self.tx_buf.len() {
+                with_timeout(self.write_timeout, "heartbeat write", self.flush_tx()).await?;
+            }
+
+            let idle_deadline = self.liveness.idle_deadline();
+            let heartbeat_due = self.liveness.heartbeat_due();
+            tokio::select! {
+                result = self.stream.read(&mut chunk) => {
+                    let n = result?;
+                    if n == 0 {
+                        return Err(IgtlError::Io(std::io::Error::new(
+                            std::io::ErrorKind::UnexpectedEof,
+                            "Connection closed by peer",
+                        )));
+                    }
+                    self.rx_buf.extend_from_slice(&chunk[..n]);
+                    self.liveness.received();
+                }
+                _ = sleep_until(idle_deadline) => {
+                    warn!("Connection idle, considering it dead");
+                    return Err(self.liveness.idle_error());
+                }
+                _ = sleep_until(heartbeat_due) => {
+                    let heartbeat = self.liveness.next_heartbeat()?;
+                    self.queue(heartbeat);
+                    trace!("Heartbeat queued");
+                }
+            }
+        }
+    }
+
+    /// Write and flush one complete frame, honouring the write timeout
+    ///
+    /// Cancel-safe: the rest of a frame left by a cancelled or timed out call is written
+    /// before the next frame.
+    pub(crate) async fn write_frame(&mut self, data: &[u8]) -> Result<()> {
+        with_timeout(self.write_timeout, "message write", async {
+            // Complete any frame left over from a cancelled send first
+            self.flush_tx().await?;
+            self.queue(data.to_vec());
+            self.flush_tx().await
+        })
+        .await?;
+        trace!(bytes_sent = data.len(), "Message sent (async)");
+        Ok(())
+    }
+
+    /// Queue an encoded frame, written by the next send, receive or flush
+    pub(crate) fn queue(&mut self, data: Vec<u8>) {
+        if self.tx_pos == self.tx_buf.len() {
+            self.tx_buf = data;
+            self.tx_pos = 0;
+        } else {
+            self.tx_buf.extend_from_slice(&data);
+        }
+    }
+
+    /// Write all queued bytes and flush the stream
+    ///
+    /// Cancel-safe: bytes accepted by the stream are accounted for before the next write.
+    pub(crate) async fn flush_tx(&mut self) -> Result<()> {
+        while self.tx_pos < self.tx_buf.len() {
+            let n = self.stream.write(&self.tx_buf[self.tx_pos..]).await?;
+            if n == 0 {
+                return Err(IgtlError::Io(std::io::Error::new(
+                    std::io::ErrorKind::WriteZero,
+                    "Failed to write message to stream",
+                )));
+            }
+            self.tx_pos += n;
+            self.liveness.sent();
+        }
+        self.tx_buf.clear();
+        self.tx_pos = 0;
+        self.stream.flush().await?;
+        Ok(())
+    }
+
+    /// Split into a [`Stream`](futures::Stream) reader and a [`Sink`](futures::Sink) writer
+    ///
+    /// Works for any stream through [`tokio::io::split`]. Data already received and
+    /// the CRC and timeout settings carry over to the halves.
+    pub fn split(self) -> (AsyncIgtlReader<ReadHalf<S>>, AsyncIgtlWriter<WriteHalf<S>>) {
+        self.split_with(tokio::io::split)
+    }
+
+    /// Split with a transport-specific function, e.g. `TcpStream::into_split`
+    ///
+    /// Data already received and the CRC and timeout settings carry over to the halves.
+    pub fn split_with<R, W>(
+        self,
+        split: impl FnOnce(S) -> (R, W),
+    ) -> (AsyncIgtlReader<R>, AsyncIgtlWriter<W>)
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        self.split_buffered(split, VecDeque::new())
+    }
+
+    /// Split, delivering `pending` frames first from the reader
+    ///
+    /// Bytes still queued for sending are written first by the writer.
+    pub(crate) fn split_buffered<R, W>(
+        mut self,
+        split: impl FnOnce(S) -> (R, W),
+        pending: VecDeque<Vec<u8>>,
+    ) -> (AsyncIgtlReader<R>, AsyncIgtlWriter<W>)
+    where
+        R: AsyncRead + Unpin,
+        W: AsyncWrite + Unpin,
+    {
+        let tx_buf = self.tx_buf.split_off(self.tx_pos);
+        let (reader, writer) = split(self.stream);
+        let mut reader =
+            AsyncIgtlReader::with_buffered(reader, self.verify_crc, self.rx_buf, pending);
+        reader.set_read_timeout(self.read_timeout);
+        let mut writer = AsyncIgtlWriter::with_buffered(writer, tx_buf);
+        writer.set_write_timeout(self.write_timeout);
+        (reader, writer)
+    }
+}
+
+impl<S> AsyncIgtlStream<S> {
+    /// Get a reference to the underlying stream
+    pub fn get_ref(&self) -> &S {
+        &self.stream
+    }
+
+    /// Get a mutable reference to the underlying stream
+    ///
+    /// Reading from the stream directly breaks message framing.
+    pub fn get_mut(&mut self) -> &mut S {
+        &mut self.stream
+    }
+
+    /// Unwrap the stream, discarding data received but not yet returned as a message
+    pub fn into_inner(self) -> S {
+        self.stream
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IgtlError;
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+    use futures::StreamExt;
+    use std::net::{TcpListener, TcpStream};
+
+    fn tcp_pair() -> (TcpStream, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        (client, server)
+    }
+
+    #[test]
+    fn test_blocking_round_trip_and_split() {
+        let (a, b) = tcp_pair();
+        let mut left = IgtlStream::new(a);
+        let right = IgtlStream::new(b);
+
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        left.send(&msg).unwrap();
+
+        let (mut reader, mut writer) = right.split();
+        let handle = std::thread::spawn(move || {
+            let msg = reader.receive_any().unwrap();
+            assert_eq!(msg.message_type(), "TRANSFORM");
+        });
+        writer
+            .send(&IgtlMessage::new(StatusMessage::ok("Ack"), "Right").unwrap())
+            .unwrap();
+        handle.join().unwrap();
+
+        let ack: IgtlMessage<StatusMessage> = left.receive().unwrap();
+        assert_eq!(ack.content.status_string, "Ack");
+    }
+
+    #[test]
+    fn test_blocking_timeout_keeps_partial_frame() {
+        let (mut a, b) = tcp_pair();
+        b.set_read_timeout(Some(Duration::from_millis(50))).unwrap();
+        let mut right = IgtlStream::new(b);
+
+        let data = IgtlMessage::new(StatusMessage::ok("Split"), "Left")
+            .unwrap()
+            .encode()
+            .unwrap();
+        a.write_all(&data[..40]).unwrap();
+        let err = right.receive_any().unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::TimedOut));
+
+        a.write_all(&data[40..]).unwrap();
+        let msg: IgtlMessage<StatusMessage> = right.receive().unwrap();
+        assert_eq!(msg.content.status_string, "Split");
+    }
+
+    #[tokio::test]
+    async fn test_async_duplex_split_keeps_buffered_data() {
+        let (a, b) = tokio::io::duplex(64 * 1024);
+        let mut left = AsyncIgtlStream::new(a);
+        let mut right = AsyncIgtlStream::new(b);
+
+        // Two messages in flight, only the first one received before the split
+        for text in ["One", "Two"] {
+            left.send(&IgtlMessage::new(StatusMessage::ok(text), "Left").unwrap())
+                .await
+                .unwrap();
+        }
+        let first: IgtlMessage<StatusMessage> = right.receive().await.unwrap();
+        assert_eq!(first.content.status_string, "One");
+
+        let (mut reader, mut writer) = right.split();
+        let second = reader.next().await.unwrap().unwrap();
+        assert_eq!(second.device_name().unwrap(), "Left");
+
+        writer
+            .send(&IgtlMessage::new(StatusMessage::ok("Back"), "Right").unwrap())
+            .await
+            .unwrap();
+        let back: IgtlMessage<StatusMessage> = left.receive().await.unwrap();
+        assert_eq!(back.content.status_string, "Back");
+    }
+
+    #[tokio::test]
+    async fn test_async_timed_out_send_keeps_framing() {
+        let (a, b) = tokio::io::duplex(1024);
+        let mut left = AsyncIgtlStream::new(a);
+        let mut right = AsyncIgtlStream::new(b);
+        left.set_write_timeout(Some(Duration::from_millis(20)));
+
+        // Larger than the pipe, so the write stalls until the other side reads
+        let big = IgtlMessage::new(StatusMessage::ok(&"x".repeat(4000)), "Left").unwrap();
+        let err = left.send(&big).await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::TimedOut));
+
+        let reader = tokio::spawn(async move {
+            let first: IgtlMessage<StatusMessage> = right.receive().await.unwrap();
+            let second: IgtlMessage<StatusMessage> = right.receive().await.unwrap();
+            (
+                first.content.status_string.len(),
+                second.content.status_string,
+            )
+        });
+        left.set_write_timeout(None);
+        left.send(&IgtlMessage::new(StatusMessage::ok("Next"), "Left").unwrap())
+            .await
+            .unwrap();
+        assert_eq!(reader.await.unwrap(), (4000, "Next".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_async_read_timeout() {
+        let (_a, b) = tokio::io::duplex(1024);
+        let mut right = AsyncIgtlStream::new(b);
+        right.set_read_timeout(Some(Duration::from_millis(20)));
+        let err = right.receive_any().await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::TimedOut));
+    }
+}
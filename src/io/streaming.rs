@@ -0,0 +1,849 @@
+//! Server-side streaming engine for STT_*/STP_* requests
+//!
+//! OpenIGTLink clients such as 3D Slicer request continuous data by sending a
+//! start-streaming message (`STT_TDATA`, `STT_IMAGE`, ...) and expect the server to
+//! acknowledge with the matching ready-to-send message (`RTS_TDATA`, `RTS_IMAGE`, ...)
+//! before pushing data at the requested interval until `STP_*` arrives.
+//!
+//! This module implements that protocol on top of [`AsyncIgtlConnection`]:
+//!
+//! - A server registers [`DataSource`]s keyed by the streamed message type
+//!   (`"TDATA"`, `"QTDATA"`, `"IMAGE"`, `"TRANSFORM"`, `"POSITION"`, `"NDARRAY"`)
+//! - Each accepted connection is wrapped in a [`StreamingSession`], which answers
+//!   STT_* with RTS_*, runs one timer task per active (message type, device) stream
+//!   and stops it on STP_* or disconnect
+//! - Sessions are independent, so several clients can stream the same source at
+//!   different rates
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::streaming::{StreamRequest, StreamingEngine};
+//! use openigtlink_rust::io::AsyncIgtlServer;
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::{TDataMessage, TrackingDataElement, TrackingInstrumentType};
+//! use openigtlink_rust::protocol::AnyMessage;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let engine = StreamingEngine::new();
+//! engine.register_source("TDATA", |request: &StreamRequest| {
+//!     let tdata = TDataMessage::new(vec![TrackingDataElement::identity(
+//!         "Probe",
+//!         TrackingInstrumentType::Instrument6D,
+//!     )]);
+//!     let msg = IgtlMessage::new(tdata, &request.device_name)?;
+//!     Ok(Some(AnyMessage::TData(msg)))
+//! });
+//!
+//! let server = AsyncIgtlServer::bind("0.0.0.0:18944").await?;
+//! loop {
+//!     let conn = server.accept().await?;
+//!     let session = engine.session(conn);
+//!     tokio::spawn(async move {
+//!         let _ = session.run().await;
+//!     });
+//! }
+//! # }
+//! ```
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, info, trace, warn};
+
+use crate::error::{IgtlError, Result};
+use crate::io::async_server::{
+    AsyncIgtlConnection, AsyncIgtlConnectionReader, AsyncIgtlConnectionWriter,
+};
+use crate::io::repository::DeviceRepository;
+use crate::protocol::header::TypeName;
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::types::{RtsQtDataMessage, RtsTDataMessage, StatusMessage};
+use crate::protocol::AnyMessage;
+
+/// Default streaming interval used when a request carries no resolution
+pub const DEFAULT_STREAM_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Parameters of an active stream, passed to [`DataSource::next_message`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StreamRequest {
+    /// Streamed message type (e.g. "TDATA", "IMAGE")
+    pub message_type: String,
+    /// Device name from the STT_* header (empty means "any device")
+    pub device_name: String,
+    /// Interval between two produced messages
+    pub resolution: Duration,
+    /// Requested coordinate system name (empty if not specified)
+    pub coordinate_name: String,
+}
+
+/// Producer of streamed messages
+///
+/// Called once per tick of every active stream. Returning `Ok(None)` skips the
+/// tick (e.g. no new frame available); returning an error is logged and the
+/// stream keeps running.
+///
+/// Closures with the signature `Fn(&StreamRequest) -> Result<Option<AnyMessage>>`
+/// implement this trait.
+pub trait DataSource: Send + Sync {
+    /// Produce the next message for the given stream
+    fn next_message(&self, request: &StreamRequest) -> Result<Option<AnyMessage>>;
+}
+
+impl<F> DataSource for F
+where
+    F: Fn(&StreamRequest) -> Result<Option<AnyMessage>> + Send + Sync,
+{
+    fn next_message(&self, request: &StreamRequest) -> Result<Option<AnyMessage>> {
+        self(request)
+    }
+}
+
+/// Registry of data sources shared by all streaming sessions
+///
+/// Cloning is cheap; all clones share the same set of sources, so sources can be
+/// registered or removed while sessions are running. Already active streams keep
+/// the source they were started with.
+#[derive(Clone)]
+pub struct StreamingEngine {
+    sources: Arc<RwLock<HashMap<String, Arc<dyn DataSource>>>>,
+    default_interval: Duration,
+    min_interval: Duration,
+    repository: Option<DeviceRepository>,
+}
+
+impl StreamingEngine {
+    /// Create an engine with no registered sources
+    pub fn new() -> Self {
+        Self {
+            sources: Arc::new(RwLock::new(HashMap::new())),
+            default_interval: DEFAULT_STREAM_INTERVAL,
+            min_interval: Duration::from_millis(1),
+            repository: None,
+        }
+    }
+
+    /// Set the interval used when a request has no (or a zero) resolution
+    ///
+    /// STT_IMAGE, STT_TRANSFOR, STT_POSITION and STT_NDARRAY carry no parameters
+    /// and always use this interval.
+    pub fn with_default_interval(mut self, interval: Duration) -> Self {
+        self.default_interval = interval;
+        self
+    }
+
+    /// Set the shortest interval a client may request
+    ///
+    /// Requests for faster streams are clamped to this value.
+    pub fn with_min_interval(mut self, interval: Duration) -> Self {
+        self.min_interval = interval;
+        self
+    }
+
+    /// Answer GET_* queries in every session from a [`DeviceRepository`]
+    ///
+    /// Queries are then handled by [`StreamingSession::next_message`] like STT_*/STP_*
+    /// and never returned to the caller. To also stream the stored values, register
+    /// the repository as a source (e.g. `engine.register_source("TRANSFORM", repository.clone())`).
+    pub fn with_repository(mut self, repository: DeviceRepository) -> Self {
+        self.repository = Some(repository);
+        self
+    }
+
+    /// Register a source for a streamed message type
+    ///
+    /// Replaces any previously registered source for the same type.
+    ///
+    /// # Arguments
+    /// * `message_type` - Streamed message type (e.g. "TDATA", "IMAGE")
+    /// * `source` - Producer of messages of that type
+    pub fn register_source<S>(&self, message_type: &str, source: S)
+    where
+        S: DataSource + 'static,
+    {
+        info!(message_type = message_type, "Registering streaming source");
+        self.sources
+            .write()
+            .unwrap()
+            .insert(message_type.to_string(), Arc::new(source));
+    }
+
+    /// Remove the source for a message type
+    ///
+    /// # Returns
+    /// true if a source was registered
+    pub fn unregister_source(&self, message_type: &str) -> bool {
+        self.sources.write().unwrap().remove(message_type).is_some()
+    }
+
+    /// Check whether a source is registered for a message type
+    pub fn has_source(&self, message_type: &str) -> bool {
+        self.sources.read().unwrap().contains_key(message_type)
+    }
+
+    /// Wrap an accepted connection in a streaming session
+    pub fn session(&self, conn: AsyncIgtlConnection) -> StreamingSession {
+        let peer_addr = conn.peer_addr().ok();
+        let (reader, writer) = conn.into_split();
+        StreamingSession {
+            reader,
+            writer: Arc::new(Mutex::new(writer)),
+            engine: self.clone(),
+            streams: HashMap::new(),
+            peer_addr,
+        }
+    }
+
+    fn source(&self, message_type: &str) -> Option<Arc<dyn DataSource>> {
+        self.sources.read().unwrap().get(message_type).cloned()
+    }
+
+    fn interval_for(&self, resolution_ms: u32) -> Duration {
+        if resolution_ms == 0 {
+            self.default_interval.max(self.min_interval)
+        } else {
+            Duration::from_millis(resolution_ms as u64).max(self.min_interval)
+        }
+    }
+}
+
+impl Default for StreamingEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Streaming control request decoded from an incoming message
+enum StreamControl {
+    Start {
+        message_type: &'static str,
+        device_name: String,
+        resolution_ms: u32,
+        coordinate_name: String,
+    },
+    Stop {
+        message_type: &'static str,
+        device_name: String,
+    },
+}
+
+impl StreamControl {
+    fn from_message(msg: &AnyMessage) -> Option<Self> {
+        let device_name = msg.device_name().unwrap_or("").to_string();
+        let start = |message_type, resolution_ms, coordinate_name: &str| StreamControl::Start {
+            message_type,
+            device_name: device_name.clone(),
+            resolution_ms,
+            coordinate_name: coordinate_name.to_string(),
+        };
+
+        let control = match msg {
+            AnyMessage::StartTData(m) => {
+                start("TDATA", m.content.resolution, &m.content.coordinate_name)
+            }
+            AnyMessage::StartQtData(m) => {
+                start("QTDATA", m.content.resolution, &m.content.coordinate_name)
+            }
+            AnyMessage::StartImage(_) => start("IMAGE", 0, ""),
+            AnyMessage::StartTransform(_) => start("TRANSFORM", 0, ""),
+            AnyMessage::StartPosition(_) => start("POSITION", 0, ""),
+            AnyMessage::StartNdArray(_) => start("NDARRAY", 0, ""),
+            AnyMessage::StopTData(_) => StreamControl::Stop {
+                message_type: "TDATA",
+                device_name,
+            },
+            AnyMessage::StopQtData(_) => StreamControl::Stop {
+                message_type: "QTDATA",
+                device_name,
+            },
+            AnyMessage::StopImage(_) => StreamControl::Stop {
+                message_type: "IMAGE",
+                device_name,
+            },
+            AnyMessage::StopTransform(_) => StreamControl::Stop {
+                message_type: "TRANSFORM",
+                device_name,
+            },
+            AnyMessage::StopPosition(_) => StreamControl::Stop {
+                message_type: "POSITION",
+                device_name,
+            },
+            AnyMessage::StopNdArray(_) => StreamControl::Stop {
+                message_type: "NDARRAY",
+                device_name,
+            },
+            _ => return None,
+        };
+        Some(control)
+    }
+}
+
+/// Build the RTS_* acknowledgement for a streamed message type
+fn rts_reply(message_type: &str, device_name: &str, ok: bool) -> Result<AnyMessage> {
+    match message_type {
+        "TDATA" => {
+            let content = if ok {
+                RtsTDataMessage::ok()
+            } else {
+                RtsTDataMessage::error()
+            };
+            Ok(AnyMessage::RtsTData(IgtlMessage::new(
+                content,
+                device_name,
+            )?))
+        }
+        "QTDATA" => {
+            let content = if ok {
+                RtsQtDataMessage::ok()
+            } else {
+                RtsQtDataMessage::error()
+            };
+            Ok(AnyMessage::RtsQtData(IgtlMessage::new(
+                content,
+                device_name,
+            )?))
+        }
+        _ => {
+            // Other RTS_* messages use the STATUS body format
+            let content = if ok {
+                StatusMessage::ok("Ready to send")
+            } else {
+                StatusMessage::error("NotAvailable", "No data source for requested stream")
+            };
+            let mut msg = IgtlMessage::new(content, device_name)?;
+            let wire_type = match message_type {
+                "TRANSFORM" => "RTS_TRANSFOR".to_string(),
+                other => format!("RTS_{}", other),
+            };
+            msg.header.type_name = TypeName::new(&wire_type)?;
+            Ok(match message_type {
+                "IMAGE" => AnyMessage::RtsImage(msg),
+                "TRANSFORM" => AnyMessage::RtsTransform(msg),
+                _ => AnyMessage::Unknown {
+                    header: msg.header.clone(),
+                    body: msg.content.encode_content()?,
+                },
+            })
+        }
+    }
+}
+
+/// Per-connection streaming state machine
+///
+/// Created by [`StreamingEngine::session`]. Drive it with [`next_message`](Self::next_message)
+/// to handle non-streaming messages yourself, or with [`run`](Self::run) to only
+/// serve streams. All active streams are stopped when the peer disconnects or the
+/// session is dropped.
+pub struct StreamingSession {
+    reader: AsyncIgtlConnectionReader,
+    writer: Arc<Mutex<AsyncIgtlConnectionWriter>>,
+    engine: StreamingEngine,
+    /// Stream tasks by (message type, device name)
+    streams: HashMap<(&'static str, String), JoinHandle<()>>,
+    peer_addr: Option<std::net::SocketAddr>,
+}
+
+impl StreamingSession {
+    /// Receive the next message that is not a streaming control request
+    ///
+    /// STT_* and STP_* messages are answered and handled internally, as are GET_*
+    /// queries when the engine has a [`DeviceRepository`]; every other message is
+    /// returned to the caller.
+    ///
+    /// # Returns
+    /// `Ok(None)` when the peer closed the connection
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read or write failed
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption detected
+    pub async fn next_message(&mut self) -> Result<Option<AnyMessage>> {
+        loop {
+            let msg = match self.reader.receive_any().await {
+                Ok(msg) => msg,
+                Err(IgtlError::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                    info!(peer_addr = ?self.peer_addr, "Streaming client disconnected");
+                    self.stop_all();
+                    return Ok(None);
+                }
+                Err(e) => {
+                    if matches!(e, IgtlError::Io(_)) {
+                        self.stop_all();
+                    }
+                    return Err(e);
+                }
+            };
+
+            if let Some(control) = StreamControl::from_message(&msg) {
+                self.handle_control(control).await?;
+                continue;
+            }
+
+            if let Some(repository) = &self.engine.repository {
+                if let Some(replies) = repository.respond(&msg)? {
+                    let mut writer = self.writer.lock().await;
+                    for reply in &replies {
+                        writer.send_any(reply).await?;
+                    }
+                    continue;
+                }
+            }
+
+            return Ok(Some(msg));
+        }
+    }
+
+    /// Serve streaming requests until the peer disconnects
+    ///
+    /// Messages other than STT_*/STP_* (and GET_* when a repository is attached)
+    /// are logged and discarded.
+    pub async fn run(mut self) -> Result<()> {
+        while let Some(msg) = self.next_message().await? {
+            debug!(
+                msg_type = msg.message_type(),
+                "Ignoring non-streaming message in streaming session"
+            );
+        }
+        Ok(())
+    }
+
+    /// Send a message to the peer, interleaved with active streams
+    pub async fn send<T: Message>(&self, msg: &IgtlMessage<T>) -> Result<()> {
+        self.writer.lock().await.send(msg).await
+    }
+
+    /// Send a dynamically typed message to the peer, interleaved with active streams
+    pub async fn send_any(&self, msg: &AnyMessage) -> Result<()> {
+        self.writer.lock().await.send_any(msg).await
+    }
+
+    /// Streams currently running for this peer, as (message type, device name)
+    pub fn active_streams(&self) -> Vec<(String, String)> {
+        let mut streams: Vec<_> = self
+            .streams
+            .iter()
+            .filter(|(_, handle)| !handle.is_finished())
+            .map(|((message_type, device_name), _)| (message_type.to_string(), device_name.clone()))
+            .collect();
+        streams.sort();
+        streams
+    }
+
+    /// Check whether a message type is currently being streamed to this peer for a device
+    pub fn is_streaming(&self, message_type: &str, device_name: &str) -> bool {
+        self.streams.iter().any(|((ty, device), handle)| {
+            *ty == message_type && device == device_name && !handle.is_finished()
+        })
+    }
+
+    /// Get the remote peer address
+    pub fn peer_addr(&self) -> Option<std::net::SocketAddr> {
+        self.peer_addr
+    }
+
+    /// Stop all active streams without closing the connection
+    pub fn stop_all(&mut self) {
+        for ((message_type, device_name), handle) in self.streams.drain() {
+            debug!(message_type = message_type, device_name = %device_name, "Stopping stream");
+            handle.abort();
+        }
+    }
+
+    async fn handle_control(&mut self, control: StreamControl) -> Result<()> {
+        match control {
+            StreamControl::Start {
+                message_type,
+                device_name,
+                resolution_ms,
+                coordinate_name,
+            } => {
+                let source = self.engine.source(message_type);
+                let reply = rts_reply(message_type, &device_name, source.is_some())?;
+                self.writer.lock().await.send_any(&reply).await?;
+
+                let Some(source) = source else {
+                    warn!(
+                        message_type = message_type,
+                        peer_addr = ?self.peer_addr,
+                        "Rejected stream request: no data source registered"
+                    );
+                    return Ok(());
+                };
+
+                let request = StreamRequest {
+                    message_type: message_type.to_string(),
+                    device_name,
+                    resolution: self.engine.interval_for(resolution_ms),
+                    coordinate_name,
+                };
+
+                info!(
+                    message_type = message_type,
+                    device_name = %request.device_name,
+                    interval_ms = request.resolution.as_millis() as u64,
+                    peer_addr = ?self.peer_addr,
+                    "Starting stream"
+                );
+
+                let key = (message_type, request.device_name.clone());
+                let handle = tokio::spawn(stream_loop(source, request, self.writer.clone()));
+                if let Some(previous) = self.streams.insert(key, handle) {
+                    previous.abort();
+                }
+            }
+            StreamControl::Stop {
+                message_type,
+                device_name,
+            } => {
+                // An empty device name stops the stream for every device
+                let stopped: Vec<_> = self
+                    .streams
+                    .keys()
+                    .filter(|(ty, device)| {
+                        *ty == message_type && (device_name.is_empty() || *device == device_name)
+                    })
+                    .cloned()
+                    .collect();
+                if stopped.is_empty() {
+                    debug!(
+                        message_type = message_type,
+                        device_name = %device_name,
+                        "Stop requested for inactive stream"
+                    );
+                }
+                for key in stopped {
+                    if let Some(handle) = self.streams.remove(&key) {
+                        info!(
+                            message_type = message_type,
+                            device_name = %key.1,
+                            peer_addr = ?self.peer_addr,
+                            "Stopping stream"
+                        );
+                        handle.abort();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for StreamingSession {
+    fn drop(&mut self) {
+        self.stop_all();
+    }
+}
+
+/// Produce and send messages for one stream until the connection fails
+async fn stream_loop(
+    source: Arc<dyn DataSource>,
+    request: StreamRequest,
+    writer: Arc<Mutex<AsyncIgtlConnectionWriter>>,
+) {
+    let mut ticker = tokio::time::interval(request.resolution);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+    loop {
+        ticker.tick().await;
+
+        let msg = match source.next_message(&request) {
+            Ok(Some(msg)) => msg,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!(
+                    message_type = %request.message_type,
+                    error = %e,
+                    "Data source failed to produce message"
+                );
+                continue;
+            }
+        };
+
+        if let Err(e) = writer.lock().await.send_any(&msg).await {
+            warn!(
+                message_type = %request.message_type,
+                error = %e,
+                "Stream write failed, stopping stream"
+            );
+            return;
+        }
+
+        trace!(message_type = %request.message_type, "Streamed message sent");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::{AsyncIgtlServer, ClientBuilder};
+    use crate::protocol::types::{
+        GetTransformMessage, StartImageMessage, StartTDataMessage, StopTDataMessage, TDataMessage,
+        TrackingDataElement, TrackingInstrumentType, TransformMessage,
+    };
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn tdata_source(request: &StreamRequest) -> Result<Option<AnyMessage>> {
+        let tdata = TDataMessage::new(vec![TrackingDataElement::identity(
+            "Probe",
+            TrackingInstrumentType::Instrument6D,
+        )]);
+        Ok(Some(AnyMessage::TData(IgtlMessage::new(
+            tdata,
+            &request.device_name,
+        )?)))
+    }
+
+    async fn spawn_server(engine: StreamingEngine) -> std::net::SocketAddr {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let conn = server.accept().await.unwrap();
+                let session = engine.session(conn);
+                tokio::spawn(session.run());
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn test_interval_for_resolution() {
+        let engine = StreamingEngine::new()
+            .with_default_interval(Duration::from_millis(40))
+            .with_min_interval(Duration::from_millis(5));
+
+        assert_eq!(engine.interval_for(0), Duration::from_millis(40));
+        assert_eq!(engine.interval_for(2), Duration::from_millis(5));
+        assert_eq!(engine.interval_for(50), Duration::from_millis(50));
+    }
+
+    #[test]
+    fn test_register_unregister_source() {
+        let engine = StreamingEngine::new();
+        assert!(!engine.has_source("TDATA"));
+
+        engine.register_source("TDATA", tdata_source);
+        assert!(engine.has_source("TDATA"));
+
+        assert!(engine.unregister_source("TDATA"));
+        assert!(!engine.unregister_source("TDATA"));
+    }
+
+    #[test]
+    fn test_rts_reply_wire_types() {
+        let reply = rts_reply("TRANSFORM", "Tracker", true).unwrap();
+        assert_eq!(reply.header().type_name.as_str().unwrap(), "RTS_TRANSFOR");
+
+        let reply = rts_reply("NDARRAY", "Tracker", false).unwrap();
+        assert_eq!(reply.header().type_name.as_str().unwrap(), "RTS_NDARRAY");
+        assert!(reply.is_unknown());
+    }
+
+    #[tokio::test]
+    async fn test_stream_start_and_stop() {
+        let engine = StreamingEngine::new();
+        engine.register_source("TDATA", tdata_source);
+        let addr = spawn_server(engine).await;
+
+        let mut client = ClientBuilder::new()
+            .tcp(addr.to_string())
+            .async_mode()
+            .build()
+            .await
+            .unwrap();
+
+        let start = IgtlMessage::new(StartTDataMessage::new(10, "RAS"), "Tracker").unwrap();
+        client.send(&start).await.unwrap();
+
+        let ack: IgtlMessage<RtsTDataMessage> = client.receive().await.unwrap();
+        assert_eq!(ack.content.status, 1);
+
+        for _ in 0..3 {
+            let msg: IgtlMessage<TDataMessage> = client.receive().await.unwrap();
+            assert_eq!(msg.header.device_name.as_str().unwrap(), "Tracker");
+            assert_eq!(msg.content.elements[0].name, "Probe");
+        }
+
+        client
+            .send(&IgtlMessage::new(StopTDataMessage, "Tracker").unwrap())
+            .await
+            .unwrap();
+
+        // Drain in-flight messages, then expect silence
+        let drained = tokio::time::timeout(Duration::from_millis(200), async {
+            loop {
+                let _ = client.receive_any().await;
+            }
+        })
+        .await;
+        assert!(drained.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_streams_are_keyed_by_device() {
+        let engine = StreamingEngine::new();
+        engine.register_source("TDATA", tdata_source);
+        let addr = spawn_server(engine).await;
+
+        let mut client = ClientBuilder::new()
+            .tcp(addr.to_string())
+            .async_mode()
+            .build()
+            .await
+            .unwrap();
+
+        for device in ["Left", "Right"] {
+            let start = IgtlMessage::new(StartTDataMessage::new(10, "RAS"), device).unwrap();
+            client.send(&start).await.unwrap();
+        }
+
+        // Both devices stream side by side
+        let (mut left, mut right) = (0, 0);
+        while left < 3 || right < 3 {
+            if let AnyMessage::TData(msg) = client.receive_any().await.unwrap() {
+                match msg.header.device_name.as_str().unwrap() {
+                    "Left" => left += 1,
+                    _ => right += 1,
+                }
+            }
+        }
+
+        client
+            .send(&IgtlMessage::new(StopTDataMessage, "Left").unwrap())
+            .await
+            .unwrap();
+
+        // Once in-flight frames from "Left" are drained, only "Right" keeps streaming
+        let mut right_only = 0;
+        while right_only < 10 {
+            if let AnyMessage::TData(msg) = client.receive_any().await.unwrap() {
+                match msg.header.device_name.as_str().unwrap() {
+                    "Right" => right_only += 1,
+                    _ => right_only = 0,
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_reports_disconnect() {
+        use crate::io::ConnectionEvent;
+
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let mut events = server.subscribe_events();
+
+        let client = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let session = StreamingEngine::new().session(server.accept().await.unwrap());
+        drop(client);
+        session.run().await.unwrap();
+
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ConnectionEvent::Connected { .. }
+        ));
+        assert!(matches!(
+            events.recv().await.unwrap(),
+            ConnectionEvent::Disconnected { .. }
+        ));
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stream_rejected_without_source() {
+        let addr = spawn_server(StreamingEngine::new()).await;
+
+        let mut client = ClientBuilder::new()
+            .tcp(addr.to_string())
+            .async_mode()
+            .build()
+            .await
+            .unwrap();
+
+        client
+            .send(&IgtlMessage::new(StartImageMessage, "US").unwrap())
+            .await
+            .unwrap();
+
+        let ack = client.receive_any().await.unwrap();
+        match ack {
+            AnyMessage::RtsImage(msg) => assert_ne!(msg.content.code, 1),
+            other => panic!("Unexpected reply: {}", other.message_type()),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_answers_queries_from_repository() {
+        let repository = DeviceRepository::new();
+        repository
+            .store(AnyMessage::Transform(
+                IgtlMessage::new(TransformMessage::identity(), "Needle").unwrap(),
+            ))
+            .unwrap();
+        let addr = spawn_server(StreamingEngine::new().with_repository(repository)).await;
+
+        let mut client = ClientBuilder::new()
+            .tcp(addr.to_string())
+            .async_mode()
+            .build()
+            .await
+            .unwrap();
+
+        client
+            .send(&IgtlMessage::new(GetTransformMessage, "Needle").unwrap())
+            .await
+            .unwrap();
+        let reply: IgtlMessage<TransformMessage> = client.receive().await.unwrap();
+        assert_eq!(reply.header.device_name.as_str().unwrap(), "Needle");
+    }
+
+    #[tokio::test]
+    async fn test_independent_client_rates() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        let engine = StreamingEngine::new();
+        engine.register_source("TDATA", move |request: &StreamRequest| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            tdata_source(request)
+        });
+        let addr = spawn_server(engine).await;
+
+        let mut fast = ClientBuilder::new()
+            .tcp(addr.to_string())
+            .async_mode()
+            .build()
+            .await
+            .unwrap();
+        let mut slow = ClientBuilder::new()
+            .tcp(addr.to_string())
+            .async_mode()
+            .build()
+            .await
+            .unwrap();
+
+        fast.send(&IgtlMessage::new(StartTDataMessage::new(10, ""), "Fast").unwrap())
+            .await
+            .unwrap();
+        slow.send(&IgtlMessage::new(StartTDataMessage::new(1000, ""), "Slow").unwrap())
+            .await
+            .unwrap();
+
+        let _: IgtlMessage<RtsTDataMessage> = fast.receive().await.unwrap();
+        let _: IgtlMessage<RtsTDataMessage> = slow.receive().await.unwrap();
+
+        // The fast client receives several frames before the slow client's second one
+        for _ in 0..5 {
+            let msg: IgtlMessage<TDataMessage> = fast.receive().await.unwrap();
+            assert_eq!(msg.header.device_name.as_str().unwrap(), "Fast");
+        }
+        let first_slow: IgtlMessage<TDataMessage> = slow.receive().await.unwrap();
+        assert_eq!(first_slow.header.device_name.as_str().unwrap(), "Slow");
+
+        assert!(calls.load(Ordering::Relaxed) >= 6);
+    }
+}
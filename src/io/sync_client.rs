@@ -1,16 +1,27 @@
 //! Synchronous OpenIGTLink client
 //!
-//! Simple blocking TCP client for OpenIGTLink communication.
+//! Simple blocking TCP client for OpenIGTLink communication, optionally TLS-encrypted.
 
-use std::io::{Read, Write};
-use std::net::TcpStream;
+use std::collections::VecDeque;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use crate::error::Result;
+use crate::error::{IgtlError, Result};
+use crate::io::common::{
+    decode_any_frame, status_query, timed_out, ReplyMatcher, DEFAULT_QUERY_TIMEOUT,
+};
+use crate::io::events::{ConnectionEvent, EventEmitter};
+use crate::io::stream::IgtlStream;
+use crate::io::sync_stream::SyncStream;
 use crate::protocol::any_message::AnyMessage;
-use crate::protocol::factory::MessageFactory;
-use crate::protocol::header::Header;
 use crate::protocol::message::{IgtlMessage, Message};
-use tracing::{debug, info, trace};
+use crate::protocol::types::{
+    CapabilityMessage, GetCapabilityMessage, GetTransformMessage, StatusMessage, TransformMessage,
+};
+use tokio::sync::broadcast;
+use tokio_rustls::rustls;
+use tracing::{debug, info, trace, warn};
 
 /// Synchronous OpenIGTLink client
 ///
@@ -23,8 +34,11 @@ use tracing::{debug, info, trace};
 /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
 /// ```
 pub struct SyncTcpClient {
-    stream: TcpStream,
-    verify_crc: bool,
+    stream: IgtlStream<SyncStream>,
+    /// Complete messages set aside while waiting for a query reply
+    pending: VecDeque<Vec<u8>>,
+    events: EventEmitter,
+    peer: Option<SocketAddr>,
 }
 
 impl SyncTcpClient {
@@ -44,28 +58,100 @@ impl SyncTcpClient {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn connect(addr: &str) -> Result<Self> {
+        Self::connect_with_events(addr, EventEmitter::default())
+    }
+
+    /// Connect and publish lifecycle events on `events`
+    pub(crate) fn connect_with_events(addr: &str, events: EventEmitter) -> Result<Self> {
+        Self::open(addr, None, events)
+    }
+
+    /// Connect to an OpenIGTLink server with TLS encryption
+    ///
+    /// # Arguments
+    ///
+    /// * `hostname` - Server hostname, verified against the server certificate
+    /// * `port` - Server port
+    /// * `tls_config` - TLS client configuration
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - Connection or TLS handshake failed
+    pub fn connect_with_tls(
+        hostname: &str,
+        port: u16,
+        tls_config: Arc<rustls::ClientConfig>,
+    ) -> Result<Self> {
+        Self::connect_tls_with_events(hostname, port, tls_config, EventEmitter::default())
+    }
+
+    /// Connect with TLS and publish lifecycle events on `events`
+    pub(crate) fn connect_tls_with_events(
+        hostname: &str,
+        port: u16,
+        tls_config: Arc<rustls::ClientConfig>,
+        events: EventEmitter,
+    ) -> Result<Self> {
+        let addr = format!("{}:{}", hostname, port);
+        Self::open(&addr, Some((hostname, tls_config)), events)
+    }
+
+    fn open(
+        addr: &str,
+        tls: Option<(&str, Arc<rustls::ClientConfig>)>,
+        events: EventEmitter,
+    ) -> Result<Self> {
         info!("Connecting to {}", addr);
-        let stream = TcpStream::connect(addr)?;
+        events.emit(ConnectionEvent::Connecting {
+            addr: addr.to_string(),
+        });
+        let tcp_stream = TcpStream::connect(addr)?;
+        let peer = tcp_stream.peer_addr()?;
+
+        let stream = match tls {
+            Some((hostname, config)) => {
+                debug!("TCP connected to {}, starting TLS handshake", addr);
+                SyncStream::tls_client(tcp_stream, hostname, config).map_err(|e| {
+                    warn!(error = %e, "TLS handshake failed");
+                    events.emit(ConnectionEvent::TlsHandshakeFailed {
+                        peer: Some(peer),
+                        error: e.to_string(),
+                    });
+                    e
+                })?
+            }
+            None => SyncStream::Plain(tcp_stream),
+        };
         debug!("Connected to {}", addr);
+        events.emit(ConnectionEvent::Connected { peer });
 
         Ok(SyncTcpClient {
-            stream,
-            verify_crc: true,
+            stream: IgtlStream::new(stream),
+            pending: VecDeque::new(),
+            events: events.connection(Some(peer)),
+            peer: Some(peer),
         })
     }
 
+    /// Subscribe to connection lifecycle events
+    ///
+    /// See [`events`](crate::io::events).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+
     /// Enable or disable CRC verification for received messages
     ///
     /// # Arguments
     ///
     /// * `verify` - true to enable CRC verification, false to disable
     pub fn set_verify_crc(&mut self, verify: bool) {
-        self.verify_crc = verify;
+        self.stream.set_verify_crc(verify);
     }
 
     /// Get current CRC verification setting
     pub fn verify_crc(&self) -> bool {
-        self.verify_crc
+        self.stream.verify_crc()
     }
 
     /// Set read timeout for receive operations
@@ -74,7 +160,7 @@ impl SyncTcpClient {
     ///
     /// * `timeout` - Timeout duration (None for blocking forever)
     pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> Result<()> {
-        self.stream.set_read_timeout(timeout)?;
+        self.stream.get_ref().tcp().set_read_timeout(timeout)?;
         Ok(())
     }
 
@@ -84,7 +170,7 @@ impl SyncTcpClient {
     ///
     /// * `timeout` - Timeout duration (None for blocking forever)
     pub fn set_write_timeout(&self, timeout: Option<std::time::Duration>) -> Result<()> {
-        self.stream.set_write_timeout(timeout)?;
+        self.stream.get_ref().tcp().set_write_timeout(timeout)?;
         Ok(())
     }
 
@@ -115,8 +201,9 @@ impl SyncTcpClient {
         let data = msg.encode()?;
         trace!("Sending {} bytes", data.len());
 
-        self.stream.write_all(&data)?;
-        self.stream.flush()?;
+        let result = self.stream.write_frame(&data);
+        self.events.check_result(&result, self.peer);
+        result?;
 
         debug!("Sent {} bytes", data.len());
         Ok(())
@@ -140,24 +227,10 @@ impl SyncTcpClient {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
-        // Read header (58 bytes)
-        let mut header_buf = [0u8; 58];
-        self.stream.read_exact(&mut header_buf)?;
-
-        let header = Header::decode(&header_buf)?;
-        debug!("Received header: size={}", header.body_size);
-
-        // Read body
-        let body_size = header.body_size as usize;
-        let mut body_buf = vec![0u8; body_size];
-        self.stream.read_exact(&mut body_buf)?;
-
-        // Combine header and body
-        let mut full_msg = header_buf.to_vec();
-        full_msg.extend_from_slice(&body_buf);
-
-        // Decode message
-        let result = IgtlMessage::decode_with_options(&full_msg, self.verify_crc);
+        let result = self
+            .next_frame()
+            .and_then(|frame| IgtlMessage::decode_with_options(&frame, self.stream.verify_crc()));
+        self.events.check_result(&result, self.peer);
 
         match &result {
             Ok(_msg) => {
@@ -222,36 +295,261 @@ impl SyncTcpClient {
     /// # }
     /// ```
     pub fn receive_any(&mut self) -> Result<AnyMessage> {
-        // Read header (58 bytes)
-        let mut header_buf = [0u8; Header::SIZE];
-        self.stream.read_exact(&mut header_buf)?;
-
-        let header = Header::decode(&header_buf)?;
-        debug!(
-            "Received header: type={}, device={}, size={}",
-            header.type_name.as_str().unwrap_or("?"),
-            header.device_name.as_str().unwrap_or("?"),
-            header.body_size
-        );
+        let result = self
+            .next_frame()
+            .and_then(|frame| decode_any_frame(&frame, self.stream.verify_crc()));
+        self.events.check_result(&result, self.peer);
+        result
+    }
+
+    /// Send a query and wait for the matching reply
+    ///
+    /// Replies are matched by message type and device name (any device if `device_name`
+    /// is empty). Messages that arrive in the meantime are kept and returned by later
+    /// [`receive`](Self::receive) / [`receive_any`](Self::receive_any) calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_name` - Device name of the query and of the expected reply
+    /// * `request` - Query content (e.g. `GetTransformMessage`)
+    /// * `timeout` - Maximum time to wait for the reply
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network failure, or
+    ///   [`TimedOut`](std::io::ErrorKind::TimedOut) if no reply arrived in time
+    pub fn query<Req: Message, Resp: Message>(
+        &mut self,
+        device_name: &str,
+        request: Req,
+        timeout: Duration,
+    ) -> Result<IgtlMessage<Resp>> {
+        let msg = IgtlMessage::new(request, device_name)?;
+        self.query_message(&msg, timeout)
+    }
 
-        // Read body
-        let body_size = header.body_size as usize;
-        let mut body_buf = vec![0u8; body_size];
-        self.stream.read_exact(&mut body_buf)?;
+    /// Send a prepared query message and wait for the matching reply
+    ///
+    /// Like [`query`](Self::query), but if the message carries a v3 message ID
+    /// (see [`IgtlMessage::set_message_id`]), replies that also carry one must
+    /// echo the same ID.
+    pub fn query_message<Req: Message, Resp: Message>(
+        &mut self,
+        request: &IgtlMessage<Req>,
+        timeout: Duration,
+    ) -> Result<IgtlMessage<Resp>> {
+        let matcher = ReplyMatcher::new::<Resp>(
+            request.header.device_name.as_str()?,
+            request.get_message_id(),
+        );
+        self.send(request)?;
 
-        // Decode using MessageFactory
-        let factory = MessageFactory::new();
-        let result = factory.decode_any(&header, &body_buf, self.verify_crc);
+        let deadline = Instant::now() + timeout;
+        let previous_timeout = self.stream.get_ref().tcp().read_timeout()?;
+        let mut skipped = VecDeque::new();
 
-        match &result {
-            Ok(msg) => {
-                trace!("Successfully decoded {} message", msg.message_type());
+        let result = loop {
+            match self.read_frame_until(deadline) {
+                Ok(frame) if matcher.matches(&frame) => break Ok(frame),
+                Ok(frame) => {
+                    trace!("Buffering unrelated message while waiting for reply");
+                    skipped.push_back(frame);
+                }
+                Err(e) => break Err(e),
             }
-            Err(e) => {
-                debug!("Failed to decode message: {}", e);
-            }
-        }
+        };
 
+        self.stream
+            .get_ref()
+            .tcp()
+            .set_read_timeout(previous_timeout)?;
+        self.pending.append(&mut skipped);
+
+        let result = result
+            .map_err(|e| match e {
+                IgtlError::Io(ref io) if io.kind() == std::io::ErrorKind::TimedOut => {
+                    timed_out(&matcher.describe())
+                }
+                e => e,
+            })
+            .and_then(|frame| IgtlMessage::decode_with_options(&frame, self.stream.verify_crc()));
+        self.events.check_result(&result, self.peer);
         result
     }
+
+    /// Query the server's supported message types (GET_CAPABIL → CAPABILITY)
+    pub fn get_capability(&mut self) -> Result<IgtlMessage<CapabilityMessage>> {
+        self.query("", GetCapabilityMessage, DEFAULT_QUERY_TIMEOUT)
+    }
+
+    /// Query the status of a device (GET_STATUS → STATUS)
+    ///
+    /// The reply must come from `device_name`. With an empty device name the query
+    /// carries a v3 message ID and STATUS messages with another ID are skipped; if the
+    /// server does not echo the ID, the first STATUS without one is taken as the reply,
+    /// even if it was sent unsolicited just before it.
+    pub fn get_status(&mut self, device_name: &str) -> Result<IgtlMessage<StatusMessage>> {
+        let query = status_query(device_name)?;
+        self.query_message(&query, DEFAULT_QUERY_TIMEOUT)
+    }
+
+    /// Query the current transform of a device (GET_TRANSFOR → TRANSFORM)
+    pub fn get_transform(&mut self, device_name: &str) -> Result<IgtlMessage<TransformMessage>> {
+        self.query(device_name, GetTransformMessage, DEFAULT_QUERY_TIMEOUT)
+    }
+
+    /// Number of messages buffered while waiting for query replies
+    pub fn pending_messages(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Next frame: buffered messages first, then the socket
+    fn next_frame(&mut self) -> Result<Vec<u8>> {
+        match self.pending.pop_front() {
+            Some(frame) => Ok(frame),
+            None => self.stream.read_frame(),
+        }
+    }
+
+    /// Read one complete frame from the socket, failing with
+    /// [`TimedOut`](std::io::ErrorKind::TimedOut) at `deadline`
+    ///
+    /// Partial data is kept in the receive buffer when a read times out, so the
+    /// stream stays aligned on message boundaries.
+    fn read_frame_until(&mut self, deadline: Instant) -> Result<Vec<u8>> {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(timed_out("message"));
+        }
+        self.stream
+            .get_ref()
+            .tcp()
+            .set_read_timeout(Some(remaining))?;
+        self.stream.read_frame()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::tls::{CertificatePin, ClientTlsConfig};
+    use crate::io::IgtlServer;
+    use crate::protocol::types::GetStatusMessage;
+
+    #[test]
+    fn test_query_buffers_unrelated_messages() {
+        let server = IgtlServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut conn = server.accept().unwrap();
+            let _query: IgtlMessage<GetCapabilityMessage> = conn.receive().unwrap();
+            conn.send(&IgtlMessage::new(TransformMessage::identity(), "Needle").unwrap())
+                .unwrap();
+            let capability = CapabilityMessage::new(vec!["TRANSFORM".to_string()]);
+            conn.send(&IgtlMessage::new(capability, "Server").unwrap())
+                .unwrap();
+        });
+
+        let mut client = SyncTcpClient::connect(&addr.to_string()).unwrap();
+        let reply = client.get_capability().unwrap();
+        assert_eq!(reply.content.types, vec!["TRANSFORM"]);
+        assert_eq!(client.pending_messages(), 1);
+
+        let buffered: IgtlMessage<TransformMessage> = client.receive().unwrap();
+        assert_eq!(buffered.header.device_name.as_str().unwrap(), "Needle");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_query_timeout_keeps_read_timeout() {
+        let server = IgtlServer::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = std::thread::spawn(move || {
+            let mut conn = server.accept().unwrap();
+            let _query: IgtlMessage<GetStatusMessage> = conn.receive().unwrap();
+            std::thread::sleep(Duration::from_millis(300));
+        });
+
+        let mut client = SyncTcpClient::connect(&addr.to_string()).unwrap();
+        let err = client
+            .query::<_, StatusMessage>("", GetStatusMessage, Duration::from_millis(100))
+            .unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::TimedOut));
+        assert_eq!(client.stream.get_ref().tcp().read_timeout().unwrap(), None);
+        handle.join().unwrap();
+    }
+
+    /// Trusts the self-signed test certificate by its fingerprint
+    fn pinned_tls_config() -> Arc<rustls::ClientConfig> {
+        let pin = CertificatePin::from_pem_file("examples/certs/cert.pem").unwrap();
+        ClientTlsConfig::new().with_pin(pin).build().unwrap()
+    }
+
+    fn tls_server() -> crate::io::SyncTlsIgtlServer {
+        crate::io::SyncTlsIgtlServer::bind(
+            "127.0.0.1:0",
+            "examples/certs/cert.pem",
+            "examples/certs/key.pem",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_tls_round_trip() {
+        let server = tls_server();
+        let port = server.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let mut conn = server.accept().unwrap();
+            let msg: IgtlMessage<StatusMessage> = conn.receive().unwrap();
+            conn.send(&IgtlMessage::new(msg.content, "Echo").unwrap())
+                .unwrap();
+        });
+
+        let mut client = crate::io::ClientBuilder::new()
+            .tcp(format!("localhost:{}", port))
+            .sync()
+            .with_tls(pinned_tls_config())
+            .build()
+            .unwrap();
+        assert!(matches!(client, crate::io::SyncIgtlClient::Tls(_)));
+
+        client
+            .send(&IgtlMessage::new(StatusMessage::ok("encrypted"), "Client").unwrap())
+            .unwrap();
+        let reply: IgtlMessage<StatusMessage> = client.receive().unwrap();
+        assert_eq!(reply.content.status_string, "encrypted");
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_tls_handshake_failure_is_reported_and_skipped() {
+        let server = tls_server();
+        let port = server.local_addr().unwrap().port();
+        let mut server_events = server.subscribe_events();
+        let handle = std::thread::spawn(move || server.accept().map(|conn| conn.peer_addr()));
+
+        // No trusted roots: the self-signed server certificate is rejected
+        let config = rustls::ClientConfig::builder()
+            .with_root_certificates(rustls::RootCertStore::empty())
+            .with_no_client_auth();
+        let err = SyncTcpClient::connect_with_tls("localhost", port, Arc::new(config))
+            .err()
+            .unwrap();
+        assert!(
+            matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::ConnectionRefused)
+        );
+        assert!(matches!(
+            server_events.blocking_recv().unwrap(),
+            ConnectionEvent::TlsHandshakeFailed { .. }
+        ));
+
+        // The server keeps accepting
+        let client =
+            SyncTcpClient::connect_with_tls("localhost", port, pinned_tls_config()).unwrap();
+        let accepted = handle.join().unwrap().unwrap().unwrap();
+        assert_eq!(
+            accepted,
+            client.stream.get_ref().tcp().local_addr().unwrap()
+        );
+    }
 }
@@ -0,0 +1,109 @@
+//! Blocking byte streams shared by the synchronous client and server
+//!
+//! Wraps a plain `TcpStream` or a rustls [`StreamOwned`] so the message framing code does
+//! not depend on whether the connection is encrypted.
+
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+
+use rustls::pki_types::ServerName;
+use rustls::{ClientConfig, ClientConnection, ServerConfig, ServerConnection, StreamOwned};
+
+use crate::error::{IgtlError, Result};
+
+/// Plain or TLS-encrypted blocking stream
+pub(crate) enum SyncStream {
+    Plain(TcpStream),
+    TlsClient(Box<StreamOwned<ClientConnection, TcpStream>>),
+    TlsServer(Box<StreamOwned<ServerConnection, TcpStream>>),
+}
+
+impl SyncStream {
+    /// Perform the client side of a TLS handshake on a connected socket
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] with [`InvalidInput`](std::io::ErrorKind::InvalidInput) - Invalid hostname
+    /// - [`IgtlError::Io`] with [`ConnectionRefused`](std::io::ErrorKind::ConnectionRefused) -
+    ///   Handshake failed
+    pub(crate) fn tls_client(
+        mut sock: TcpStream,
+        hostname: &str,
+        config: Arc<ClientConfig>,
+    ) -> Result<Self> {
+        let server_name = ServerName::try_from(hostname.to_string()).map_err(|e| {
+            IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("Invalid hostname: {}", e),
+            ))
+        })?;
+        let mut conn = ClientConnection::new(config, server_name).map_err(handshake_failed)?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut sock).map_err(handshake_failed)?;
+        }
+        Ok(SyncStream::TlsClient(Box::new(StreamOwned::new(
+            conn, sock,
+        ))))
+    }
+
+    /// Perform the server side of a TLS handshake on an accepted socket
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] with [`ConnectionRefused`](std::io::ErrorKind::ConnectionRefused) -
+    ///   Handshake failed
+    pub(crate) fn tls_server(mut sock: TcpStream, config: Arc<ServerConfig>) -> Result<Self> {
+        let mut conn = ServerConnection::new(config).map_err(handshake_failed)?;
+        while conn.is_handshaking() {
+            conn.complete_io(&mut sock).map_err(handshake_failed)?;
+        }
+        Ok(SyncStream::TlsServer(Box::new(StreamOwned::new(
+            conn, sock,
+        ))))
+    }
+
+    /// Underlying TCP socket (for timeouts and socket options)
+    pub(crate) fn tcp(&self) -> &TcpStream {
+        match self {
+            SyncStream::Plain(stream) => stream,
+            SyncStream::TlsClient(stream) => stream.get_ref(),
+            SyncStream::TlsServer(stream) => stream.get_ref(),
+        }
+    }
+}
+
+impl Read for SyncStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            SyncStream::Plain(stream) => stream.read(buf),
+            SyncStream::TlsClient(stream) => stream.read(buf),
+            SyncStream::TlsServer(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for SyncStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            SyncStream::Plain(stream) => stream.write(buf),
+            SyncStream::TlsClient(stream) => stream.write(buf),
+            SyncStream::TlsServer(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            SyncStream::Plain(stream) => stream.flush(),
+            SyncStream::TlsClient(stream) => stream.flush(),
+            SyncStream::TlsServer(stream) => stream.flush(),
+        }
+    }
+}
+
+fn handshake_failed(e: impl std::fmt::Display) -> IgtlError {
+    IgtlError::Io(std::io::Error::new(
+        std::io::ErrorKind::ConnectionRefused,
+        format!("TLS handshake failed: {}", e),
+    ))
+}
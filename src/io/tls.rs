@@ -0,0 +1,821 @@
+//! TLS configuration and peer identity
+//!
+//! Helpers to build rustls configurations from PEM files, including mutual TLS:
+//!
+//! - [`ServerTlsConfig`] - server certificate and key, optionally requiring client
+//!   certificates signed by a CA bundle
+//! - [`ClientTlsConfig`] - trusted CA bundle and an optional client certificate chain
+//! - [`PeerCertificate`] - subject, subject alternative names and SHA-256 fingerprint of
+//!   a verified peer certificate, so handlers can authorize devices
+//! - [`ReloadableCertResolver`] - server certificate that can be replaced while running,
+//!   explicitly or when the files change, without affecting established sessions
+//! - [`CertificatePin`] - SPKI or certificate SHA-256 pins that replace CA trust on
+//!   closed networks
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::builder::ClientBuilder;
+//! use openigtlink_rust::io::tls::{ClientTlsConfig, ServerTlsConfig};
+//! use openigtlink_rust::io::TlsIgtlServer;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! // Server accepting only devices with a certificate issued by the hospital CA
+//! let server_config = ServerTlsConfig::new("certs/cert.pem", "certs/key.pem")
+//!     .with_client_auth("certs/ca.pem")
+//!     .build()?;
+//! let server = TlsIgtlServer::bind_with_config("0.0.0.0:18944", server_config).await?;
+//!
+//! // Device presenting its certificate
+//! let client_config = ClientTlsConfig::new()
+//!     .with_ca("certs/ca.pem")
+//!     .with_client_cert("certs/client.pem", "certs/client-key.pem")
+//!     .build()?;
+//! let client = ClientBuilder::new()
+//!     .tcp("localhost:18944")
+//!     .async_mode()
+//!     .with_tls(client_config)
+//!     .build()
+//!     .await?;
+//!
+//! let conn = server.accept().await?;
+//! if let Some(cert) = conn.peer_certificate() {
+//!     println!("Device {} ({})", cert.subject(), cert.fingerprint_hex());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime};
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, WebPkiSupportedAlgorithms};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::{ClientHello, ResolvesServerCert};
+use rustls::sign::CertifiedKey;
+use rustls::{DigitallySignedStruct, SignatureScheme};
+use sha2::{Digest, Sha256};
+use tokio::task::JoinHandle;
+use tokio_rustls::rustls;
+use tracing::{info, warn};
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+use crate::error::{IgtlError, Result};
+
+/// Server-side TLS settings loaded from PEM files
+#[derive(Debug, Clone)]
+pub struct ServerTlsConfig {
+    /// PEM file with the server certificate chain
+    pub cert_path: PathBuf,
+    /// PEM file with the server private key
+    pub key_path: PathBuf,
+    /// PEM bundle of CAs that client certificates must chain to (None = no client auth)
+    pub client_ca_path: Option<PathBuf>,
+}
+
+impl ServerTlsConfig {
+    /// Serve `cert_path` with `key_path`, without client authentication
+    pub fn new(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Self {
+        Self {
+            cert_path: cert_path.into(),
+            key_path: key_path.into(),
+            client_ca_path: None,
+        }
+    }
+
+    /// Require clients to present a certificate signed by a CA in `ca_path`
+    pub fn with_client_auth(mut self, ca_path: impl Into<PathBuf>) -> Self {
+        self.client_ca_path = Some(ca_path.into());
+        self
+    }
+
+    /// Load the files and build the rustls configuration
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - A file could not be read or parsed, or the key does not
+    ///   match the certificate
+    pub fn build(&self) -> Result<rustls::ServerConfig> {
+        let certs = load_certs(&self.cert_path)?;
+        let key = load_key(&self.key_path)?;
+        self.config_builder()?
+            .with_single_cert(certs, key)
+            .map_err(config_error)
+    }
+
+    /// Build a configuration whose certificate can be replaced while the server runs
+    ///
+    /// The returned resolver reloads `cert_path` and `key_path`; see
+    /// [`ReloadableCertResolver`].
+    ///
+    /// # Errors
+    ///
+    /// Same as [`build`](Self::build)
+    pub fn build_reloadable(&self) -> Result<(rustls::ServerConfig, Arc<ReloadableCertResolver>)> {
+        let resolver = Arc::new(ReloadableCertResolver::new(
+            &self.cert_path,
+            &self.key_path,
+        )?);
+        let config = self.config_builder()?.with_cert_resolver(resolver.clone());
+        Ok((config, resolver))
+    }
+
+    fn config_builder(
+        &self,
+    ) -> Result<rustls::ConfigBuilder<rustls::ServerConfig, rustls::server::WantsServerCert>> {
+        Ok(match &self.client_ca_path {
+            Some(ca_path) => {
+                let roots = Arc::new(load_roots(ca_path)?);
+                let verifier = rustls::server::WebPkiClientVerifier::builder(roots)
+                    .build()
+                    .map_err(config_error)?;
+                rustls::ServerConfig::builder().with_client_cert_verifier(verifier)
+            }
+            None => rustls::ServerConfig::builder().with_no_client_auth(),
+        })
+    }
+}
+
+/// Server certificate resolver that reloads its certificate and key from PEM files
+///
+/// Only new handshakes use a reloaded certificate; established sessions are not
+/// affected. A failed reload keeps the previous certificate.
+///
+/// # Examples
+///
+/// ```no_run
+/// use openigtlink_rust::io::tls::ServerTlsConfig;
+/// use openigtlink_rust::io::TlsIgtlServer;
+/// use std::time::Duration;
+///
+/// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+/// let config = ServerTlsConfig::new("/etc/igtl/cert.pem", "/etc/igtl/key.pem");
+/// let server = TlsIgtlServer::bind_reloadable("0.0.0.0:18944", &config).await?;
+///
+/// // Pick up rotated certificates within a minute
+/// let resolver = server.cert_resolver().unwrap();
+/// resolver.watch(Duration::from_secs(60));
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct ReloadableCertResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    current: RwLock<Arc<CertifiedKey>>,
+    /// Modification times of the loaded certificate and key files
+    loaded_mtimes: Mutex<(Option<SystemTime>, Option<SystemTime>)>,
+}
+
+impl ReloadableCertResolver {
+    /// Load the certificate chain and key from PEM files
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - A file could not be read or parsed, or the key does not
+    ///   match the certificate
+    pub fn new(cert_path: impl Into<PathBuf>, key_path: impl Into<PathBuf>) -> Result<Self> {
+        let cert_path = cert_path.into();
+        let key_path = key_path.into();
+        let mtimes = (modified(&cert_path), modified(&key_path));
+        let key = load_certified_key(&cert_path, &key_path)?;
+        Ok(Self {
+            cert_path,
+            key_path,
+            current: RwLock::new(Arc::new(key)),
+            loaded_mtimes: Mutex::new(mtimes),
+        })
+    }
+
+    /// Reload the certificate and key now
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - The new files are invalid; the previous certificate stays
+    ///   in use
+    pub fn reload(&self) -> Result<()> {
+        let mtimes = (modified(&self.cert_path), modified(&self.key_path));
+        let key = load_certified_key(&self.cert_path, &self.key_path)?;
+        *self.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(key);
+        *self.loaded_mtimes.lock().unwrap_or_else(|e| e.into_inner()) = mtimes;
+        info!(cert = %self.cert_path.display(), "TLS certificate reloaded");
+        Ok(())
+    }
+
+    /// Reload if the certificate or key file was modified since the last load
+    ///
+    /// # Returns
+    ///
+    /// `true` if the certificate was reloaded
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let mtimes = (modified(&self.cert_path), modified(&self.key_path));
+        if mtimes == *self.loaded_mtimes.lock().unwrap_or_else(|e| e.into_inner()) {
+            return Ok(false);
+        }
+        self.reload()?;
+        Ok(true)
+    }
+
+    /// Check the files for modifications every `interval` on a background task
+    ///
+    /// Failed reloads are logged and retried at the next change. The task runs until it
+    /// is aborted through the returned handle. Requires a Tokio runtime; blocking
+    /// servers can call [`reload_if_changed`](Self::reload_if_changed) periodically
+    /// instead.
+    pub fn watch(self: &Arc<Self>, interval: Duration) -> JoinHandle<()> {
+        let resolver = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = resolver.reload_if_changed() {
+                    warn!(error = %e, "TLS certificate reload failed");
+                    // Retry only once the files change again
+                    *resolver
+                        .loaded_mtimes
+                        .lock()
+                        .unwrap_or_else(|e| e.into_inner()) =
+                        (modified(&resolver.cert_path), modified(&resolver.key_path));
+                }
+            }
+        })
+    }
+
+    /// Certificate chain currently served
+    pub fn certificates(&self) -> Vec<CertificateDer<'static>> {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .cert
+            .clone()
+    }
+}
+
+impl ResolvesServerCert for ReloadableCertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.current
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        )
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey> {
+    let certs = load_certs(cert_path)?;
+    if certs.is_empty() {
+        return Err(config_error(format!(
+            "no certificate found in {}",
+            cert_path.display()
+        )));
+    }
+    CertifiedKey::from_der(certs, load_key(key_path)?, &crypto_provider()).map_err(config_error)
+}
+
+fn crypto_provider() -> Arc<CryptoProvider> {
+    CryptoProvider::get_default()
+        .cloned()
+        .unwrap_or_else(|| Arc::new(rustls::crypto::aws_lc_rs::default_provider()))
+}
+
+/// Client-side TLS settings loaded from PEM files
+#[derive(Debug, Clone, Default)]
+pub struct ClientTlsConfig {
+    /// PEM bundle of trusted CAs (None = the platform's native root store)
+    pub ca_path: Option<PathBuf>,
+    /// PEM file with the client certificate chain presented to the server
+    pub cert_path: Option<PathBuf>,
+    /// PEM file with the client private key
+    pub key_path: Option<PathBuf>,
+    /// Accepted server certificates; when not empty, CA trust is not used
+    pub pins: Vec<CertificatePin>,
+}
+
+impl ClientTlsConfig {
+    /// Trust the platform's native root store and present no client certificate
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Trust only the CAs in `ca_path`
+    pub fn with_ca(mut self, ca_path: impl Into<PathBuf>) -> Self {
+        self.ca_path = Some(ca_path.into());
+        self
+    }
+
+    /// Accept only server certificates matching `pin` (may be called several times)
+    ///
+    /// With pins, the server is authenticated by its key alone: CA bundles and the
+    /// platform root store are not consulted and the hostname is not checked.
+    pub fn with_pin(mut self, pin: CertificatePin) -> Self {
+        self.pins.push(pin);
+        self
+    }
+
+    /// Present the certificate chain in `cert_path`, signed with the key in `key_path`
+    pub fn with_client_cert(
+        mut self,
+        cert_path: impl Into<PathBuf>,
+        key_path: impl Into<PathBuf>,
+    ) -> Self {
+        self.cert_path = Some(cert_path.into());
+        self.key_path = Some(key_path.into());
+        self
+    }
+
+    /// Load the files and build the rustls configuration
+    ///
+    /// The result can be passed to
+    /// [`ClientBuilder::with_tls`](crate::io::builder::ClientBuilder).
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - A file could not be read or parsed, or the key does not
+    ///   match the certificate
+    pub fn build(&self) -> Result<Arc<rustls::ClientConfig>> {
+        let builder = if !self.pins.is_empty() {
+            rustls::ClientConfig::builder()
+                .dangerous()
+                .with_custom_certificate_verifier(Arc::new(PinnedCertVerifier::new(
+                    self.pins.clone(),
+                )))
+        } else {
+            let roots = match &self.ca_path {
+                Some(ca_path) => load_roots(ca_path)?,
+                None => native_roots()?,
+            };
+            rustls::ClientConfig::builder().with_root_certificates(roots)
+        };
+
+        let config = match (&self.cert_path, &self.key_path) {
+            (Some(cert_path), Some(key_path)) => builder
+                .with_client_auth_cert(load_certs(cert_path)?, load_key(key_path)?)
+                .map_err(config_error)?,
+            (None, None) => builder.with_no_client_auth(),
+            _ => {
+                return Err(IgtlError::Io(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "Client certificate and key must be given together",
+                )))
+            }
+        };
+        Ok(Arc::new(config))
+    }
+}
+
+/// SHA-256 pin of a server certificate
+///
+/// SPKI pins survive certificate renewals that keep the key pair; certificate pins
+/// match one exact certificate.
+///
+/// # Examples
+///
+/// ```
+/// use openigtlink_rust::io::tls::CertificatePin;
+///
+/// // As printed by `openssl x509 -noout -fingerprint -sha256`
+/// let pin = CertificatePin::certificate_hex(
+///     "26:B5:80:A3:70:9C:29:2B:4B:90:D1:2C:71:05:9E:B7:3B:7C:95:F0:19:75:D4:2F:78:D8:44:DE:F8:03:9C:9B",
+/// )?;
+/// # Ok::<(), openigtlink_rust::error::IgtlError>(())
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificatePin {
+    /// SHA-256 of the DER-encoded SubjectPublicKeyInfo
+    Spki([u8; 32]),
+    /// SHA-256 of the DER-encoded certificate
+    Certificate([u8; 32]),
+}
+
+impl CertificatePin {
+    /// SPKI pin from hex (colons and whitespace are ignored)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] with [`InvalidInput`](std::io::ErrorKind::InvalidInput) - Not
+    ///   32 hex-encoded bytes
+    pub fn spki_hex(hex: &str) -> Result<Self> {
+        Ok(CertificatePin::Spki(parse_sha256_hex(hex)?))
+    }
+
+    /// Certificate fingerprint pin from hex (colons and whitespace are ignored)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] with [`InvalidInput`](std::io::ErrorKind::InvalidInput) - Not
+    ///   32 hex-encoded bytes
+    pub fn certificate_hex(hex: &str) -> Result<Self> {
+        Ok(CertificatePin::Certificate(parse_sha256_hex(hex)?))
+    }
+
+    /// Certificate fingerprint pin of the first certificate in a PEM file
+    ///
+    /// Lets clients trust a self-signed server certificate distributed to them.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] - The file could not be read or holds no certificate
+    pub fn from_pem_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let cert = load_certs(path)?.into_iter().next().ok_or_else(|| {
+            IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("No certificate found in {}", path.display()),
+            ))
+        })?;
+        Ok(CertificatePin::Certificate(
+            Sha256::digest(cert.as_ref()).into(),
+        ))
+    }
+
+    /// Whether a DER-encoded certificate matches this pin
+    pub fn matches(&self, cert: &CertificateDer<'_>) -> bool {
+        match self {
+            CertificatePin::Certificate(hash) => {
+                <[u8; 32]>::from(Sha256::digest(cert.as_ref())) == *hash
+            }
+            CertificatePin::Spki(hash) => match X509Certificate::from_der(cert.as_ref()) {
+                Ok((_, parsed)) => {
+                    <[u8; 32]>::from(Sha256::digest(parsed.public_key().raw)) == *hash
+                }
+                Err(_) => false,
+            },
+        }
+    }
+}
+
+fn parse_sha256_hex(hex: &str) -> Result<[u8; 32]> {
+    let digits: Vec<u8> = hex
+        .bytes()
+        .filter(|b| *b != b':' && !b.is_ascii_whitespace())
+        .collect();
+    let invalid = || config_error(format!("invalid SHA-256 pin: {}", hex));
+    if digits.len() != 64 {
+        return Err(invalid());
+    }
+    let mut hash = [0u8; 32];
+    for (byte, pair) in hash.iter_mut().zip(digits.chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(hash)
+}
+
+/// Server certificate verifier accepting only pinned certificates
+///
+/// Used by [`ClientTlsConfig::with_pin`]; handshake signatures are still verified.
+#[derive(Debug)]
+pub struct PinnedCertVerifier {
+    pins: Vec<CertificatePin>,
+    algorithms: WebPkiSupportedAlgorithms,
+}
+
+impl PinnedCertVerifier {
+    /// Accept server certificates matching any of `pins`
+    pub fn new(pins: Vec<CertificatePin>) -> Self {
+        Self {
+            pins,
+            algorithms: crypto_provider().signature_verification_algorithms,
+        }
+    }
+}
+
+impl ServerCertVerifier for PinnedCertVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> std::result::Result<ServerCertVerified, rustls::Error> {
+        if self.pins.iter().any(|pin| pin.matches(end_entity)) {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            warn!("Server certificate does not match any pin");
+            Err(rustls::Error::InvalidCertificate(
+                rustls::CertificateError::ApplicationVerificationFailure,
+            ))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> std::result::Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(message, cert, dss, &self.algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.algorithms.supported_schemes()
+    }
+}
+
+/// Subject alternative name of a certificate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubjectAltName {
+    /// DNS name
+    Dns(String),
+    /// IP address
+    Ip(IpAddr),
+    /// E-mail address
+    Email(String),
+    /// URI
+    Uri(String),
+}
+
+impl fmt::Display for SubjectAltName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubjectAltName::Dns(name) => write!(f, "DNS:{}", name),
+            SubjectAltName::Ip(ip) => write!(f, "IP:{}", ip),
+            SubjectAltName::Email(email) => write!(f, "email:{}", email),
+            SubjectAltName::Uri(uri) => write!(f, "URI:{}", uri),
+        }
+    }
+}
+
+/// Identity of a verified TLS peer
+#[derive(Debug, Clone)]
+pub struct PeerCertificate {
+    der: CertificateDer<'static>,
+    subject: String,
+    subject_alt_names: Vec<SubjectAltName>,
+    fingerprint: [u8; 32],
+    spki_fingerprint: [u8; 32],
+}
+
+impl PeerCertificate {
+    /// Parse a DER-encoded X.509 certificate
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`] with [`InvalidData`](std::io::ErrorKind::InvalidData) - Not a
+    ///   valid certificate
+    pub fn from_der(der: CertificateDer<'static>) -> Result<Self> {
+        let (_, cert) = X509Certificate::from_der(der.as_ref()).map_err(|e| {
+            IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Invalid peer certificate: {}", e),
+            ))
+        })?;
+
+        let subject_alt_names = match cert.subject_alternative_name() {
+            Ok(Some(ext)) => ext
+                .value
+                .general_names
+                .iter()
+                .filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(SubjectAltName::Dns(dns.to_string())),
+                    GeneralName::RFC822Name(email) => {
+                        Some(SubjectAltName::Email(email.to_string()))
+                    }
+                    GeneralName::URI(uri) => Some(SubjectAltName::Uri(uri.to_string())),
+                    GeneralName::IPAddress(bytes) => ip_from_bytes(bytes).map(SubjectAltName::Ip),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let subject = cert.subject().to_string();
+        let spki_fingerprint = Sha256::digest(cert.public_key().raw).into();
+
+        Ok(Self {
+            fingerprint: Sha256::digest(der.as_ref()).into(),
+            spki_fingerprint,
+            der,
+            subject,
+            subject_alt_names,
+        })
+    }
+
+    /// Subject distinguished name (e.g. `"C=US, O=Hospital, CN=tracker-01"`)
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    /// Subject alternative names
+    pub fn subject_alt_names(&self) -> &[SubjectAltName] {
+        &self.subject_alt_names
+    }
+
+    /// SHA-256 fingerprint of the DER-encoded certificate
+    pub fn fingerprint(&self) -> [u8; 32] {
+        self.fingerprint
+    }
+
+    /// SHA-256 fingerprint as colon-separated uppercase hex, as printed by
+    /// `openssl x509 -fingerprint -sha256`
+    pub fn fingerprint_hex(&self) -> String {
+        self.fingerprint
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// SHA-256 of the certificate's SubjectPublicKeyInfo, usable as
+    /// [`CertificatePin::Spki`]
+    pub fn spki_fingerprint(&self) -> [u8; 32] {
+        self.spki_fingerprint
+    }
+
+    /// DER-encoded certificate
+    pub fn der(&self) -> &[u8] {
+        self.der.as_ref()
+    }
+}
+
+/// Identity of the end-entity certificate presented by the peer of a connection
+pub(crate) fn peer_certificate(
+    certs: Option<&[CertificateDer<'static>]>,
+) -> Option<PeerCertificate> {
+    let der = certs?.first()?.clone();
+    match PeerCertificate::from_der(der) {
+        Ok(cert) => Some(cert),
+        Err(e) => {
+            warn!(error = %e, "Failed to parse peer certificate");
+            None
+        }
+    }
+}
+
+/// Load a PEM certificate chain
+pub(crate) fn load_certs(path: impl AsRef<Path>) -> Result<Vec<CertificateDer<'static>>> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| {
+        IgtlError::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to open certificate file {}: {}", path.display(), e),
+        ))
+    })?;
+    let mut reader = BufReader::new(file);
+
+    rustls_pemfile::certs(&mut reader)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|e| {
+            IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Failed to parse certificates: {}", e),
+            ))
+        })
+}
+
+/// Load a PEM private key (PKCS#8, PKCS#1 or SEC1)
+pub(crate) fn load_key(path: impl AsRef<Path>) -> Result<PrivateKeyDer<'static>> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|e| {
+        IgtlError::Io(std::io::Error::new(
+            e.kind(),
+            format!("Failed to open key file {}: {}", path.display(), e),
+        ))
+    })?;
+    let mut reader = BufReader::new(file);
+
+    rustls_pemfile::private_key(&mut reader)
+        .map_err(|e| {
+            IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("Failed to parse private key: {}", e),
+            ))
+        })?
+        .ok_or_else(|| {
+            IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "No private key found in file",
+            ))
+        })
+}
+
+/// Load a PEM CA bundle into a root store
+fn load_roots(path: impl AsRef<Path>) -> Result<rustls::RootCertStore> {
+    let mut roots = rustls::RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(config_error)?;
+    }
+    Ok(roots)
+}
+
+/// The platform's native root store
+fn native_roots() -> Result<rustls::RootCertStore> {
+    let mut roots = rustls::RootCertStore::empty();
+    let result = rustls_native_certs::load_native_certs();
+    for error in &result.errors {
+        warn!(error = %error, "Failed to load a native root certificate");
+    }
+    roots.add_parsable_certificates(result.certs);
+    Ok(roots)
+}
+
+fn ip_from_bytes(bytes: &[u8]) -> Option<IpAddr> {
+    match bytes.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(bytes).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(bytes).ok()?)),
+        _ => None,
+    }
+}
+
+fn config_error(e: impl fmt::Display) -> IgtlError {
+    IgtlError::Io(std::io::Error::new(
+        std::io::ErrorKind::InvalidInput,
+        format!("TLS config error: {}", e),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_cert_requires_key() {
+        let config = ClientTlsConfig {
+            cert_path: Some("client.pem".into()),
+            ..ClientTlsConfig::new().with_ca("examples/certs/cert.pem")
+        };
+        assert!(config.build().is_err());
+    }
+
+    #[test]
+    fn test_peer_certificate_from_der() {
+        let der = load_certs("examples/certs/cert.pem").unwrap().remove(0);
+        let cert = PeerCertificate::from_der(der.clone()).unwrap();
+        assert!(cert.subject().contains("CN=localhost"));
+        assert_eq!(cert.der(), der.as_ref());
+        assert_eq!(cert.fingerprint_hex().len(), 32 * 3 - 1);
+        assert!(PeerCertificate::from_der(CertificateDer::from(vec![0u8; 4])).is_err());
+    }
+
+    #[test]
+    fn test_certificate_pins() {
+        let der = load_certs("examples/certs/cert.pem").unwrap().remove(0);
+        let cert = PeerCertificate::from_der(der.clone()).unwrap();
+
+        let by_cert = CertificatePin::certificate_hex(&cert.fingerprint_hex()).unwrap();
+        assert!(by_cert.matches(&der));
+        assert!(CertificatePin::Spki(cert.spki_fingerprint()).matches(&der));
+        assert!(!CertificatePin::Spki(cert.fingerprint()).matches(&der));
+
+        assert!(CertificatePin::spki_hex("abcd").is_err());
+        assert!(CertificatePin::spki_hex(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_reload_if_changed() {
+        let dir = std::env::temp_dir().join(format!("igtl-reload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (cert, key) = (dir.join("cert.pem"), dir.join("key.pem"));
+        std::fs::copy("examples/certs/cert.pem", &cert).unwrap();
+        std::fs::copy("examples/certs/key.pem", &key).unwrap();
+
+        let resolver = ReloadableCertResolver::new(&cert, &key).unwrap();
+        assert!(!resolver.reload_if_changed().unwrap());
+
+        // A broken file keeps the previous certificate
+        std::fs::write(&cert, "not a certificate").unwrap();
+        assert!(resolver.reload().is_err());
+        assert_eq!(resolver.certificates().len(), 1);
+
+        std::fs::copy("examples/certs/cert.pem", &cert).unwrap();
+        let file = std::fs::File::options().write(true).open(&cert).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(1))
+            .unwrap();
+        assert!(resolver.reload_if_changed().unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_subject_alt_name_display() {
+        assert_eq!(
+            SubjectAltName::Ip("127.0.0.1".parse().unwrap()).to_string(),
+            "IP:127.0.0.1"
+        );
+        assert_eq!(
+            SubjectAltName::Dns("tracker.local".into()).to_string(),
+            "DNS:tracker.local"
+        );
+    }
+}
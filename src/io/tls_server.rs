@@ -2,15 +2,23 @@
 //!
 //! Provides secure server with TLS/SSL encryption.
 
-use crate::error::{IgtlError, Result};
-use crate::protocol::header::Header;
+use crate::error::Result;
+use crate::io::events::{ConnectionEvent, EventEmitter};
+use crate::io::handshake::{with_deadline, Handshakes};
+use crate::io::limits::{admit_session, ServerLimits, SessionGovernor, SessionTracker};
+use crate::io::liveness::{set_tcp_keepalive, Liveness, LivenessConfig};
+use crate::io::shutdown::{
+    close_session, run_until, shutdown_error, CancellationToken, Interrupted, ShutdownConfig,
+    ShutdownSignal,
+};
+use crate::io::stream::AsyncIgtlStream;
+use crate::io::tls::{peer_certificate, PeerCertificate, ReloadableCertResolver, ServerTlsConfig};
 use crate::protocol::message::{IgtlMessage, Message};
-use rustls::pki_types::{CertificateDer, PrivateKeyDer};
-use std::fs::File;
-use std::io::BufReader;
+use std::net::SocketAddr;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::time::Duration;
 use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
 use tokio_rustls::server::TlsStream;
 use tokio_rustls::{rustls, TlsAcceptor};
 use tracing::{debug, info, trace, warn};
@@ -39,6 +47,12 @@ use tracing::{debug, info, trace, warn};
 pub struct TlsIgtlServer {
     listener: TcpListener,
     acceptor: TlsAcceptor,
+    cert_resolver: Option<Arc<ReloadableCertResolver>>,
+    events: EventEmitter,
+    limits: ServerLimits,
+    sessions: Arc<SessionTracker>,
+    shutdown: Arc<ShutdownSignal>,
+    handshakes: Handshakes<TlsIgtlConnection>,
 }
 
 impl TlsIgtlServer {
@@ -61,20 +75,7 @@ impl TlsIgtlServer {
             "Binding TLS-enabled OpenIGTLink server"
         );
 
-        // Load certificates
-        let certs = Self::load_certs(cert_path)?;
-        let key = Self::load_key(key_path)?;
-
-        // Create TLS config
-        let config = rustls::ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(certs, key)
-            .map_err(|e| {
-                IgtlError::Io(std::io::Error::new(
-                    std::io::ErrorKind::InvalidInput,
-                    format!("TLS config error: {}", e),
-                ))
-            })?;
+        let config = ServerTlsConfig::new(cert_path, key_path).build()?;
 
         let acceptor = TlsAcceptor::from(Arc::new(config));
         let listener = TcpListener::bind(addr).await?;
@@ -85,12 +86,22 @@ impl TlsIgtlServer {
             "TLS server listening"
         );
 
-        Ok(TlsIgtlServer { listener, acceptor })
+        Ok(TlsIgtlServer {
+            listener,
+            acceptor,
+            cert_resolver: None,
+            events: EventEmitter::default(),
+            limits: ServerLimits::default(),
+            sessions: Arc::default(),
+            shutdown: Arc::default(),
+            handshakes: Handshakes::default(),
+        })
     }
 
     /// Bind with custom TLS configuration
     ///
     /// Allows advanced configuration like client authentication, cipher suites, etc.
+    /// See [`ServerTlsConfig`] for requiring client certificates.
     pub async fn bind_with_config(addr: &str, config: rustls::ServerConfig) -> Result<Self> {
         info!(addr = addr, "Binding TLS server with custom config");
 
@@ -99,33 +110,159 @@ impl TlsIgtlServer {
 
         info!("TLS server listening with custom config");
 
-        Ok(TlsIgtlServer { listener, acceptor })
+        Ok(TlsIgtlServer {
+            listener,
+            acceptor,
+            cert_resolver: None,
+            events: EventEmitter::default(),
+            limits: ServerLimits::default(),
+            sessions: Arc::default(),
+            shutdown: Arc::default(),
+            handshakes: Handshakes::default(),
+        })
+    }
+
+    /// Bind with a certificate that can be reloaded while the server runs
+    ///
+    /// Use [`cert_resolver`](Self::cert_resolver) to reload explicitly or to watch the
+    /// files. Established sessions keep their certificate.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind or load certificates
+    pub async fn bind_reloadable(addr: &str, config: &ServerTlsConfig) -> Result<Self> {
+        let (config, resolver) = config.build_reloadable()?;
+        let mut server = Self::bind_with_config(addr, config).await?;
+        server.cert_resolver = Some(resolver);
+        Ok(server)
+    }
+
+    /// Certificate resolver of a server created with [`bind_reloadable`](Self::bind_reloadable)
+    pub fn cert_resolver(&self) -> Option<&Arc<ReloadableCertResolver>> {
+        self.cert_resolver.as_ref()
     }
 
     /// Accept a new TLS client connection
     ///
-    /// Performs TCP accept followed by TLS handshake.
+    /// Performs TCP accept followed by TLS handshake. Handshakes run concurrently within
+    /// the first-message deadline of the [`ServerLimits`], or
+    /// [`DEFAULT_HANDSHAKE_TIMEOUT`](crate::io::limits::DEFAULT_HANDSHAKE_TIMEOUT) if none
+    /// is set; failed or timed out handshakes are logged, reported as
+    /// [`ConnectionEvent::TlsHandshakeFailed`] and skipped. Fails with
+    /// [`ConnectionAborted`](std::io::ErrorKind::ConnectionAborted) once the server is
+    /// [shut down](Self::shutdown).
     pub async fn accept(&self) -> Result<TlsIgtlConnection> {
+        self.accept_until(&CancellationToken::new())
+            .await?
+            .ok_or_else(shutdown_error)
+    }
+
+    /// Accept a new TLS client connection unless `token` is cancelled first
+    ///
+    /// Returns `Ok(None)` once `token` is cancelled or the server is
+    /// [shut down](Self::shutdown). Handshakes in progress continue on the next call.
+    pub async fn accept_until(
+        &self,
+        token: &CancellationToken,
+    ) -> Result<Option<TlsIgtlConnection>> {
         trace!("Waiting for TLS client connection");
+        let accept = self.handshakes.next(&self.listener, |tcp_stream, addr| {
+            let session = admit_session(&self.sessions, &self.limits, &self.events, addr)?;
+            debug!(peer_addr = %addr, "TCP connection accepted, starting TLS handshake");
+            let acceptor = self.acceptor.clone();
+            let events = self.events.clone();
+            let shutdown = Arc::clone(&self.shutdown);
+            Some(Box::pin(async move {
+                let handshake = acceptor.accept(tcp_stream);
+                let tls_stream = match with_deadline(session.handshake_deadline(), handshake).await
+                {
+                    Ok(tls_stream) => tls_stream,
+                    Err(e) => {
+                        warn!(error = %e, peer_addr = %addr, "TLS handshake failed");
+                        events.emit(ConnectionEvent::TlsHandshakeFailed {
+                            peer: Some(addr),
+                            error: e.to_string(),
+                        });
+                        return None;
+                    }
+                };
+
+                let peer_certificate = peer_certificate(tls_stream.get_ref().1.peer_certificates());
+                info!(
+                    peer_addr = %addr,
+                    peer_subject = peer_certificate.as_ref().map(|c| c.subject()),
+                    "TLS client connected"
+                );
+                events.emit(ConnectionEvent::Connected { peer: addr });
+                Some(TlsIgtlConnection {
+                    inner: AsyncIgtlStream::new(tls_stream),
+                    peer_certificate,
+                    peer: addr,
+                    events: events.connection(Some(addr)),
+                    session,
+                    shutdown,
+                })
+            }))
+        });
+        match run_until(accept, &self.shutdown, Some(token)).await {
+            Ok(connection) => Ok(Some(connection?)),
+            Err(_) => {
+                debug!("TLS accept cancelled");
+                Ok(None)
+            }
+        }
+    }
 
-        let (tcp_stream, addr) = self.listener.accept().await?;
+    /// Stop accepting and close all sessions, waiting up to the drain deadline
+    ///
+    /// Each session sends the STATUS notice of `config` and a TLS `close_notify` alert
+    /// as soon as it is receiving or next sends; see [`shutdown`](crate::io::shutdown).
+    ///
+    /// # Returns
+    ///
+    /// Number of sessions still open when the drain deadline passed
+    pub async fn shutdown(&self, config: ShutdownConfig) -> usize {
+        let drain_timeout = config.drain_timeout;
+        info!(
+            sessions = self.sessions.active(),
+            "Shutting down TLS server"
+        );
+        self.shutdown.begin(config);
+        self.handshakes.clear().await;
+        let _ = tokio::time::timeout(drain_timeout, self.sessions.wait_idle()).await;
+        let remaining = self.sessions.active();
+        if remaining > 0 {
+            warn!(
+                remaining,
+                "TLS sessions still open after shutdown drain deadline"
+            );
+        }
+        remaining
+    }
 
-        debug!(peer_addr = %addr, "TCP connection accepted, starting TLS handshake");
+    /// Token cancelled when [`shutdown`](Self::shutdown) starts
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.token().clone()
+    }
 
-        let tls_stream = self.acceptor.accept(tcp_stream).await.map_err(|e| {
-            warn!(error = %e, peer_addr = %addr, "TLS handshake failed");
-            IgtlError::Io(std::io::Error::new(
-                std::io::ErrorKind::ConnectionRefused,
-                format!("TLS handshake failed: {}", e),
-            ))
-        })?;
+    /// Apply connection and resource limits to sessions accepted from now on
+    ///
+    /// The first-message deadline also bounds the TLS handshake, which is otherwise
+    /// limited to [`DEFAULT_HANDSHAKE_TIMEOUT`](crate::io::limits::DEFAULT_HANDSHAKE_TIMEOUT). See
+    /// [`limits`](crate::io::limits).
+    pub fn with_limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
 
-        info!(peer_addr = %addr, "TLS client connected");
+    /// Limits applied to accepted sessions
+    pub fn limits(&self) -> &ServerLimits {
+        &self.limits
+    }
 
-        Ok(TlsIgtlConnection {
-            stream: tls_stream,
-            verify_crc: true,
-        })
+    /// Number of accepted sessions that are still open
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.active()
     }
 
     /// Get the local address this server is bound to
@@ -133,157 +270,177 @@ impl TlsIgtlServer {
         Ok(self.listener.local_addr()?)
     }
 
-    // Helper: Load certificates from PEM file
-    fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>> {
-        let file = File::open(path).map_err(|e| {
-            IgtlError::Io(std::io::Error::new(
-                e.kind(),
-                format!("Failed to open certificate file {}: {}", path, e),
-            ))
-        })?;
-        let mut reader = BufReader::new(file);
-
-        rustls_pemfile::certs(&mut reader)
-            .collect::<std::result::Result<Vec<_>, _>>()
-            .map_err(|e| {
-                IgtlError::Io(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Failed to parse certificates: {}", e),
-                ))
-            })
-    }
-
-    // Helper: Load private key from PEM file
-    fn load_key(path: &str) -> Result<PrivateKeyDer<'static>> {
-        let file = File::open(path).map_err(|e| {
-            IgtlError::Io(std::io::Error::new(
-                e.kind(),
-                format!("Failed to open key file {}: {}", path, e),
-            ))
-        })?;
-        let mut reader = BufReader::new(file);
-
-        // Try PKCS8 first, then RSA
-        rustls_pemfile::private_key(&mut reader)
-            .map_err(|e| {
-                IgtlError::Io(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    format!("Failed to parse private key: {}", e),
-                ))
-            })?
-            .ok_or_else(|| {
-                IgtlError::Io(std::io::Error::new(
-                    std::io::ErrorKind::InvalidData,
-                    "No private key found in file",
-                ))
-            })
+    /// Subscribe to lifecycle events of all sessions accepted by this server
+    ///
+    /// Also reports rejected connections and failed TLS handshakes. See [`events`](crate::io::events).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
     }
 }
 
 /// TLS-encrypted client connection
 pub struct TlsIgtlConnection {
-    stream: TlsStream<TcpStream>,
-    verify_crc: bool,
+    inner: AsyncIgtlStream<TlsStream<TcpStream>>,
+    peer_certificate: Option<PeerCertificate>,
+    peer: SocketAddr,
+    events: EventEmitter,
+    session: SessionGovernor,
+    shutdown: Arc<ShutdownSignal>,
 }
 
 impl TlsIgtlConnection {
     /// Enable or disable CRC verification
     pub fn set_verify_crc(&mut self, verify: bool) {
-        if verify != self.verify_crc {
-            info!(verify = verify, "CRC verification setting changed");
-            if !verify {
-                warn!("CRC verification disabled");
-            }
-        }
-        self.verify_crc = verify;
+        self.inner.set_verify_crc(verify);
     }
 
     /// Get current CRC verification setting
     pub fn verify_crc(&self) -> bool {
-        self.verify_crc
+        self.inner.verify_crc()
     }
 
     /// Send a message over TLS
     pub async fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
-        let data = msg.encode()?;
-        let msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN");
-        let device_name = msg.header.device_name.as_str().unwrap_or("UNKNOWN");
-
-        debug!(
-            msg_type = msg_type,
-            device_name = device_name,
-            size = data.len(),
-            "Sending message to TLS client"
-        );
-
-        self.stream.write_all(&data).await?;
-        self.stream.flush().await?;
-
-        trace!(
-            msg_type = msg_type,
-            bytes_sent = data.len(),
-            "Message sent to TLS client"
-        );
-
-        Ok(())
+        self.check_open().await?;
+        let result = self.inner.send(msg).await;
+        self.events.check_result(&result, Some(self.peer));
+        result
     }
 
     /// Receive a message over TLS
     pub async fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
-        trace!("Waiting for message header from TLS client");
-
-        let mut header_buf = vec![0u8; Header::SIZE];
-        self.stream.read_exact(&mut header_buf).await?;
+        let frame = self.read_frame(None).await?.ok_or_else(shutdown_error)?;
+        let result = IgtlMessage::decode_with_options(&frame, self.inner.verify_crc());
+        self.events.check_decode(&result, Some(self.peer));
+        result
+    }
 
-        let header = Header::decode(&header_buf)?;
+    /// Receive a message unless `token` is cancelled first
+    ///
+    /// Returns `Ok(None)` once `token` is cancelled, keeping partially received data,
+    /// or after closing the session because the server shuts down.
+    pub async fn receive_until<T: Message>(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<IgtlMessage<T>>> {
+        let Some(frame) = self.read_frame(Some(token)).await? else {
+            return Ok(None);
+        };
+        let result = IgtlMessage::decode_with_options(&frame, self.inner.verify_crc());
+        self.events.check_decode(&result, Some(self.peer));
+        result.map(Some)
+    }
 
-        let msg_type = header.type_name.as_str().unwrap_or("UNKNOWN");
-        let device_name = header.device_name.as_str().unwrap_or("UNKNOWN");
+    /// Flush and close the connection, sending a TLS `close_notify` alert
+    ///
+    /// Later sends and receives fail with [`NotConnected`](std::io::ErrorKind::NotConnected).
+    pub async fn close(&mut self) -> Result<()> {
+        self.session.check_open()?;
+        self.session.close("closed by the server");
+        drop(self.session.take_slot());
+        self.events
+            .disconnected(Some(self.peer), &"closed by the server");
+        info!(peer_addr = %self.peer, "Closing TLS connection");
+        self.inner.close().await
+    }
 
-        debug!(
-            msg_type = msg_type,
-            device_name = device_name,
-            body_size = header.body_size,
-            "Received message header from TLS client"
-        );
+    /// Read one frame within the session limits, closing the session on a violation
+    /// or a server shutdown
+    ///
+    /// Returns `None` if interrupted by `token` or the shutdown.
+    async fn read_frame(&mut self, token: Option<&CancellationToken>) -> Result<Option<Vec<u8>>> {
+        self.check_open().await?;
+        let shutdown = Arc::clone(&self.shutdown);
+        let read = self.session.read_frame(self.inner.read_frame());
+        let result = match run_until(read, &shutdown, token).await {
+            Ok(result) => result,
+            Err(Interrupted::Cancelled) => return Ok(None),
+            Err(Interrupted::Shutdown) => {
+                self.close_for_shutdown().await;
+                return Ok(None);
+            }
+        };
+        if result.is_err() && self.inner.idle_expired() {
+            self.session
+                .close("no data received within the idle timeout");
+        }
+        if self.session.is_closed() {
+            let _ = self.inner.close().await;
+        }
+        self.events.check_result(&result, Some(self.peer));
+        result.map(Some)
+    }
 
-        let mut body_buf = vec![0u8; header.body_size as usize];
-        self.stream.read_exact(&mut body_buf).await?;
+    /// Fail if the session is closed, closing it first if the server shuts down
+    async fn check_open(&mut self) -> Result<()> {
+        if self.shutdown.is_shutting_down() && !self.session.is_closed() {
+            self.close_for_shutdown().await;
+            return Err(shutdown_error());
+        }
+        self.session.check_open()
+    }
 
-        trace!(
-            msg_type = msg_type,
-            bytes_read = body_buf.len(),
-            "Message body received from TLS client"
-        );
+    /// Notify the peer and close the session because the server shuts down
+    async fn close_for_shutdown(&mut self) {
+        self.session.close("server shutting down");
+        if let Err(e) = close_session(&mut self.inner, &self.shutdown.config()).await {
+            debug!(error = %e, peer_addr = %self.peer, "TLS session did not close cleanly");
+        }
+        self.events.disconnected(Some(self.peer), &shutdown_error());
+        drop(self.session.take_slot());
+    }
 
-        let mut full_msg = header_buf;
-        full_msg.extend_from_slice(&body_buf);
+    /// Set the deadline of each `receive` call (`None` waits forever, the default)
+    ///
+    /// An expired receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut). Partially
+    /// received data is kept, so the connection stays usable.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_read_timeout(timeout);
+    }
 
-        let result = IgtlMessage::decode_with_options(&full_msg, self.verify_crc);
+    /// Set the deadline of each `send` call (`None` waits forever, the default)
+    ///
+    /// An expired send fails with [`TimedOut`](std::io::ErrorKind::TimedOut). The rest of
+    /// the message stays queued and is written before the next one, so framing is kept.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.inner.set_write_timeout(timeout);
+    }
 
-        match &result {
-            Ok(_) => {
-                debug!(
-                    msg_type = msg_type,
-                    device_name = device_name,
-                    "Message decoded successfully"
-                );
-            }
-            Err(e) => {
-                warn!(
-                    msg_type = msg_type,
-                    error = %e,
-                    "Failed to decode message from TLS client"
-                );
-            }
-        }
+    /// Configure keepalive, heartbeats and timeouts for this connection
+    ///
+    /// Heartbeats and the idle timeout are serviced while waiting in `receive`. When the
+    /// idle timeout expires the receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut)
+    /// and the session is closed. See [`liveness`](crate::io::liveness).
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - TCP keepalive could not be configured
+    pub fn set_liveness(&mut self, config: LivenessConfig) -> Result<()> {
+        self.set_tcp_keepalive(config.tcp_keepalive)?;
+        self.inner.set_read_timeout(config.read_timeout);
+        self.inner.set_write_timeout(config.write_timeout);
+        self.inner.set_liveness(Liveness::new(&config));
+        Ok(())
+    }
 
-        result
+    /// Enable TCP keepalive probes after `idle` without traffic, or disable them with `None`
+    pub fn set_tcp_keepalive(&self, idle: Option<Duration>) -> Result<()> {
+        set_tcp_keepalive(self.inner.get_ref().get_ref().0, idle)?;
+        debug!(idle = ?idle, "TCP keepalive configured (TLS)");
+        Ok(())
     }
 
     /// Get the remote peer address
     pub fn peer_addr(&self) -> Result<std::net::SocketAddr> {
-        Ok(self.stream.get_ref().0.peer_addr()?)
+        Ok(self.peer)
+    }
+
+    /// Verified certificate presented by the client
+    ///
+    /// Only available when the server requires client certificates
+    /// (see [`ServerTlsConfig::with_client_auth`]).
+    pub fn peer_certificate(&self) -> Option<&PeerCertificate> {
+        self.peer_certificate.as_ref()
     }
 }
 
@@ -6,7 +6,8 @@
 //! # Important Notes
 //!
 //! - **No delivery guarantee**: UDP does not guarantee message delivery or ordering
-//! - **MTU limitation**: Single UDP datagram limited to ~65507 bytes
+//! - **MTU limitation**: Single UDP datagram limited to ~65507 bytes, unless
+//!   fragmentation is enabled (see [`udp_fragment`](crate::io::udp_fragment))
 //! - **Use cases**: High-frequency tracking data (>60Hz), non-critical status updates
 //! - **Not recommended for**: Large images, critical commands, file transfers
 //!
@@ -29,11 +30,17 @@
 //! # Ok::<(), openigtlink_rust::error::IgtlError>(())
 //! ```
 
+use std::collections::HashMap;
 use std::net::{SocketAddr, UdpSocket};
 use std::time::Duration;
 
 use crate::error::{IgtlError, Result};
+use crate::io::common::decode_any_frame;
+use crate::io::udp_fragment::{FragmentConfig, FragmentStats, Fragmentation};
+use crate::io::udp_sequence::{SequenceConfig, SequenceSource, SequenceStats, Sequencing};
+use crate::protocol::header::Header;
 use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::AnyMessage;
 
 /// Maximum UDP datagram size (IPv4 max - IP header - UDP header)
 /// 65535 (max IP packet) - 20 (IP header) - 8 (UDP header) = 65507 bytes
@@ -65,6 +72,7 @@ pub const MAX_UDP_DATAGRAM_SIZE: usize = 65507;
 /// ```
 pub struct UdpClient {
     socket: UdpSocket,
+    layers: DatagramLayers,
 }
 
 impl UdpClient {
@@ -92,7 +100,10 @@ impl UdpClient {
     /// ```
     pub fn bind(local_addr: &str) -> Result<Self> {
         let socket = UdpSocket::bind(local_addr)?;
-        Ok(UdpClient { socket })
+        Ok(UdpClient {
+            socket,
+            layers: DatagramLayers::default(),
+        })
     }
 
     /// Send a message to a remote address
@@ -105,7 +116,7 @@ impl UdpClient {
     /// # Errors
     ///
     /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network transmission failed
-    /// - [`IgtlError::BodyTooLarge`](crate::error::IgtlError::BodyTooLarge) - Message exceeds UDP MTU (65507 bytes)
+    /// - [`IgtlError::BodyTooLarge`](crate::error::IgtlError::BodyTooLarge) - Message exceeds UDP MTU (65507 bytes) and fragmentation is disabled
     ///
     /// # Examples
     ///
@@ -121,16 +132,9 @@ impl UdpClient {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn send_to<T: Message>(&self, msg: &IgtlMessage<T>, target: &str) -> Result<()> {
-        let data = msg.encode()?;
-
-        if data.len() > MAX_UDP_DATAGRAM_SIZE {
-            return Err(IgtlError::BodyTooLarge {
-                size: data.len(),
-                max: MAX_UDP_DATAGRAM_SIZE,
-            });
+        for datagram in self.layers.encode(msg)? {
+            self.socket.send_to(&datagram, target)?;
         }
-
-        self.socket.send_to(&data, target)?;
         Ok(())
     }
 
@@ -160,10 +164,25 @@ impl UdpClient {
     /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
     /// ```
     pub fn receive_from<T: Message>(&self) -> Result<(IgtlMessage<T>, SocketAddr)> {
-        let mut buf = vec![0u8; MAX_UDP_DATAGRAM_SIZE];
-        let (size, src) = self.socket.recv_from(&mut buf)?;
+        let (data, src) = self.recv_frame()?;
+        let msg = IgtlMessage::decode(&data)?;
+        Ok((msg, src))
+    }
 
-        let msg = IgtlMessage::decode(&buf[..size])?;
+    /// Receive a message of any type (blocking)
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (message, sender_address)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read failed or timeout
+    /// - [`IgtlError::InvalidSize`](crate::error::IgtlError::InvalidSize) - Datagram shorter than a header
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption detected
+    pub fn receive_any_from(&self) -> Result<(AnyMessage, SocketAddr)> {
+        let (data, src) = self.recv_frame()?;
+        let msg = decode_any_datagram(&data, true)?;
         Ok((msg, src))
     }
 
@@ -214,6 +233,45 @@ impl UdpClient {
     pub fn local_addr(&self) -> Result<SocketAddr> {
         Ok(self.socket.local_addr()?)
     }
+
+    /// Enable fragmentation of messages larger than one datagram
+    ///
+    /// Both endpoints must enable fragmentation to exchange large messages; small messages
+    /// are still sent as plain datagrams.
+    pub fn with_fragmentation(mut self, config: FragmentConfig) -> Self {
+        self.layers.fragmentation = Some(Fragmentation::new(config));
+        self
+    }
+
+    /// Fragmentation statistics (None if fragmentation is disabled)
+    pub fn fragment_stats(&self) -> Option<FragmentStats> {
+        self.layers.fragmentation.as_ref().map(Fragmentation::stats)
+    }
+
+    /// Stamp sent messages with per-device sequence numbers and track received ones
+    ///
+    /// See [`udp_sequence`](crate::io::udp_sequence). Sequencing overwrites the v3
+    /// message ID of sent messages.
+    pub fn with_sequencing(mut self, config: SequenceConfig) -> Self {
+        self.layers.sequencing = Some(Sequencing::new(config));
+        self
+    }
+
+    /// Per-source receive statistics (None if sequencing is disabled)
+    pub fn sequence_stats(&self) -> Option<HashMap<SequenceSource, SequenceStats>> {
+        self.layers.sequencing.as_ref().map(Sequencing::stats)
+    }
+
+    /// Receive datagrams until a complete message frame is available
+    fn recv_frame(&self) -> Result<(Vec<u8>, SocketAddr)> {
+        let mut buf = vec![0u8; MAX_UDP_DATAGRAM_SIZE];
+        loop {
+            let (size, src) = self.socket.recv_from(&mut buf)?;
+            if let Some(frame) = self.layers.receive(&buf[..size], src) {
+                return Ok((frame, src));
+            }
+        }
+    }
 }
 
 /// UDP server for receiving OpenIGTLink messages
@@ -247,6 +305,7 @@ impl UdpClient {
 /// ```
 pub struct UdpServer {
     socket: UdpSocket,
+    layers: DatagramLayers,
 }
 
 impl UdpServer {
@@ -270,7 +329,10 @@ impl UdpServer {
     /// ```
     pub fn bind(addr: &str) -> Result<Self> {
         let socket = UdpSocket::bind(addr)?;
-        Ok(UdpServer { socket })
+        Ok(UdpServer {
+            socket,
+            layers: DatagramLayers::default(),
+        })
     }
 
     /// Receive a message (blocking)
@@ -285,10 +347,25 @@ impl UdpServer {
     /// - [`IgtlError::InvalidHeader`](crate::error::IgtlError::InvalidHeader) - Malformed header
     /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption
     pub fn receive<T: Message>(&self) -> Result<(IgtlMessage<T>, SocketAddr)> {
-        let mut buf = vec![0u8; MAX_UDP_DATAGRAM_SIZE];
-        let (size, src) = self.socket.recv_from(&mut buf)?;
+        let (data, src) = self.recv_frame()?;
+        let msg = IgtlMessage::decode(&data)?;
+        Ok((msg, src))
+    }
 
-        let msg = IgtlMessage::decode(&buf[..size])?;
+    /// Receive a message of any type (blocking)
+    ///
+    /// # Returns
+    ///
+    /// Tuple of (message, sender_address)
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read failed or timeout
+    /// - [`IgtlError::InvalidSize`](crate::error::IgtlError::InvalidSize) - Datagram shorter than a header
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption
+    pub fn receive_any(&self) -> Result<(AnyMessage, SocketAddr)> {
+        let (data, src) = self.recv_frame()?;
+        let msg = decode_any_datagram(&data, true)?;
         Ok((msg, src))
     }
 
@@ -302,18 +379,11 @@ impl UdpServer {
     /// # Errors
     ///
     /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network transmission failed
-    /// - [`IgtlError::BodyTooLarge`](crate::error::IgtlError::BodyTooLarge) - Message exceeds UDP MTU
+    /// - [`IgtlError::BodyTooLarge`](crate::error::IgtlError::BodyTooLarge) - Message exceeds UDP MTU and fragmentation is disabled
     pub fn send_to<T: Message>(&self, msg: &IgtlMessage<T>, target: SocketAddr) -> Result<()> {
-        let data = msg.encode()?;
-
-        if data.len() > MAX_UDP_DATAGRAM_SIZE {
-            return Err(IgtlError::BodyTooLarge {
-                size: data.len(),
-                max: MAX_UDP_DATAGRAM_SIZE,
-            });
+        for datagram in self.layers.encode(msg)? {
+            self.socket.send_to(&datagram, target)?;
         }
-
-        self.socket.send_to(&data, target)?;
         Ok(())
     }
 
@@ -339,6 +409,106 @@ impl UdpServer {
     pub fn local_addr(&self) -> Result<SocketAddr> {
         Ok(self.socket.local_addr()?)
     }
+
+    /// Enable fragmentation of messages larger than one datagram
+    ///
+    /// Both endpoints must enable fragmentation to exchange large messages; small messages
+    /// are still sent as plain datagrams.
+    pub fn with_fragmentation(mut self, config: FragmentConfig) -> Self {
+        self.layers.fragmentation = Some(Fragmentation::new(config));
+        self
+    }
+
+    /// Fragmentation statistics (None if fragmentation is disabled)
+    pub fn fragment_stats(&self) -> Option<FragmentStats> {
+        self.layers.fragmentation.as_ref().map(Fragmentation::stats)
+    }
+
+    /// Stamp sent messages with per-device sequence numbers and track received ones
+    ///
+    /// See [`udp_sequence`](crate::io::udp_sequence). Sequencing overwrites the v3
+    /// message ID of sent messages.
+    pub fn with_sequencing(mut self, config: SequenceConfig) -> Self {
+        self.layers.sequencing = Some(Sequencing::new(config));
+        self
+    }
+
+    /// Per-source receive statistics (None if sequencing is disabled)
+    pub fn sequence_stats(&self) -> Option<HashMap<SequenceSource, SequenceStats>> {
+        self.layers.sequencing.as_ref().map(Sequencing::stats)
+    }
+
+    /// Receive datagrams until a complete message frame is available
+    fn recv_frame(&self) -> Result<(Vec<u8>, SocketAddr)> {
+        let mut buf = vec![0u8; MAX_UDP_DATAGRAM_SIZE];
+        loop {
+            let (size, src) = self.socket.recv_from(&mut buf)?;
+            if let Some(frame) = self.layers.receive(&buf[..size], src) {
+                return Ok((frame, src));
+            }
+        }
+    }
+}
+
+/// Optional fragmentation and sequencing applied by the sync and async UDP endpoints
+#[derive(Default)]
+pub(crate) struct DatagramLayers {
+    pub(crate) fragmentation: Option<Fragmentation>,
+    pub(crate) sequencing: Option<Sequencing>,
+}
+
+impl DatagramLayers {
+    /// Encode a message into one datagram, or into fragments if fragmentation is enabled
+    pub(crate) fn encode<T: Message>(&self, msg: &IgtlMessage<T>) -> Result<Vec<Vec<u8>>> {
+        let data = match &self.sequencing {
+            Some(sequencing) => sequencing.encode(msg)?,
+            None => msg.encode()?,
+        };
+        match &self.fragmentation {
+            Some(fragmentation) => fragmentation.split(data),
+            None => Ok(vec![check_datagram_size(data)?]),
+        }
+    }
+
+    /// Process a received datagram, returning a complete frame to deliver if there is one
+    pub(crate) fn receive(&self, datagram: &[u8], src: SocketAddr) -> Option<Vec<u8>> {
+        let frame = match &self.fragmentation {
+            Some(fragmentation) => fragmentation.push(datagram, src)?,
+            None => datagram.to_vec(),
+        };
+        match &self.sequencing {
+            Some(sequencing) if !sequencing.accept(&frame, src) => None,
+            _ => Some(frame),
+        }
+    }
+}
+
+/// Encode a message, rejecting it if it does not fit in a single datagram
+pub(crate) fn encode_datagram<T: Message>(msg: &IgtlMessage<T>) -> Result<Vec<u8>> {
+    check_datagram_size(msg.encode()?)
+}
+
+/// Reject encoded data that does not fit in a single datagram
+pub(crate) fn check_datagram_size(data: Vec<u8>) -> Result<Vec<u8>> {
+    if data.len() > MAX_UDP_DATAGRAM_SIZE {
+        return Err(IgtlError::BodyTooLarge {
+            size: data.len(),
+            max: MAX_UDP_DATAGRAM_SIZE,
+        });
+    }
+
+    Ok(data)
+}
+
+/// Decode one datagram into an [`AnyMessage`]
+pub(crate) fn decode_any_datagram(data: &[u8], verify_crc: bool) -> Result<AnyMessage> {
+    if data.len() < Header::SIZE {
+        return Err(IgtlError::InvalidSize {
+            expected: Header::SIZE,
+            actual: data.len(),
+        });
+    }
+    decode_any_frame(data, verify_crc)
 }
 
 #[cfg(test)]
@@ -394,6 +564,144 @@ mod tests {
         assert_eq!(sender, client.local_addr().unwrap());
     }
 
+    #[test]
+    fn test_receive_any() {
+        let server = UdpServer::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap().to_string();
+        let client = UdpClient::bind("127.0.0.1:0").unwrap();
+
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        client.send_to(&msg, &server_addr).unwrap();
+        let (received, sender) = server.receive_any().unwrap();
+        assert!(matches!(received, AnyMessage::Transform(_)));
+        assert_eq!(received.device_name().unwrap(), "Tool");
+        assert_eq!(sender, client.local_addr().unwrap());
+
+        // Truncated datagrams are rejected instead of panicking
+        let raw = UdpSocket::bind("127.0.0.1:0").unwrap();
+        raw.send_to(&[0u8; 10], &server_addr).unwrap();
+        assert!(matches!(
+            server.receive_any(),
+            Err(IgtlError::InvalidSize { actual: 10, .. })
+        ));
+    }
+
+    #[test]
+    fn test_fragmented_image() {
+        use crate::protocol::types::{ImageMessage, ImageScalarType};
+
+        let server = UdpServer::bind("127.0.0.1:0")
+            .unwrap()
+            .with_fragmentation(FragmentConfig::default());
+        let server_addr = server.local_addr().unwrap().to_string();
+        let client = UdpClient::bind("127.0.0.1:0")
+            .unwrap()
+            .with_fragmentation(FragmentConfig::default());
+
+        let pixels: Vec<u8> = (0..256 * 256).map(|i| (i % 251) as u8).collect();
+        let image = ImageMessage::new(ImageScalarType::Uint8, [256, 256, 1], pixels).unwrap();
+        let msg = IgtlMessage::new(image.clone(), "Ultrasound").unwrap();
+        assert!(msg.encode().unwrap().len() > MAX_UDP_DATAGRAM_SIZE);
+        client.send_to(&msg, &server_addr).unwrap();
+
+        let (received, _) = server.receive::<ImageMessage>().unwrap();
+        assert_eq!(received.content, image);
+        let stats = server.fragment_stats().unwrap();
+        assert_eq!(stats.messages_reassembled, 1);
+        assert_eq!(stats.messages_discarded, 0);
+        assert_eq!(client.fragment_stats().unwrap().messages_fragmented, 1);
+
+        // Without fragmentation the same message is rejected
+        let plain = UdpClient::bind("127.0.0.1:0").unwrap();
+        assert!(matches!(
+            plain.send_to(&msg, &server_addr),
+            Err(IgtlError::BodyTooLarge { .. })
+        ));
+    }
+
+    #[test]
+    fn test_fragmentation_interoperates_with_plain_endpoints() {
+        let plain_server = UdpServer::bind("127.0.0.1:0").unwrap();
+        let fragmenting_server = UdpServer::bind("127.0.0.1:0")
+            .unwrap()
+            .with_fragmentation(FragmentConfig::default());
+        let fragmenting_client = UdpClient::bind("127.0.0.1:0")
+            .unwrap()
+            .with_fragmentation(FragmentConfig::default());
+        let plain_client = UdpClient::bind("127.0.0.1:0").unwrap();
+
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        let target = plain_server.local_addr().unwrap().to_string();
+        fragmenting_client.send_to(&msg, &target).unwrap();
+        assert!(plain_server.receive::<TransformMessage>().is_ok());
+
+        let target = fragmenting_server.local_addr().unwrap().to_string();
+        plain_client.send_to(&msg, &target).unwrap();
+        assert!(fragmenting_server.receive::<TransformMessage>().is_ok());
+        assert_eq!(
+            fragmenting_server
+                .fragment_stats()
+                .unwrap()
+                .plain_datagrams_received,
+            1
+        );
+    }
+
+    #[test]
+    fn test_sequence_statistics() {
+        let server = UdpServer::bind("127.0.0.1:0")
+            .unwrap()
+            .with_sequencing(SequenceConfig::new().with_drop_out_of_order(true));
+        let server_addr = server.local_addr().unwrap().to_string();
+        let client = UdpClient::bind("127.0.0.1:0")
+            .unwrap()
+            .with_sequencing(SequenceConfig::new());
+
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Probe").unwrap();
+        for _ in 0..3 {
+            client.send_to(&msg, &server_addr).unwrap();
+        }
+        for expected in 0..3 {
+            let (received, _) = server.receive::<TransformMessage>().unwrap();
+            assert_eq!(received.get_message_id(), Some(expected));
+        }
+
+        // Reordered and lost frames from a pre-stamped sender
+        let raw = UdpSocket::bind("127.0.0.1:0").unwrap();
+        for id in [0, 1, 3, 2, 5] {
+            let mut msg = IgtlMessage::new(TransformMessage::identity(), "Probe").unwrap();
+            msg.set_message_id(id);
+            raw.send_to(&msg.encode().unwrap(), &server_addr).unwrap();
+        }
+        let ids: Vec<_> = (0..4)
+            .map(|_| {
+                server
+                    .receive::<TransformMessage>()
+                    .unwrap()
+                    .0
+                    .get_message_id()
+            })
+            .collect();
+        assert_eq!(ids, [Some(0), Some(1), Some(3), Some(5)]);
+
+        let stats = server.sequence_stats().unwrap();
+        let client_stats = stats[&SequenceSource {
+            addr: client.local_addr().unwrap(),
+            device_name: "Probe".to_string(),
+        }];
+        assert_eq!(client_stats.received, 3);
+        assert_eq!(client_stats.lost, 0);
+        let raw_stats = stats[&SequenceSource {
+            addr: raw.local_addr().unwrap(),
+            device_name: "Probe".to_string(),
+        }];
+        assert_eq!(raw_stats.received, 5);
+        assert_eq!(raw_stats.lost, 1);
+        assert_eq!(raw_stats.out_of_order, 1);
+        assert_eq!(raw_stats.dropped, 1);
+        assert_eq!(raw_stats.last_sequence, 5);
+    }
+
     #[test]
     fn test_timeout() {
         let client = UdpClient::bind("127.0.0.1:0").unwrap();
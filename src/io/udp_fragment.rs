@@ -0,0 +1,500 @@
+//! Opt-in fragmentation of large OpenIGTLink messages over UDP
+//!
+//! Messages that do not fit in one datagram (IMAGE, POLYDATA, ...) are split into
+//! fragments, each prefixed with a small header:
+//!
+//! ```text
+//! +-------+------------+-------+-------+---------+
+//! | "IGTF"| message id | index | count | payload |
+//! |  4 B  |  u32 (BE)  |  u16  |  u16  |   ...   |
+//! +-------+------------+-------+-------+---------+
+//! ```
+//!
+//! Messages that fit in [`FragmentConfig::max_datagram_size`] are still sent as plain
+//! datagrams, and plain datagrams are accepted by a fragmenting receiver, so endpoints with
+//! and without fragmentation interoperate for small messages. A plain OpenIGTLink datagram
+//! starts with the big-endian header version (`0x00 0x01..0x03`), so it can never be
+//! mistaken for a fragment.
+//!
+//! The receiver reassembles fragments per (sender, message id), discards messages that are
+//! still incomplete after [`FragmentConfig::reassembly_timeout`], and counts losses in
+//! [`FragmentStats`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::udp_fragment::FragmentConfig;
+//! use openigtlink_rust::io::{UdpClient, UdpServer};
+//!
+//! let server = UdpServer::bind("0.0.0.0:18944")?.with_fragmentation(FragmentConfig::default());
+//! let client = UdpClient::bind("0.0.0.0:0")?.with_fragmentation(FragmentConfig::default());
+//! # Ok::<(), openigtlink_rust::error::IgtlError>(())
+//! ```
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use tracing::{debug, warn};
+
+use crate::error::{IgtlError, Result};
+use crate::io::udp::MAX_UDP_DATAGRAM_SIZE;
+
+/// Magic bytes identifying a fragment datagram
+pub const FRAGMENT_MAGIC: [u8; 4] = *b"IGTF";
+
+/// Size of the fragment header (magic, message id, index, count)
+pub const FRAGMENT_HEADER_SIZE: usize = 12;
+
+/// Fragmentation settings
+///
+/// # Examples
+///
+/// ```
+/// use openigtlink_rust::io::udp_fragment::FragmentConfig;
+/// use std::time::Duration;
+///
+/// // Loopback link with a large MTU
+/// let config = FragmentConfig::new()
+///     .with_max_datagram_size(16384)
+///     .with_reassembly_timeout(Duration::from_millis(200));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FragmentConfig {
+    /// Largest datagram sent, including the fragment header (default 1472, Ethernet MTU
+    /// minus IP and UDP headers). Clamped to `FRAGMENT_HEADER_SIZE + 1 ..= 65507`.
+    pub max_datagram_size: usize,
+    /// Time allowed for all fragments of a message to arrive (default 1s)
+    pub reassembly_timeout: Duration,
+    /// Maximum number of partially received messages kept at once; the oldest is
+    /// discarded when exceeded (default 64)
+    pub max_pending_messages: usize,
+}
+
+impl FragmentConfig {
+    /// Create the default configuration
+    pub fn new() -> Self {
+        Self {
+            max_datagram_size: 1472,
+            reassembly_timeout: Duration::from_secs(1),
+            max_pending_messages: 64,
+        }
+    }
+
+    /// Set the largest datagram sent, including the fragment header
+    pub fn with_max_datagram_size(mut self, size: usize) -> Self {
+        self.max_datagram_size = size;
+        self
+    }
+
+    /// Set the time allowed for all fragments of a message to arrive
+    pub fn with_reassembly_timeout(mut self, timeout: Duration) -> Self {
+        self.reassembly_timeout = timeout;
+        self
+    }
+
+    /// Set the maximum number of partially received messages kept at once
+    pub fn with_max_pending_messages(mut self, count: usize) -> Self {
+        self.max_pending_messages = count;
+        self
+    }
+
+    /// Payload bytes carried by each fragment
+    fn payload_size(&self) -> usize {
+        self.max_datagram_size
+            .clamp(FRAGMENT_HEADER_SIZE + 1, MAX_UDP_DATAGRAM_SIZE)
+            - FRAGMENT_HEADER_SIZE
+    }
+}
+
+impl Default for FragmentConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Fragmentation and reassembly counters
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FragmentStats {
+    /// Messages sent as more than one fragment
+    pub messages_fragmented: u64,
+    /// Fragment datagrams received
+    pub fragments_received: u64,
+    /// Plain (unfragmented) datagrams received
+    pub plain_datagrams_received: u64,
+    /// Messages completely reassembled from fragments
+    pub messages_reassembled: u64,
+    /// Incomplete messages discarded after the reassembly timeout or eviction
+    pub messages_discarded: u64,
+    /// Fragments missing from discarded messages
+    pub fragments_lost: u64,
+    /// Fragments received more than once
+    pub duplicate_fragments: u64,
+    /// Fragments with an inconsistent header
+    pub invalid_fragments: u64,
+}
+
+impl FragmentStats {
+    /// Fraction of fragmented messages that were lost (0.0 if none were received)
+    pub fn message_loss_ratio(&self) -> f64 {
+        let total = self.messages_reassembled + self.messages_discarded;
+        if total == 0 {
+            0.0
+        } else {
+            self.messages_discarded as f64 / total as f64
+        }
+    }
+}
+
+/// Reassembles fragmented messages from datagrams received on a socket of your own
+///
+/// [`UdpServer`](crate::io::UdpServer) and [`UdpClient`](crate::io::UdpClient) do this
+/// internally when fragmentation is enabled; use a reassembler when the datagrams come
+/// from elsewhere, e.g. a capture tool or a socket shared with other protocols.
+///
+/// # Examples
+///
+/// ```no_run
+/// use openigtlink_rust::io::udp_fragment::{FragmentConfig, FragmentReassembler};
+/// use std::net::UdpSocket;
+///
+/// let socket = UdpSocket::bind("0.0.0.0:18944")?;
+/// let reassembler = FragmentReassembler::new(FragmentConfig::default());
+/// let mut buf = vec![0u8; 65535];
+/// loop {
+///     let (n, src) = socket.recv_from(&mut buf)?;
+///     if let Some(frame) = reassembler.push(&buf[..n], src) {
+///         println!("{} byte message from {}", frame.len(), src);
+///     }
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct FragmentReassembler {
+    fragmentation: Fragmentation,
+}
+
+impl FragmentReassembler {
+    /// Create a reassembler using the timeout and pending limit of `config`
+    pub fn new(config: FragmentConfig) -> Self {
+        Self {
+            fragmentation: Fragmentation::new(config),
+        }
+    }
+
+    /// Feed a received datagram, returning a complete message frame if one is ready
+    ///
+    /// Plain (unfragmented) datagrams are returned as they are.
+    pub fn push(&self, datagram: &[u8], src: SocketAddr) -> Option<Vec<u8>> {
+        self.fragmentation.push(datagram, src)
+    }
+
+    /// Reassembly counters
+    pub fn stats(&self) -> FragmentStats {
+        self.fragmentation.stats()
+    }
+}
+
+/// Per-endpoint fragmentation state shared by the sync and async UDP endpoints
+pub(crate) struct Fragmentation {
+    config: FragmentConfig,
+    next_id: AtomicU32,
+    state: Mutex<Reassembler>,
+}
+
+impl Fragmentation {
+    pub(crate) fn new(config: FragmentConfig) -> Self {
+        Self {
+            config,
+            next_id: AtomicU32::new(0),
+            state: Mutex::new(Reassembler::default()),
+        }
+    }
+
+    /// Split an encoded message into datagrams
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::BodyTooLarge`] - More than `u16::MAX` fragments would be needed
+    pub(crate) fn split(&self, data: Vec<u8>) -> Result<Vec<Vec<u8>>> {
+        let payload_size = self.config.payload_size();
+        if data.len() <= self.config.max_datagram_size.min(MAX_UDP_DATAGRAM_SIZE) {
+            return Ok(vec![data]);
+        }
+
+        let max = payload_size * u16::MAX as usize;
+        if data.len() > max {
+            return Err(IgtlError::BodyTooLarge {
+                size: data.len(),
+                max,
+            });
+        }
+
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let count = data.len().div_ceil(payload_size) as u16;
+        let datagrams = data
+            .chunks(payload_size)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let mut datagram = Vec::with_capacity(FRAGMENT_HEADER_SIZE + chunk.len());
+                datagram.extend_from_slice(&FRAGMENT_MAGIC);
+                datagram.extend_from_slice(&id.to_be_bytes());
+                datagram.extend_from_slice(&(index as u16).to_be_bytes());
+                datagram.extend_from_slice(&count.to_be_bytes());
+                datagram.extend_from_slice(chunk);
+                datagram
+            })
+            .collect();
+
+        self.lock().stats.messages_fragmented += 1;
+        debug!(message_id = id, fragments = count, "Fragmented UDP message");
+        Ok(datagrams)
+    }
+
+    /// Feed a received datagram, returning a complete message frame if one is ready
+    pub(crate) fn push(&self, datagram: &[u8], src: SocketAddr) -> Option<Vec<u8>> {
+        let config = &self.config;
+        self.lock().push(datagram, src, Instant::now(), config)
+    }
+
+    pub(crate) fn stats(&self) -> FragmentStats {
+        self.lock().stats
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Reassembler> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Partially received message
+struct Pending {
+    fragments: Vec<Option<Vec<u8>>>,
+    received: usize,
+    started: Instant,
+}
+
+#[derive(Default)]
+struct Reassembler {
+    pending: HashMap<(SocketAddr, u32), Pending>,
+    stats: FragmentStats,
+}
+
+impl Reassembler {
+    fn push(
+        &mut self,
+        datagram: &[u8],
+        src: SocketAddr,
+        now: Instant,
+        config: &FragmentConfig,
+    ) -> Option<Vec<u8>> {
+        self.expire(now, config.reassembly_timeout);
+
+        if !datagram.starts_with(&FRAGMENT_MAGIC) {
+            self.stats.plain_datagrams_received += 1;
+            return Some(datagram.to_vec());
+        }
+        self.stats.fragments_received += 1;
+
+        if datagram.len() < FRAGMENT_HEADER_SIZE {
+            self.stats.invalid_fragments += 1;
+            return None;
+        }
+        let id = u32::from_be_bytes(datagram[4..8].try_into().unwrap());
+        let index = u16::from_be_bytes([datagram[8], datagram[9]]) as usize;
+        let count = u16::from_be_bytes([datagram[10], datagram[11]]) as usize;
+        if index >= count {
+            self.stats.invalid_fragments += 1;
+            return None;
+        }
+
+        let key = (src, id);
+        if !self.pending.contains_key(&key) && self.pending.len() >= config.max_pending_messages {
+            self.evict_oldest();
+        }
+        let pending = self.pending.entry(key).or_insert_with(|| Pending {
+            fragments: vec![None; count],
+            received: 0,
+            started: now,
+        });
+        if pending.fragments.len() != count {
+            self.stats.invalid_fragments += 1;
+            return None;
+        }
+        if pending.fragments[index].is_some() {
+            self.stats.duplicate_fragments += 1;
+            return None;
+        }
+        pending.fragments[index] = Some(datagram[FRAGMENT_HEADER_SIZE..].to_vec());
+        pending.received += 1;
+        if pending.received < count {
+            return None;
+        }
+
+        let pending = self.pending.remove(&key)?;
+        self.stats.messages_reassembled += 1;
+        Some(pending.fragments.into_iter().flatten().flatten().collect())
+    }
+
+    /// Discard messages whose reassembly timeout has expired
+    fn expire(&mut self, now: Instant, timeout: Duration) {
+        let expired: Vec<_> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| now.duration_since(p.started) >= timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            self.discard(key);
+        }
+    }
+
+    fn evict_oldest(&mut self) {
+        if let Some(key) = self
+            .pending
+            .iter()
+            .min_by_key(|(_, p)| p.started)
+            .map(|(key, _)| *key)
+        {
+            self.discard(key);
+        }
+    }
+
+    fn discard(&mut self, key: (SocketAddr, u32)) {
+        if let Some(pending) = self.pending.remove(&key) {
+            let lost = pending.fragments.len() - pending.received;
+            warn!(
+                peer = %key.0,
+                message_id = key.1,
+                lost_fragments = lost,
+                "Discarding incomplete UDP message"
+            );
+            self.stats.messages_discarded += 1;
+            self.stats.fragments_lost += lost as u64;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(port: u16) -> SocketAddr {
+        SocketAddr::from(([127, 0, 0, 1], port))
+    }
+
+    fn config() -> FragmentConfig {
+        FragmentConfig::new().with_max_datagram_size(FRAGMENT_HEADER_SIZE + 10)
+    }
+
+    #[test]
+    fn test_small_message_is_sent_plain() {
+        let fragmentation = Fragmentation::new(config());
+        let datagrams = fragmentation.split(vec![0u8; 22]).unwrap();
+        assert_eq!(datagrams, vec![vec![0u8; 22]]);
+
+        assert_eq!(fragmentation.push(&[0, 1, 2], addr(1)), Some(vec![0, 1, 2]));
+        assert_eq!(fragmentation.stats().plain_datagrams_received, 1);
+    }
+
+    #[test]
+    fn test_reassembly_out_of_order() {
+        let sender = Fragmentation::new(config());
+        let receiver = Fragmentation::new(config());
+        let data: Vec<u8> = (0..=255).collect();
+
+        let mut datagrams = sender.split(data.clone()).unwrap();
+        assert_eq!(datagrams.len(), 26);
+        assert!(datagrams
+            .iter()
+            .all(|d| d.len() <= FRAGMENT_HEADER_SIZE + 10));
+        datagrams.reverse();
+        let duplicate = datagrams[3].clone();
+
+        let (last, rest) = datagrams.split_last().unwrap();
+        for datagram in rest {
+            assert_eq!(receiver.push(datagram, addr(1)), None);
+        }
+        assert_eq!(receiver.push(&duplicate, addr(1)), None);
+        assert_eq!(receiver.push(last, addr(1)), Some(data));
+
+        let stats = receiver.stats();
+        assert_eq!(stats.messages_reassembled, 1);
+        assert_eq!(stats.duplicate_fragments, 1);
+        assert_eq!(stats.fragments_received, 27);
+        assert_eq!(sender.stats().messages_fragmented, 1);
+    }
+
+    #[test]
+    fn test_public_reassembler() {
+        let sender = Fragmentation::new(config());
+        let reassembler = FragmentReassembler::new(config());
+        let data: Vec<u8> = (0..50).collect();
+
+        let mut frames = Vec::new();
+        for datagram in sender.split(data.clone()).unwrap() {
+            frames.extend(reassembler.push(&datagram, addr(1)));
+        }
+        assert_eq!(frames, vec![data]);
+        assert_eq!(reassembler.stats().messages_reassembled, 1);
+    }
+
+    #[test]
+    fn test_fragments_are_keyed_by_sender() {
+        let sender = Fragmentation::new(config());
+        let receiver = Fragmentation::new(config());
+        let datagrams = sender.split(vec![7u8; 25]).unwrap();
+        assert_eq!(datagrams.len(), 3);
+
+        assert_eq!(receiver.push(&datagrams[0], addr(1)), None);
+        assert_eq!(receiver.push(&datagrams[1], addr(1)), None);
+        assert_eq!(receiver.push(&datagrams[2], addr(2)), None);
+        assert_eq!(receiver.push(&datagrams[2], addr(1)), Some(vec![7u8; 25]));
+    }
+
+    #[test]
+    fn test_incomplete_message_is_discarded() {
+        let config = config().with_reassembly_timeout(Duration::from_millis(100));
+        let sender = Fragmentation::new(config.clone());
+        let mut receiver = Reassembler::default();
+        let start = Instant::now();
+
+        let datagrams = sender.split(vec![1u8; 35]).unwrap();
+        assert_eq!(datagrams.len(), 4);
+        receiver.push(&datagrams[0], addr(1), start, &config);
+        receiver.push(&datagrams[2], addr(1), start, &config);
+
+        // The late fragment starts a new (incomplete) message
+        let later = start + Duration::from_millis(150);
+        assert_eq!(receiver.push(&datagrams[1], addr(1), later, &config), None);
+        assert_eq!(receiver.stats.messages_discarded, 1);
+        assert_eq!(receiver.stats.fragments_lost, 2);
+        assert_eq!(receiver.stats.message_loss_ratio(), 1.0);
+    }
+
+    #[test]
+    fn test_pending_messages_are_bounded() {
+        let config = config().with_max_pending_messages(2);
+        let sender = Fragmentation::new(config.clone());
+        let mut receiver = Reassembler::default();
+        let now = Instant::now();
+
+        for i in 0..3u64 {
+            let datagrams = sender.split(vec![0u8; 30]).unwrap();
+            let at = now + Duration::from_millis(i);
+            receiver.push(&datagrams[0], addr(1), at, &config);
+        }
+        assert_eq!(receiver.pending.len(), 2);
+        assert_eq!(receiver.stats.messages_discarded, 1);
+    }
+
+    #[test]
+    fn test_invalid_fragment_header() {
+        let receiver = Fragmentation::new(config());
+        let mut bad = FRAGMENT_MAGIC.to_vec();
+        bad.extend_from_slice(&[0, 0, 0, 1, 0, 2, 0, 2]);
+        assert_eq!(receiver.push(&bad, addr(1)), None);
+        assert_eq!(receiver.push(&FRAGMENT_MAGIC, addr(1)), None);
+        assert_eq!(receiver.stats().invalid_fragments, 2);
+    }
+}
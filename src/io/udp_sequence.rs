@@ -0,0 +1,383 @@
+//! Sequence numbering and loss statistics for UDP streams
+//!
+//! UDP datagrams carry no sequence information. With sequencing enabled, a sender stamps
+//! each outgoing message with a monotonically increasing v3 `message_id`, counted
+//! separately per device name. The receiver tracks these IDs per (sender address, device
+//! name) and reports received, lost, duplicated and out-of-order frames together with the
+//! RFC 3550 inter-arrival jitter (based on the header timestamps).
+//!
+//! Messages without a message ID (e.g. from senders without sequencing) are delivered
+//! unchanged and not counted.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::udp_sequence::SequenceConfig;
+//! use openigtlink_rust::io::{UdpClient, UdpServer};
+//! use openigtlink_rust::protocol::types::TransformMessage;
+//!
+//! let server = UdpServer::bind("0.0.0.0:18944")?
+//!     .with_sequencing(SequenceConfig::new().with_drop_out_of_order(true));
+//! let client = UdpClient::bind("0.0.0.0:0")?.with_sequencing(SequenceConfig::new());
+//!
+//! let (msg, _) = server.receive::<TransformMessage>()?;
+//! for (source, stats) in server.sequence_stats().unwrap() {
+//!     println!("{} {}: {} lost", source.addr, source.device_name, stats.lost);
+//! }
+//! # Ok::<(), openigtlink_rust::error::IgtlError>(())
+//! ```
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::debug;
+
+use crate::error::Result;
+use crate::io::common::frame_message_id;
+use crate::protocol::header::Header;
+use crate::protocol::message::{IgtlMessage, Message};
+
+/// Number of recent sequence numbers remembered for duplicate detection
+const WINDOW: u32 = 64;
+
+/// Forward jump treated as a sender restart rather than as loss
+const MAX_FORWARD_JUMP: u32 = 1 << 15;
+
+/// Sequencing settings
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SequenceConfig {
+    /// Drop duplicated and out-of-order (stale) frames instead of returning them
+    /// (default false)
+    pub drop_out_of_order: bool,
+}
+
+impl SequenceConfig {
+    /// Create the default configuration
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop duplicated and out-of-order frames instead of returning them
+    pub fn with_drop_out_of_order(mut self, drop: bool) -> Self {
+        self.drop_out_of_order = drop;
+        self
+    }
+}
+
+/// Stream identity used to key receive statistics
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SequenceSource {
+    /// Sender socket address
+    pub addr: SocketAddr,
+    /// Device name of the messages
+    pub device_name: String,
+}
+
+/// Receive statistics for one source
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SequenceStats {
+    /// Distinct frames received (including out-of-order ones)
+    pub received: u64,
+    /// Frames never received (gaps in the sequence, reduced when late frames arrive)
+    pub lost: u64,
+    /// Frames received more than once
+    pub duplicated: u64,
+    /// Frames that arrived after a later frame
+    pub out_of_order: u64,
+    /// Duplicated and out-of-order frames dropped by
+    /// [`SequenceConfig::drop_out_of_order`]
+    pub dropped: u64,
+    /// Times the sequence restarted (e.g. the sender was restarted)
+    pub resets: u64,
+    /// Highest sequence number received
+    pub last_sequence: u32,
+    /// Inter-arrival jitter (RFC 3550), zero if messages carry no timestamp
+    pub jitter: Duration,
+}
+
+impl SequenceStats {
+    /// Fraction of frames lost (0.0 if nothing was expected yet)
+    pub fn loss_ratio(&self) -> f64 {
+        let expected = self.received + self.lost;
+        if expected == 0 {
+            0.0
+        } else {
+            self.lost as f64 / expected as f64
+        }
+    }
+}
+
+/// Classification of a received sequence number
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arrival {
+    InOrder,
+    Duplicate,
+    OutOfOrder,
+}
+
+/// Tracking state for one source
+struct SourceState {
+    stats: SequenceStats,
+    /// Bit `i` set if `last_sequence - i` was received
+    window: u64,
+    /// Previous transit time (arrival - send) in nanoseconds
+    last_transit: Option<i128>,
+    /// Jitter estimate in nanoseconds
+    jitter: f64,
+}
+
+impl SourceState {
+    fn new(sequence: u32) -> Self {
+        Self {
+            stats: SequenceStats {
+                received: 1,
+                last_sequence: sequence,
+                ..Default::default()
+            },
+            window: 1,
+            last_transit: None,
+            jitter: 0.0,
+        }
+    }
+
+    fn record(&mut self, sequence: u32) -> Arrival {
+        let stats = &mut self.stats;
+        let ahead = sequence.wrapping_sub(stats.last_sequence);
+        let behind = stats.last_sequence.wrapping_sub(sequence);
+
+        if ahead != 0 && ahead < MAX_FORWARD_JUMP {
+            stats.lost += u64::from(ahead - 1);
+            self.window = if ahead >= WINDOW {
+                0
+            } else {
+                self.window << ahead
+            };
+            self.window |= 1;
+            stats.last_sequence = sequence;
+            stats.received += 1;
+            return Arrival::InOrder;
+        }
+
+        if behind < WINDOW {
+            if self.window & (1 << behind) != 0 {
+                stats.duplicated += 1;
+                return Arrival::Duplicate;
+            }
+            self.window |= 1 << behind;
+            stats.lost = stats.lost.saturating_sub(1);
+            stats.out_of_order += 1;
+            stats.received += 1;
+            return Arrival::OutOfOrder;
+        }
+
+        // Far behind or far ahead: the sender started a new sequence
+        stats.resets += 1;
+        stats.received += 1;
+        stats.last_sequence = sequence;
+        self.window = 1;
+        Arrival::InOrder
+    }
+
+    fn update_jitter(&mut self, sent: u64, arrived: u64) {
+        if sent == 0 {
+            return;
+        }
+        let transit = i128::from(arrived) - i128::from(sent);
+        if let Some(last) = self.last_transit {
+            let d = (transit - last).unsigned_abs() as f64;
+            self.jitter += (d - self.jitter) / 16.0;
+            self.stats.jitter = Duration::from_nanos(self.jitter as u64);
+        }
+        self.last_transit = Some(transit);
+    }
+}
+
+/// Per-endpoint sequencing state shared by the sync and async UDP endpoints
+pub(crate) struct Sequencing {
+    config: SequenceConfig,
+    next: Mutex<HashMap<String, u32>>,
+    sources: Mutex<HashMap<SequenceSource, SourceState>>,
+}
+
+impl Sequencing {
+    pub(crate) fn new(config: SequenceConfig) -> Self {
+        Self {
+            config,
+            next: Mutex::new(HashMap::new()),
+            sources: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Encode a message stamped with the next sequence number of its device
+    pub(crate) fn encode<T: Message>(&self, msg: &IgtlMessage<T>) -> Result<Vec<u8>> {
+        let device_name = msg.header.device_name.as_str().unwrap_or_default();
+        let sequence = {
+            let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+            let counter = next.entry(device_name.to_string()).or_insert(0);
+            let sequence = *counter;
+            *counter = counter.wrapping_add(1);
+            sequence
+        };
+        msg.encode_with_message_id(sequence)
+    }
+
+    /// Record a received frame, returning false if it should be dropped
+    pub(crate) fn accept(&self, frame: &[u8], src: SocketAddr) -> bool {
+        let Some(header) = frame
+            .get(..Header::SIZE)
+            .and_then(|h| Header::decode(h).ok())
+        else {
+            return true;
+        };
+        let Some(sequence) = frame_message_id(&header, frame) else {
+            return true;
+        };
+        let arrived = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or_default();
+
+        let source = SequenceSource {
+            addr: src,
+            device_name: header.device_name.as_str().unwrap_or_default().to_string(),
+        };
+        let mut sources = self.sources.lock().unwrap_or_else(|e| e.into_inner());
+        let state = match sources.get_mut(&source) {
+            Some(state) => state,
+            None => {
+                let state = sources
+                    .entry(source)
+                    .or_insert_with(|| SourceState::new(sequence));
+                state.update_jitter(header.timestamp.to_nanos(), arrived);
+                return true;
+            }
+        };
+
+        let arrival = state.record(sequence);
+        if arrival == Arrival::InOrder {
+            state.update_jitter(header.timestamp.to_nanos(), arrived);
+            return true;
+        }
+        debug!(sequence, arrival = ?arrival, "Late UDP frame");
+        if self.config.drop_out_of_order {
+            state.stats.dropped += 1;
+            return false;
+        }
+        true
+    }
+
+    pub(crate) fn stats(&self) -> HashMap<SequenceSource, SequenceStats> {
+        self.sources
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .map(|(source, state)| (source.clone(), state.stats))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::types::TransformMessage;
+
+    fn record_all(sequences: &[u32]) -> (SourceState, Vec<Arrival>) {
+        let mut state = SourceState::new(sequences[0]);
+        let arrivals = sequences[1..].iter().map(|&s| state.record(s)).collect();
+        (state, arrivals)
+    }
+
+    #[test]
+    fn test_in_order_stream() {
+        let (state, arrivals) = record_all(&[0, 1, 2, 3]);
+        assert!(arrivals.iter().all(|a| *a == Arrival::InOrder));
+        assert_eq!(state.stats.received, 4);
+        assert_eq!(state.stats.lost, 0);
+        assert_eq!(state.stats.last_sequence, 3);
+    }
+
+    #[test]
+    fn test_loss_reordering_and_duplicates() {
+        let (state, arrivals) = record_all(&[0, 1, 4, 2, 2, 6]);
+        assert_eq!(
+            arrivals,
+            [
+                Arrival::InOrder,
+                Arrival::InOrder,
+                Arrival::OutOfOrder,
+                Arrival::Duplicate,
+                Arrival::InOrder,
+            ]
+        );
+        // 3 and 5 never arrived
+        assert_eq!(state.stats.lost, 2);
+        assert_eq!(state.stats.received, 5);
+        assert_eq!(state.stats.out_of_order, 1);
+        assert_eq!(state.stats.duplicated, 1);
+        assert!((state.stats.loss_ratio() - 2.0 / 7.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_wrapping_and_restart() {
+        let (state, arrivals) = record_all(&[u32::MAX - 1, u32::MAX, 0, 1]);
+        assert!(arrivals.iter().all(|a| *a == Arrival::InOrder));
+        assert_eq!(state.stats.lost, 0);
+
+        let (state, _) = record_all(&[1000, 1001, 0, 1]);
+        assert_eq!(state.stats.resets, 1);
+        assert_eq!(state.stats.lost, 0);
+        assert_eq!(state.stats.last_sequence, 1);
+    }
+
+    #[test]
+    fn test_jitter() {
+        let mut state = SourceState::new(0);
+        state.update_jitter(1_000, 2_000);
+        state.update_jitter(2_000, 3_000);
+        assert_eq!(state.stats.jitter, Duration::ZERO);
+        state.update_jitter(3_000, 5_600);
+        assert_eq!(state.stats.jitter, Duration::from_nanos(100));
+    }
+
+    #[test]
+    fn test_stamping_per_device() {
+        let sequencing = Sequencing::new(SequenceConfig::new().with_drop_out_of_order(true));
+        let probe = IgtlMessage::new(TransformMessage::identity(), "Probe").unwrap();
+        let reference = IgtlMessage::new(TransformMessage::identity(), "Reference").unwrap();
+        let src: SocketAddr = "127.0.0.1:1000".parse().unwrap();
+
+        let frames: Vec<Vec<u8>> = [&probe, &probe, &reference, &probe]
+            .iter()
+            .map(|msg| sequencing.encode(msg).unwrap())
+            .collect();
+        let ids: Vec<u32> = frames
+            .iter()
+            .map(|f| {
+                let msg = IgtlMessage::<TransformMessage>::decode(f).unwrap();
+                msg.get_message_id().unwrap()
+            })
+            .collect();
+        assert_eq!(ids, [0, 1, 0, 2]);
+        assert!(probe.get_message_id().is_none());
+
+        assert!(sequencing.accept(&frames[0], src));
+        assert!(sequencing.accept(&frames[3], src));
+        assert!(!sequencing.accept(&frames[1], src));
+        assert!(sequencing.accept(&frames[2], src));
+        // Unsequenced messages are passed through
+        assert!(sequencing.accept(&probe.encode().unwrap(), src));
+
+        let stats = sequencing.stats();
+        let probe_stats = stats[&SequenceSource {
+            addr: src,
+            device_name: "Probe".to_string(),
+        }];
+        assert_eq!(probe_stats.received, 3);
+        assert_eq!(probe_stats.out_of_order, 1);
+        assert_eq!(probe_stats.dropped, 1);
+        assert_eq!(probe_stats.lost, 0);
+        assert_eq!(stats.len(), 2);
+    }
+}
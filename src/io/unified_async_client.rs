@@ -12,6 +12,7 @@
 //! **Our approach**: Single `UnifiedAsyncClient` with optional features:
 //! - Internal `Transport` enum: `Plain(TcpStream)` or `Tls(TlsStream)`
 //! - Optional `reconnect_config: Option<ReconnectConfig>`
+//! - `liveness: LivenessConfig` for keepalive, heartbeats and timeouts
 //! - ✅ Scales linearly with features (not exponentially!)
 //! - ✅ Easy to add new features (compression, authentication, etc.)
 //! - ✅ Maintains type safety through builder pattern
@@ -20,12 +21,13 @@
 //!
 //! ```text
 //! UnifiedAsyncClient
-//! ├─ transport: Option<Transport>
+//! ├─ stream: Option<AsyncIgtlStream<Transport>>  ← Framing, heartbeats, idle timeout
 //! │  ├─ Plain(TcpStream)     ← Regular TCP
 //! │  └─ Tls(TlsStream)       ← TLS-encrypted TCP
 //! ├─ reconnect_config: Option<ReconnectConfig>
 //! │  ├─ None                 ← No auto-reconnection
 //! │  └─ Some(config)         ← Auto-reconnect with backoff
+//! ├─ liveness: LivenessConfig ← Keepalive, heartbeats, read/write/idle timeouts
 //! ├─ conn_params: ConnectionParams (host, port, TLS config)
 //! └─ verify_crc: bool        ← CRC verification
 //! ```
@@ -132,16 +134,32 @@
 //! ```
 
 use crate::error::{IgtlError, Result};
+use crate::io::common::{
+    decode_any_frame, status_query, timed_out, ReplyMatcher, DEFAULT_QUERY_TIMEOUT,
+};
+use crate::io::events::{ConnectionEvent, EventEmitter};
+use crate::io::liveness::{set_tcp_keepalive, with_timeout, Liveness, LivenessConfig};
 use crate::io::reconnect::ReconnectConfig;
+use crate::io::session::{OfflineQueue, OfflineQueueConfig, StreamRegistry};
+use crate::io::shutdown::{unless_cancelled, CancellationToken};
+use crate::io::split::{AsyncIgtlReader, AsyncIgtlWriter};
+use crate::io::stream::AsyncIgtlStream;
 use crate::protocol::any_message::AnyMessage;
-use crate::protocol::factory::MessageFactory;
-use crate::protocol::header::Header;
 use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::types::{
+    CapabilityMessage, GetCapabilityMessage, GetTransformMessage, StatusMessage, TransformMessage,
+};
 use rustls::pki_types::ServerName;
+use std::collections::VecDeque;
+use std::net::SocketAddr;
+use std::pin::Pin;
 use std::sync::Arc;
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::TcpStream;
-use tokio::time::sleep;
+use tokio::sync::broadcast;
+use tokio::time::{sleep, Instant};
 use tokio_rustls::client::TlsStream;
 use tokio_rustls::{rustls, TlsConnector};
 use tracing::{debug, info, trace, warn};
@@ -153,52 +171,91 @@ enum Transport {
 }
 
 impl Transport {
-    async fn write_all(&mut self, data: &[u8]) -> Result<()> {
+    /// Underlying TCP socket
+    fn tcp_stream(&self) -> &TcpStream {
         match self {
-            Transport::Plain(stream) => {
-                stream.write_all(data).await?;
-                Ok(())
-            }
-            Transport::Tls(stream) => {
-                stream.write_all(data).await?;
-                Ok(())
-            }
+            Transport::Plain(stream) => stream,
+            Transport::Tls(stream) => stream.get_ref().0,
         }
     }
+}
 
-    async fn flush(&mut self) -> Result<()> {
-        match self {
-            Transport::Plain(stream) => {
-                stream.flush().await?;
-                Ok(())
-            }
-            Transport::Tls(stream) => {
-                stream.flush().await?;
-                Ok(())
-            }
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::Tls(stream) => Pin::new(stream.as_mut()).poll_read(cx, buf),
         }
     }
+}
 
-    async fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
-        match self {
-            Transport::Plain(stream) => {
-                stream.read_exact(buf).await?;
-                Ok(())
-            }
-            Transport::Tls(stream) => {
-                stream.read_exact(buf).await?;
-                Ok(())
-            }
+impl AsyncWrite for Transport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::Tls(stream) => Pin::new(stream.as_mut()).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::Tls(stream) => Pin::new(stream.as_mut()).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::Tls(stream) => Pin::new(stream.as_mut()).poll_shutdown(cx),
         }
     }
 }
 
+/// Read half of a split [`UnifiedAsyncClient`] (plain TCP or TLS)
+pub type AsyncClientReader = AsyncIgtlReader<Box<dyn AsyncRead + Send + Unpin>>;
+
+/// Write half of a split [`UnifiedAsyncClient`] (plain TCP or TLS)
+pub type AsyncClientWriter = AsyncIgtlWriter<Box<dyn AsyncWrite + Send + Unpin>>;
+
 /// Connection parameters for reconnection
-struct ConnectionParams {
+pub(crate) struct ConnectionParams {
     addr: String,
-    hostname: Option<String>,
-    port: Option<u16>,
-    tls_config: Option<Arc<rustls::ClientConfig>>,
+    tls: Option<TlsParams>,
+}
+
+/// TLS part of the connection parameters
+struct TlsParams {
+    /// Server name for SNI and certificate verification
+    hostname: String,
+    config: Arc<rustls::ClientConfig>,
+}
+
+impl ConnectionParams {
+    pub(crate) fn plain(addr: &str) -> Self {
+        Self {
+            addr: addr.to_string(),
+            tls: None,
+        }
+    }
+
+    pub(crate) fn tls(hostname: &str, port: u16, config: Arc<rustls::ClientConfig>) -> Self {
+        Self {
+            addr: format!("{}:{}", hostname, port),
+            tls: Some(TlsParams {
+                hostname: hostname.to_string(),
+                config,
+            }),
+        }
+    }
 }
 
 /// Unified async OpenIGTLink client
@@ -228,11 +285,26 @@ struct ConnectionParams {
 /// # }
 /// ```
 pub struct UnifiedAsyncClient {
-    transport: Option<Transport>,
+    /// Framed connection, with its receive and send buffers
+    stream: Option<AsyncIgtlStream<Transport>>,
     conn_params: ConnectionParams,
     reconnect_config: Option<ReconnectConfig>,
     reconnect_count: usize,
     verify_crc: bool,
+    /// Complete messages set aside while waiting for a query reply
+    pending: VecDeque<Vec<u8>>,
+    liveness: LivenessConfig,
+    /// Streams restarted after reconnection
+    streams: StreamRegistry,
+    /// Messages sent while disconnected
+    offline_queue: Option<OfflineQueue>,
+    /// Failed reconnection attempts made by sends during the current outage
+    offline_attempts: usize,
+    next_offline_attempt: Option<Instant>,
+    /// Emitter scoped to the current (or last) connection
+    events: EventEmitter,
+    /// Address of the current (or last) server connection
+    peer: Option<SocketAddr>,
 }
 
 impl UnifiedAsyncClient {
@@ -241,27 +313,7 @@ impl UnifiedAsyncClient {
     /// # Arguments
     /// * `addr` - Server address (e.g., "127.0.0.1:18944")
     pub async fn connect(addr: &str) -> Result<Self> {
-        info!(addr = addr, "Connecting to OpenIGTLink server");
-        let stream = TcpStream::connect(addr).await?;
-        let local_addr = stream.local_addr()?;
-        info!(
-            local_addr = %local_addr,
-            remote_addr = addr,
-            "Connected to OpenIGTLink server"
-        );
-
-        Ok(Self {
-            transport: Some(Transport::Plain(stream)),
-            conn_params: ConnectionParams {
-                addr: addr.to_string(),
-                hostname: None,
-                port: None,
-                tls_config: None,
-            },
-            reconnect_config: None,
-            reconnect_count: 0,
-            verify_crc: true,
-        })
+        Self::connect_with_params(ConnectionParams::plain(addr), EventEmitter::default()).await
     }
 
     /// Connect to a TLS-enabled server
@@ -275,29 +327,87 @@ impl UnifiedAsyncClient {
         port: u16,
         tls_config: Arc<rustls::ClientConfig>,
     ) -> Result<Self> {
+        Self::connect_with_params(
+            ConnectionParams::tls(hostname, port, tls_config),
+            EventEmitter::default(),
+        )
+        .await
+    }
+
+    /// Connect and publish lifecycle events on `events`
+    pub(crate) async fn connect_with_params(
+        conn_params: ConnectionParams,
+        events: EventEmitter,
+    ) -> Result<Self> {
+        let (transport, peer) = Self::open_transport(&conn_params, &events).await?;
+
+        Ok(Self {
+            stream: Some(AsyncIgtlStream::new(transport)),
+            conn_params,
+            reconnect_config: None,
+            reconnect_count: 0,
+            verify_crc: true,
+            pending: VecDeque::new(),
+            liveness: LivenessConfig::default(),
+            streams: StreamRegistry::default(),
+            offline_queue: None,
+            offline_attempts: 0,
+            next_offline_attempt: None,
+            events: events.connection(Some(peer)),
+            peer: Some(peer),
+        })
+    }
+
+    /// Open a plain or TLS connection as described by `params`
+    async fn open_transport(
+        params: &ConnectionParams,
+        events: &EventEmitter,
+    ) -> Result<(Transport, SocketAddr)> {
+        events.emit(ConnectionEvent::Connecting {
+            addr: params.addr.clone(),
+        });
+
+        let Some(tls) = &params.tls else {
+            info!(addr = %params.addr, "Connecting to OpenIGTLink server");
+            let stream = TcpStream::connect(&params.addr).await?;
+            let local_addr = stream.local_addr()?;
+            let peer = stream.peer_addr()?;
+            info!(
+                local_addr = %local_addr,
+                remote_addr = %params.addr,
+                "Connected to OpenIGTLink server"
+            );
+            events.emit(ConnectionEvent::Connected { peer });
+            return Ok((Transport::Plain(stream), peer));
+        };
+
         info!(
-            hostname = hostname,
-            port = port,
+            addr = %params.addr,
+            hostname = %tls.hostname,
             "Connecting to TLS-enabled OpenIGTLink server"
         );
 
-        let addr = format!("{}:{}", hostname, port);
-        let tcp_stream = TcpStream::connect(&addr).await?;
+        let tcp_stream = TcpStream::connect(&params.addr).await?;
         let local_addr = tcp_stream.local_addr()?;
+        let peer = tcp_stream.peer_addr()?;
 
-        let server_name = ServerName::try_from(hostname.to_string()).map_err(|e| {
+        let server_name = ServerName::try_from(tls.hostname.clone()).map_err(|e| {
             IgtlError::Io(std::io::Error::new(
                 std::io::ErrorKind::InvalidInput,
                 format!("Invalid hostname: {}", e),
             ))
         })?;
 
-        let connector = TlsConnector::from(tls_config.clone());
+        let connector = TlsConnector::from(tls.config.clone());
         let tls_stream = connector
             .connect(server_name, tcp_stream)
             .await
             .map_err(|e| {
                 warn!(error = %e, "TLS handshake failed");
+                events.emit(ConnectionEvent::TlsHandshakeFailed {
+                    peer: Some(peer),
+                    error: e.to_string(),
+                });
                 IgtlError::Io(std::io::Error::new(
                     std::io::ErrorKind::ConnectionRefused,
                     format!("TLS handshake failed: {}", e),
@@ -306,22 +416,21 @@ impl UnifiedAsyncClient {
 
         info!(
             local_addr = %local_addr,
-            remote_addr = %addr,
+            remote_addr = %params.addr,
             "TLS connection established"
         );
+        events.emit(ConnectionEvent::Connected { peer });
 
-        Ok(Self {
-            transport: Some(Transport::Tls(Box::new(tls_stream))),
-            conn_params: ConnectionParams {
-                addr,
-                hostname: Some(hostname.to_string()),
-                port: Some(port),
-                tls_config: Some(tls_config),
-            },
-            reconnect_config: None,
-            reconnect_count: 0,
-            verify_crc: true,
-        })
+        Ok((Transport::Tls(Box::new(tls_stream)), peer))
+    }
+
+    /// Subscribe to connection lifecycle events
+    ///
+    /// Only events published after subscribing are received; pass a channel with
+    /// [`ClientBuilder::with_events`](crate::io::builder::ClientBuilder) to also observe
+    /// the initial connection. See [`events`](crate::io::events).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
     }
 
     /// Enable automatic reconnection
@@ -333,6 +442,74 @@ impl UnifiedAsyncClient {
         self
     }
 
+    /// Buffer messages sent while disconnected and deliver them after reconnecting
+    ///
+    /// Only effective together with [`with_reconnect`](Self::with_reconnect). See
+    /// [`session`](crate::io::session) for details.
+    ///
+    /// # Arguments
+    /// * `config` - Queue capacity, drop policy and time-to-live
+    pub fn with_offline_queue(mut self, config: OfflineQueueConfig) -> Self {
+        self.offline_queue = Some(OfflineQueue::new(config));
+        self
+    }
+
+    /// Number of messages waiting in the offline queue
+    pub fn queued_messages(&self) -> usize {
+        self.offline_queue.as_ref().map_or(0, |q| q.len())
+    }
+
+    /// Number of messages discarded by the offline queue (full or expired)
+    pub fn dropped_messages(&self) -> u64 {
+        self.offline_queue.as_ref().map_or(0, |q| q.dropped())
+    }
+
+    /// Streams restarted after reconnection, as (STT_* type name, device name)
+    pub fn active_streams(&self) -> Vec<(String, String)> {
+        self.streams.list()
+    }
+
+    /// Configure keepalive, heartbeats and timeouts
+    ///
+    /// Heartbeats and the idle timeout are serviced while the client waits in
+    /// [`receive`](Self::receive), [`receive_any`](Self::receive_any) or a query. When the
+    /// idle timeout expires the connection is dropped and, if enabled, re-established.
+    ///
+    /// # Arguments
+    /// * `config` - Liveness configuration
+    pub fn with_liveness(mut self, config: LivenessConfig) -> Self {
+        self.liveness = config;
+        if let Some(stream) = &mut self.stream {
+            Self::configure(stream, &self.liveness);
+        }
+        self.apply_tcp_keepalive();
+        self
+    }
+
+    /// Get the liveness configuration
+    pub fn liveness(&self) -> &LivenessConfig {
+        &self.liveness
+    }
+
+    /// Set the deadline of each `receive` call (`None` waits forever)
+    ///
+    /// An expired receive fails with [`TimedOut`](std::io::ErrorKind::TimedOut); partially
+    /// received data is kept and the connection stays open.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.liveness.read_timeout = timeout;
+    }
+
+    /// Set the deadline of each `send` call (`None` waits forever)
+    ///
+    /// An expired send closes the connection, since the message may have been partially
+    /// written. With reconnection enabled the message is sent again after reconnecting.
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.liveness.write_timeout = timeout;
+        if let Some(stream) = &mut self.stream {
+            stream.set_write_timeout(timeout);
+        }
+    }
+
     /// Enable or disable CRC verification
     pub fn set_verify_crc(&mut self, verify: bool) {
         self.verify_crc = verify;
@@ -350,16 +527,16 @@ impl UnifiedAsyncClient {
 
     /// Check if currently connected
     pub fn is_connected(&self) -> bool {
-        self.transport.is_some()
+        self.stream.is_some()
     }
 
     /// Ensure we have a valid connection, reconnecting if necessary
     async fn ensure_connected(&mut self) -> Result<()> {
-        if self.transport.is_some() {
+        if self.stream.is_some() {
             return Ok(());
         }
 
-        let Some(ref config) = self.reconnect_config else {
+        let Some(config) = self.reconnect_config.clone() else {
             return Err(IgtlError::Io(std::io::Error::new(
                 std::io::ErrorKind::NotConnected,
                 "Connection lost and reconnection is not enabled",
@@ -376,6 +553,8 @@ impl UnifiedAsyncClient {
                         max_attempts = max,
                         "Max reconnection attempts reached"
                     );
+                    self.events
+                        .emit(ConnectionEvent::GaveUp { attempts: attempt });
                     return Err(IgtlError::Io(std::io::Error::new(
                         std::io::ErrorKind::TimedOut,
                         "Max reconnection attempts exceeded",
@@ -383,7 +562,15 @@ impl UnifiedAsyncClient {
                 }
             }
 
-            let delay = config.delay_for_attempt(attempt);
+            let delay = if attempt > 0 {
+                config.delay_for_attempt(attempt)
+            } else {
+                Duration::ZERO
+            };
+            self.events.emit(ConnectionEvent::ReconnectAttempt {
+                attempt: attempt + 1,
+                delay,
+            });
             if attempt > 0 {
                 info!(
                     attempt = attempt + 1,
@@ -393,26 +580,13 @@ impl UnifiedAsyncClient {
                 sleep(delay).await;
             }
 
-            let result = if let Some(ref tls_config) = self.conn_params.tls_config {
-                // TLS reconnection
-                let hostname = self.conn_params.hostname.as_ref().unwrap();
-                let port = self.conn_params.port.unwrap();
-                Self::connect_with_tls(hostname, port, tls_config.clone()).await
-            } else {
-                // Plain TCP reconnection
-                Self::connect(&self.conn_params.addr).await
-            };
-
-            match result {
-                Ok(new_client) => {
-                    self.transport = new_client.transport;
-                    if attempt > 0 {
-                        self.reconnect_count += 1;
-                        info!(
-                            reconnect_count = self.reconnect_count,
-                            "Reconnection successful"
-                        );
-                    }
+            match self.reconnect_once().await {
+                Ok(()) => {
+                    info!(
+                        attempts = attempt + 1,
+                        reconnect_count = self.reconnect_count,
+                        "Reconnection successful"
+                    );
                     return Ok(());
                 }
                 Err(e) => {
@@ -427,28 +601,168 @@ impl UnifiedAsyncClient {
         }
     }
 
+    /// Make one connection attempt and restore the session on success
+    async fn reconnect_once(&mut self) -> Result<()> {
+        let (transport, peer) = Self::open_transport(&self.conn_params, &self.events).await?;
+
+        let mut stream = AsyncIgtlStream::new(transport);
+        Self::configure(&mut stream, &self.liveness);
+        self.stream = Some(stream);
+        self.peer = Some(peer);
+        self.events = self.events.connection(Some(peer));
+        self.apply_tcp_keepalive();
+
+        if let Err(e) = self.restore_session().await {
+            self.stream = None;
+            self.events.disconnected(self.peer, &e);
+            return Err(e);
+        }
+        self.reconnect_count += 1;
+        self.offline_attempts = 0;
+        self.next_offline_attempt = None;
+        Ok(())
+    }
+
+    /// Replay active streams, then deliver queued messages
+    ///
+    /// Everything is queued on the new connection before writing, so a restore cut short
+    /// by a cancelled receive is completed by the next operation instead of being lost or
+    /// interleaved with other messages.
+    async fn restore_session(&mut self) -> Result<()> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+
+        for frame in self.streams.frames() {
+            stream.queue(frame.to_vec());
+        }
+        let mut delivered = 0;
+        if let Some(queue) = &mut self.offline_queue {
+            while let Some(frame) = queue.front() {
+                stream.queue(frame.to_vec());
+                queue.pop();
+                delivered += 1;
+            }
+        }
+        with_timeout(
+            self.liveness.write_timeout,
+            "session restore",
+            stream.flush_tx(),
+        )
+        .await?;
+
+        if delivered > 0 {
+            info!(
+                delivered = delivered,
+                "Delivered messages queued while offline"
+            );
+        }
+        if !self.streams.is_empty() {
+            info!(streams = self.streams.len(), "Active streams restarted");
+        }
+        Ok(())
+    }
+
+    /// Single reconnection attempt for a send made while disconnected
+    ///
+    /// Attempts are spaced by the reconnect backoff so that sending at a high rate
+    /// during an outage does not hammer the server.
+    async fn try_reconnect_now(&mut self) -> bool {
+        if self
+            .next_offline_attempt
+            .is_some_and(|at| Instant::now() < at)
+        {
+            return false;
+        }
+        self.events.emit(ConnectionEvent::ReconnectAttempt {
+            attempt: self.offline_attempts + 1,
+            delay: Duration::ZERO,
+        });
+        match self.reconnect_once().await {
+            Ok(()) => {
+                info!(
+                    reconnect_count = self.reconnect_count,
+                    "Reconnection successful"
+                );
+                true
+            }
+            Err(e) => {
+                let delay = self.reconnect_config.as_ref().map_or(Duration::ZERO, |c| {
+                    c.delay_for_attempt(self.offline_attempts)
+                });
+                self.offline_attempts += 1;
+                self.next_offline_attempt = Some(Instant::now() + delay);
+                debug!(error = %e, retry_in_ms = delay.as_millis(), "Still disconnected");
+                false
+            }
+        }
+    }
+
+    /// Buffer a message sent while disconnected
+    fn enqueue_offline(&mut self, msg_type: &str, data: Vec<u8>) -> Result<()> {
+        // Stream requests are restored from the stream registry instead
+        if StreamRegistry::is_stream_control(msg_type) {
+            return Ok(());
+        }
+        if let Some(queue) = &mut self.offline_queue {
+            queue.push(data)?;
+            debug!(
+                msg_type = msg_type,
+                queued = queue.len(),
+                "Disconnected, message queued"
+            );
+        }
+        Ok(())
+    }
+
     /// Send a message
+    ///
+    /// STT_* / STP_* messages update the set of [active streams](Self::active_streams)
+    /// restarted after reconnection. With an [offline queue](Self::with_offline_queue),
+    /// a message sent while disconnected is queued if an immediate reconnection attempt
+    /// fails.
     pub async fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
         let data = msg.encode()?;
         let msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN");
         let device_name = msg.header.device_name.as_str().unwrap_or("UNKNOWN");
+        self.send_data(msg_type, device_name, data).await
+    }
+
+    /// Send a dynamically typed message, e.g. one received from another connection
+    ///
+    /// Behaves like [`send`](Self::send).
+    pub async fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        let data = msg.encode()?;
+        let device_name = msg.device_name().unwrap_or("UNKNOWN");
+        self.send_data(msg.message_type(), device_name, data).await
+    }
 
+    /// Send an encoded message, reconnecting or queueing it if configured
+    async fn send_data(&mut self, msg_type: &str, device_name: &str, data: Vec<u8>) -> Result<()> {
         debug!(
             msg_type = msg_type,
             device_name = device_name,
             size = data.len(),
             "Sending message"
         );
+        self.streams.record(msg_type, device_name, &data);
 
         loop {
+            if self.stream.is_none()
+                && self.offline_queue.is_some()
+                && self.reconnect_config.is_some()
+                && !self.try_reconnect_now().await
+            {
+                return self.enqueue_offline(msg_type, data);
+            }
+
             if self.reconnect_config.is_some() {
                 self.ensure_connected().await?;
             }
 
-            if let Some(transport) = &mut self.transport {
-                match transport.write_all(&data).await {
-                    Ok(_) => {
-                        transport.flush().await?;
+            if let Some(stream) = &mut self.stream {
+                match stream.write_frame(&data).await {
+                    Ok(()) => {
                         trace!(
                             msg_type = msg_type,
                             bytes_sent = data.len(),
@@ -457,13 +771,9 @@ impl UnifiedAsyncClient {
                         return Ok(());
                     }
                     Err(e) => {
-                        if self.reconnect_config.is_some() {
-                            warn!(error = %e, "Send failed, will reconnect");
-                            self.transport = None;
-                            // Loop will retry after reconnection
-                        } else {
-                            return Err(e);
-                        }
+                        warn!(error = %e, "Send failed");
+                        // Loop will retry after reconnection
+                        self.drop_connection(e)?;
                     }
                 }
             } else {
@@ -477,91 +787,24 @@ impl UnifiedAsyncClient {
 
     /// Receive a message
     pub async fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
-        loop {
-            if self.reconnect_config.is_some() {
-                self.ensure_connected().await?;
-            }
-
-            if let Some(transport) = &mut self.transport {
-                // Read header
-                let mut header_buf = vec![0u8; Header::SIZE];
-                match transport.read_exact(&mut header_buf).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        if self.reconnect_config.is_some() {
-                            warn!(error = %e, "Header read failed, will reconnect");
-                            self.transport = None;
-                            continue;
-                        } else {
-                            return Err(e);
-                        }
-                    }
-                }
-
-                let header = Header::decode(&header_buf)?;
-                let msg_type = header.type_name.as_str().unwrap_or("UNKNOWN");
-                let device_name = header.device_name.as_str().unwrap_or("UNKNOWN");
+        let frame = self.next_frame_timed().await?;
+        let result = IgtlMessage::decode_with_options(&frame, self.verify_crc);
+        self.events.check_decode(&result, self.peer);
 
+        match &result {
+            Ok(msg) => {
                 debug!(
-                    msg_type = msg_type,
-                    device_name = device_name,
-                    body_size = header.body_size,
-                    version = header.version,
-                    "Received message header"
-                );
-
-                // Read body
-                let mut body_buf = vec![0u8; header.body_size as usize];
-                match transport.read_exact(&mut body_buf).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        if self.reconnect_config.is_some() {
-                            warn!(error = %e, "Body read failed, will reconnect");
-                            self.transport = None;
-                            continue;
-                        } else {
-                            return Err(e);
-                        }
-                    }
-                }
-
-                trace!(
-                    msg_type = msg_type,
-                    bytes_read = body_buf.len(),
-                    "Message body received"
+                    msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN"),
+                    device_name = msg.header.device_name.as_str().unwrap_or("UNKNOWN"),
+                    "Message decoded successfully"
                 );
-
-                // Decode full message
-                let mut full_msg = header_buf;
-                full_msg.extend_from_slice(&body_buf);
-
-                let result = IgtlMessage::decode_with_options(&full_msg, self.verify_crc);
-
-                match &result {
-                    Ok(_) => {
-                        debug!(
-                            msg_type = msg_type,
-                            device_name = device_name,
-                            "Message decoded successfully"
-                        );
-                    }
-                    Err(e) => {
-                        warn!(
-                            msg_type = msg_type,
-                            error = %e,
-                            "Failed to decode message"
-                        );
-                    }
-                }
-
-                return result;
-            } else {
-                return Err(IgtlError::Io(std::io::Error::new(
-                    std::io::ErrorKind::NotConnected,
-                    "Not connected",
-                )));
+            }
+            Err(e) => {
+                warn!(error = %e, "Failed to decode message");
             }
         }
+
+        result
     }
 
     /// Receive any message type dynamically without knowing the type in advance
@@ -616,88 +859,840 @@ impl UnifiedAsyncClient {
     /// # }
     /// ```
     pub async fn receive_any(&mut self) -> Result<AnyMessage> {
+        let frame = self.next_frame_timed().await?;
+        let result = decode_any_frame(&frame, self.verify_crc);
+        self.events.check_decode(&result, self.peer);
+        result
+    }
+
+    /// Receive a message unless `token` is cancelled first
+    ///
+    /// Returns `Ok(None)` once `token` is cancelled. Partially received data, and the rest
+    /// of a heartbeat or session replay that was being written, are kept, so the client
+    /// stays usable.
+    pub async fn receive_until<T: Message>(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<IgtlMessage<T>>> {
+        unless_cancelled(self.receive(), token).await.transpose()
+    }
+
+    /// Receive any message type unless `token` is cancelled first
+    ///
+    /// See [`receive_until`](Self::receive_until).
+    pub async fn receive_any_until(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<AnyMessage>> {
+        unless_cancelled(self.receive_any(), token)
+            .await
+            .transpose()
+    }
+
+    /// Flush and close the connection without reconnecting
+    ///
+    /// TLS connections send a `close_notify` alert, so the server sees an orderly close
+    /// rather than a truncated stream. Closing a disconnected client does nothing.
+    pub async fn close(mut self) -> Result<()> {
+        let Some(mut stream) = self.stream.take() else {
+            return Ok(());
+        };
+        info!(addr = %self.conn_params.addr, "Closing connection (async)");
+        let result = stream.close().await;
+        self.events.disconnected(self.peer, &"closed by the client");
+        result
+    }
+
+    /// Send a query and wait for the matching reply
+    ///
+    /// Replies are matched by message type and device name (any device if `device_name`
+    /// is empty). Messages that arrive in the meantime are kept and returned by later
+    /// [`receive`](Self::receive) / [`receive_any`](Self::receive_any) calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `device_name` - Device name of the query and of the expected reply
+    /// * `request` - Query content (e.g. `GetTransformMessage`)
+    /// * `timeout` - Maximum time to wait for the reply
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network failure, or
+    ///   [`TimedOut`](std::io::ErrorKind::TimedOut) if no reply arrived in time
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::builder::ClientBuilder;
+    /// use openigtlink_rust::protocol::message::IgtlMessage;
+    /// use openigtlink_rust::protocol::types::{RtsTDataMessage, StartTDataMessage};
+    /// use std::time::Duration;
+    ///
+    /// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+    /// let mut client = ClientBuilder::new()
+    ///     .tcp("127.0.0.1:18944")
+    ///     .async_mode()
+    ///     .build()
+    ///     .await?;
+    ///
+    /// let ack: IgtlMessage<RtsTDataMessage> = client
+    ///     .query("Tracker", StartTDataMessage::new(50, "RAS"), Duration::from_secs(2))
+    ///     .await?;
+    /// println!("Stream accepted: {}", ack.content.status == 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn query<Req: Message, Resp: Message>(
+        &mut self,
+        device_name: &str,
+        request: Req,
+        timeout: Duration,
+    ) -> Result<IgtlMessage<Resp>> {
+        let msg = IgtlMessage::new(request, device_name)?;
+        self.query_message(&msg, timeout).await
+    }
+
+    /// Send a prepared query message and wait for the matching reply
+    ///
+    /// Like [`query`](Self::query), but if the message carries a v3 message ID
+    /// (see [`IgtlMessage::set_message_id`]), replies that also carry one must
+    /// echo the same ID.
+    pub async fn query_message<Req: Message, Resp: Message>(
+        &mut self,
+        request: &IgtlMessage<Req>,
+        timeout: Duration,
+    ) -> Result<IgtlMessage<Resp>> {
+        let matcher = ReplyMatcher::new::<Resp>(
+            request.header.device_name.as_str()?,
+            request.get_message_id(),
+        );
+        self.send(request).await?;
+
+        let mut skipped = VecDeque::new();
+        let result = tokio::time::timeout(timeout, async {
+            loop {
+                let frame = self.read_frame().await?;
+                if matcher.matches(&frame) {
+                    return Ok::<_, IgtlError>(frame);
+                }
+                trace!("Buffering unrelated message while waiting for reply");
+                skipped.push_back(frame);
+            }
+        })
+        .await;
+        self.pending.append(&mut skipped);
+
+        let frame = result.map_err(|_| timed_out(&matcher.describe()))??;
+        let result = IgtlMessage::decode_with_options(&frame, self.verify_crc);
+        self.events.check_decode(&result, self.peer);
+        result
+    }
+
+    /// Query the server's supported message types (GET_CAPABIL → CAPABILITY)
+    pub async fn get_capability(&mut self) -> Result<IgtlMessage<CapabilityMessage>> {
+        self.query("", GetCapabilityMessage, DEFAULT_QUERY_TIMEOUT)
+            .await
+    }
+
+    /// Query the status of a device (GET_STATUS → STATUS)
+    ///
+    /// The reply must come from `device_name`. With an empty device name the query
+    /// carries a v3 message ID and STATUS messages with another ID are skipped; if the
+    /// server does not echo the ID, the first STATUS without one is taken as the reply,
+    /// even if it was sent unsolicited just before it.
+    pub async fn get_status(&mut self, device_name: &str) -> Result<IgtlMessage<StatusMessage>> {
+        let query = status_query(device_name)?;
+        self.query_message(&query, DEFAULT_QUERY_TIMEOUT).await
+    }
+
+    /// Query the current transform of a device (GET_TRANSFOR → TRANSFORM)
+    pub async fn get_transform(
+        &mut self,
+        device_name: &str,
+    ) -> Result<IgtlMessage<TransformMessage>> {
+        self.query(device_name, GetTransformMessage, DEFAULT_QUERY_TIMEOUT)
+            .await
+    }
+
+    /// Number of messages buffered while waiting for query replies
+    pub fn pending_messages(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Split the client into independent read and write halves
+    ///
+    /// Lets one task wait in [`receive_any`](AsyncIgtlReader::receive_any) (or poll the
+    /// reader as a [`Stream`](futures::Stream)) while another sends. Messages buffered by
+    /// earlier queries are delivered first by the reader.
+    ///
+    /// The halves keep the read and write timeouts but do not reconnect or send
+    /// heartbeats; a lost connection ends the reader stream and fails writes.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Not currently connected
+    pub fn into_split(self) -> Result<(AsyncClientReader, AsyncClientWriter)> {
+        let Some(stream) = self.stream else {
+            return Err(IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::NotConnected,
+                "Cannot split a disconnected client",
+            )));
+        };
+
+        debug!(addr = %self.conn_params.addr, "Splitting client into read/write halves");
+
+        let (mut reader, mut writer) = stream.split_buffered(
+            |transport| -> (
+                Box<dyn AsyncRead + Send + Unpin>,
+                Box<dyn AsyncWrite + Send + Unpin>,
+            ) {
+                match transport {
+                    Transport::Plain(stream) => {
+                        let (reader, writer) = stream.into_split();
+                        (Box::new(reader), Box::new(writer))
+                    }
+                    Transport::Tls(stream) => {
+                        let (reader, writer) = tokio::io::split(*stream);
+                        (Box::new(reader), Box::new(writer))
+                    }
+                }
+            },
+            self.pending,
+        );
+        reader.set_verify_crc(self.verify_crc);
+        reader.set_read_timeout(self.liveness.read_timeout);
+        // The halves share the connection, which ends once both are dropped
+        reader.set_events(self.events.clone(), self.peer);
+        writer.set_events(self.events, self.peer);
+        Ok((reader, writer))
+    }
+
+    /// Next frame within the read timeout
+    async fn next_frame_timed(&mut self) -> Result<Vec<u8>> {
+        let read_timeout = self.liveness.read_timeout;
+        with_timeout(read_timeout, "message", self.next_frame()).await
+    }
+
+    /// Next frame: buffered messages first, then the transport
+    async fn next_frame(&mut self) -> Result<Vec<u8>> {
+        match self.pending.pop_front() {
+            Some(frame) => Ok(frame),
+            None => self.read_frame().await,
+        }
+    }
+
+    /// Read one complete frame from the connection, reconnecting if enabled
+    ///
+    /// The stream sends due heartbeats, consumes heartbeat replies and enforces the idle
+    /// timeout. Cancel-safe: partial data stays buffered if the future is dropped.
+    async fn read_frame(&mut self) -> Result<Vec<u8>> {
         loop {
             if self.reconnect_config.is_some() {
                 self.ensure_connected().await?;
             }
+            let Some(stream) = &mut self.stream else {
+                return Err(IgtlError::Io(std::io::Error::new(
+                    std::io::ErrorKind::NotConnected,
+                    "Not connected",
+                )));
+            };
 
-            if let Some(transport) = &mut self.transport {
-                // Read header
-                let mut header_buf = vec![0u8; Header::SIZE];
-                match transport.read_exact(&mut header_buf).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        if self.reconnect_config.is_some() {
-                            warn!(error = %e, "Header read failed, will reconnect");
-                            self.transport = None;
-                            continue;
-                        } else {
-                            return Err(e);
-                        }
+            match stream.read_frame().await {
+                Ok(frame) => {
+                    trace!(bytes_read = frame.len(), "Message received");
+                    return Ok(frame);
+                }
+                Err(IgtlError::Io(e)) => {
+                    warn!(error = %e, "Connection lost");
+                    self.drop_connection(IgtlError::Io(e))?;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Apply the heartbeat, idle timeout and write timeout of `liveness` to a connection
+    ///
+    /// The read timeout is applied by the client, since it does not cover queries.
+    fn configure(stream: &mut AsyncIgtlStream<Transport>, liveness: &LivenessConfig) {
+        stream.set_write_timeout(liveness.write_timeout);
+        stream.set_liveness(Liveness::new(liveness));
+    }
+
+    /// Declare the connection dead
+    ///
+    /// Returns `Ok` if it will be re-established by the next operation, or `error`
+    /// when reconnection is not enabled.
+    fn drop_connection(&mut self, error: IgtlError) -> Result<()> {
+        self.stream = None;
+        self.events.disconnected(self.peer, &error);
+        if self.reconnect_config.is_some() {
+            info!("Will reconnect");
+            Ok(())
+        } else {
+            Err(error)
+        }
+    }
+
+    /// Apply the configured TCP keepalive to the current connection
+    fn apply_tcp_keepalive(&self) {
+        let (Some(idle), Some(stream)) = (self.liveness.tcp_keepalive, &self.stream) else {
+            return;
+        };
+        if let Err(e) = set_tcp_keepalive(stream.get_ref().tcp_stream(), Some(idle)) {
+            warn!(error = %e, "Failed to enable TCP keepalive");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::liveness::HeartbeatConfig;
+    use crate::io::AsyncIgtlServer;
+
+    /// Server answering GET_TRANSFOR and GET_STATUS after an unrelated STATUS message
+    async fn spawn_server() -> std::net::SocketAddr {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut conn = server.accept().await.unwrap();
+            while let Ok(msg) = conn.receive_any().await {
+                if let AnyMessage::GetStatus(query) = &msg {
+                    // An unsolicited notice carrying a message ID of its own
+                    let mut status = IgtlMessage::new(StatusMessage::ok("busy"), "Robot").unwrap();
+                    status.set_message_id(u32::MAX);
+                    conn.send(&status).await.unwrap();
+                    let mut reply = IgtlMessage::new(StatusMessage::ok("ready"), "Server").unwrap();
+                    if let Some(id) = query.get_message_id() {
+                        reply.set_message_id(id);
+                    }
+                    conn.send(&reply).await.unwrap();
+                }
+                if let AnyMessage::GetTransform(query) = msg {
+                    let device = query.header.device_name.as_str().unwrap().to_string();
+                    let status = IgtlMessage::new(StatusMessage::ok("busy"), "Robot").unwrap();
+                    conn.send(&status).await.unwrap();
+                    let mut reply =
+                        IgtlMessage::new(TransformMessage::identity(), &device).unwrap();
+                    if let Some(id) = query.get_message_id() {
+                        reply.set_message_id(id);
                     }
+                    conn.send(&reply).await.unwrap();
                 }
+            }
+        });
+        addr
+    }
 
-                let header = Header::decode(&header_buf)?;
-                let msg_type = header.type_name.as_str().unwrap_or("UNKNOWN");
-                let device_name = header.device_name.as_str().unwrap_or("UNKNOWN");
+    #[tokio::test]
+    async fn test_query_buffers_unrelated_messages() {
+        let addr = spawn_server().await;
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap();
 
-                debug!(
-                    msg_type = msg_type,
-                    device_name = device_name,
-                    body_size = header.body_size,
-                    version = header.version,
-                    "Received message header"
+        let reply = client.get_transform("Needle").await.unwrap();
+        assert_eq!(reply.header.device_name.as_str().unwrap(), "Needle");
+        assert_eq!(client.pending_messages(), 1);
+
+        let buffered = client.receive_any().await.unwrap();
+        assert_eq!(buffered.message_type(), "STATUS");
+        assert_eq!(client.pending_messages(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_get_status_ignores_unsolicited_status() {
+        let addr = spawn_server().await;
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap();
+
+        let reply = client.get_status("").await.unwrap();
+        assert_eq!(reply.content.status_string, "ready");
+        assert_eq!(client.pending_messages(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_status_without_echoed_message_id() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut conn = server.accept().await.unwrap();
+            // A v2 server answers without the message ID of the query
+            let query = conn.receive_any().await.unwrap();
+            assert_eq!(query.message_type(), "GET_STATUS");
+            let reply = IgtlMessage::new(StatusMessage::ok("ready"), "Server").unwrap();
+            conn.send(&reply).await.unwrap();
+            let _ = conn.receive_any().await;
+        });
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap();
+
+        let reply = tokio::time::timeout(Duration::from_secs(1), client.get_status(""))
+            .await
+            .expect("no fallback to a reply without message ID")
+            .unwrap();
+        assert_eq!(reply.content.status_string, "ready");
+    }
+
+    #[tokio::test]
+    async fn test_split_halves_keep_buffered_messages() {
+        use futures::StreamExt;
+
+        let addr = spawn_server().await;
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap();
+        client.get_transform("Needle").await.unwrap();
+
+        let (mut reader, mut writer) = client.into_split().unwrap();
+        let buffered = reader.next().await.unwrap().unwrap();
+        assert_eq!(buffered.message_type(), "STATUS");
+
+        let reader_task = tokio::spawn(async move {
+            let mut types = Vec::new();
+            while types.len() < 2 {
+                types.push(
+                    reader
+                        .next()
+                        .await
+                        .unwrap()
+                        .unwrap()
+                        .message_type()
+                        .to_string(),
                 );
+            }
+            types
+        });
+        let query = IgtlMessage::new(GetTransformMessage, "Probe").unwrap();
+        writer.send(&query).await.unwrap();
 
-                // Read body
-                let mut body_buf = vec![0u8; header.body_size as usize];
-                match transport.read_exact(&mut body_buf).await {
-                    Ok(_) => {}
-                    Err(e) => {
-                        if self.reconnect_config.is_some() {
-                            warn!(error = %e, "Body read failed, will reconnect");
-                            self.transport = None;
-                            continue;
-                        } else {
-                            return Err(e);
-                        }
+        assert_eq!(reader_task.await.unwrap(), vec!["STATUS", "TRANSFORM"]);
+    }
+
+    #[tokio::test]
+    async fn test_query_message_id_and_timeout() {
+        let addr = spawn_server().await;
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap();
+
+        let mut query = IgtlMessage::new(GetTransformMessage, "Needle").unwrap();
+        query.set_message_id(42);
+        let reply: IgtlMessage<TransformMessage> = client
+            .query_message(&query, Duration::from_secs(1))
+            .await
+            .unwrap();
+        assert_eq!(reply.get_message_id(), Some(42));
+
+        // The server never answers GET_CAPABIL
+        let err = client
+            .query::<_, CapabilityMessage>("", GetCapabilityMessage, Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::TimedOut));
+    }
+
+    #[tokio::test]
+    async fn test_read_timeout_keeps_connection() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut conn = server.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            let status = IgtlMessage::new(StatusMessage::ok("late"), "Robot").unwrap();
+            conn.send(&status).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap();
+        client.set_read_timeout(Some(Duration::from_millis(50)));
+
+        match client.receive_any().await {
+            Err(IgtlError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::TimedOut),
+            other => panic!(
+                "expected timeout, got {:?}",
+                other.map(|m| m.message_type().to_string())
+            ),
+        }
+        assert!(client.is_connected());
+
+        client.set_read_timeout(None);
+        let msg = client.receive_any().await.unwrap();
+        assert_eq!(msg.message_type(), "STATUS");
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_triggers_reconnect() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            // First connection stays silent like a half-open socket
+            let _silent = server.accept().await.unwrap();
+            let mut conn = server.accept().await.unwrap();
+            let status = IgtlMessage::new(StatusMessage::ok("back"), "Robot").unwrap();
+            conn.send(&status).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap()
+            .with_liveness(LivenessConfig::new().with_idle_timeout(Duration::from_millis(100)))
+            .with_reconnect(ReconnectConfig::with_max_attempts(3));
+
+        let msg = tokio::time::timeout(Duration::from_secs(5), client.receive_any())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.message_type(), "STATUS");
+        assert_eq!(client.reconnect_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout_without_reconnect_fails() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let _silent = server.accept().await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap()
+            .with_liveness(LivenessConfig::new().with_idle_timeout(Duration::from_millis(50)));
+
+        assert!(client.receive_any().await.is_err());
+        assert!(!client.is_connected());
+    }
+
+    #[tokio::test]
+    async fn test_get_status_heartbeat_replies_are_consumed() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut conn = server.accept().await.unwrap();
+            let mut heartbeats = 0;
+            while let Ok(msg) = conn.receive_any().await {
+                if let AnyMessage::GetStatus(query) = msg {
+                    let device = query.header.device_name.as_str().unwrap().to_string();
+                    let mut reply = IgtlMessage::new(StatusMessage::ok("alive"), &device).unwrap();
+                    reply.set_message_id(query.get_message_id().unwrap());
+                    conn.send(&reply).await.unwrap();
+                    heartbeats += 1;
+                    if heartbeats == 3 {
+                        // Same device as the heartbeats, but not a reply
+                        let event = IgtlMessage::new(StatusMessage::ok("event"), &device).unwrap();
+                        conn.send(&event).await.unwrap();
+                        let done = IgtlMessage::new(TransformMessage::identity(), "Done").unwrap();
+                        conn.send(&done).await.unwrap();
                     }
                 }
+            }
+        });
 
-                trace!(
-                    msg_type = msg_type,
-                    bytes_read = body_buf.len(),
-                    "Message body received"
-                );
+        // Idle timeout shorter than the test: only heartbeat replies keep it alive
+        let liveness = LivenessConfig::new()
+            .with_heartbeat(HeartbeatConfig::get_status(Duration::from_millis(30)))
+            .with_idle_timeout(Duration::from_millis(200));
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap()
+            .with_liveness(liveness);
 
-                // Decode using MessageFactory
-                let factory = MessageFactory::new();
-                let result = factory.decode_any(&header, &body_buf, self.verify_crc);
+        let msg: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(msg.content.status_string, "event");
+        let msg = client.receive_any().await.unwrap();
+        assert_eq!(msg.message_type(), "TRANSFORM");
+        assert_eq!(msg.device_name().unwrap(), "Done");
+    }
 
-                match &result {
-                    Ok(msg) => {
-                        debug!(
-                            msg_type = msg.message_type(),
-                            device_name = device_name,
-                            "Message decoded successfully"
-                        );
+    #[tokio::test]
+    async fn test_cancel_during_heartbeat_write_keeps_framing() {
+        use crate::protocol::types::{ImageMessage, ImageScalarType};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let liveness = LivenessConfig::new()
+            .with_heartbeat(HeartbeatConfig::get_status(Duration::from_millis(5)));
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap()
+            .with_liveness(liveness);
+        let (server, _) = listener.accept().await.unwrap();
+
+        // The server does not read yet, so a large image fills the socket buffers and the
+        // send is cancelled mid-frame
+        let image =
+            ImageMessage::new(ImageScalarType::Uint8, [1024, 1024, 32], vec![7; 32 << 20]).unwrap();
+        let image = IgtlMessage::new(image, "Volume").unwrap();
+        let send = tokio::time::timeout(Duration::from_millis(100), client.send(&image)).await;
+        assert!(send.is_err());
+
+        // Receiving writes the rest before heartbeats; cancel it while the socket is full
+        let token = CancellationToken::new();
+        tokio::spawn({
+            let token = token.clone();
+            async move {
+                sleep(Duration::from_millis(50)).await;
+                token.cancel();
+            }
+        });
+        assert!(client.receive_any_until(&token).await.unwrap().is_none());
+        assert!(client.is_connected());
+
+        // Heartbeats resume once the image is through; stop at the first one
+        let heartbeat_seen = CancellationToken::new();
+        let reader = tokio::spawn({
+            let heartbeat_seen = heartbeat_seen.clone();
+            async move {
+                let mut conn = AsyncIgtlStream::new(server);
+                let mut types = Vec::new();
+                loop {
+                    let msg = conn.receive_any().await.unwrap();
+                    if let AnyMessage::Image(image) = &msg {
+                        assert_eq!(image.content.data.len(), 32 << 20);
                     }
-                    Err(e) => {
-                        warn!(
-                            msg_type = msg_type,
-                            error = %e,
-                            "Failed to decode message"
-                        );
+                    let msg_type = msg.message_type().to_string();
+                    if msg_type == "GET_STATUS" {
+                        heartbeat_seen.cancel();
+                    }
+                    if types.last() != Some(&msg_type) {
+                        types.push(msg_type);
+                    }
+                    if msg.device_name().unwrap() == "Client" {
+                        return types;
                     }
                 }
+            }
+        });
+        assert!(client
+            .receive_any_until(&heartbeat_seen)
+            .await
+            .unwrap()
+            .is_none());
+        let done = IgtlMessage::new(StatusMessage::ok("done"), "Client").unwrap();
+        client.send(&done).await.unwrap();
 
-                return result;
-            } else {
-                return Err(IgtlError::Io(std::io::Error::new(
-                    std::io::ErrorKind::NotConnected,
-                    "Not connected",
-                )));
+        assert_eq!(reader.await.unwrap(), vec!["IMAGE", "GET_STATUS", "STATUS"]);
+    }
+
+    #[tokio::test]
+    async fn test_session_restored_after_reconnect() {
+        use crate::io::session::OfflineQueueConfig;
+        use crate::protocol::types::StartTDataMessage;
+
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap()
+            .with_reconnect(ReconnectConfig::with_delays(
+                Duration::from_millis(10),
+                Duration::from_millis(50),
+            ))
+            .with_offline_queue(OfflineQueueConfig::new(8));
+
+        let start = IgtlMessage::new(StartTDataMessage::new(50, "RAS"), "Tracker").unwrap();
+        client.send(&start).await.unwrap();
+
+        // Server receives the stream request, then goes away completely
+        let mut conn = server.accept().await.unwrap();
+        assert_eq!(
+            conn.receive_any().await.unwrap().message_type(),
+            "STT_TDATA"
+        );
+        drop(conn);
+        drop(server);
+
+        // Client notices the outage; reconnection keeps failing
+        let _ = tokio::time::timeout(Duration::from_millis(100), client.receive_any()).await;
+        assert!(!client.is_connected());
+
+        let command = IgtlMessage::new(TransformMessage::identity(), "Needle").unwrap();
+        client.send(&command).await.unwrap();
+        assert_eq!(client.queued_messages(), 1);
+
+        // Server comes back on the same address
+        let server = AsyncIgtlServer::bind(&addr.to_string()).await.unwrap();
+        let server_task = tokio::spawn(async move {
+            let mut conn = server.accept().await.unwrap();
+            let first = conn.receive_any().await.unwrap();
+            let second = conn.receive_any().await.unwrap();
+            let ack = IgtlMessage::new(StatusMessage::ok("restored"), "Server").unwrap();
+            conn.send(&ack).await.unwrap();
+            (
+                first.message_type().to_string(),
+                second.message_type().to_string(),
+            )
+        });
+
+        let msg = tokio::time::timeout(Duration::from_secs(5), client.receive_any())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.message_type(), "STATUS");
+        assert_eq!(
+            server_task.await.unwrap(),
+            ("STT_TDATA".to_string(), "TRANSFORM".to_string())
+        );
+        assert_eq!(client.queued_messages(), 0);
+        assert_eq!(client.reconnect_count(), 1);
+        assert_eq!(
+            client.active_streams(),
+            vec![("STT_TDATA".to_string(), "Tracker".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lifecycle_events_across_reconnect() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            // Drop the first session, answer on the second
+            drop(server.accept().await.unwrap());
+            let mut conn = server.accept().await.unwrap();
+            let status = IgtlMessage::new(StatusMessage::ok("back"), "Robot").unwrap();
+            conn.send(&status).await.unwrap();
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        });
+
+        let (tx, mut events) = broadcast::channel(16);
+        let mut client = UnifiedAsyncClient::connect_with_params(
+            ConnectionParams::plain(&addr.to_string()),
+            EventEmitter::new(tx),
+        )
+        .await
+        .unwrap()
+        .with_reconnect(ReconnectConfig::with_max_attempts(3));
+
+        let msg = tokio::time::timeout(Duration::from_secs(5), client.receive_any())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(msg.message_type(), "STATUS");
+
+        let mut seen = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            seen.push(event);
+        }
+        assert!(matches!(seen[0], ConnectionEvent::Connecting { .. }));
+        assert_eq!(seen[1], ConnectionEvent::Connected { peer: addr });
+        assert!(
+            matches!(seen[2], ConnectionEvent::Disconnected { peer: Some(p), .. } if p == addr)
+        );
+        assert_eq!(
+            seen[3],
+            ConnectionEvent::ReconnectAttempt {
+                attempt: 1,
+                delay: Duration::ZERO
+            }
+        );
+        assert!(matches!(seen[4], ConnectionEvent::Connecting { .. }));
+        assert_eq!(seen[5], ConnectionEvent::Connected { peer: addr });
+    }
+
+    fn disconnects(events: &mut broadcast::Receiver<ConnectionEvent>) -> Vec<String> {
+        let mut reasons = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let ConnectionEvent::Disconnected { reason, .. } = event {
+                reasons.push(reason);
+            }
+        }
+        reasons
+    }
+
+    #[tokio::test]
+    async fn test_close_and_drop_report_disconnect() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        let (tx, mut events) = broadcast::channel(16);
+
+        let client = UnifiedAsyncClient::connect_with_params(
+            ConnectionParams::plain(&addr),
+            EventEmitter::new(tx.clone()),
+        )
+        .await
+        .unwrap();
+        client.close().await.unwrap();
+        assert_eq!(disconnects(&mut events), ["closed by the client"]);
+
+        let client = UnifiedAsyncClient::connect_with_params(
+            ConnectionParams::plain(&addr),
+            EventEmitter::new(tx),
+        )
+        .await
+        .unwrap();
+        drop(client);
+        assert_eq!(disconnects(&mut events), ["connection dropped"]);
+    }
+
+    #[tokio::test]
+    async fn test_failed_restore_reports_disconnect() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        // Accept connections but never read from them
+        let accepting = tokio::spawn(async move {
+            let mut sessions = Vec::new();
+            while let Ok((session, _)) = listener.accept().await {
+                sessions.push(session);
             }
+        });
+        let (tx, mut events) = broadcast::channel(16);
+        let mut client = UnifiedAsyncClient::connect_with_params(
+            ConnectionParams::plain(&addr),
+            EventEmitter::new(tx),
+        )
+        .await
+        .unwrap();
+        client.liveness.write_timeout = Some(Duration::from_millis(50));
+        let mut queue = OfflineQueue::new(OfflineQueueConfig::new(1));
+        queue.push(vec![0; 32 << 20]).unwrap();
+        client.offline_queue = Some(queue);
+
+        let lost = IgtlError::Io(std::io::ErrorKind::ConnectionReset.into());
+        assert!(client.drop_connection(lost).is_err());
+        // The queued frame does not fit into the socket buffers of the new connection
+        let err = client.reconnect_once().await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::TimedOut));
+        assert!(!client.is_connected());
+
+        let reasons = disconnects(&mut events);
+        assert_eq!(reasons.len(), 2, "{reasons:?}");
+        assert_eq!(reasons[1], err.to_string());
+        drop(client);
+        assert!(disconnects(&mut events).is_empty());
+        accepting.abort();
+    }
+
+    #[tokio::test]
+    async fn test_gave_up_event() {
+        let server = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        let accept = tokio::spawn(async move { server.accept().await.unwrap() });
+
+        let mut client = UnifiedAsyncClient::connect(&addr.to_string())
+            .await
+            .unwrap()
+            .with_reconnect(ReconnectConfig {
+                max_attempts: Some(2),
+                ..ReconnectConfig::with_delays(Duration::from_millis(1), Duration::from_millis(1))
+            });
+        let mut events = client.subscribe_events();
+        // Close the session and the listener
+        drop(accept.await.unwrap());
+
+        assert!(client.receive_any().await.is_err());
+        let mut last = None;
+        while let Ok(event) = events.try_recv() {
+            last = Some(event);
         }
+        assert_eq!(last, Some(ConnectionEvent::GaveUp { attempts: 2 }));
     }
 }
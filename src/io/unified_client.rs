@@ -3,14 +3,19 @@
 //! This module provides simplified client enums that delegate to internal implementations.
 
 use crate::error::Result;
+use crate::io::events::ConnectionEvent;
+use crate::io::shutdown::CancellationToken;
 use crate::io::sync_client::SyncTcpClient;
-use crate::io::unified_async_client::UnifiedAsyncClient;
+use crate::io::unified_async_client::{AsyncClientReader, AsyncClientWriter, UnifiedAsyncClient};
 use crate::protocol::any_message::AnyMessage;
 use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::types::{CapabilityMessage, StatusMessage, TransformMessage};
+use std::time::Duration;
+use tokio::sync::broadcast;
 
 /// Synchronous OpenIGTLink client
 ///
-/// Simple wrapper around the synchronous TCP client.
+/// Simple wrapper around the synchronous TCP client, with or without TLS.
 ///
 /// **Recommended**: Use [`ClientBuilder`](crate::io::builder::ClientBuilder):
 /// ```no_run
@@ -22,9 +27,21 @@ use crate::protocol::message::{IgtlMessage, Message};
 pub enum SyncIgtlClient {
     /// Standard TCP synchronous client
     TcpSync(SyncTcpClient),
+    /// TLS-encrypted synchronous client
+    Tls(SyncTcpClient),
 }
 
 impl SyncIgtlClient {
+    /// Subscribe to connection lifecycle events
+    #[inline(always)]
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        match self {
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => {
+                client.subscribe_events()
+            }
+        }
+    }
+
     /// Send a message to the server
     ///
     /// # Arguments
@@ -35,7 +52,7 @@ impl SyncIgtlClient {
     #[inline(always)]
     pub fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
         match self {
-            SyncIgtlClient::TcpSync(client) => client.send(msg),
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => client.send(msg),
         }
     }
 
@@ -46,7 +63,7 @@ impl SyncIgtlClient {
     #[inline(always)]
     pub fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
         match self {
-            SyncIgtlClient::TcpSync(client) => client.receive(),
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => client.receive(),
         }
     }
 
@@ -57,7 +74,9 @@ impl SyncIgtlClient {
     #[inline(always)]
     pub fn set_verify_crc(&mut self, verify: bool) {
         match self {
-            SyncIgtlClient::TcpSync(client) => client.set_verify_crc(verify),
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => {
+                client.set_verify_crc(verify)
+            }
         }
     }
 
@@ -71,7 +90,9 @@ impl SyncIgtlClient {
     #[inline(always)]
     pub fn set_read_timeout(&self, timeout: Option<std::time::Duration>) -> Result<()> {
         match self {
-            SyncIgtlClient::TcpSync(client) => client.set_read_timeout(timeout),
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => {
+                client.set_read_timeout(timeout)
+            }
         }
     }
 
@@ -82,7 +103,77 @@ impl SyncIgtlClient {
     #[inline(always)]
     pub fn receive_any(&mut self) -> Result<AnyMessage> {
         match self {
-            SyncIgtlClient::TcpSync(client) => client.receive_any(),
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => client.receive_any(),
+        }
+    }
+
+    /// Send a query and wait for the matching reply
+    ///
+    /// Replies are correlated by type and device (and v3 message ID when present);
+    /// unrelated messages received meanwhile are buffered for later `receive` calls.
+    ///
+    /// # Arguments
+    /// * `device_name` - Queried device (empty for any device)
+    /// * `request` - Query content (e.g. `GetTransformMessage`)
+    /// * `timeout` - Maximum time to wait for the reply
+    ///
+    /// # Returns
+    /// The reply message, or a `TimedOut` I/O error
+    #[inline(always)]
+    pub fn query<Req: Message, Resp: Message>(
+        &mut self,
+        device_name: &str,
+        request: Req,
+        timeout: Duration,
+    ) -> Result<IgtlMessage<Resp>> {
+        match self {
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => {
+                client.query(device_name, request, timeout)
+            }
+        }
+    }
+
+    /// Send a prepared query message (e.g. with a v3 message ID) and wait for the reply
+    #[inline(always)]
+    pub fn query_message<Req: Message, Resp: Message>(
+        &mut self,
+        request: &IgtlMessage<Req>,
+        timeout: Duration,
+    ) -> Result<IgtlMessage<Resp>> {
+        match self {
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => {
+                client.query_message(request, timeout)
+            }
+        }
+    }
+
+    /// Query the server's supported message types
+    #[inline(always)]
+    pub fn get_capability(&mut self) -> Result<IgtlMessage<CapabilityMessage>> {
+        match self {
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => {
+                client.get_capability()
+            }
+        }
+    }
+
+    /// Query the status of a device
+    #[inline(always)]
+    pub fn get_status(&mut self, device_name: &str) -> Result<IgtlMessage<StatusMessage>> {
+        match self {
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => {
+                client.get_status(device_name)
+            }
+        }
+    }
+
+    /// Query the current transform of a device
+    #[inline(always)]
+    pub fn get_transform(&mut self, device_name: &str) -> Result<IgtlMessage<TransformMessage>> {
+        match self {
+            SyncIgtlClient::TcpSync(client) | SyncIgtlClient::Tls(client) => {
+                client.get_transform(device_name)
+            }
         }
     }
 }
@@ -121,6 +212,20 @@ impl AsyncIgtlClient {
         }
     }
 
+    /// Send a dynamically typed message to the server asynchronously
+    ///
+    /// # Arguments
+    /// * `msg` - Message to send, e.g. one received from another connection
+    ///
+    /// # Returns
+    /// Ok(()) on success, error otherwise
+    #[inline(always)]
+    pub async fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.send_any(msg).await,
+        }
+    }
+
     /// Receive a message from the server asynchronously
     ///
     /// # Returns
@@ -154,6 +259,70 @@ impl AsyncIgtlClient {
         }
     }
 
+    /// Set the deadline of each receive call (`None` waits forever)
+    ///
+    /// # Arguments
+    /// * `timeout` - Maximum time to wait for a message
+    #[inline(always)]
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.set_read_timeout(timeout),
+        }
+    }
+
+    /// Set the deadline of each send call (`None` waits forever)
+    ///
+    /// # Arguments
+    /// * `timeout` - Maximum time to write a message
+    #[inline(always)]
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.set_write_timeout(timeout),
+        }
+    }
+
+    /// Check if currently connected
+    #[inline(always)]
+    pub fn is_connected(&self) -> bool {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.is_connected(),
+        }
+    }
+
+    /// Subscribe to connection lifecycle events
+    ///
+    /// See [`events`](crate::io::events) for the event types.
+    #[inline(always)]
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.subscribe_events(),
+        }
+    }
+
+    /// Number of messages waiting in the offline queue
+    #[inline(always)]
+    pub fn queued_messages(&self) -> usize {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.queued_messages(),
+        }
+    }
+
+    /// Number of messages discarded by the offline queue
+    #[inline(always)]
+    pub fn dropped_messages(&self) -> u64 {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.dropped_messages(),
+        }
+    }
+
+    /// Streams restarted after reconnection, as (STT_* type name, device name)
+    #[inline(always)]
+    pub fn active_streams(&self) -> Vec<(String, String)> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.active_streams(),
+        }
+    }
+
     /// Receive any message type dynamically without knowing the type in advance
     ///
     /// # Returns
@@ -164,4 +333,115 @@ impl AsyncIgtlClient {
             AsyncIgtlClient::Unified(client) => client.receive_any().await,
         }
     }
+
+    /// Receive a message unless `token` is cancelled first
+    ///
+    /// # Returns
+    /// Decoded message, `None` once `token` is cancelled, or error
+    #[inline(always)]
+    pub async fn receive_until<T: Message>(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<IgtlMessage<T>>> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.receive_until(token).await,
+        }
+    }
+
+    /// Receive any message type unless `token` is cancelled first
+    ///
+    /// # Returns
+    /// Decoded message, `None` once `token` is cancelled, or error
+    #[inline(always)]
+    pub async fn receive_any_until(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<AnyMessage>> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.receive_any_until(token).await,
+        }
+    }
+
+    /// Flush and close the connection (with TLS `close_notify`)
+    #[inline(always)]
+    pub async fn close(self) -> Result<()> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.close().await,
+        }
+    }
+
+    /// Send a query and wait for the matching reply
+    ///
+    /// Replies are correlated by type and device (and v3 message ID when present);
+    /// unrelated messages received meanwhile are buffered for later `receive` calls.
+    ///
+    /// # Arguments
+    /// * `device_name` - Queried device (empty for any device)
+    /// * `request` - Query content (e.g. `GetTransformMessage`)
+    /// * `timeout` - Maximum time to wait for the reply
+    ///
+    /// # Returns
+    /// The reply message, or a `TimedOut` I/O error
+    #[inline(always)]
+    pub async fn query<Req: Message, Resp: Message>(
+        &mut self,
+        device_name: &str,
+        request: Req,
+        timeout: Duration,
+    ) -> Result<IgtlMessage<Resp>> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.query(device_name, request, timeout).await,
+        }
+    }
+
+    /// Send a prepared query message (e.g. with a v3 message ID) and wait for the reply
+    #[inline(always)]
+    pub async fn query_message<Req: Message, Resp: Message>(
+        &mut self,
+        request: &IgtlMessage<Req>,
+        timeout: Duration,
+    ) -> Result<IgtlMessage<Resp>> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.query_message(request, timeout).await,
+        }
+    }
+
+    /// Query the server's supported message types
+    #[inline(always)]
+    pub async fn get_capability(&mut self) -> Result<IgtlMessage<CapabilityMessage>> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.get_capability().await,
+        }
+    }
+
+    /// Query the status of a device
+    #[inline(always)]
+    pub async fn get_status(&mut self, device_name: &str) -> Result<IgtlMessage<StatusMessage>> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.get_status(device_name).await,
+        }
+    }
+
+    /// Split the client into independent read and write halves
+    ///
+    /// The reader is a [`Stream`](futures::Stream) of received messages and the writer a
+    /// [`Sink`](futures::Sink) of outgoing messages. See
+    /// [`UnifiedAsyncClient::into_split`] for details.
+    #[inline(always)]
+    pub fn into_split(self) -> Result<(AsyncClientReader, AsyncClientWriter)> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.into_split(),
+        }
+    }
+
+    /// Query the current transform of a device
+    #[inline(always)]
+    pub async fn get_transform(
+        &mut self,
+        device_name: &str,
+    ) -> Result<IgtlMessage<TransformMessage>> {
+        match self {
+            AsyncIgtlClient::Unified(client) => client.get_transform(device_name).await,
+        }
+    }
 }
@@ -0,0 +1,433 @@
+//! Unix domain socket transport
+//!
+//! OpenIGTLink over local stream sockets, for peers on the same host that do not
+//! need the TCP stack. Framing, CRC handling and the `send` / `receive` /
+//! `receive_any` API are the same as for TCP connections.
+//!
+//! Unix domain connections are symmetric: the client types
+//! ([`UnixIgtlClient`], [`AsyncUnixIgtlClient`]) are connections opened with
+//! `connect`, and servers hand out the same types from `accept`. Both ends can look up
+//! the credentials of the process on the other side with `peer_credentials`, e.g. to
+//! only accept sessions from a given user.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::builder::ClientBuilder;
+//! use openigtlink_rust::io::unix::AsyncUnixIgtlServer;
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::StatusMessage;
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let server = AsyncUnixIgtlServer::bind("/run/igtl.sock")?;
+//! tokio::spawn(async move {
+//!     let mut conn = server.accept().await?;
+//!     if conn.peer_credentials()?.uid != 0 {
+//!         return Ok(());
+//!     }
+//!     let msg = conn.receive_any().await?;
+//!     println!("Received {}", msg.message_type());
+//!     Ok::<(), openigtlink_rust::error::IgtlError>(())
+//! });
+//!
+//! let mut client = ClientBuilder::new()
+//!     .unix("/run/igtl.sock")
+//!     .async_mode()
+//!     .build()
+//!     .await?;
+//! client
+//!     .send(&IgtlMessage::new(StatusMessage::ok("Ready"), "Client")?)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tracing::{info, trace};
+
+use crate::error::Result;
+use crate::io::split::{AsyncIgtlReader, AsyncIgtlWriter};
+use crate::io::stream::{AsyncIgtlStream, IgtlStream};
+
+/// Credentials of the process at the other end of a Unix domain connection
+///
+/// Taken by the kernel when the connection was established, so they cannot be forged
+/// by the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeerCredentials {
+    /// Effective user ID of the peer
+    pub uid: u32,
+    /// Effective group ID of the peer
+    pub gid: u32,
+    /// Process ID of the peer, where the platform reports it (Linux, Android)
+    pub pid: Option<i32>,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn peer_credentials(fd: RawFd) -> Result<PeerCredentials> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    let ret = unsafe {
+        libc::getsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut _ as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(PeerCredentials {
+        uid: cred.uid,
+        gid: cred.gid,
+        pid: Some(cred.pid),
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn peer_credentials(fd: RawFd) -> Result<PeerCredentials> {
+    let mut uid = 0;
+    let mut gid = 0;
+
+    let ret = unsafe { libc::getpeereid(fd, &mut uid, &mut gid) };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(PeerCredentials {
+        uid,
+        gid,
+        pid: None,
+    })
+}
+
+// ============================================================================
+// Synchronous
+// ============================================================================
+
+/// Synchronous OpenIGTLink server on a Unix domain socket
+///
+/// The socket file is created by [`bind`](Self::bind) and removed when the server is
+/// dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use openigtlink_rust::io::unix::UnixIgtlServer;
+///
+/// let server = UnixIgtlServer::bind("/run/igtl.sock")?;
+/// let mut conn = server.accept()?;
+/// let msg = conn.receive_any()?;
+/// # Ok::<(), openigtlink_rust::error::IgtlError>(())
+/// ```
+pub struct UnixIgtlServer {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl UnixIgtlServer {
+    /// Create the socket file at `path` and listen on it
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind, e.g.
+    ///   [`AddrInUse`](std::io::ErrorKind::AddrInUse) if the path already exists
+    pub fn bind(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        info!(path = %path.display(), "Binding OpenIGTLink Unix socket server");
+        let listener = UnixListener::bind(&path)?;
+        Ok(UnixIgtlServer { listener, path })
+    }
+
+    /// Accept a new connection, blocking until a client connects
+    pub fn accept(&self) -> Result<UnixIgtlConnection> {
+        trace!("Waiting for Unix socket connection");
+        let (stream, _) = self.listener.accept()?;
+        info!(path = %self.path.display(), "Unix socket client connected");
+        Ok(IgtlStream::new(stream))
+    }
+
+    /// Path of the socket file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for UnixIgtlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Synchronous OpenIGTLink connection over a Unix domain socket
+///
+/// Returned by [`UnixIgtlServer::accept`] and [`UnixIgtlConnection::connect`]. The
+/// generic [`IgtlStream`] provides `send`, `receive`, `receive_any` and `split`.
+pub type UnixIgtlConnection = IgtlStream<UnixStream>;
+
+/// Synchronous OpenIGTLink client over a Unix domain socket
+///
+/// Build with [`ClientBuilder::unix`](crate::io::builder::ClientBuilder::unix) or
+/// [`UnixIgtlConnection::connect`].
+pub type UnixIgtlClient = UnixIgtlConnection;
+
+impl IgtlStream<UnixStream> {
+    /// Connect to a server listening at `path`
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::unix::UnixIgtlConnection;
+    ///
+    /// let mut client = UnixIgtlConnection::connect("/run/igtl.sock")?;
+    /// # Ok::<(), openigtlink_rust::error::IgtlError>(())
+    /// ```
+    pub fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        info!(path = %path.display(), "Connecting to OpenIGTLink Unix socket server");
+        Ok(IgtlStream::new(UnixStream::connect(path)?))
+    }
+
+    /// Set the read timeout (`None` blocks forever, the default)
+    ///
+    /// A timed-out receive keeps partially received data for the next call.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.get_ref().set_read_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Set the write timeout (`None` blocks forever, the default)
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> Result<()> {
+        self.get_ref().set_write_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Credentials of the process at the other end of the connection
+    pub fn peer_credentials(&self) -> Result<PeerCredentials> {
+        peer_credentials(self.get_ref().as_raw_fd())
+    }
+}
+
+// ============================================================================
+// Asynchronous
+// ============================================================================
+
+/// Asynchronous OpenIGTLink server on a Unix domain socket
+///
+/// The socket file is created by [`bind`](Self::bind) and removed when the server is
+/// dropped. Must be created inside a Tokio runtime.
+pub struct AsyncUnixIgtlServer {
+    listener: tokio::net::UnixListener,
+    path: PathBuf,
+}
+
+impl AsyncUnixIgtlServer {
+    /// Create the socket file at `path` and listen on it
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind, e.g.
+    ///   [`AddrInUse`](std::io::ErrorKind::AddrInUse) if the path already exists
+    pub fn bind(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        info!(path = %path.display(), "Binding OpenIGTLink Unix socket server (async)");
+        let listener = tokio::net::UnixListener::bind(&path)?;
+        Ok(AsyncUnixIgtlServer { listener, path })
+    }
+
+    /// Accept a new connection
+    pub async fn accept(&self) -> Result<AsyncUnixIgtlConnection> {
+        trace!("Waiting for Unix socket connection (async)");
+        let (stream, _) = self.listener.accept().await?;
+        info!(path = %self.path.display(), "Unix socket client connected (async)");
+        Ok(AsyncIgtlStream::new(stream))
+    }
+
+    /// Path of the socket file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for AsyncUnixIgtlServer {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Read half of an async Unix domain connection
+pub type AsyncUnixIgtlReader = AsyncIgtlReader<OwnedReadHalf>;
+
+/// Write half of an async Unix domain connection
+pub type AsyncUnixIgtlWriter = AsyncIgtlWriter<OwnedWriteHalf>;
+
+/// Asynchronous OpenIGTLink connection over a Unix domain socket
+///
+/// Returned by [`AsyncUnixIgtlServer::accept`] and [`AsyncUnixIgtlConnection::connect`].
+/// The generic [`AsyncIgtlStream`] provides `send`, `receive`, `receive_any`, timeouts
+/// and splitting.
+pub type AsyncUnixIgtlConnection = AsyncIgtlStream<tokio::net::UnixStream>;
+
+/// Asynchronous OpenIGTLink client over a Unix domain socket
+///
+/// Build with [`ClientBuilder::unix`](crate::io::builder::ClientBuilder::unix) or
+/// [`AsyncUnixIgtlConnection::connect`].
+pub type AsyncUnixIgtlClient = AsyncUnixIgtlConnection;
+
+impl AsyncIgtlStream<tokio::net::UnixStream> {
+    /// Connect to a server listening at `path`
+    pub async fn connect(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        info!(path = %path.display(), "Connecting to OpenIGTLink Unix socket server (async)");
+        Ok(AsyncIgtlStream::new(
+            tokio::net::UnixStream::connect(path).await?,
+        ))
+    }
+
+    /// Credentials of the process at the other end of the connection
+    pub fn peer_credentials(&self) -> Result<PeerCredentials> {
+        peer_credentials(self.get_ref().as_raw_fd())
+    }
+
+    /// Split the connection into read and write halves without locking
+    ///
+    /// The reader is a [`Stream`](futures::Stream) of messages and the writer a
+    /// [`Sink`](futures::Sink).
+    pub fn into_split(self) -> (AsyncUnixIgtlReader, AsyncUnixIgtlWriter) {
+        self.split_with(tokio::net::UnixStream::into_split)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IgtlError;
+    use crate::protocol::message::IgtlMessage;
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+    use crate::protocol::AnyMessage;
+    use std::io::Write;
+
+    fn socket_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("igtl-{}-{}.sock", std::process::id(), name));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_sync_round_trip_and_credentials() {
+        let server = UnixIgtlServer::bind(socket_path("sync")).unwrap();
+        let path = server.path().to_path_buf();
+
+        let handle = std::thread::spawn(move || {
+            let mut conn = server.accept().unwrap();
+            let creds = conn.peer_credentials().unwrap();
+            let msg: IgtlMessage<StatusMessage> = conn.receive().unwrap();
+            assert_eq!(msg.content.status_string, "Hello");
+            conn.send(&IgtlMessage::new(TransformMessage::identity(), "Server").unwrap())
+                .unwrap();
+            creds
+        });
+
+        let mut client = UnixIgtlConnection::connect(&path).unwrap();
+        client
+            .send(&IgtlMessage::new(StatusMessage::ok("Hello"), "Client").unwrap())
+            .unwrap();
+        match client.receive_any().unwrap() {
+            AnyMessage::Transform(msg) => {
+                assert_eq!(msg.header.device_name.as_str().unwrap(), "Server")
+            }
+            other => panic!("unexpected message {}", other.message_type()),
+        }
+
+        let creds = handle.join().unwrap();
+        assert_eq!(creds.uid, unsafe { libc::getuid() });
+        assert_eq!(creds, client.peer_credentials().unwrap());
+        #[cfg(target_os = "linux")]
+        assert_eq!(creds.pid, Some(std::process::id() as i32));
+    }
+
+    #[test]
+    fn test_sync_server_removes_socket_file() {
+        let path = socket_path("drop");
+        let server = UnixIgtlServer::bind(&path).unwrap();
+        assert!(path.exists());
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_sync_read_timeout_keeps_partial_frame() {
+        let server = UnixIgtlServer::bind(socket_path("timeout")).unwrap();
+        let mut client = UnixIgtlConnection::connect(server.path()).unwrap();
+        let mut conn = server.accept().unwrap();
+        conn.set_read_timeout(Some(Duration::from_millis(50)))
+            .unwrap();
+
+        let data = IgtlMessage::new(StatusMessage::ok("Split"), "Client")
+            .unwrap()
+            .encode()
+            .unwrap();
+        client.get_mut().write_all(&data[..30]).unwrap();
+        let err = conn.receive_any().unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::TimedOut));
+
+        client.get_mut().write_all(&data[30..]).unwrap();
+        let msg: IgtlMessage<StatusMessage> = conn.receive().unwrap();
+        assert_eq!(msg.content.status_string, "Split");
+    }
+
+    #[tokio::test]
+    async fn test_async_round_trip_and_split() {
+        let server = AsyncUnixIgtlServer::bind(socket_path("async")).unwrap();
+        let path = server.path().to_path_buf();
+
+        let handle = tokio::spawn(async move {
+            let conn = server.accept().await.unwrap();
+            let creds = conn.peer_credentials().unwrap();
+            let (mut reader, mut writer) = conn.into_split();
+            let msg = reader.receive_any().await.unwrap();
+            writer.send_any(&msg).await.unwrap();
+            creds
+        });
+
+        let mut client = AsyncUnixIgtlConnection::connect(&path).await.unwrap();
+        client.set_verify_crc(false);
+        client
+            .send(&IgtlMessage::new(StatusMessage::ok("Echo"), "Client").unwrap())
+            .await
+            .unwrap();
+        let reply: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(reply.content.status_string, "Echo");
+
+        let creds = handle.await.unwrap();
+        assert_eq!(creds.uid, unsafe { libc::getuid() });
+        assert_eq!(creds.gid, client.peer_credentials().unwrap().gid);
+    }
+
+    #[tokio::test]
+    async fn test_async_peer_close() {
+        let server = AsyncUnixIgtlServer::bind(socket_path("close")).unwrap();
+        let mut client = AsyncUnixIgtlConnection::connect(server.path())
+            .await
+            .unwrap();
+        drop(server.accept().await.unwrap());
+
+        let err = client.receive_any().await.unwrap_err();
+        assert!(
+            matches!(err, IgtlError::Io(ref e) if e.kind() == std::io::ErrorKind::UnexpectedEof)
+        );
+    }
+}
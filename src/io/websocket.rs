@@ -0,0 +1,957 @@
+//! WebSocket transport for browser-based clients
+//!
+//! Browsers cannot open raw TCP sockets, so this module carries OpenIGTLink over
+//! WebSocket: each binary frame holds exactly one complete message (header, extended
+//! header, content and metadata), encoded and decoded exactly as on TCP. Secure
+//! WebSockets (`wss://`) run over the same rustls configuration as the TLS servers
+//! and clients.
+//!
+//! A [`WebSocketIgtlServer`] listens on its own port and can run next to an
+//! [`AsyncIgtlServer`](crate::io::AsyncIgtlServer), e.g. both answering queries from one
+//! shared [`DeviceRepository`](crate::io::DeviceRepository). To serve WebSocket
+//! upgrades from an existing listener instead, pass accepted sockets to
+//! [`WebSocketIgtlServer::accept_stream`].
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::error::IgtlError;
+//! use openigtlink_rust::io::websocket::WebSocketIgtlServer;
+//! use openigtlink_rust::io::{AsyncIgtlServer, DeviceRepository};
+//!
+//! // Browsers: answer GET_* queries from the shared repository
+//! async fn serve_browsers(
+//!     ws: WebSocketIgtlServer,
+//!     repository: DeviceRepository,
+//! ) -> Result<(), IgtlError> {
+//!     loop {
+//!         // Failed handshakes are skipped; only listener errors end the loop
+//!         let mut conn = ws.accept().await?;
+//!         let repository = repository.clone();
+//!         tokio::spawn(async move {
+//!             while let Ok(query) = conn.receive_any().await {
+//!                 for reply in repository.respond(&query)?.unwrap_or_default() {
+//!                     conn.send_any(&reply).await?;
+//!                 }
+//!             }
+//!             Ok::<(), IgtlError>(())
+//!         });
+//!     }
+//! }
+//!
+//! # async fn example() -> Result<(), IgtlError> {
+//! let repository = DeviceRepository::new();
+//! let tcp = AsyncIgtlServer::bind("0.0.0.0:18944").await?;
+//! let ws = WebSocketIgtlServer::bind("0.0.0.0:18945").await?;
+//! tokio::spawn(serve_browsers(ws, repository.clone()));
+//!
+//! // Slicer and other TCP clients are served by `tcp` in the same way
+//! # let _ = tcp;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use rustls::pki_types::ServerName;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio_rustls::{rustls, TlsAcceptor, TlsConnector};
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::{self, Message as WsMessage};
+use tokio_tungstenite::WebSocketStream;
+use tracing::{debug, info, trace, warn};
+
+use crate::error::{IgtlError, Result};
+use crate::io::common::decode_any_frame;
+use crate::io::events::{ConnectionEvent, EventEmitter};
+use crate::io::handshake::{with_deadline, Handshakes};
+use crate::io::limits::{admit_session, ServerLimits, SessionGovernor, SessionTracker};
+use crate::io::liveness::with_timeout;
+use crate::io::shutdown::{
+    run_until, shutdown_error, shutdown_notice, within_drain_deadline, CancellationToken,
+    Interrupted, ShutdownConfig, ShutdownSignal,
+};
+use crate::protocol::header::Header;
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::AnyMessage;
+
+/// Byte stream below the WebSocket protocol
+enum Transport {
+    Plain(TcpStream),
+    ServerTls(Box<tokio_rustls::server::TlsStream<TcpStream>>),
+    ClientTls(Box<tokio_rustls::client::TlsStream<TcpStream>>),
+}
+
+impl AsyncRead for Transport {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::ServerTls(stream) => Pin::new(stream).poll_read(cx, buf),
+            Transport::ClientTls(stream) => Pin::new(stream).poll_read(cx, buf),
+        }
+    }
+}
+
+impl AsyncWrite for Transport {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::ServerTls(stream) => Pin::new(stream).poll_write(cx, buf),
+            Transport::ClientTls(stream) => Pin::new(stream).poll_write(cx, buf),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::ServerTls(stream) => Pin::new(stream).poll_flush(cx),
+            Transport::ClientTls(stream) => Pin::new(stream).poll_flush(cx),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        match self.get_mut() {
+            Transport::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::ServerTls(stream) => Pin::new(stream).poll_shutdown(cx),
+            Transport::ClientTls(stream) => Pin::new(stream).poll_shutdown(cx),
+        }
+    }
+}
+
+/// Map a WebSocket protocol error to the I/O error kinds used by the other transports
+fn ws_error(e: tungstenite::Error) -> IgtlError {
+    match e {
+        tungstenite::Error::Io(e) => IgtlError::Io(e),
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            connection_closed()
+        }
+        tungstenite::Error::Http(response) => IgtlError::Io(std::io::Error::new(
+            ErrorKind::ConnectionRefused,
+            format!("WebSocket upgrade rejected: HTTP {}", response.status()),
+        )),
+        tungstenite::Error::Url(e) => {
+            IgtlError::Io(std::io::Error::new(ErrorKind::InvalidInput, e.to_string()))
+        }
+        e => IgtlError::Io(std::io::Error::new(ErrorKind::InvalidData, e.to_string())),
+    }
+}
+
+fn connection_closed() -> IgtlError {
+    IgtlError::Io(std::io::Error::new(
+        ErrorKind::UnexpectedEof,
+        "Connection closed by peer",
+    ))
+}
+
+/// Check that a binary WebSocket frame holds exactly one OpenIGTLink message
+fn check_frame(data: &[u8]) -> Result<()> {
+    if data.len() < Header::SIZE {
+        return Err(IgtlError::InvalidSize {
+            expected: Header::SIZE,
+            actual: data.len(),
+        });
+    }
+    let header = Header::decode(&data[..Header::SIZE])?;
+    let expected = Header::SIZE + header.body_size as usize;
+    if data.len() != expected {
+        return Err(IgtlError::InvalidSize {
+            expected,
+            actual: data.len(),
+        });
+    }
+    Ok(())
+}
+
+// ============================================================================
+// Server
+// ============================================================================
+
+/// OpenIGTLink server accepting WebSocket (`ws://`) or secure WebSocket (`wss://`)
+/// connections
+///
+/// Any request path is accepted. TLS and WebSocket handshakes run concurrently within
+/// the first-message deadline of the [`ServerLimits`], or
+/// [`DEFAULT_HANDSHAKE_TIMEOUT`](crate::io::limits::DEFAULT_HANDSHAKE_TIMEOUT) if none
+/// is set, so a stalled client does not hold up others.
+pub struct WebSocketIgtlServer {
+    listener: TcpListener,
+    acceptor: Option<TlsAcceptor>,
+    events: EventEmitter,
+    limits: ServerLimits,
+    sessions: Arc<SessionTracker>,
+    handshakes: Handshakes<WebSocketIgtlConnection>,
+    shutdown: Arc<ShutdownSignal>,
+}
+
+impl WebSocketIgtlServer {
+    /// Bind a plain WebSocket (`ws://`) server
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind
+    pub async fn bind(addr: &str) -> Result<Self> {
+        info!(addr = %addr, "Binding OpenIGTLink WebSocket server");
+        Ok(Self::new(TcpListener::bind(addr).await?, None))
+    }
+
+    /// Bind a secure WebSocket (`wss://`) server
+    ///
+    /// `config` is typically built with [`ServerTlsConfig`](crate::io::ServerTlsConfig),
+    /// so client authentication and reloadable certificates work as for
+    /// [`TlsIgtlServer`](crate::io::TlsIgtlServer).
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind
+    pub async fn bind_with_tls(addr: &str, config: rustls::ServerConfig) -> Result<Self> {
+        info!(addr = %addr, "Binding OpenIGTLink secure WebSocket server");
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+        Ok(Self::new(TcpListener::bind(addr).await?, Some(acceptor)))
+    }
+
+    fn new(listener: TcpListener, acceptor: Option<TlsAcceptor>) -> Self {
+        WebSocketIgtlServer {
+            listener,
+            acceptor,
+            events: EventEmitter::default(),
+            limits: ServerLimits::default(),
+            sessions: Arc::new(SessionTracker::default()),
+            handshakes: Handshakes::default(),
+            shutdown: Arc::new(ShutdownSignal::default()),
+        }
+    }
+
+    /// Accept the next connection whose TLS and WebSocket handshakes complete
+    ///
+    /// Connections above a session limit and failed or timed out handshakes are logged,
+    /// reported as events and skipped. Cancel-safe: handshakes in progress continue on
+    /// the next call.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Accepting from the listener
+    ///   failed, or [`ConnectionAborted`](std::io::ErrorKind::ConnectionAborted) once the
+    ///   server is [shut down](Self::shutdown)
+    pub async fn accept(&self) -> Result<WebSocketIgtlConnection> {
+        self.accept_until(&CancellationToken::new())
+            .await?
+            .ok_or_else(shutdown_error)
+    }
+
+    /// Accept the next connection unless `token` is cancelled first
+    ///
+    /// Returns `Ok(None)` once `token` is cancelled or the server is
+    /// [shut down](Self::shutdown). Handshakes in progress continue on the next call.
+    pub async fn accept_until(
+        &self,
+        token: &CancellationToken,
+    ) -> Result<Option<WebSocketIgtlConnection>> {
+        trace!("Waiting for WebSocket connection");
+        let accept = self.handshakes.next(&self.listener, |stream, peer| {
+            let session = admit_session(&self.sessions, &self.limits, &self.events, peer)?;
+            let acceptor = self.acceptor.clone();
+            let events = self.events.clone();
+            let shutdown = Arc::clone(&self.shutdown);
+            Some(Box::pin(async move {
+                handshake(acceptor, stream, peer, session, events, shutdown)
+                    .await
+                    .ok()
+            }))
+        });
+        match run_until(accept, &self.shutdown, Some(token)).await {
+            Ok(connection) => Ok(Some(connection?)),
+            Err(_) => {
+                debug!("WebSocket accept cancelled");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Complete the handshakes on a TCP connection accepted elsewhere
+    ///
+    /// Lets an application route some of the sockets of its own listener to this server.
+    /// The limits of this server apply.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) -
+    ///   [`ConnectionRefused`](std::io::ErrorKind::ConnectionRefused) if a session limit is
+    ///   reached or the TLS handshake failed,
+    ///   [`TimedOut`](std::io::ErrorKind::TimedOut) if the handshakes did not complete in
+    ///   time; other handshake failures are reported as
+    ///   [`InvalidData`](std::io::ErrorKind::InvalidData)
+    pub async fn accept_stream(&self, stream: TcpStream) -> Result<WebSocketIgtlConnection> {
+        let peer = stream.peer_addr()?;
+        let session =
+            admit_session(&self.sessions, &self.limits, &self.events, peer).ok_or_else(|| {
+                IgtlError::Io(std::io::Error::new(
+                    ErrorKind::ConnectionRefused,
+                    "session limit reached",
+                ))
+            })?;
+        handshake(
+            self.acceptor.clone(),
+            stream,
+            peer,
+            session,
+            self.events.clone(),
+            Arc::clone(&self.shutdown),
+        )
+        .await
+    }
+
+    /// Stop accepting and close all sessions, waiting up to the drain deadline
+    ///
+    /// Handshakes in progress are aborted. Each session sends the STATUS notice of
+    /// `config` and a WebSocket close frame as soon as it is receiving or next sends;
+    /// see [`shutdown`](crate::io::shutdown).
+    ///
+    /// # Returns
+    ///
+    /// Number of sessions still open when the drain deadline passed
+    pub async fn shutdown(&self, config: ShutdownConfig) -> usize {
+        let drain_timeout = config.drain_timeout;
+        info!(
+            sessions = self.sessions.active(),
+            "Shutting down WebSocket server"
+        );
+        self.shutdown.begin(config);
+        self.handshakes.clear().await;
+        let _ = tokio::time::timeout(drain_timeout, self.sessions.wait_idle()).await;
+        let remaining = self.sessions.active();
+        if remaining > 0 {
+            warn!(
+                remaining,
+                "WebSocket sessions still open after shutdown drain deadline"
+            );
+        }
+        remaining
+    }
+
+    /// Token cancelled when [`shutdown`](Self::shutdown) starts
+    pub fn shutdown_token(&self) -> CancellationToken {
+        self.shutdown.token().clone()
+    }
+
+    /// Apply connection and resource limits to sessions accepted from now on
+    ///
+    /// The first-message deadline also bounds the TLS and WebSocket handshakes. See
+    /// [`limits`](crate::io::limits).
+    pub fn with_limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Limits applied to accepted sessions
+    pub fn limits(&self) -> &ServerLimits {
+        &self.limits
+    }
+
+    /// Number of accepted sessions that are still open
+    pub fn active_sessions(&self) -> usize {
+        self.sessions.active()
+    }
+
+    /// Get the local address this server is bound to
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Subscribe to lifecycle events of all sessions accepted by this server
+    ///
+    /// Also reports rejected connections and failed TLS handshakes. See
+    /// [`events`](crate::io::events).
+    pub fn subscribe_events(&self) -> broadcast::Receiver<ConnectionEvent> {
+        self.events.subscribe()
+    }
+}
+
+/// Complete the TLS and WebSocket handshakes of an admitted connection before its deadline
+async fn handshake(
+    acceptor: Option<TlsAcceptor>,
+    stream: TcpStream,
+    peer: SocketAddr,
+    session: SessionGovernor,
+    events: EventEmitter,
+    shutdown: Arc<ShutdownSignal>,
+) -> Result<WebSocketIgtlConnection> {
+    let upgrade = async {
+        let transport = match acceptor {
+            None => Transport::Plain(stream),
+            Some(acceptor) => {
+                let tls_stream = acceptor.accept(stream).await.map_err(|e| {
+                    events.emit(ConnectionEvent::TlsHandshakeFailed {
+                        peer: Some(peer),
+                        error: e.to_string(),
+                    });
+                    IgtlError::Io(std::io::Error::new(
+                        ErrorKind::ConnectionRefused,
+                        format!("TLS handshake failed: {}", e),
+                    ))
+                })?;
+                Transport::ServerTls(Box::new(tls_stream))
+            }
+        };
+        tokio_tungstenite::accept_async(transport)
+            .await
+            .map_err(ws_error)
+    };
+    let ws = with_deadline(session.handshake_deadline(), upgrade)
+        .await
+        .map_err(|e| {
+            warn!(error = %e, peer_addr = %peer, "WebSocket handshake failed");
+            e
+        })?;
+
+    info!(peer_addr = %peer, "WebSocket client connected");
+    events.emit(ConnectionEvent::Connected { peer });
+    let mut connection = WebSocketIgtlConnection::new(ws, peer, events.connection(Some(peer)));
+    connection.session = Some(session);
+    connection.shutdown = shutdown;
+    Ok(connection)
+}
+
+// ============================================================================
+// Connection
+// ============================================================================
+
+/// OpenIGTLink connection over a WebSocket
+///
+/// Returned by [`WebSocketIgtlServer::accept`] and [`WebSocketIgtlConnection::connect`].
+/// Ping frames are answered automatically; text frames are rejected.
+pub struct WebSocketIgtlConnection {
+    ws: WebSocketStream<Transport>,
+    peer: SocketAddr,
+    events: EventEmitter,
+    verify_crc: bool,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    /// Limits of the accepting server; `None` for clients
+    session: Option<SessionGovernor>,
+    shutdown: Arc<ShutdownSignal>,
+}
+
+/// OpenIGTLink client over a WebSocket
+///
+/// Build with [`ClientBuilder::websocket`](crate::io::builder::ClientBuilder::websocket)
+/// or [`WebSocketIgtlConnection::connect`].
+pub type WebSocketIgtlClient = WebSocketIgtlConnection;
+
+impl WebSocketIgtlConnection {
+    fn new(ws: WebSocketStream<Transport>, peer: SocketAddr, events: EventEmitter) -> Self {
+        WebSocketIgtlConnection {
+            ws,
+            peer,
+            events,
+            verify_crc: true,
+            read_timeout: None,
+            write_timeout: None,
+            session: None,
+            shutdown: Arc::new(ShutdownSignal::default()),
+        }
+    }
+
+    /// Connect to a `ws://` URL
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use openigtlink_rust::io::websocket::WebSocketIgtlConnection;
+    ///
+    /// # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+    /// let mut client = WebSocketIgtlConnection::connect("ws://127.0.0.1:18945/igtl").await?;
+    /// let msg = client.receive_any().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn connect(url: &str) -> Result<Self> {
+        Self::open(url, None, EventEmitter::default()).await
+    }
+
+    /// Connect to a `wss://` URL with the given rustls client configuration
+    pub async fn connect_with_tls(url: &str, config: Arc<rustls::ClientConfig>) -> Result<Self> {
+        Self::open(url, Some(config), EventEmitter::default()).await
+    }
+
+    pub(crate) async fn open(
+        url: &str,
+        tls: Option<Arc<rustls::ClientConfig>>,
+        events: EventEmitter,
+    ) -> Result<Self> {
+        let request = url.into_client_request().map_err(ws_error)?;
+        let uri = request.uri();
+        let secure = match uri.scheme_str() {
+            Some("ws") => false,
+            Some("wss") => true,
+            _ => {
+                return Err(IgtlError::Io(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Not a ws:// or wss:// URL: {}", url),
+                )))
+            }
+        };
+        let host = uri.host().unwrap_or_default().to_string();
+        let port = uri.port_u16().unwrap_or(if secure { 443 } else { 80 });
+        let addr = format!("{}:{}", host.trim_matches(['[', ']']), port);
+
+        events.emit(ConnectionEvent::Connecting {
+            addr: url.to_string(),
+        });
+        info!(url = %url, "Connecting to OpenIGTLink WebSocket server");
+        let stream = TcpStream::connect(&addr).await?;
+        let peer = stream.peer_addr()?;
+
+        let transport = match (secure, tls) {
+            (false, _) => Transport::Plain(stream),
+            (true, Some(config)) => {
+                let server_name = ServerName::try_from(host.trim_matches(['[', ']']).to_string())
+                    .map_err(|e| {
+                    IgtlError::Io(std::io::Error::new(
+                        ErrorKind::InvalidInput,
+                        format!("Invalid hostname: {}", e),
+                    ))
+                })?;
+                let tls_stream = TlsConnector::from(config)
+                    .connect(server_name, stream)
+                    .await
+                    .map_err(|e| {
+                        warn!(error = %e, url = %url, "TLS handshake failed");
+                        events.emit(ConnectionEvent::TlsHandshakeFailed {
+                            peer: Some(peer),
+                            error: e.to_string(),
+                        });
+                        IgtlError::Io(e)
+                    })?;
+                Transport::ClientTls(Box::new(tls_stream))
+            }
+            (true, None) => {
+                return Err(IgtlError::Io(std::io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "wss:// requires a TLS configuration",
+                )))
+            }
+        };
+
+        let (ws, _) = tokio_tungstenite::client_async(request, transport)
+            .await
+            .map_err(ws_error)?;
+
+        info!(url = %url, "Connected to OpenIGTLink WebSocket server");
+        events.emit(ConnectionEvent::Connected { peer });
+        Ok(Self::new(ws, peer, events.connection(Some(peer))))
+    }
+
+    /// Enable or disable CRC verification for received messages
+    pub fn set_verify_crc(&mut self, verify: bool) {
+        if verify != self.verify_crc {
+            info!(verify = verify, "CRC verification setting changed");
+            if !verify {
+                warn!("CRC verification disabled - use only in trusted environments");
+            }
+        }
+        self.verify_crc = verify;
+    }
+
+    /// Get current CRC verification setting
+    pub fn verify_crc(&self) -> bool {
+        self.verify_crc
+    }
+
+    /// Send a message as one binary frame
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network write failed, or
+    ///   [`TimedOut`](std::io::ErrorKind::TimedOut) if the write timeout expired
+    pub async fn send<T: Message>(&mut self, msg: &IgtlMessage<T>) -> Result<()> {
+        let data = msg.encode()?;
+        debug!(
+            msg_type = msg.header.type_name.as_str().unwrap_or("UNKNOWN"),
+            size = data.len(),
+            "Sending message (websocket)"
+        );
+        self.write_frame(data).await
+    }
+
+    /// Send a dynamically typed message as one binary frame
+    ///
+    /// # Errors
+    ///
+    /// Same as [`send`](Self::send).
+    pub async fn send_any(&mut self, msg: &AnyMessage) -> Result<()> {
+        let data = msg.encode()?;
+        debug!(
+            msg_type = msg.message_type(),
+            size = data.len(),
+            "Sending message (websocket)"
+        );
+        self.write_frame(data).await
+    }
+
+    /// Receive a message of a known type
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Network read failed,
+    ///   [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof) if the peer closed the
+    ///   connection, [`TimedOut`](std::io::ErrorKind::TimedOut) if the read timeout expired,
+    ///   or [`InvalidData`](std::io::ErrorKind::InvalidData) for a text frame
+    /// - [`IgtlError::InvalidSize`](crate::error::IgtlError::InvalidSize) - The frame does
+    ///   not hold exactly one message
+    /// - [`IgtlError::CrcMismatch`](crate::error::IgtlError::CrcMismatch) - Data corruption detected
+    pub async fn receive<T: Message>(&mut self) -> Result<IgtlMessage<T>> {
+        let frame = self.read_frame(None).await?.ok_or_else(shutdown_error)?;
+        let result = IgtlMessage::decode_with_options(&frame, self.verify_crc);
+        self.events.check_decode(&result, Some(self.peer));
+        result
+    }
+
+    /// Receive a message unless `token` is cancelled first
+    ///
+    /// Returns `Ok(None)` once `token` is cancelled, or after closing the session
+    /// because the server shuts down.
+    pub async fn receive_until<T: Message>(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<IgtlMessage<T>>> {
+        let Some(frame) = self.read_frame(Some(token)).await? else {
+            return Ok(None);
+        };
+        let result = IgtlMessage::decode_with_options(&frame, self.verify_crc);
+        self.events.check_decode(&result, Some(self.peer));
+        result.map(Some)
+    }
+
+    /// Receive any message type dynamically
+    ///
+    /// # Errors
+    ///
+    /// Same as [`receive`](Self::receive).
+    pub async fn receive_any(&mut self) -> Result<AnyMessage> {
+        let frame = self.read_frame(None).await?.ok_or_else(shutdown_error)?;
+        let result = decode_any_frame(&frame, self.verify_crc);
+        self.events.check_decode(&result, Some(self.peer));
+        result
+    }
+
+    /// Receive any message type unless `token` is cancelled first
+    ///
+    /// See [`receive_until`](Self::receive_until).
+    pub async fn receive_any_until(
+        &mut self,
+        token: &CancellationToken,
+    ) -> Result<Option<AnyMessage>> {
+        let Some(frame) = self.read_frame(Some(token)).await? else {
+            return Ok(None);
+        };
+        let result = decode_any_frame(&frame, self.verify_crc);
+        self.events.check_decode(&result, Some(self.peer));
+        result.map(Some)
+    }
+
+    /// Set the deadline of each receive (`None` waits forever, the default)
+    ///
+    /// Receives are cancel-safe, so an expired receive does not lose frames.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    /// Set the deadline of each send (`None` waits forever, the default)
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout;
+    }
+
+    /// Get the remote peer address
+    pub fn peer_addr(&self) -> Result<SocketAddr> {
+        Ok(self.peer)
+    }
+
+    /// Send a WebSocket close frame and wait for the peer to acknowledge it
+    pub async fn close(&mut self) -> Result<()> {
+        let reason = match &mut self.session {
+            Some(session) => {
+                session.check_open()?;
+                session.close("closed by the server");
+                drop(session.take_slot());
+                "closed by the server"
+            }
+            None => "closed by the client",
+        };
+        self.events.disconnected(Some(self.peer), &reason);
+        match self.ws.close(None).await {
+            Ok(()) | Err(tungstenite::Error::ConnectionClosed) => {}
+            Err(e) => return Err(ws_error(e)),
+        }
+        // Drain until the peer's close frame arrives
+        while let Some(msg) = self.ws.next().await {
+            if let Err(e) = msg {
+                return match e {
+                    tungstenite::Error::ConnectionClosed => Ok(()),
+                    e => Err(ws_error(e)),
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Fail if the server closed the session, closing it first if the server shuts down
+    async fn check_open(&mut self) -> Result<()> {
+        let Some(session) = &self.session else {
+            return Ok(());
+        };
+        if self.shutdown.is_shutting_down() && !session.is_closed() {
+            self.close_for_shutdown().await;
+            return Err(shutdown_error());
+        }
+        session.check_open()
+    }
+
+    /// Notify the peer and close the session because the server shuts down
+    async fn close_for_shutdown(&mut self) {
+        let Some(session) = &mut self.session else {
+            return;
+        };
+        session.close("server shutting down");
+        let config = self.shutdown.config();
+        let ws = &mut self.ws;
+        let close = within_drain_deadline(&config, async {
+            if let Some(msg) = shutdown_notice(&config)? {
+                ws.send(WsMessage::binary(msg.encode()?))
+                    .await
+                    .map_err(ws_error)?;
+            }
+            ws.close(None).await.map_err(ws_error)
+        });
+        if let Err(e) = close.await {
+            debug!(error = %e, peer_addr = %self.peer, "WebSocket session did not close cleanly");
+        }
+        self.events.disconnected(Some(self.peer), &shutdown_error());
+        drop(session.take_slot());
+    }
+
+    async fn write_frame(&mut self, data: Vec<u8>) -> Result<()> {
+        self.check_open().await?;
+        let size = data.len();
+        let ws = &mut self.ws;
+        let result = with_timeout(self.write_timeout, "message write", async {
+            ws.send(WsMessage::binary(data)).await.map_err(ws_error)
+        })
+        .await;
+        self.events.check_result(&result, Some(self.peer));
+        result?;
+        trace!(bytes_sent = size, "Message sent (websocket)");
+        Ok(())
+    }
+
+    /// Read one frame within the session limits, closing the session on a violation
+    /// or a server shutdown
+    ///
+    /// Returns `None` if interrupted by `token` or the shutdown.
+    async fn read_frame(&mut self, token: Option<&CancellationToken>) -> Result<Option<Vec<u8>>> {
+        self.check_open().await?;
+        let shutdown = Arc::clone(&self.shutdown);
+        let read = with_timeout(self.read_timeout, "message", next_frame(&mut self.ws));
+        let read = async {
+            match &mut self.session {
+                Some(session) => session.read_frame(read).await,
+                None => read.await,
+            }
+        };
+        let result = match run_until(read, &shutdown, token).await {
+            Ok(result) => result,
+            Err(Interrupted::Cancelled) => return Ok(None),
+            Err(Interrupted::Shutdown) => {
+                self.close_for_shutdown().await;
+                return Ok(None);
+            }
+        };
+        if self.session.as_ref().is_some_and(|s| s.is_closed()) {
+            let ws = &mut self.ws;
+            let _ = with_timeout(self.write_timeout, "close", async {
+                ws.close(None).await.map_err(ws_error)
+            })
+            .await;
+        }
+        self.events.check_result(&result, Some(self.peer));
+        let frame = result?;
+        debug!(size = frame.len(), "Received message (websocket)");
+        Ok(Some(frame))
+    }
+}
+
+/// Read the next binary frame, answering pings and skipping other control frames
+async fn next_frame(ws: &mut WebSocketStream<Transport>) -> Result<Vec<u8>> {
+    loop {
+        match ws.next().await {
+            Some(Ok(WsMessage::Binary(data))) => {
+                check_frame(&data)?;
+                return Ok(data.to_vec());
+            }
+            Some(Ok(WsMessage::Text(_))) => {
+                return Err(IgtlError::Io(std::io::Error::new(
+                    ErrorKind::InvalidData,
+                    "Unexpected WebSocket text frame",
+                )))
+            }
+            // Pings are answered by the WebSocket layer
+            Some(Ok(WsMessage::Ping(_) | WsMessage::Pong(_) | WsMessage::Frame(_))) => {}
+            Some(Ok(WsMessage::Close(_))) => {
+                // Send the queued close reply to complete the closing handshake
+                let _ = ws.flush().await;
+                return Err(connection_closed());
+            }
+            None => return Err(connection_closed()),
+            Some(Err(e)) => return Err(ws_error(e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+
+    async fn pair() -> (WebSocketIgtlConnection, WebSocketIgtlConnection) {
+        let server = WebSocketIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}/igtl", server.local_addr().unwrap());
+        let (client, conn) = tokio::join!(WebSocketIgtlConnection::connect(&url), server.accept());
+        (client.unwrap(), conn.unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_round_trip() {
+        let (mut client, mut conn) = pair().await;
+
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        client.send(&msg).await.unwrap();
+        match conn.receive_any().await.unwrap() {
+            AnyMessage::Transform(received) => assert_eq!(received.content, msg.content),
+            other => panic!("unexpected message {}", other.message_type()),
+        }
+
+        let reply = IgtlMessage::new(StatusMessage::ok("Ready"), "Server").unwrap();
+        conn.send(&reply).await.unwrap();
+        let received: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(received.content.status_string, "Ready");
+    }
+
+    #[tokio::test]
+    async fn test_one_message_per_frame() {
+        let (mut client, mut conn) = pair().await;
+
+        // Two messages in one frame are rejected
+        let mut data = IgtlMessage::new(StatusMessage::ok("A"), "Dev")
+            .unwrap()
+            .encode()
+            .unwrap();
+        data.extend_from_slice(&data.clone());
+        client.ws.send(WsMessage::binary(data)).await.unwrap();
+        let err = conn.receive_any().await.unwrap_err();
+        assert!(matches!(err, IgtlError::InvalidSize { .. }));
+
+        // Text frames are rejected without closing the connection
+        client.ws.send(WsMessage::text("hello")).await.unwrap();
+        let err = conn.receive_any().await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == ErrorKind::InvalidData));
+
+        let msg = IgtlMessage::new(StatusMessage::ok("B"), "Dev").unwrap();
+        client.send(&msg).await.unwrap();
+        let received: IgtlMessage<StatusMessage> = conn.receive().await.unwrap();
+        assert_eq!(received.content.status_string, "B");
+    }
+
+    #[tokio::test]
+    async fn test_close_is_reported_as_eof() {
+        let (mut client, mut conn) = pair().await;
+        let closing = tokio::spawn(async move { client.close().await });
+
+        let err = conn.receive_any().await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == ErrorKind::UnexpectedEof));
+        drop(conn);
+        closing.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_handshakes_do_not_block_accept() {
+        use tokio::io::AsyncWriteExt;
+
+        let server = WebSocketIgtlServer::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .with_limits(
+                ServerLimits::new().with_first_message_timeout(Duration::from_millis(300)),
+            );
+        let addr = server.local_addr().unwrap();
+        let url = format!("ws://{}/igtl", addr);
+
+        // One client never sends its upgrade request, another sends garbage
+        let _stalled = TcpStream::connect(addr).await.unwrap();
+        let mut garbage = TcpStream::connect(addr).await.unwrap();
+        garbage.write_all(b"not http\r\n\r\n").await.unwrap();
+
+        let (client, conn) = tokio::join!(WebSocketIgtlConnection::connect(&url), server.accept());
+        let (mut client, mut conn) = (client.unwrap(), conn.unwrap());
+        let msg = IgtlMessage::new(StatusMessage::ok("Ready"), "Dev").unwrap();
+        client.send(&msg).await.unwrap();
+        let received: IgtlMessage<StatusMessage> = conn.receive().await.unwrap();
+        assert_eq!(received.content.status_string, "Ready");
+
+        // The stalled handshake times out and releases its session slot
+        let accept = tokio::time::timeout(Duration::from_millis(600), server.accept()).await;
+        assert!(accept.is_err());
+        assert_eq!(server.active_sessions(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_notifies_and_closes_sessions() {
+        let server = Arc::new(WebSocketIgtlServer::bind("127.0.0.1:0").await.unwrap());
+        let url = format!("ws://{}/igtl", server.local_addr().unwrap());
+        let (client, conn) = tokio::join!(WebSocketIgtlConnection::connect(&url), server.accept());
+        let (mut client, mut conn) = (client.unwrap(), conn.unwrap());
+
+        let session = tokio::spawn(async move { conn.receive_any().await });
+        let shutdown = tokio::spawn({
+            let server = Arc::clone(&server);
+            async move { server.shutdown(ShutdownConfig::new()).await }
+        });
+
+        let notice: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(notice.content.code, crate::io::shutdown::STATUS_SHUT_DOWN);
+        let err = client.receive_any().await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == ErrorKind::UnexpectedEof));
+
+        let err = session.await.unwrap().unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == ErrorKind::ConnectionAborted));
+        assert_eq!(shutdown.await.unwrap(), 0);
+        assert!(server
+            .accept_until(&CancellationToken::new())
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_non_websocket_url() {
+        let err = WebSocketIgtlConnection::connect("http://127.0.0.1:1/")
+            .await
+            .err()
+            .unwrap();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == ErrorKind::InvalidInput));
+    }
+}
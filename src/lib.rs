@@ -91,6 +91,10 @@
 //!   - `SyncIgtlClient` / `AsyncIgtlClient` - TCP clients for sync/async workflows
 //!   - `IgtlServer` - TCP server for accepting connections
 //!
+//! - **`router`** - Router merging several upstream servers into one downstream server
+//!   - `Router` / `RouterConfig` - Upstream connections, downstream server, statistics
+//!   - `Route` - Declarative rules to drop, rename and convert messages
+//!
 //! - **`error`** - Error handling
 //!   - `IgtlError` - Unified error type for all operations
 //!   - `Result<T>` - Type alias for `Result<T, IgtlError>`
@@ -343,6 +347,9 @@ pub mod compression;
 pub mod error;
 pub mod io;
 pub mod protocol;
+pub mod router;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 // Re-export commonly used types
 pub use error::{IgtlError, Result};
@@ -3,8 +3,10 @@
 //! This module provides the `AnyMessage` enum which can hold any message type,
 //! allowing for runtime message type detection and handling.
 
+use std::collections::HashMap;
+
 use crate::error::Result;
-use crate::protocol::header::Header;
+use crate::protocol::header::{DeviceName, Header};
 use crate::protocol::message::IgtlMessage;
 use crate::protocol::types::*;
 
@@ -34,7 +36,7 @@ use crate::protocol::types::*;
 /// # Ok(())
 /// # }
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AnyMessage {
     /// TRANSFORM message
     Transform(IgtlMessage<TransformMessage>),
@@ -106,10 +108,22 @@ pub enum AnyMessage {
     RtsImage(IgtlMessage<RtsImageMessage>),
     /// RTS_TDATA response message
     RtsTData(IgtlMessage<RtsTDataMessage>),
+    /// RTS_QTDATA response message
+    RtsQtData(IgtlMessage<RtsQtDataMessage>),
 
     // Streaming control messages (STT_*, STP_*)
     /// STT_TDATA start streaming message
     StartTData(IgtlMessage<StartTDataMessage>),
+    /// STT_QTDATA start streaming message
+    StartQtData(IgtlMessage<StartQtDataMessage>),
+    /// STT_IMAGE start streaming message
+    StartImage(IgtlMessage<StartImageMessage>),
+    /// STT_TRANSFORM start streaming message
+    StartTransform(IgtlMessage<StartTransformMessage>),
+    /// STT_POSITION start streaming message
+    StartPosition(IgtlMessage<StartPositionMessage>),
+    /// STT_NDARRAY start streaming message
+    StartNdArray(IgtlMessage<StartNdArrayMessage>),
     /// STP_TRANSFORM stop streaming message
     StopTransform(IgtlMessage<StopTransformMessage>),
     /// STP_POSITION stop streaming message
@@ -186,7 +200,13 @@ impl AnyMessage {
             AnyMessage::RtsCapability(_) => "RTS_CAPABILITY",
             AnyMessage::RtsImage(_) => "RTS_IMAGE",
             AnyMessage::RtsTData(_) => "RTS_TDATA",
+            AnyMessage::RtsQtData(_) => "RTS_QTDATA",
             AnyMessage::StartTData(_) => "STT_TDATA",
+            AnyMessage::StartQtData(_) => "STT_QTDATA",
+            AnyMessage::StartImage(_) => "STT_IMAGE",
+            AnyMessage::StartTransform(_) => "STT_TRANSFORM",
+            AnyMessage::StartPosition(_) => "STT_POSITION",
+            AnyMessage::StartNdArray(_) => "STT_NDARRAY",
             AnyMessage::StopTransform(_) => "STP_TRANSFORM",
             AnyMessage::StopPosition(_) => "STP_POSITION",
             AnyMessage::StopQtData(_) => "STP_QTDATA",
@@ -248,7 +268,13 @@ impl AnyMessage {
             AnyMessage::RtsCapability(msg) => &msg.header,
             AnyMessage::RtsImage(msg) => &msg.header,
             AnyMessage::RtsTData(msg) => &msg.header,
+            AnyMessage::RtsQtData(msg) => &msg.header,
             AnyMessage::StartTData(msg) => &msg.header,
+            AnyMessage::StartQtData(msg) => &msg.header,
+            AnyMessage::StartImage(msg) => &msg.header,
+            AnyMessage::StartTransform(msg) => &msg.header,
+            AnyMessage::StartPosition(msg) => &msg.header,
+            AnyMessage::StartNdArray(msg) => &msg.header,
             AnyMessage::StopTransform(msg) => &msg.header,
             AnyMessage::StopPosition(msg) => &msg.header,
             AnyMessage::StopQtData(msg) => &msg.header,
@@ -296,7 +322,13 @@ impl AnyMessage {
             AnyMessage::RtsCapability(msg) => &msg.header,
             AnyMessage::RtsImage(msg) => &msg.header,
             AnyMessage::RtsTData(msg) => &msg.header,
+            AnyMessage::RtsQtData(msg) => &msg.header,
             AnyMessage::StartTData(msg) => &msg.header,
+            AnyMessage::StartQtData(msg) => &msg.header,
+            AnyMessage::StartImage(msg) => &msg.header,
+            AnyMessage::StartTransform(msg) => &msg.header,
+            AnyMessage::StartPosition(msg) => &msg.header,
+            AnyMessage::StartNdArray(msg) => &msg.header,
             AnyMessage::StopTransform(msg) => &msg.header,
             AnyMessage::StopPosition(msg) => &msg.header,
             AnyMessage::StopQtData(msg) => &msg.header,
@@ -307,6 +339,315 @@ impl AnyMessage {
         }
     }
 
+    /// Get mutable reference to the message header
+    pub fn header_mut(&mut self) -> &mut Header {
+        match self {
+            AnyMessage::Transform(msg) => &mut msg.header,
+            AnyMessage::Status(msg) => &mut msg.header,
+            AnyMessage::Capability(msg) => &mut msg.header,
+            AnyMessage::Image(msg) => &mut msg.header,
+            AnyMessage::Position(msg) => &mut msg.header,
+            AnyMessage::String(msg) => &mut msg.header,
+            AnyMessage::QtData(msg) => &mut msg.header,
+            AnyMessage::TData(msg) => &mut msg.header,
+            AnyMessage::Sensor(msg) => &mut msg.header,
+            AnyMessage::Point(msg) => &mut msg.header,
+            AnyMessage::Trajectory(msg) => &mut msg.header,
+            AnyMessage::NdArray(msg) => &mut msg.header,
+            AnyMessage::Bind(msg) => &mut msg.header,
+            AnyMessage::ColorTable(msg) => &mut msg.header,
+            AnyMessage::ImgMeta(msg) => &mut msg.header,
+            AnyMessage::LbMeta(msg) => &mut msg.header,
+            AnyMessage::PolyData(msg) => &mut msg.header,
+            AnyMessage::Video(msg) => &mut msg.header,
+            AnyMessage::VideoMeta(msg) => &mut msg.header,
+            AnyMessage::Command(msg) => &mut msg.header,
+            AnyMessage::GetTransform(msg) => &mut msg.header,
+            AnyMessage::GetStatus(msg) => &mut msg.header,
+            AnyMessage::GetCapability(msg) => &mut msg.header,
+            AnyMessage::GetImage(msg) => &mut msg.header,
+            AnyMessage::GetImgMeta(msg) => &mut msg.header,
+            AnyMessage::GetLbMeta(msg) => &mut msg.header,
+            AnyMessage::GetPoint(msg) => &mut msg.header,
+            AnyMessage::GetTData(msg) => &mut msg.header,
+            AnyMessage::RtsTransform(msg) => &mut msg.header,
+            AnyMessage::RtsStatus(msg) => &mut msg.header,
+            AnyMessage::RtsCapability(msg) => &mut msg.header,
+            AnyMessage::RtsImage(msg) => &mut msg.header,
+            AnyMessage::RtsTData(msg) => &mut msg.header,
+            AnyMessage::RtsQtData(msg) => &mut msg.header,
+            AnyMessage::StartTData(msg) => &mut msg.header,
+            AnyMessage::StartQtData(msg) => &mut msg.header,
+            AnyMessage::StartImage(msg) => &mut msg.header,
+            AnyMessage::StartTransform(msg) => &mut msg.header,
+            AnyMessage::StartPosition(msg) => &mut msg.header,
+            AnyMessage::StartNdArray(msg) => &mut msg.header,
+            AnyMessage::StopTransform(msg) => &mut msg.header,
+            AnyMessage::StopPosition(msg) => &mut msg.header,
+            AnyMessage::StopQtData(msg) => &mut msg.header,
+            AnyMessage::StopTData(msg) => &mut msg.header,
+            AnyMessage::StopImage(msg) => &mut msg.header,
+            AnyMessage::StopNdArray(msg) => &mut msg.header,
+            AnyMessage::Unknown { header, .. } => header,
+        }
+    }
+
+    /// Replace the device name
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::InvalidHeader`](crate::error::IgtlError::InvalidHeader) - Name longer
+    ///   than 20 bytes
+    pub fn set_device_name(&mut self, name: &str) -> Result<()> {
+        self.header_mut().device_name = DeviceName::new(name)?;
+        Ok(())
+    }
+
+    /// Get the v3 message ID from the extended header, if present
+    ///
+    /// Always returns `None` for [`AnyMessage::Unknown`], whose extended header is not parsed.
+    pub fn message_id(&self) -> Option<u32> {
+        match self {
+            AnyMessage::Transform(msg) => msg.get_message_id(),
+            AnyMessage::Status(msg) => msg.get_message_id(),
+            AnyMessage::Capability(msg) => msg.get_message_id(),
+            AnyMessage::Image(msg) => msg.get_message_id(),
+            AnyMessage::Position(msg) => msg.get_message_id(),
+            AnyMessage::String(msg) => msg.get_message_id(),
+            AnyMessage::QtData(msg) => msg.get_message_id(),
+            AnyMessage::TData(msg) => msg.get_message_id(),
+            AnyMessage::Sensor(msg) => msg.get_message_id(),
+            AnyMessage::Point(msg) => msg.get_message_id(),
+            AnyMessage::Trajectory(msg) => msg.get_message_id(),
+            AnyMessage::NdArray(msg) => msg.get_message_id(),
+            AnyMessage::Bind(msg) => msg.get_message_id(),
+            AnyMessage::ColorTable(msg) => msg.get_message_id(),
+            AnyMessage::ImgMeta(msg) => msg.get_message_id(),
+            AnyMessage::LbMeta(msg) => msg.get_message_id(),
+            AnyMessage::PolyData(msg) => msg.get_message_id(),
+            AnyMessage::Video(msg) => msg.get_message_id(),
+            AnyMessage::VideoMeta(msg) => msg.get_message_id(),
+            AnyMessage::Command(msg) => msg.get_message_id(),
+            AnyMessage::GetTransform(msg) => msg.get_message_id(),
+            AnyMessage::GetStatus(msg) => msg.get_message_id(),
+            AnyMessage::GetCapability(msg) => msg.get_message_id(),
+            AnyMessage::GetImage(msg) => msg.get_message_id(),
+            AnyMessage::GetImgMeta(msg) => msg.get_message_id(),
+            AnyMessage::GetLbMeta(msg) => msg.get_message_id(),
+            AnyMessage::GetPoint(msg) => msg.get_message_id(),
+            AnyMessage::GetTData(msg) => msg.get_message_id(),
+            AnyMessage::RtsTransform(msg) => msg.get_message_id(),
+            AnyMessage::RtsStatus(msg) => msg.get_message_id(),
+            AnyMessage::RtsCapability(msg) => msg.get_message_id(),
+            AnyMessage::RtsImage(msg) => msg.get_message_id(),
+            AnyMessage::RtsTData(msg) => msg.get_message_id(),
+            AnyMessage::RtsQtData(msg) => msg.get_message_id(),
+            AnyMessage::StartTData(msg) => msg.get_message_id(),
+            AnyMessage::StartQtData(msg) => msg.get_message_id(),
+            AnyMessage::StartImage(msg) => msg.get_message_id(),
+            AnyMessage::StartTransform(msg) => msg.get_message_id(),
+            AnyMessage::StartPosition(msg) => msg.get_message_id(),
+            AnyMessage::StartNdArray(msg) => msg.get_message_id(),
+            AnyMessage::StopTransform(msg) => msg.get_message_id(),
+            AnyMessage::StopPosition(msg) => msg.get_message_id(),
+            AnyMessage::StopQtData(msg) => msg.get_message_id(),
+            AnyMessage::StopTData(msg) => msg.get_message_id(),
+            AnyMessage::StopImage(msg) => msg.get_message_id(),
+            AnyMessage::StopNdArray(msg) => msg.get_message_id(),
+            AnyMessage::Unknown { .. } => None,
+        }
+    }
+
+    /// Get the v3 metadata, if present
+    ///
+    /// Always returns `None` for [`AnyMessage::Unknown`], whose metadata is not parsed.
+    pub fn metadata(&self) -> Option<&HashMap<String, String>> {
+        match self {
+            AnyMessage::Transform(msg) => msg.get_metadata(),
+            AnyMessage::Status(msg) => msg.get_metadata(),
+            AnyMessage::Capability(msg) => msg.get_metadata(),
+            AnyMessage::Image(msg) => msg.get_metadata(),
+            AnyMessage::Position(msg) => msg.get_metadata(),
+            AnyMessage::String(msg) => msg.get_metadata(),
+            AnyMessage::QtData(msg) => msg.get_metadata(),
+            AnyMessage::TData(msg) => msg.get_metadata(),
+            AnyMessage::Sensor(msg) => msg.get_metadata(),
+            AnyMessage::Point(msg) => msg.get_metadata(),
+            AnyMessage::Trajectory(msg) => msg.get_metadata(),
+            AnyMessage::NdArray(msg) => msg.get_metadata(),
+            AnyMessage::Bind(msg) => msg.get_metadata(),
+            AnyMessage::ColorTable(msg) => msg.get_metadata(),
+            AnyMessage::ImgMeta(msg) => msg.get_metadata(),
+            AnyMessage::LbMeta(msg) => msg.get_metadata(),
+            AnyMessage::PolyData(msg) => msg.get_metadata(),
+            AnyMessage::Video(msg) => msg.get_metadata(),
+            AnyMessage::VideoMeta(msg) => msg.get_metadata(),
+            AnyMessage::Command(msg) => msg.get_metadata(),
+            AnyMessage::GetTransform(msg) => msg.get_metadata(),
+            AnyMessage::GetStatus(msg) => msg.get_metadata(),
+            AnyMessage::GetCapability(msg) => msg.get_metadata(),
+            AnyMessage::GetImage(msg) => msg.get_metadata(),
+            AnyMessage::GetImgMeta(msg) => msg.get_metadata(),
+            AnyMessage::GetLbMeta(msg) => msg.get_metadata(),
+            AnyMessage::GetPoint(msg) => msg.get_metadata(),
+            AnyMessage::GetTData(msg) => msg.get_metadata(),
+            AnyMessage::RtsTransform(msg) => msg.get_metadata(),
+            AnyMessage::RtsStatus(msg) => msg.get_metadata(),
+            AnyMessage::RtsCapability(msg) => msg.get_metadata(),
+            AnyMessage::RtsImage(msg) => msg.get_metadata(),
+            AnyMessage::RtsTData(msg) => msg.get_metadata(),
+            AnyMessage::RtsQtData(msg) => msg.get_metadata(),
+            AnyMessage::StartTData(msg) => msg.get_metadata(),
+            AnyMessage::StartQtData(msg) => msg.get_metadata(),
+            AnyMessage::StartImage(msg) => msg.get_metadata(),
+            AnyMessage::StartTransform(msg) => msg.get_metadata(),
+            AnyMessage::StartPosition(msg) => msg.get_metadata(),
+            AnyMessage::StartNdArray(msg) => msg.get_metadata(),
+            AnyMessage::StopTransform(msg) => msg.get_metadata(),
+            AnyMessage::StopPosition(msg) => msg.get_metadata(),
+            AnyMessage::StopQtData(msg) => msg.get_metadata(),
+            AnyMessage::StopTData(msg) => msg.get_metadata(),
+            AnyMessage::StopImage(msg) => msg.get_metadata(),
+            AnyMessage::StopNdArray(msg) => msg.get_metadata(),
+            AnyMessage::Unknown { .. } => None,
+        }
+    }
+
+    /// Set the v3 message ID, creating the extended header if needed
+    ///
+    /// Has no effect on [`AnyMessage::Unknown`].
+    pub fn set_message_id(&mut self, message_id: u32) {
+        match self {
+            AnyMessage::Transform(msg) => msg.set_message_id(message_id),
+            AnyMessage::Status(msg) => msg.set_message_id(message_id),
+            AnyMessage::Capability(msg) => msg.set_message_id(message_id),
+            AnyMessage::Image(msg) => msg.set_message_id(message_id),
+            AnyMessage::Position(msg) => msg.set_message_id(message_id),
+            AnyMessage::String(msg) => msg.set_message_id(message_id),
+            AnyMessage::QtData(msg) => msg.set_message_id(message_id),
+            AnyMessage::TData(msg) => msg.set_message_id(message_id),
+            AnyMessage::Sensor(msg) => msg.set_message_id(message_id),
+            AnyMessage::Point(msg) => msg.set_message_id(message_id),
+            AnyMessage::Trajectory(msg) => msg.set_message_id(message_id),
+            AnyMessage::NdArray(msg) => msg.set_message_id(message_id),
+            AnyMessage::Bind(msg) => msg.set_message_id(message_id),
+            AnyMessage::ColorTable(msg) => msg.set_message_id(message_id),
+            AnyMessage::ImgMeta(msg) => msg.set_message_id(message_id),
+            AnyMessage::LbMeta(msg) => msg.set_message_id(message_id),
+            AnyMessage::PolyData(msg) => msg.set_message_id(message_id),
+            AnyMessage::Video(msg) => msg.set_message_id(message_id),
+            AnyMessage::VideoMeta(msg) => msg.set_message_id(message_id),
+            AnyMessage::Command(msg) => msg.set_message_id(message_id),
+            AnyMessage::GetTransform(msg) => msg.set_message_id(message_id),
+            AnyMessage::GetStatus(msg) => msg.set_message_id(message_id),
+            AnyMessage::GetCapability(msg) => msg.set_message_id(message_id),
+            AnyMessage::GetImage(msg) => msg.set_message_id(message_id),
+            AnyMessage::GetImgMeta(msg) => msg.set_message_id(message_id),
+            AnyMessage::GetLbMeta(msg) => msg.set_message_id(message_id),
+            AnyMessage::GetPoint(msg) => msg.set_message_id(message_id),
+            AnyMessage::GetTData(msg) => msg.set_message_id(message_id),
+            AnyMessage::RtsTransform(msg) => msg.set_message_id(message_id),
+            AnyMessage::RtsStatus(msg) => msg.set_message_id(message_id),
+            AnyMessage::RtsCapability(msg) => msg.set_message_id(message_id),
+            AnyMessage::RtsImage(msg) => msg.set_message_id(message_id),
+            AnyMessage::RtsTData(msg) => msg.set_message_id(message_id),
+            AnyMessage::RtsQtData(msg) => msg.set_message_id(message_id),
+            AnyMessage::StartTData(msg) => msg.set_message_id(message_id),
+            AnyMessage::StartQtData(msg) => msg.set_message_id(message_id),
+            AnyMessage::StartImage(msg) => msg.set_message_id(message_id),
+            AnyMessage::StartTransform(msg) => msg.set_message_id(message_id),
+            AnyMessage::StartPosition(msg) => msg.set_message_id(message_id),
+            AnyMessage::StartNdArray(msg) => msg.set_message_id(message_id),
+            AnyMessage::StopTransform(msg) => msg.set_message_id(message_id),
+            AnyMessage::StopPosition(msg) => msg.set_message_id(message_id),
+            AnyMessage::StopQtData(msg) => msg.set_message_id(message_id),
+            AnyMessage::StopTData(msg) => msg.set_message_id(message_id),
+            AnyMessage::StopImage(msg) => msg.set_message_id(message_id),
+            AnyMessage::StopNdArray(msg) => msg.set_message_id(message_id),
+            AnyMessage::Unknown { .. } => {}
+        }
+    }
+
+    /// Encode the message back to its wire representation
+    ///
+    /// Typed variants are re-encoded from their content (recomputing body size and CRC).
+    /// [`AnyMessage::Unknown`] is emitted as its header followed by the raw body bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use openigtlink_rust::protocol::AnyMessage;
+    /// # use openigtlink_rust::protocol::types::TransformMessage;
+    /// # use openigtlink_rust::protocol::message::IgtlMessage;
+    /// # fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+    /// let msg = IgtlMessage::new(TransformMessage::identity(), "Device")?;
+    /// let any_msg = AnyMessage::Transform(msg);
+    /// let bytes = any_msg.encode()?;
+    /// let decoded = AnyMessage::decode_with_options(&bytes, true)?;
+    /// assert_eq!(decoded.message_type(), "TRANSFORM");
+    /// # Ok(())
+    /// # }
+    /// # example().unwrap();
+    /// ```
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        match self {
+            AnyMessage::Transform(msg) => msg.encode(),
+            AnyMessage::Status(msg) => msg.encode(),
+            AnyMessage::Capability(msg) => msg.encode(),
+            AnyMessage::Image(msg) => msg.encode(),
+            AnyMessage::Position(msg) => msg.encode(),
+            AnyMessage::String(msg) => msg.encode(),
+            AnyMessage::QtData(msg) => msg.encode(),
+            AnyMessage::TData(msg) => msg.encode(),
+            AnyMessage::Sensor(msg) => msg.encode(),
+            AnyMessage::Point(msg) => msg.encode(),
+            AnyMessage::Trajectory(msg) => msg.encode(),
+            AnyMessage::NdArray(msg) => msg.encode(),
+            AnyMessage::Bind(msg) => msg.encode(),
+            AnyMessage::ColorTable(msg) => msg.encode(),
+            AnyMessage::ImgMeta(msg) => msg.encode(),
+            AnyMessage::LbMeta(msg) => msg.encode(),
+            AnyMessage::PolyData(msg) => msg.encode(),
+            AnyMessage::Video(msg) => msg.encode(),
+            AnyMessage::VideoMeta(msg) => msg.encode(),
+            AnyMessage::Command(msg) => msg.encode(),
+            AnyMessage::GetTransform(msg) => msg.encode(),
+            AnyMessage::GetStatus(msg) => msg.encode(),
+            AnyMessage::GetCapability(msg) => msg.encode(),
+            AnyMessage::GetImage(msg) => msg.encode(),
+            AnyMessage::GetImgMeta(msg) => msg.encode(),
+            AnyMessage::GetLbMeta(msg) => msg.encode(),
+            AnyMessage::GetPoint(msg) => msg.encode(),
+            AnyMessage::GetTData(msg) => msg.encode(),
+            AnyMessage::RtsTransform(msg) => msg.encode(),
+            AnyMessage::RtsStatus(msg) => msg.encode(),
+            AnyMessage::RtsCapability(msg) => msg.encode(),
+            AnyMessage::RtsImage(msg) => msg.encode(),
+            AnyMessage::RtsTData(msg) => msg.encode(),
+            AnyMessage::RtsQtData(msg) => msg.encode(),
+            AnyMessage::StartTData(msg) => msg.encode(),
+            AnyMessage::StartQtData(msg) => msg.encode(),
+            AnyMessage::StartImage(msg) => msg.encode(),
+            AnyMessage::StartTransform(msg) => msg.encode(),
+            AnyMessage::StartPosition(msg) => msg.encode(),
+            AnyMessage::StartNdArray(msg) => msg.encode(),
+            AnyMessage::StopTransform(msg) => msg.encode(),
+            AnyMessage::StopPosition(msg) => msg.encode(),
+            AnyMessage::StopQtData(msg) => msg.encode(),
+            AnyMessage::StopTData(msg) => msg.encode(),
+            AnyMessage::StopImage(msg) => msg.encode(),
+            AnyMessage::StopNdArray(msg) => msg.encode(),
+            AnyMessage::Unknown { header, body } => {
+                let mut header = header.clone();
+                header.body_size = body.len() as u64;
+                let mut buf = Vec::with_capacity(Header::SIZE + body.len());
+                buf.extend_from_slice(&header.encode());
+                buf.extend_from_slice(body);
+                Ok(buf)
+            }
+        }
+    }
+
     /// Try to extract as a Transform message
     pub fn as_transform(&self) -> Option<&IgtlMessage<TransformMessage>> {
         match self {
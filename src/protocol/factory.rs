@@ -153,7 +153,7 @@ impl MessageFactory {
             )),
 
             // Query messages
-            "GET_TRANS" => Ok(AnyMessage::GetTransform(
+            "GET_TRANSFOR" => Ok(AnyMessage::GetTransform(
                 IgtlMessage::<GetTransformMessage>::decode_with_options(&full_msg, false)?,
             )),
             "GET_STATUS" => Ok(AnyMessage::GetStatus(
@@ -181,7 +181,7 @@ impl MessageFactory {
             )),
 
             // Response messages
-            "RTS_TRANS" => Ok(AnyMessage::RtsTransform(
+            "RTS_TRANSFOR" => Ok(AnyMessage::RtsTransform(
                 IgtlMessage::<RtsTransformMessage>::decode_with_options(&full_msg, false)?,
             )),
             "RTS_STATUS" => Ok(AnyMessage::RtsStatus(
@@ -198,12 +198,34 @@ impl MessageFactory {
             "RTS_TDATA" => Ok(AnyMessage::RtsTData(
                 IgtlMessage::<RtsTDataMessage>::decode_with_options(&full_msg, false)?,
             )),
+            "RTS_QTDATA" => Ok(AnyMessage::RtsQtData(
+                IgtlMessage::<RtsQtDataMessage>::decode_with_options(&full_msg, false)?,
+            )),
 
             // Streaming control messages
             "STT_TDATA" => Ok(AnyMessage::StartTData(
                 IgtlMessage::<StartTDataMessage>::decode_with_options(&full_msg, false)?,
             )),
-            "STP_TRANS" => Ok(AnyMessage::StopTransform(IgtlMessage::<
+            "STT_QTDATA" => Ok(AnyMessage::StartQtData(
+                IgtlMessage::<StartQtDataMessage>::decode_with_options(&full_msg, false)?,
+            )),
+            "STT_IMAGE" => Ok(AnyMessage::StartImage(
+                IgtlMessage::<StartImageMessage>::decode_with_options(&full_msg, false)?,
+            )),
+            "STT_TRANSFOR" => Ok(AnyMessage::StartTransform(IgtlMessage::<
+                StartTransformMessage,
+            >::decode_with_options(
+                &full_msg, false
+            )?)),
+            "STT_POSITION" => Ok(AnyMessage::StartPosition(IgtlMessage::<
+                StartPositionMessage,
+            >::decode_with_options(
+                &full_msg, false
+            )?)),
+            "STT_NDARRAY" => Ok(AnyMessage::StartNdArray(
+                IgtlMessage::<StartNdArrayMessage>::decode_with_options(&full_msg, false)?,
+            )),
+            "STP_TRANSFOR" => Ok(AnyMessage::StopTransform(IgtlMessage::<
                 StopTransformMessage,
             >::decode_with_options(
                 &full_msg, false
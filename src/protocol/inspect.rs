@@ -0,0 +1,319 @@
+//! Inspection of received frames for diagnostics
+//!
+//! [`FrameInfo::inspect`] takes one complete frame (e.g. from
+//! [`AsyncIgtlStream::receive_frame`](crate::io::AsyncIgtlStream::receive_frame) or a UDP
+//! datagram) and reports its header, whether the body CRC matches and the decoded
+//! message. Unlike `receive_any`, a CRC mismatch does not hide the message: the body is
+//! decoded anyway so corrupted traffic can still be looked at. [`summarize`] renders
+//! the content of a message as one short line. The `igtl-dump` binary (feature `cli`)
+//! is built on this module.
+//!
+//! # Examples
+//!
+//! ```
+//! use openigtlink_rust::protocol::inspect::FrameInfo;
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::TransformMessage;
+//!
+//! # fn main() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let frame = IgtlMessage::new(TransformMessage::translation(1.0, 2.0, 3.0), "Probe")?.encode()?;
+//! let info = FrameInfo::inspect(&frame)?;
+//! assert!(info.crc.is_valid());
+//! assert_eq!(info.device_name(), "Probe");
+//! assert_eq!(info.summary(), "translation (1.000, 2.000, 3.000)");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::error::{IgtlError, Result};
+use crate::protocol::any_message::AnyMessage;
+use crate::protocol::crc::calculate_crc;
+use crate::protocol::factory::MessageFactory;
+use crate::protocol::header::Header;
+
+/// Longest text shown by [`summarize`] for strings, commands and status messages
+const MAX_TEXT_LEN: usize = 60;
+
+/// Names listed by [`summarize`] before eliding the rest
+const MAX_NAMES: usize = 4;
+
+/// Result of checking the body CRC of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrcStatus {
+    /// The CRC in the header matches the body
+    Valid,
+    /// The CRC in the header does not match the body
+    Mismatch {
+        /// CRC in the header
+        expected: u64,
+        /// CRC computed over the received body
+        computed: u64,
+    },
+}
+
+impl CrcStatus {
+    /// Whether the CRC matches
+    pub fn is_valid(&self) -> bool {
+        matches!(self, CrcStatus::Valid)
+    }
+}
+
+/// Header, CRC status and decoded content of one frame
+#[derive(Debug)]
+pub struct FrameInfo {
+    /// Decoded header
+    pub header: Header,
+    /// Frame size in bytes, header included
+    pub size: usize,
+    /// Whether the body CRC matches the header
+    pub crc: CrcStatus,
+    /// Decoded message, or why the body could not be decoded
+    pub message: Result<AnyMessage>,
+}
+
+impl FrameInfo {
+    /// Inspect one complete frame
+    ///
+    /// The body is decoded even if its CRC does not match.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::InvalidSize`](crate::error::IgtlError::InvalidSize) - `frame` is
+    ///   shorter than the header or than the body size in the header announces
+    pub fn inspect(frame: &[u8]) -> Result<Self> {
+        let header = Header::decode(frame)?;
+        let end = Header::SIZE as u64 + header.body_size;
+        if (frame.len() as u64) < end {
+            return Err(IgtlError::InvalidSize {
+                expected: end as usize,
+                actual: frame.len(),
+            });
+        }
+        let body = &frame[Header::SIZE..end as usize];
+
+        let computed = calculate_crc(body);
+        let crc = if computed == header.crc {
+            CrcStatus::Valid
+        } else {
+            CrcStatus::Mismatch {
+                expected: header.crc,
+                computed,
+            }
+        };
+        let message = MessageFactory::new().decode_any(&header, body, false);
+        Ok(FrameInfo {
+            header,
+            size: end as usize,
+            crc,
+            message,
+        })
+    }
+
+    /// Message type from the header
+    pub fn message_type(&self) -> &str {
+        self.header.type_name.as_str().unwrap_or("?")
+    }
+
+    /// Device name from the header
+    pub fn device_name(&self) -> &str {
+        self.header.device_name.as_str().unwrap_or("?")
+    }
+
+    /// One-line summary of the content, or the decoding error
+    pub fn summary(&self) -> String {
+        match &self.message {
+            Ok(msg) => summarize(msg),
+            Err(e) => format!("undecodable: {e}"),
+        }
+    }
+}
+
+/// Summarize the content of a message in one line
+///
+/// Returns an empty string for messages without content worth showing, such as most
+/// GET_, STT_, STP_ and RTS_ messages.
+pub fn summarize(msg: &AnyMessage) -> String {
+    match msg {
+        AnyMessage::Transform(msg) => {
+            let m = &msg.content.matrix;
+            format!(
+                "translation ({:.3}, {:.3}, {:.3})",
+                m[0][3], m[1][3], m[2][3]
+            )
+        }
+        AnyMessage::Position(msg) => {
+            let (p, q) = (msg.content.position, msg.content.quaternion);
+            format!(
+                "position ({:.3}, {:.3}, {:.3}) quaternion ({:.3}, {:.3}, {:.3}, {:.3})",
+                p[0], p[1], p[2], q[0], q[1], q[2], q[3]
+            )
+        }
+        AnyMessage::Status(msg) => {
+            let status = &msg.content;
+            let mut summary = format!("code {}", status.code);
+            if status.subcode != 0 {
+                summary += &format!(" subcode {}", status.subcode);
+            }
+            if !status.error_name.is_empty() {
+                summary += &format!(" {}", status.error_name);
+            }
+            if !status.status_string.is_empty() {
+                summary += &format!(" \"{}\"", elide(&status.status_string));
+            }
+            summary
+        }
+        AnyMessage::Capability(msg) => format!("types {}", msg.content.types.join(", ")),
+        AnyMessage::Image(msg) => {
+            let image = &msg.content;
+            format!(
+                "{}x{}x{} {:?} x{}, {} bytes",
+                image.size[0],
+                image.size[1],
+                image.size[2],
+                image.scalar_type,
+                image.num_components,
+                image.data.len()
+            )
+        }
+        AnyMessage::String(msg) => format!("\"{}\"", elide(&msg.content.string)),
+        AnyMessage::Sensor(msg) => {
+            let values: Vec<String> = msg.content.data.iter().map(|v| format!("{v:.3}")).collect();
+            format!("[{}]", elide(&values.join(", ")))
+        }
+        AnyMessage::TData(msg) => names(
+            "element",
+            msg.content.elements.iter().map(|e| e.name.as_str()),
+        ),
+        AnyMessage::QtData(msg) => names(
+            "element",
+            msg.content.elements.iter().map(|e| e.name.as_str()),
+        ),
+        AnyMessage::Point(msg) => {
+            names("point", msg.content.points.iter().map(|p| p.name.as_str()))
+        }
+        AnyMessage::Trajectory(msg) => count(msg.content.trajectories.len(), "trajectory"),
+        AnyMessage::NdArray(msg) => format!(
+            "{:?} {:?}, {} bytes",
+            msg.content.size,
+            msg.content.scalar_type,
+            msg.content.data.len()
+        ),
+        AnyMessage::Bind(msg) => count(msg.content.entries.len(), "entry"),
+        AnyMessage::ColorTable(msg) => count(msg.content.colors.len(), "color"),
+        AnyMessage::ImgMeta(msg) => {
+            names("image", msg.content.images.iter().map(|i| i.name.as_str()))
+        }
+        AnyMessage::LbMeta(msg) => {
+            names("label", msg.content.labels.iter().map(|l| l.name.as_str()))
+        }
+        AnyMessage::PolyData(msg) => format!(
+            "{}, {}",
+            count(msg.content.points.len(), "point"),
+            count(msg.content.polygons.len(), "polygon value")
+        ),
+        AnyMessage::Video(msg) => format!(
+            "{}x{} {:?}, {} bytes",
+            msg.content.width,
+            msg.content.height,
+            msg.content.codec,
+            msg.content.frame_data.len()
+        ),
+        AnyMessage::VideoMeta(msg) => format!(
+            "{}x{} {:?} {} fps",
+            msg.content.width, msg.content.height, msg.content.codec, msg.content.framerate
+        ),
+        AnyMessage::Command(msg) => format!(
+            "#{} {} \"{}\"",
+            msg.content.command_id,
+            msg.content.command_name,
+            elide(&msg.content.command)
+        ),
+        AnyMessage::Unknown { body, .. } => format!("{} byte body", body.len()),
+        _ => String::new(),
+    }
+}
+
+/// Shorten `text` to [`MAX_TEXT_LEN`] characters
+fn elide(text: &str) -> String {
+    if text.chars().count() <= MAX_TEXT_LEN {
+        return text.to_string();
+    }
+    let mut short: String = text.chars().take(MAX_TEXT_LEN - 3).collect();
+    short.push_str("...");
+    short
+}
+
+/// "1 point" / "3 points"
+fn count(n: usize, noun: &str) -> String {
+    match (n, noun.strip_suffix('y')) {
+        (1, _) => format!("1 {noun}"),
+        (_, Some(stem)) => format!("{n} {stem}ies"),
+        (_, None) => format!("{n} {noun}s"),
+    }
+}
+
+/// Count followed by the first few names, e.g. "3 elements: Probe, Tool, ..."
+fn names<'a>(noun: &str, names: impl ExactSizeIterator<Item = &'a str>) -> String {
+    let total = names.len();
+    let mut listed: Vec<&str> = names.take(MAX_NAMES).collect();
+    if total > MAX_NAMES {
+        listed.push("...");
+    }
+    if total == 0 {
+        count(0, noun)
+    } else {
+        format!("{}: {}", count(total, noun), listed.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::message::IgtlMessage;
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+
+    #[test]
+    fn test_inspect_valid_frame() {
+        let mut msg = IgtlMessage::new(StatusMessage::ok("ready"), "Tracker").unwrap();
+        msg.add_metadata("Patient".to_string(), "42".to_string());
+        let frame = msg.encode().unwrap();
+
+        let info = FrameInfo::inspect(&frame).unwrap();
+        assert_eq!(info.crc, CrcStatus::Valid);
+        assert_eq!(info.size, frame.len());
+        assert_eq!(info.message_type(), "STATUS");
+        assert_eq!(info.device_name(), "Tracker");
+        assert_eq!(info.summary(), "code 1 \"ready\"");
+        let decoded = info.message.as_ref().unwrap();
+        assert_eq!(decoded.metadata().unwrap()["Patient"], "42");
+    }
+
+    #[test]
+    fn test_inspect_reports_crc_mismatch_and_still_decodes() {
+        let msg = IgtlMessage::new(TransformMessage::translation(1.0, 2.0, 3.0), "Probe").unwrap();
+        let mut frame = msg.encode().unwrap();
+        // Corrupt the x translation
+        let last = frame.len() - 12;
+        frame[last] ^= 0x40;
+
+        let info = FrameInfo::inspect(&frame).unwrap();
+        assert!(matches!(info.crc, CrcStatus::Mismatch { .. }));
+        assert!(info.message.is_ok());
+
+        assert!(FrameInfo::inspect(&frame[..frame.len() - 1]).is_err());
+        assert!(FrameInfo::inspect(&frame[..10]).is_err());
+    }
+
+    #[test]
+    fn test_summary_helpers() {
+        assert_eq!(count(1, "entry"), "1 entry");
+        assert_eq!(count(2, "entry"), "2 entries");
+        assert_eq!(count(0, "point"), "0 points");
+        let tools = ["A", "B", "C", "D", "E"];
+        assert_eq!(
+            names("element", tools.iter().copied()),
+            "5 elements: A, B, C, D, ..."
+        );
+        assert_eq!(elide(&"x".repeat(100)).len(), MAX_TEXT_LEN);
+    }
+}
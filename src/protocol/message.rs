@@ -41,7 +41,7 @@ pub trait Message: Sized {
 ///
 /// # Type Parameters
 /// * `T` - Message type that implements the `Message` trait
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct IgtlMessage<T: Message> {
     /// Message header (58 bytes)
     pub header: Header,
@@ -263,6 +263,26 @@ impl<T: Message> IgtlMessage<T> {
     /// # Returns
     /// Complete message as byte vector
     pub fn encode(&self) -> Result<Vec<u8>> {
+        self.encode_parts(self.extended_header.as_ref(), self.header.version)
+    }
+
+    /// Encode with the given v3 message ID, leaving `self` unchanged
+    ///
+    /// Equivalent to encoding a copy after [`set_message_id`](Self::set_message_id).
+    pub(crate) fn encode_with_message_id(&self, message_id: u32) -> Result<Vec<u8>> {
+        let mut ext_header = self
+            .extended_header
+            .clone()
+            .unwrap_or_else(|| ExtendedHeader::with_message_id(message_id));
+        ext_header.message_id = message_id;
+        self.encode_parts(Some(&ext_header), self.header.version.max(3))
+    }
+
+    fn encode_parts(
+        &self,
+        extended_header: Option<&ExtendedHeader>,
+        version: u16,
+    ) -> Result<Vec<u8>> {
         use crate::protocol::crc::calculate_crc;
 
         // 1. Encode content
@@ -273,8 +293,7 @@ impl<T: Message> IgtlMessage<T> {
         // V3 Format: Separate header and body
         // Header: INDEX_COUNT (2) + [KEY_SIZE (2) + VALUE_ENCODING (2) + VALUE_SIZE (4)]...
         // Body: [KEY + VALUE]...
-        let (metadata_header, metadata_body) = if self.metadata.is_some() {
-            let metadata = self.metadata.as_ref().unwrap();
+        let (metadata_header, metadata_body) = if let Some(metadata) = &self.metadata {
             let mut meta_header = Vec::new();
             let mut meta_body = Vec::new();
 
@@ -304,9 +323,8 @@ impl<T: Message> IgtlMessage<T> {
         // 3. Build body based on extended header and metadata presence (NOT version)
         // Extended Header format is determined by the presence of extended_header or metadata fields,
         // not by the version number, as version information may be unreliable.
-        let body_bytes = if self.extended_header.is_some() {
+        let body_bytes = if let Some(ext_header) = extended_header {
             // With extended header
-            let ext_header = self.extended_header.as_ref().unwrap();
 
             // Update Extended Header with current metadata information
             let mut ext_header_to_encode = ext_header.clone();
@@ -362,6 +380,7 @@ impl<T: Message> IgtlMessage<T> {
 
         // 4. Update header with correct body_size and CRC
         let mut header = self.header.clone();
+        header.version = version;
         header.body_size = body_bytes.len() as u64;
         header.crc = calculate_crc(&body_bytes);
 
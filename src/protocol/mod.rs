@@ -7,6 +7,7 @@ pub mod crc;
 pub mod extended_header;
 pub mod factory;
 pub mod header;
+pub mod inspect;
 pub mod message;
 pub mod types;
 
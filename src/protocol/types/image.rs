@@ -153,13 +153,19 @@ impl CoordinateSystem {
     }
 }
 
+/// Size of the IMAGE header preceding the image data
+const IMAGE_HEADER_SIZE: usize = 72;
+
 /// IMAGE message for 2D/3D medical image data
 ///
 /// # OpenIGTLink Specification
 /// - Message type: "IMAGE"
-/// - Header: VERSION (uint16) + NUM_COMPONENTS (uint8) + SCALAR_TYPE (uint8) + ENDIAN (uint8) + COORD (uint8) + SIZE (`uint16[3]`) + MATRIX (`float32[12]`)
-/// - Header size: 2 + 1 + 1 + 1 + 1 + 6 + 48 = 60 bytes
-/// - Followed by image data
+/// - Header: VERSION (uint16) + NUM_COMPONENTS (uint8) + SCALAR_TYPE (uint8) + ENDIAN (uint8) + COORD (uint8) + SIZE (`uint16[3]`) + MATRIX (`float32[12]`) + SUBVOL_OFFSET (`uint16[3]`) + SUBVOL_SIZE (`uint16[3]`)
+/// - Header size: 2 + 1 + 1 + 1 + 1 + 6 + 48 + 6 + 6 = 72 bytes
+/// - Followed by the image data of the sub-volume
+///
+/// A message may carry only part of the image: `data` then holds the voxels of the
+/// box at `subvolume_offset` with `subvolume_size`, within an image of `size`.
 #[derive(Debug, Clone, PartialEq)]
 pub struct ImageMessage {
     /// Protocol version (should be 1 or 2)
@@ -176,7 +182,11 @@ pub struct ImageMessage {
     pub size: [u16; 3],
     /// 4x3 transformation matrix (stored row-major, upper 3x4 of 4x4 matrix)
     pub matrix: [[f32; 4]; 3],
-    /// Image data (raw bytes)
+    /// Position of the transferred sub-volume within the image [columns, rows, slices]
+    pub subvolume_offset: [u16; 3],
+    /// Size of the transferred sub-volume; equal to `size` for a whole image
+    pub subvolume_size: [u16; 3],
+    /// Image data of the sub-volume (raw bytes)
     pub data: Vec<u8>,
 }
 
@@ -209,6 +219,8 @@ impl ImageMessage {
                 [0.0, 1.0, 0.0, 0.0],
                 [0.0, 0.0, 1.0, 0.0],
             ],
+            subvolume_offset: [0; 3],
+            subvolume_size: size,
             data,
         })
     }
@@ -241,6 +253,8 @@ impl ImageMessage {
                 [0.0, 1.0, 0.0, 0.0],
                 [0.0, 0.0, 1.0, 0.0],
             ],
+            subvolume_offset: [0; 3],
+            subvolume_size: size,
             data,
         })
     }
@@ -261,6 +275,81 @@ impl ImageMessage {
     pub fn num_pixels(&self) -> usize {
         (self.size[0] as usize) * (self.size[1] as usize) * (self.size[2] as usize)
     }
+
+    /// Whether the message carries only part of the image
+    pub fn is_subvolume(&self) -> bool {
+        self.subvolume_offset != [0; 3] || self.subvolume_size != self.size
+    }
+
+    /// Bytes per voxel (all components)
+    pub fn voxel_size(&self) -> usize {
+        self.num_components as usize * self.scalar_type.size()
+    }
+
+    /// Copy the box at `offset` with `size` into a sub-volume message
+    ///
+    /// `offset` is relative to the whole image. The box must lie within the sub-volume
+    /// this message carries.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::InvalidHeader`] - The box is empty or not within the sub-volume
+    /// - [`IgtlError::InvalidSize`] - `data` does not hold the carried sub-volume
+    pub fn subvolume(&self, offset: [u16; 3], size: [u16; 3]) -> Result<Self> {
+        let voxel = self.voxel_size();
+        let expected = self
+            .subvolume_size
+            .iter()
+            .map(|&s| s as usize)
+            .product::<usize>()
+            * voxel;
+        if self.data.len() != expected {
+            return Err(IgtlError::InvalidSize {
+                expected,
+                actual: self.data.len(),
+            });
+        }
+        let contained = (0..3).all(|axis| {
+            let start = offset[axis] as usize;
+            let end = start + size[axis] as usize;
+            let own_start = self.subvolume_offset[axis] as usize;
+            size[axis] > 0
+                && start >= own_start
+                && end <= own_start + self.subvolume_size[axis] as usize
+        });
+        if !contained {
+            return Err(IgtlError::InvalidHeader(format!(
+                "Sub-volume {:?}+{:?} is not within {:?}+{:?}",
+                offset, size, self.subvolume_offset, self.subvolume_size
+            )));
+        }
+
+        // Copy row by row; rows are contiguous along the first axis
+        let [own_x, own_y, _] = self.subvolume_size.map(usize::from);
+        let relative = |axis: usize| (offset[axis] - self.subvolume_offset[axis]) as usize;
+        let (x, y, z) = (relative(0), relative(1), relative(2));
+        let row_len = size[0] as usize * voxel;
+        let mut data = Vec::with_capacity(row_len * size[1] as usize * size[2] as usize);
+        for k in z..z + size[2] as usize {
+            for j in y..y + size[1] as usize {
+                let start = ((k * own_y + j) * own_x + x) * voxel;
+                data.extend_from_slice(&self.data[start..start + row_len]);
+            }
+        }
+
+        Ok(ImageMessage {
+            version: self.version,
+            num_components: self.num_components,
+            scalar_type: self.scalar_type,
+            endian: self.endian,
+            coordinate: self.coordinate,
+            size: self.size,
+            matrix: self.matrix,
+            subvolume_offset: offset,
+            subvolume_size: size,
+            data,
+        })
+    }
 }
 
 impl Message for ImageMessage {
@@ -269,7 +358,7 @@ impl Message for ImageMessage {
     }
 
     fn encode_content(&self) -> Result<Vec<u8>> {
-        let mut buf = Vec::with_capacity(60 + self.data.len());
+        let mut buf = Vec::with_capacity(IMAGE_HEADER_SIZE + self.data.len());
 
         // Encode VERSION (uint16)
         buf.put_u16(self.version);
@@ -298,6 +387,11 @@ impl Message for ImageMessage {
             }
         }
 
+        // Encode SUBVOL_OFFSET and SUBVOL_SIZE (`uint16[3]` each)
+        for &s in self.subvolume_offset.iter().chain(&self.subvolume_size) {
+            buf.put_u16(s);
+        }
+
         // Encode image data
         buf.extend_from_slice(&self.data);
 
@@ -305,9 +399,9 @@ impl Message for ImageMessage {
     }
 
     fn decode_content(mut data: &[u8]) -> Result<Self> {
-        if data.len() < 60 {
+        if data.len() < IMAGE_HEADER_SIZE {
             return Err(IgtlError::InvalidSize {
-                expected: 60,
+                expected: IMAGE_HEADER_SIZE,
                 actual: data.len(),
             });
         }
@@ -338,13 +432,26 @@ impl Message for ImageMessage {
             }
         }
 
+        // Decode SUBVOL_OFFSET and SUBVOL_SIZE (`uint16[3]` each)
+        let subvolume_offset = [data.get_u16(), data.get_u16(), data.get_u16()];
+        let subvolume_size = [data.get_u16(), data.get_u16(), data.get_u16()];
+        let within = (0..3).all(|axis| {
+            subvolume_offset[axis] as usize + subvolume_size[axis] as usize <= size[axis] as usize
+        });
+        if !within {
+            return Err(IgtlError::InvalidHeader(format!(
+                "Sub-volume {:?}+{:?} exceeds image size {:?}",
+                subvolume_offset, subvolume_size, size
+            )));
+        }
+
         // Decode image data (remaining bytes)
         let image_data = data.to_vec();
 
-        // Validate data size
-        let expected_size = (size[0] as usize)
-            * (size[1] as usize)
-            * (size[2] as usize)
+        // Validate data size against the sub-volume
+        let expected_size = (subvolume_size[0] as usize)
+            * (subvolume_size[1] as usize)
+            * (subvolume_size[2] as usize)
             * (num_components as usize)
             * scalar_type.size();
 
@@ -363,6 +470,8 @@ impl Message for ImageMessage {
             coordinate,
             size,
             matrix,
+            subvolume_offset,
+            subvolume_size,
             data: image_data,
         })
     }
@@ -497,6 +606,63 @@ mod tests {
         assert_eq!(decoded.matrix, matrix);
     }
 
+    #[test]
+    fn test_subvolume_fields_roundtrip() {
+        let mut image = ImageMessage::new(ImageScalarType::Uint8, [4, 4, 4], vec![7; 64]).unwrap();
+        assert!(!image.is_subvolume());
+        let encoded = image.encode_content().unwrap();
+        assert_eq!(encoded.len(), 72 + 64);
+        assert_eq!(ImageMessage::decode_content(&encoded).unwrap(), image);
+
+        // Rows 2-3 of slices 1-3, columns 1-2
+        image.subvolume_offset = [1, 2, 1];
+        image.subvolume_size = [2, 2, 3];
+        image.data = (0..12).collect();
+        assert!(image.is_subvolume());
+        let encoded = image.encode_content().unwrap();
+        assert_eq!(encoded[60..72], [0, 1, 0, 2, 0, 1, 0, 2, 0, 2, 0, 3]);
+        assert_eq!(ImageMessage::decode_content(&encoded).unwrap(), image);
+
+        // A sub-volume reaching past the image is rejected
+        let mut invalid = encoded.clone();
+        invalid[66..68].copy_from_slice(&5u16.to_be_bytes());
+        assert!(ImageMessage::decode_content(&invalid).is_err());
+        // Data must match the sub-volume size
+        assert!(ImageMessage::decode_content(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_subvolume_roundtrip() {
+        // Voxel value encodes its position: x + 4 * y + 16 * z
+        let data = (0..64u8).collect();
+        let image = ImageMessage::new(ImageScalarType::Uint8, [4, 4, 4], data).unwrap();
+        assert!(!image.is_subvolume());
+
+        let part = image.subvolume([1, 2, 1], [2, 2, 3]).unwrap();
+        assert!(part.is_subvolume());
+        assert_eq!(part.size, [4, 4, 4]);
+        assert_eq!(part.data, [25, 26, 29, 30, 41, 42, 45, 46, 57, 58, 61, 62]);
+        let decoded = ImageMessage::decode_content(&part.encode_content().unwrap()).unwrap();
+        assert_eq!(decoded, part);
+
+        // Offsets stay relative to the whole image
+        let voxel = part.subvolume([2, 3, 3], [1, 1, 1]).unwrap();
+        assert_eq!(voxel.data, [62]);
+        assert!(part.subvolume([0, 2, 1], [1, 1, 1]).is_err());
+        assert!(image.subvolume([0, 0, 0], [4, 4, 0]).is_err());
+
+        // Hand-built messages with too little data are rejected instead of panicking
+        let mut truncated = image.clone();
+        truncated.data.truncate(60);
+        assert!(matches!(
+            truncated.subvolume([0, 0, 3], [4, 4, 1]),
+            Err(IgtlError::InvalidSize {
+                expected: 64,
+                actual: 60
+            })
+        ));
+    }
+
     #[test]
     fn test_decode_invalid_header() {
         let data = vec![0u8; 50]; // Too short
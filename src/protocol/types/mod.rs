@@ -40,9 +40,10 @@ pub use qtdata::{InstrumentType, QtDataMessage, TrackingElement};
 pub use query::{
     GetCapabilityMessage, GetImageMessage, GetImgMetaMessage, GetLbMetaMessage, GetPointMessage,
     GetStatusMessage, GetTDataMessage, GetTransformMessage, RtsCapabilityMessage, RtsImageMessage,
-    RtsStatusMessage, RtsTDataMessage, RtsTransformMessage, StartTDataMessage, StopImageMessage,
-    StopNdArrayMessage, StopPositionMessage, StopQtDataMessage, StopTDataMessage,
-    StopTransformMessage,
+    RtsQtDataMessage, RtsStatusMessage, RtsTDataMessage, RtsTransformMessage, StartImageMessage,
+    StartNdArrayMessage, StartPositionMessage, StartQtDataMessage, StartTDataMessage,
+    StartTransformMessage, StopImageMessage, StopNdArrayMessage, StopPositionMessage,
+    StopQtDataMessage, StopTDataMessage, StopTransformMessage,
 };
 pub use sensor::SensorMessage;
 pub use status::StatusMessage;
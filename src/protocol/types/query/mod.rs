@@ -17,11 +17,13 @@ pub mod streaming;
 // Re-export query message types
 pub use get::*;
 pub use rts::{
-    RtsCapabilityMessage, RtsImageMessage, RtsStatusMessage, RtsTDataMessage, RtsTransformMessage,
+    RtsCapabilityMessage, RtsImageMessage, RtsQtDataMessage, RtsStatusMessage, RtsTDataMessage,
+    RtsTransformMessage,
 };
 pub use streaming::{
-    StartTDataMessage, StopImageMessage, StopNdArrayMessage, StopPositionMessage,
-    StopQtDataMessage, StopTDataMessage, StopTransformMessage,
+    StartImageMessage, StartNdArrayMessage, StartPositionMessage, StartQtDataMessage,
+    StartTDataMessage, StartTransformMessage, StopImageMessage, StopNdArrayMessage,
+    StopPositionMessage, StopQtDataMessage, StopTDataMessage, StopTransformMessage,
 };
 
 /// Macro to define empty-body query messages (GET_*, STP_*)
@@ -57,7 +59,6 @@ pub(crate) use impl_empty_query;
 
 #[cfg(test)]
 mod tests {
-    use super::*;
     use crate::protocol::message::Message;
 
     // Test macro expansion
@@ -57,6 +57,56 @@ impl Message for RtsTDataMessage {
     }
 }
 
+/// RTS_QTDATA: Ready-to-send response for quaternion tracking data
+///
+/// # OpenIGTLink Specification
+/// - Message type: "RTS_QTDATA"
+/// - Body size: 2 bytes
+/// - Encoding: u16 status code (0=error, 1=ok, big-endian)
+#[derive(Debug, Clone, PartialEq)]
+pub struct RtsQtDataMessage {
+    /// Status code: 0 = error, 1 = ok
+    pub status: u16,
+}
+
+impl RtsQtDataMessage {
+    /// Create OK response
+    pub fn ok() -> Self {
+        Self { status: 1 }
+    }
+
+    /// Create error response
+    pub fn error() -> Self {
+        Self { status: 0 }
+    }
+
+    /// Create with specific status code
+    pub fn new(status: u16) -> Self {
+        Self { status }
+    }
+}
+
+impl Message for RtsQtDataMessage {
+    fn message_type() -> &'static str {
+        "RTS_QTDATA"
+    }
+
+    fn encode_content(&self) -> Result<Vec<u8>> {
+        Ok(self.status.to_be_bytes().to_vec())
+    }
+
+    fn decode_content(data: &[u8]) -> Result<Self> {
+        if data.len() < 2 {
+            return Err(IgtlError::InvalidSize {
+                expected: 2,
+                actual: data.len(),
+            });
+        }
+        let status = u16::from_be_bytes([data[0], data[1]]);
+        Ok(Self { status })
+    }
+}
+
 // Type aliases for RTS messages that use StatusMessage format
 /// RTS_CAPABILITY: Uses StatusMessage format
 pub type RtsCapabilityMessage = StatusMessage;
@@ -133,6 +183,17 @@ mod tests {
         assert!(matches!(result, Err(IgtlError::InvalidSize { .. })));
     }
 
+    #[test]
+    fn test_rts_qtdata_roundtrip() {
+        assert_eq!(RtsQtDataMessage::message_type(), "RTS_QTDATA");
+
+        let encoded = RtsQtDataMessage::ok().encode_content().unwrap();
+        assert_eq!(encoded.len(), 2);
+
+        let decoded = RtsQtDataMessage::decode_content(&encoded).unwrap();
+        assert_eq!(decoded.status, 1);
+    }
+
     #[test]
     fn test_type_aliases() {
         // Verify type aliases compile correctly
@@ -17,6 +17,12 @@ impl_empty_query!(StopPositionMessage, "STP_POSITION");
 impl_empty_query!(StopQtDataMessage, "STP_QTDATA");
 impl_empty_query!(StopNdArrayMessage, "STP_NDARRAY");
 
+// STT (Start) messages without parameters - empty body
+impl_empty_query!(StartImageMessage, "STT_IMAGE");
+impl_empty_query!(StartTransformMessage, "STT_TRANSFOR");
+impl_empty_query!(StartPositionMessage, "STT_POSITION");
+impl_empty_query!(StartNdArrayMessage, "STT_NDARRAY");
+
 // STT_TDATA: Start tracking data streaming
 /// Start tracking data streaming message
 ///
@@ -59,41 +65,63 @@ impl Message for StartTDataMessage {
     }
 
     fn encode_content(&self) -> Result<Vec<u8>> {
-        let mut buf = Vec::with_capacity(36);
-
-        // Encode resolution (4 bytes, big-endian)
-        buf.put_u32(self.resolution);
-
-        // Encode coordinate_name (32 bytes, null-padded)
-        let mut coord_bytes = [0u8; 32];
-        let name_bytes = self.coordinate_name.as_bytes();
-        let len = name_bytes.len().min(32);
-        coord_bytes[..len].copy_from_slice(&name_bytes[..len]);
-        buf.extend_from_slice(&coord_bytes);
-
-        Ok(buf)
+        Ok(encode_stream_params(self.resolution, &self.coordinate_name))
     }
 
     fn decode_content(data: &[u8]) -> Result<Self> {
-        if data.len() < 36 {
-            return Err(IgtlError::InvalidSize {
-                expected: 36,
-                actual: data.len(),
-            });
-        }
+        let (resolution, coordinate_name) = decode_stream_params(data)?;
+        Ok(Self {
+            resolution,
+            coordinate_name,
+        })
+    }
+}
 
-        let mut cursor = std::io::Cursor::new(data);
+// STT_QTDATA: Start quaternion tracking data streaming
+/// Start quaternion tracking data streaming message
+///
+/// # OpenIGTLink Specification
+/// - Message type: "STT_QTDATA"
+/// - Body size: 36 bytes (fixed)
+/// - Encoding: identical to STT_TDATA (resolution + coordinate_name)
+///
+/// # C++ Compatibility
+/// Matches igtl_stt_qtdata structure:
+/// ```c
+/// typedef struct {
+///   igtl_uint32 resolution;   // 4 bytes
+///   char coord_name[32];      // 32 bytes
+/// } igtl_stt_qtdata;          // total: 36 bytes
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartQtDataMessage {
+    /// Streaming interval in milliseconds (e.g., 50ms = 20Hz)
+    pub resolution: u32,
+    /// Coordinate system name (max 32 characters)
+    pub coordinate_name: String,
+}
 
-        // Decode resolution (4 bytes, big-endian)
-        let resolution = cursor.get_u32();
+impl StartQtDataMessage {
+    /// Create a new StartQtDataMessage
+    pub fn new(resolution: u32, coordinate_name: impl Into<String>) -> Self {
+        Self {
+            resolution,
+            coordinate_name: coordinate_name.into(),
+        }
+    }
+}
+
+impl Message for StartQtDataMessage {
+    fn message_type() -> &'static str {
+        "STT_QTDATA"
+    }
 
-        // Decode coordinate_name (32 bytes, null-terminated)
-        let coord_bytes = &data[4..36];
-        let len = coord_bytes.iter().position(|&b| b == 0).unwrap_or(32);
-        let coordinate_name = String::from_utf8(coord_bytes[..len].to_vec()).map_err(|_| {
-            IgtlError::InvalidHeader("Invalid UTF-8 in coordinate name".to_string())
-        })?;
+    fn encode_content(&self) -> Result<Vec<u8>> {
+        Ok(encode_stream_params(self.resolution, &self.coordinate_name))
+    }
 
+    fn decode_content(data: &[u8]) -> Result<Self> {
+        let (resolution, coordinate_name) = decode_stream_params(data)?;
         Ok(Self {
             resolution,
             coordinate_name,
@@ -101,6 +129,46 @@ impl Message for StartTDataMessage {
     }
 }
 
+/// Encode the 36-byte resolution + coordinate name body shared by STT_TDATA and STT_QTDATA
+fn encode_stream_params(resolution: u32, coordinate_name: &str) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(36);
+
+    // Encode resolution (4 bytes, big-endian)
+    buf.put_u32(resolution);
+
+    // Encode coordinate_name (32 bytes, null-padded)
+    let mut coord_bytes = [0u8; 32];
+    let name_bytes = coordinate_name.as_bytes();
+    let len = name_bytes.len().min(32);
+    coord_bytes[..len].copy_from_slice(&name_bytes[..len]);
+    buf.extend_from_slice(&coord_bytes);
+
+    buf
+}
+
+/// Decode the 36-byte resolution + coordinate name body shared by STT_TDATA and STT_QTDATA
+fn decode_stream_params(data: &[u8]) -> Result<(u32, String)> {
+    if data.len() < 36 {
+        return Err(IgtlError::InvalidSize {
+            expected: 36,
+            actual: data.len(),
+        });
+    }
+
+    let mut cursor = std::io::Cursor::new(data);
+
+    // Decode resolution (4 bytes, big-endian)
+    let resolution = cursor.get_u32();
+
+    // Decode coordinate_name (32 bytes, null-terminated)
+    let coord_bytes = &data[4..36];
+    let len = coord_bytes.iter().position(|&b| b == 0).unwrap_or(32);
+    let coordinate_name = String::from_utf8(coord_bytes[..len].to_vec())
+        .map_err(|_| IgtlError::InvalidHeader("Invalid UTF-8 in coordinate name".to_string()))?;
+
+    Ok((resolution, coordinate_name))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -129,6 +197,25 @@ mod tests {
         assert!(StopNdArrayMessage::message_type().len() <= 12);
     }
 
+    #[test]
+    fn test_all_start_messages_type_names() {
+        assert!(StartImageMessage::message_type().len() <= 12);
+        assert!(StartTransformMessage::message_type().len() <= 12);
+        assert!(StartPositionMessage::message_type().len() <= 12);
+        assert!(StartNdArrayMessage::message_type().len() <= 12);
+        assert!(StartQtDataMessage::message_type().len() <= 12);
+    }
+
+    #[test]
+    fn test_start_qtdata_roundtrip() {
+        let original = StartQtDataMessage::new(20, "RAS");
+        let encoded = original.encode_content().unwrap();
+        assert_eq!(encoded.len(), 36);
+
+        let decoded = StartQtDataMessage::decode_content(&encoded).unwrap();
+        assert_eq!(decoded, original);
+    }
+
     // StartTDataMessage tests
     #[test]
     fn test_start_tdata_message_type() {
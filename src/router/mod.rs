@@ -0,0 +1,811 @@
+//! OpenIGTLink router: merge several upstream servers into one downstream server
+//!
+//! A [`Router`] connects as a client to every configured [`Upstream`] (tracker,
+//! ultrasound, robot, ...) and serves the merged message stream to any number of
+//! downstream clients. Each message from an upstream is checked against the
+//! [`Route`]s in order; the first match drops it or forwards it, optionally under a new
+//! device name and converted to another coordinate convention. Messages matching no
+//! route are forwarded unchanged unless
+//! [`forward_unmatched`](RouterConfig::forward_unmatched) is off.
+//!
+//! Messages from downstream clients go the other way: a message addressed to a renamed
+//! device (e.g. a GET_TRANSFORM for "ProbeToTracker") is sent to that route's upstream
+//! under the original name with the inverse conversion; anything else is sent to every
+//! upstream. Replies are forwarded like any other upstream message, to all downstream
+//! clients.
+//!
+//! Upstreams reconnect with the [`ReconnectConfig`] of the router and restart the
+//! streams downstream clients requested with STT_* messages (and not stopped with
+//! STP_* since). Downstream clients may
+//! come and go at any time; one that falls too far behind misses messages instead of
+//! slowing the others down. [`Router::stats`] reports per-route, per-upstream and
+//! downstream counters.
+//!
+//! The `igtl-router` binary (feature `cli`) runs a router from a JSON configuration
+//! file.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use openigtlink_rust::io::CancellationToken;
+//! use openigtlink_rust::router::{Conversion, Route, Router, RouterConfig, Upstream};
+//!
+//! # async fn example() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let config = RouterConfig::new()
+//!     .with_listen("0.0.0.0:18944")
+//!     .with_upstream(Upstream::new("Tracker", "10.0.0.2:18944"))
+//!     .with_upstream(Upstream::new("Robot", "10.0.0.3:18944"))
+//!     .with_route(
+//!         Route::new("probe")
+//!             .from("Tracker:Probe")
+//!             .rename("ProbeToTracker")
+//!             .convert(Conversion::LpsToRas),
+//!     )
+//!     .with_route(Route::new("no-images").with_types(["IMAGE"]).drop());
+//!
+//! let router = Router::bind(config).await?;
+//! let stop = CancellationToken::new();
+//! router.run(&stop).await;
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod rules;
+
+pub use rules::{Conversion, Route, RouteAction};
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{broadcast, mpsc};
+use tokio::task::JoinSet;
+use tokio_rustls::rustls;
+use tracing::{debug, info, trace, warn};
+
+use crate::error::{IgtlError, Result};
+use crate::io::builder::ClientBuilder;
+use crate::io::limits::ServerLimits;
+use crate::io::reconnect::ReconnectConfig;
+use crate::io::session::StreamRegistry;
+use crate::io::shutdown::{unless_cancelled, CancellationToken, ShutdownConfig};
+use crate::io::{AsyncClientReader, AsyncClientWriter, AsyncIgtlConnection, AsyncIgtlServer};
+use crate::protocol::AnyMessage;
+
+/// Default number of messages buffered per downstream client
+pub const DEFAULT_DOWNSTREAM_BUFFER: usize = 1024;
+
+/// Messages from downstream clients buffered per upstream
+const UPSTREAM_QUEUE_CAPACITY: usize = 256;
+
+/// Server the router connects to as a client
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    /// Name used in routes and statistics
+    pub name: String,
+    /// Server address, e.g. "10.0.0.2:18944"
+    pub addr: String,
+    /// TLS configuration, if the server requires TLS (default none)
+    pub tls: Option<Arc<rustls::ClientConfig>>,
+}
+
+impl Upstream {
+    /// Create a plain TCP upstream
+    pub fn new(name: impl Into<String>, addr: impl Into<String>) -> Self {
+        Upstream {
+            name: name.into(),
+            addr: addr.into(),
+            tls: None,
+        }
+    }
+
+    /// Connect over TLS, see [`ClientTlsConfig`](crate::io::tls::ClientTlsConfig)
+    pub fn with_tls(mut self, config: Arc<rustls::ClientConfig>) -> Self {
+        self.tls = Some(config);
+        self
+    }
+}
+
+/// Router configuration
+#[derive(Debug, Clone)]
+pub struct RouterConfig {
+    /// Address downstream clients connect to (default "0.0.0.0:18944")
+    pub listen: String,
+    /// Servers to merge
+    pub upstreams: Vec<Upstream>,
+    /// Routing rules, checked in order
+    pub routes: Vec<Route>,
+    /// Forward upstream messages that match no route (default true)
+    pub forward_unmatched: bool,
+    /// Reconnection strategy for upstreams (default infinite retries)
+    pub reconnect: ReconnectConfig,
+    /// Messages buffered per downstream client before it misses messages (default 1024)
+    pub downstream_buffer: usize,
+    /// Limits for downstream sessions (default none)
+    pub limits: ServerLimits,
+    /// How downstream sessions are closed when the router stops
+    pub shutdown: ShutdownConfig,
+}
+
+impl RouterConfig {
+    /// Create the default configuration without upstreams
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the address downstream clients connect to
+    pub fn with_listen(mut self, addr: impl Into<String>) -> Self {
+        self.listen = addr.into();
+        self
+    }
+
+    /// Add a server to merge
+    pub fn with_upstream(mut self, upstream: Upstream) -> Self {
+        self.upstreams.push(upstream);
+        self
+    }
+
+    /// Add a routing rule after the existing ones
+    pub fn with_route(mut self, route: Route) -> Self {
+        self.routes.push(route);
+        self
+    }
+
+    /// Forward or drop upstream messages that match no route
+    pub fn with_forward_unmatched(mut self, forward: bool) -> Self {
+        self.forward_unmatched = forward;
+        self
+    }
+
+    /// Set the reconnection strategy for upstreams
+    pub fn with_reconnect(mut self, config: ReconnectConfig) -> Self {
+        self.reconnect = config;
+        self
+    }
+
+    /// Set the number of messages buffered per downstream client
+    ///
+    /// # Panics
+    ///
+    /// Panics if `messages` is zero.
+    pub fn with_downstream_buffer(mut self, messages: usize) -> Self {
+        assert!(
+            messages > 0,
+            "downstream buffer must hold at least one message"
+        );
+        self.downstream_buffer = messages;
+        self
+    }
+
+    /// Apply limits to downstream sessions
+    pub fn with_limits(mut self, limits: ServerLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Set how downstream sessions are closed when the router stops
+    pub fn with_shutdown(mut self, config: ShutdownConfig) -> Self {
+        self.shutdown = config;
+        self
+    }
+
+    /// Check upstream names and routes
+    fn validate(&self) -> Result<()> {
+        let invalid = |reason: String| {
+            Err(IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                reason,
+            )))
+        };
+        let mut names: Vec<&str> = Vec::with_capacity(self.upstreams.len());
+        for upstream in &self.upstreams {
+            if upstream.name.is_empty() || upstream.name.contains(':') {
+                return invalid(format!("invalid upstream name \"{}\"", upstream.name));
+            }
+            if names.contains(&upstream.name.as_str()) {
+                return invalid(format!("duplicate upstream \"{}\"", upstream.name));
+            }
+            names.push(&upstream.name);
+        }
+        for route in &self.routes {
+            route.validate(&names)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        RouterConfig {
+            listen: "0.0.0.0:18944".to_string(),
+            upstreams: Vec::new(),
+            routes: Vec::new(),
+            forward_unmatched: true,
+            reconnect: ReconnectConfig::infinite(),
+            downstream_buffer: DEFAULT_DOWNSTREAM_BUFFER,
+            limits: ServerLimits::default(),
+            shutdown: ShutdownConfig::default(),
+        }
+    }
+}
+
+/// Counters of one route
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouteStats {
+    /// Messages matched
+    pub matched: u64,
+    /// Messages forwarded downstream
+    pub forwarded: u64,
+    /// Bytes forwarded downstream
+    pub bytes: u64,
+    /// Messages that could not be renamed or encoded
+    pub errors: u64,
+}
+
+/// Counters of one upstream
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct UpstreamStats {
+    /// Upstream name
+    pub name: String,
+    /// Whether the upstream is currently connected
+    pub connected: bool,
+    /// Connections established, including reconnections
+    pub connections: u64,
+    /// Messages received from the upstream
+    pub received: u64,
+    /// Messages from downstream clients sent to the upstream
+    pub sent: u64,
+}
+
+/// Snapshot of the router counters
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RouterStats {
+    /// Counters per route, in route order
+    pub routes: Vec<(String, RouteStats)>,
+    /// Counters of messages that matched no route
+    pub unmatched: RouteStats,
+    /// Counters per upstream, in configuration order
+    pub upstreams: Vec<UpstreamStats>,
+    /// Downstream clients currently connected
+    pub downstream_clients: usize,
+    /// Messages missed by downstream clients that fell behind
+    pub downstream_lagged: u64,
+}
+
+/// Router merging upstream servers into one downstream server
+///
+/// See the [module documentation](self).
+pub struct Router {
+    server: AsyncIgtlServer,
+    config: RouterConfig,
+    stats: Arc<Mutex<RouterStats>>,
+}
+
+impl Router {
+    /// Check the configuration and bind the downstream listener
+    ///
+    /// Upstreams are connected by [`run`](Self::run).
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind, or
+    ///   [`InvalidInput`](std::io::ErrorKind::InvalidInput) for a route naming an unknown
+    ///   upstream, a duplicate upstream name or an invalid rename or scale factor
+    pub async fn bind(config: RouterConfig) -> Result<Self> {
+        config.validate()?;
+        let server = AsyncIgtlServer::bind(&config.listen)
+            .await?
+            .with_limits(config.limits.clone());
+        let stats = RouterStats {
+            routes: config
+                .routes
+                .iter()
+                .map(|route| (route.name.clone(), RouteStats::default()))
+                .collect(),
+            upstreams: config
+                .upstreams
+                .iter()
+                .map(|upstream| UpstreamStats {
+                    name: upstream.name.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        };
+        info!(
+            listen = %config.listen,
+            upstreams = config.upstreams.len(),
+            routes = config.routes.len(),
+            "Router bound"
+        );
+        Ok(Router {
+            server,
+            config,
+            stats: Arc::new(Mutex::new(stats)),
+        })
+    }
+
+    /// Address downstream clients connect to
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        self.server.local_addr()
+    }
+
+    /// Current counters
+    pub fn stats(&self) -> RouterStats {
+        let mut stats = self.stats.lock().unwrap().clone();
+        stats.downstream_clients = self.server.active_sessions();
+        stats
+    }
+
+    /// Connect the upstreams and serve downstream clients until `token` is cancelled
+    ///
+    /// Then closes the upstream connections and shuts the downstream server down as
+    /// configured by [`RouterConfig::shutdown`]. A router runs only once.
+    pub async fn run(&self, token: &CancellationToken) {
+        let (frames, _) = broadcast::channel(self.config.downstream_buffer);
+        let mut upstream_queues = HashMap::new();
+        let mut receivers = Vec::new();
+        for upstream in &self.config.upstreams {
+            let (tx, rx) = mpsc::channel(UPSTREAM_QUEUE_CAPACITY);
+            upstream_queues.insert(upstream.name.clone(), tx);
+            receivers.push(rx);
+        }
+        let ctx = Arc::new(Context {
+            routes: self.config.routes.clone(),
+            upstream_names: self
+                .config
+                .upstreams
+                .iter()
+                .map(|u| u.name.clone())
+                .collect(),
+            forward_unmatched: self.config.forward_unmatched,
+            frames,
+            upstream_queues,
+            stats: Arc::clone(&self.stats),
+        });
+
+        let mut tasks = JoinSet::new();
+        for (index, (upstream, outgoing)) in self.config.upstreams.iter().zip(receivers).enumerate()
+        {
+            tasks.spawn(run_upstream(
+                Arc::clone(&ctx),
+                index,
+                upstream.clone(),
+                self.config.reconnect.clone(),
+                outgoing,
+                token.clone(),
+            ));
+        }
+
+        loop {
+            match self.server.accept_until(token).await {
+                Ok(Some(conn)) => {
+                    let frames = ctx.frames.subscribe();
+                    tasks.spawn(run_downstream(Arc::clone(&ctx), conn, frames));
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    warn!(error = %e, "Failed to accept downstream client");
+                    if unless_cancelled(tokio::time::sleep(ACCEPT_RETRY_DELAY), token)
+                        .await
+                        .is_none()
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+
+        info!("Stopping router");
+        let remaining = self.server.shutdown(self.config.shutdown.clone()).await;
+        if remaining > 0 {
+            warn!(remaining, "Downstream clients still connected at shutdown");
+        }
+        tasks.shutdown().await;
+        info!("Router stopped");
+    }
+}
+
+/// Pause after a failed accept, e.g. when out of file descriptors
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// State shared by the upstream and downstream tasks of a running router
+struct Context {
+    routes: Vec<Route>,
+    upstream_names: Vec<String>,
+    forward_unmatched: bool,
+    /// Encoded messages for all downstream clients
+    frames: broadcast::Sender<Arc<Vec<u8>>>,
+    /// Messages from downstream clients, per upstream
+    upstream_queues: HashMap<String, mpsc::Sender<AnyMessage>>,
+    stats: Arc<Mutex<RouterStats>>,
+}
+
+impl Context {
+    fn update_upstream(&self, index: usize, update: impl FnOnce(&mut UpstreamStats)) {
+        update(&mut self.stats.lock().unwrap().upstreams[index]);
+    }
+
+    /// Route a message from upstream `index` to the downstream clients
+    fn route_downstream(&self, index: usize, msg: AnyMessage) {
+        let upstream = &self.upstream_names[index];
+        let device = msg.device_name().unwrap_or_default().to_string();
+        let route = self
+            .routes
+            .iter()
+            .position(|route| route.matches(upstream, &device, msg.message_type()));
+
+        let routed = match route {
+            Some(i) => self.routes[i].apply(msg),
+            None if self.forward_unmatched => Ok(Some(msg)),
+            None => Ok(None),
+        };
+        let frame = routed.and_then(|routed| routed.map(|msg| msg.encode()).transpose());
+
+        let mut stats = self.stats.lock().unwrap();
+        let counters = match route {
+            Some(i) => &mut stats.routes[i].1,
+            None => &mut stats.unmatched,
+        };
+        counters.matched += 1;
+        match frame {
+            Ok(Some(frame)) => {
+                counters.forwarded += 1;
+                counters.bytes += frame.len() as u64;
+                drop(stats);
+                trace!(upstream = %upstream, device = %device, "Forwarding message downstream");
+                // Fails only while no downstream client is connected
+                let _ = self.frames.send(Arc::new(frame));
+            }
+            Ok(None) => trace!(upstream = %upstream, device = %device, "Message dropped"),
+            Err(e) => {
+                counters.errors += 1;
+                warn!(error = %e, upstream = %upstream, device = %device, "Failed to route message");
+            }
+        }
+    }
+
+    /// Route a message from a downstream client to the upstreams
+    fn route_upstream(&self, msg: AnyMessage) {
+        for route in &self.routes {
+            match route.reverse(&msg) {
+                Ok(Some((upstream, msg))) => return self.queue_upstream(&upstream, msg),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(error = %e, route = %route.name, "Failed to route message upstream");
+                    return;
+                }
+            }
+        }
+        for upstream in &self.upstream_names {
+            self.queue_upstream(upstream, msg.clone());
+        }
+    }
+
+    fn queue_upstream(&self, upstream: &str, msg: AnyMessage) {
+        if let Err(e) = self.upstream_queues[upstream].try_send(msg) {
+            warn!(upstream = %upstream, error = %e, "Message for upstream discarded");
+        }
+    }
+}
+
+/// Keep upstream `index` connected and relay its messages until `token` is cancelled
+///
+/// Gives up once `max_attempts` consecutive connection attempts after the first have
+/// failed.
+async fn run_upstream(
+    ctx: Arc<Context>,
+    index: usize,
+    upstream: Upstream,
+    reconnect: ReconnectConfig,
+    mut outgoing: mpsc::Receiver<AnyMessage>,
+    token: CancellationToken,
+) {
+    // STT_* requests sent upstream, replayed on every new connection
+    let mut streams = StreamRegistry::default();
+    // Consecutive failed connection attempts, reset once a connection is established
+    let mut failures = 0;
+    loop {
+        if failures > 0 {
+            if reconnect.max_attempts.is_some_and(|max| failures > max) {
+                warn!(upstream = %upstream.name, failures, "Giving up on upstream");
+                return;
+            }
+            let delay = reconnect.delay_for_attempt(failures - 1);
+            if unless_cancelled(tokio::time::sleep(delay), &token)
+                .await
+                .is_none()
+            {
+                return;
+            }
+        }
+        let mut builder = ClientBuilder::new().tcp(upstream.addr.clone()).async_mode();
+        if let Some(tls) = &upstream.tls {
+            builder = builder.with_tls(Arc::clone(tls));
+        }
+        let split = match unless_cancelled(builder.build(), &token).await {
+            None => return,
+            Some(result) => result.and_then(|client| client.into_split()),
+        };
+        let (reader, writer) = match split {
+            Ok(halves) => halves,
+            Err(e) => {
+                failures += 1;
+                debug!(upstream = %upstream.name, error = %e, failures, "Upstream connection failed");
+                continue;
+            }
+        };
+        failures = 0;
+        ctx.update_upstream(index, |stats| {
+            stats.connected = true;
+            stats.connections += 1;
+        });
+        info!(upstream = %upstream.name, addr = %upstream.addr, "Upstream connected");
+
+        let lost = relay_upstream(
+            &ctx,
+            index,
+            reader,
+            writer,
+            &mut outgoing,
+            &mut streams,
+            &token,
+        )
+        .await;
+        ctx.update_upstream(index, |stats| stats.connected = false);
+        match lost {
+            Some(e) => warn!(upstream = %upstream.name, error = %e, "Upstream connection lost"),
+            None => return,
+        }
+    }
+}
+
+/// Relay messages over one upstream connection
+///
+/// Replays `streams` first, then reads and writes side by side so that neither a send
+/// nor a slow upstream holds up the other direction. Returns the error that ended the
+/// connection, or `None` once the router stops.
+async fn relay_upstream(
+    ctx: &Arc<Context>,
+    index: usize,
+    mut reader: AsyncClientReader,
+    mut writer: AsyncClientWriter,
+    outgoing: &mut mpsc::Receiver<AnyMessage>,
+    streams: &mut StreamRegistry,
+    token: &CancellationToken,
+) -> Option<IgtlError> {
+    for frame in streams.frames() {
+        if let Err(e) = writer.write_frame(frame.to_vec()).await {
+            return Some(e);
+        }
+    }
+    if !streams.is_empty() {
+        debug!(upstream = %ctx.upstream_names[index], streams = streams.len(), "Restarted upstream streams");
+    }
+
+    let mut reading = tokio::spawn({
+        let ctx = Arc::clone(ctx);
+        async move {
+            loop {
+                match reader.receive_any().await {
+                    Ok(msg) => {
+                        ctx.update_upstream(index, |stats| stats.received += 1);
+                        ctx.route_downstream(index, msg);
+                    }
+                    Err(e @ IgtlError::Io(_)) => return e,
+                    Err(e) => {
+                        warn!(upstream = %ctx.upstream_names[index], error = %e, "Dropped invalid upstream message");
+                    }
+                }
+            }
+        }
+    });
+
+    let lost = loop {
+        tokio::select! {
+            biased;
+            _ = token.cancelled() => break None,
+            read = &mut reading => {
+                break Some(read.unwrap_or_else(|e| IgtlError::Io(std::io::Error::other(e))));
+            }
+            msg = outgoing.recv() => {
+                let Some(msg) = msg else { break None };
+                let frame = match msg.encode() {
+                    Ok(frame) => frame,
+                    Err(e) => {
+                        debug!(upstream = %ctx.upstream_names[index], error = %e, "Could not encode message for upstream");
+                        continue;
+                    }
+                };
+                streams.record(msg.message_type(), msg.device_name().unwrap_or(""), &frame);
+                match writer.write_frame(frame).await {
+                    Ok(()) => ctx.update_upstream(index, |stats| stats.sent += 1),
+                    Err(e) => break Some(e),
+                }
+            }
+        }
+    };
+    reading.abort();
+    if lost.is_none() {
+        if let Err(e) = writer.shutdown().await {
+            debug!(upstream = %ctx.upstream_names[index], error = %e, "Upstream did not close cleanly");
+        }
+    }
+    lost
+}
+
+/// Serve one downstream client until it disconnects or the server shuts down
+async fn run_downstream(
+    ctx: Arc<Context>,
+    mut conn: AsyncIgtlConnection,
+    mut frames: broadcast::Receiver<Arc<Vec<u8>>>,
+) {
+    let peer = conn.peer_addr().ok();
+    loop {
+        tokio::select! {
+            frame = frames.recv() => match frame {
+                Ok(frame) => {
+                    if let Err(e) = conn.send_frame(&frame).await {
+                        debug!(peer = ?peer, error = %e, "Downstream send failed");
+                        break;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    warn!(peer = ?peer, missed, "Downstream client fell behind");
+                    ctx.stats.lock().unwrap().downstream_lagged += missed;
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            msg = conn.receive_any() => match msg {
+                Ok(msg) => ctx.route_upstream(msg),
+                Err(e) => {
+                    debug!(peer = ?peer, error = %e, "Downstream client finished");
+                    break;
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::message::IgtlMessage;
+    use crate::protocol::types::{
+        GetTransformMessage, ImageMessage, ImageScalarType, StartTDataMessage, StatusMessage,
+        TransformMessage,
+    };
+
+    async fn wait_for(router: &Router, condition: impl Fn(&RouterStats) -> bool) {
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !condition(&router.stats()) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("router did not reach the expected state");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_is_rejected() {
+        let config = RouterConfig::new()
+            .with_listen("127.0.0.1:0")
+            .with_upstream(Upstream::new("Tracker", "127.0.0.1:1"))
+            .with_route(Route::new("robot").from("Robot:Arm"));
+        assert!(Router::bind(config).await.is_err());
+
+        let config = RouterConfig::new()
+            .with_listen("127.0.0.1:0")
+            .with_upstream(Upstream::new("Tracker", "127.0.0.1:1"))
+            .with_upstream(Upstream::new("Tracker", "127.0.0.1:2"));
+        assert!(Router::bind(config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_routes_merges_and_reconnects() {
+        let tracker = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+        let config = RouterConfig::new()
+            .with_listen("127.0.0.1:0")
+            .with_upstream(Upstream::new(
+                "Tracker",
+                tracker.local_addr().unwrap().to_string(),
+            ))
+            .with_reconnect(ReconnectConfig::with_delays(
+                Duration::from_millis(10),
+                Duration::from_millis(50),
+            ))
+            .with_route(
+                Route::new("probe")
+                    .from("Tracker:Probe")
+                    .rename("ProbeToTracker")
+                    .convert(Conversion::LpsToRas),
+            )
+            .with_route(Route::new("no-images").with_types(["IMAGE"]).drop());
+        let router = Arc::new(Router::bind(config).await.unwrap());
+        let stop = CancellationToken::new();
+        let running = tokio::spawn({
+            let router = Arc::clone(&router);
+            let stop = stop.clone();
+            async move { router.run(&stop).await }
+        });
+
+        let mut upstream = tracker.accept().await.unwrap();
+        let mut client = ClientBuilder::new()
+            .tcp(router.local_addr().unwrap().to_string())
+            .async_mode()
+            .build()
+            .await
+            .unwrap();
+        wait_for(&router, |stats| stats.downstream_clients == 1).await;
+
+        let pose = TransformMessage::translation(1.0, 2.0, 3.0);
+        upstream
+            .send(&IgtlMessage::new(pose, "Probe").unwrap())
+            .await
+            .unwrap();
+        let image = ImageMessage::new(ImageScalarType::Uint8, [2, 2, 1], vec![0; 4]).unwrap();
+        upstream
+            .send(&IgtlMessage::new(image, "US").unwrap())
+            .await
+            .unwrap();
+        upstream
+            .send(&IgtlMessage::new(StatusMessage::ok("ready"), "Tracker").unwrap())
+            .await
+            .unwrap();
+
+        let routed: IgtlMessage<TransformMessage> = client.receive().await.unwrap();
+        assert_eq!(
+            routed.header.device_name.as_str().unwrap(),
+            "ProbeToTracker"
+        );
+        assert_eq!(routed.content.matrix[0][3], -1.0);
+        // The image is dropped; the status matches no route and passes unchanged
+        let status: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(status.header.device_name.as_str().unwrap(), "Tracker");
+
+        // Queries for the renamed device reach the upstream under the original name
+        client
+            .send(&IgtlMessage::new(GetTransformMessage, "ProbeToTracker").unwrap())
+            .await
+            .unwrap();
+        let query = upstream.receive_any().await.unwrap();
+        assert_eq!(query.message_type(), "GET_TRANSFORM");
+        assert_eq!(query.device_name().unwrap(), "Probe");
+        client
+            .send(&IgtlMessage::new(StartTDataMessage::new(50, "RAS"), "Tracker").unwrap())
+            .await
+            .unwrap();
+        let start = upstream.receive_any().await.unwrap();
+        assert_eq!(start.message_type(), "STT_TDATA");
+
+        // The router reconnects after the upstream connection is lost and restarts the
+        // stream requested before
+        drop(upstream);
+        let mut upstream = tracker.accept().await.unwrap();
+        let restart = upstream.receive_any().await.unwrap();
+        assert_eq!(restart.message_type(), "STT_TDATA");
+        assert_eq!(restart.device_name().unwrap(), "Tracker");
+        upstream
+            .send(&IgtlMessage::new(StatusMessage::ok("back"), "Tracker").unwrap())
+            .await
+            .unwrap();
+        let status: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(status.content.status_string, "back");
+
+        let stats = router.stats();
+        assert_eq!(stats.routes[0].1.forwarded, 1);
+        assert_eq!(stats.routes[1].1.matched, 1);
+        assert_eq!(stats.routes[1].1.forwarded, 0);
+        assert_eq!(stats.unmatched.forwarded, 2);
+        assert_eq!(stats.upstreams[0].received, 4);
+        assert_eq!(stats.upstreams[0].sent, 2);
+        assert_eq!(stats.upstreams[0].connections, 2);
+        assert!(stats.upstreams[0].connected);
+
+        stop.cancel();
+        running.await.unwrap();
+        let notice: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(notice.content.code, crate::io::shutdown::STATUS_SHUT_DOWN);
+    }
+}
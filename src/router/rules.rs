@@ -0,0 +1,421 @@
+//! Declarative routing rules
+//!
+//! A [`Route`] selects messages by upstream, device and type and either drops them or
+//! forwards them, optionally renamed and converted to another coordinate convention.
+
+use crate::error::{IgtlError, Result};
+use crate::protocol::types::{
+    PositionMessage, QtDataMessage, TDataMessage, TrajectoryMessage, TransformMessage,
+};
+use crate::protocol::AnyMessage;
+
+/// Change applied to the coordinates of tracking and geometry messages
+///
+/// Applies to TRANSFORM, POSITION, TDATA, QTDATA, POINT and TRAJECTORY; other
+/// message types pass unchanged.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conversion {
+    /// From RAS (3D Slicer) to LPS (DICOM, ITK): negate the x and y axes
+    RasToLps,
+    /// From LPS to RAS: negate the x and y axes
+    LpsToRas,
+    /// Multiply positions and translations, e.g. by 0.001 for millimetres to metres
+    Scale(f32),
+}
+
+impl Conversion {
+    /// Conversion undoing this one
+    pub fn inverse(self) -> Self {
+        match self {
+            Conversion::RasToLps => Conversion::LpsToRas,
+            Conversion::LpsToRas => Conversion::RasToLps,
+            Conversion::Scale(factor) => Conversion::Scale(1.0 / factor),
+        }
+    }
+
+    /// Convert the coordinates carried by `msg` in place
+    pub fn apply(self, msg: &mut AnyMessage) {
+        match msg {
+            AnyMessage::Transform(msg) => self.transform(&mut msg.content),
+            AnyMessage::Position(msg) => self.position(&mut msg.content),
+            AnyMessage::TData(msg) => self.tdata(&mut msg.content),
+            AnyMessage::QtData(msg) => self.qtdata(&mut msg.content),
+            AnyMessage::Point(msg) => {
+                for point in &mut msg.content.points {
+                    self.point(&mut point.position);
+                }
+            }
+            AnyMessage::Trajectory(msg) => self.trajectory(&mut msg.content),
+            _ => {}
+        }
+    }
+
+    /// Sign of each axis after the conversion
+    fn axis_signs(self) -> [f32; 3] {
+        match self {
+            Conversion::RasToLps | Conversion::LpsToRas => [-1.0, -1.0, 1.0],
+            Conversion::Scale(_) => [1.0; 3],
+        }
+    }
+
+    fn scale(self) -> f32 {
+        match self {
+            Conversion::Scale(factor) => factor,
+            _ => 1.0,
+        }
+    }
+
+    fn point(self, p: &mut [f32; 3]) {
+        let signs = self.axis_signs();
+        for (value, sign) in p.iter_mut().zip(signs) {
+            *value *= sign * self.scale();
+        }
+    }
+
+    /// Convert the rows of a homogeneous matrix: `D * M * D` plus scaled translation
+    fn matrix_rows(self, rows: &mut [[f32; 4]]) {
+        let signs = self.axis_signs();
+        for (i, row) in rows.iter_mut().take(3).enumerate() {
+            for (j, value) in row.iter_mut().take(3).enumerate() {
+                *value *= signs[i] * signs[j];
+            }
+            row[3] *= signs[i] * self.scale();
+        }
+    }
+
+    /// Rotation quaternion (x, y, z, w) after the conversion
+    fn quaternion(self, q: &mut [f32; 4]) {
+        // Negating x and y is a half turn about z, which turns the rotation axis with it
+        let signs = self.axis_signs();
+        for (value, sign) in q.iter_mut().zip(signs) {
+            *value *= sign;
+        }
+    }
+
+    fn transform(self, transform: &mut TransformMessage) {
+        self.matrix_rows(&mut transform.matrix);
+    }
+
+    fn position(self, position: &mut PositionMessage) {
+        self.point(&mut position.position);
+        self.quaternion(&mut position.quaternion);
+    }
+
+    fn tdata(self, tdata: &mut TDataMessage) {
+        for element in &mut tdata.elements {
+            self.matrix_rows(&mut element.matrix);
+        }
+    }
+
+    fn qtdata(self, qtdata: &mut QtDataMessage) {
+        for element in &mut qtdata.elements {
+            self.point(&mut element.position);
+            self.quaternion(&mut element.quaternion);
+        }
+    }
+
+    fn trajectory(self, trajectory: &mut TrajectoryMessage) {
+        for element in &mut trajectory.trajectories {
+            self.point(&mut element.entry_point);
+            self.point(&mut element.target_point);
+        }
+    }
+}
+
+/// What a route does with the messages it matches
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteAction {
+    /// Forward downstream, renamed and converted if configured
+    Forward {
+        /// New device name
+        rename: Option<String>,
+        /// Conversions applied in order
+        conversions: Vec<Conversion>,
+    },
+    /// Discard the message
+    Drop,
+}
+
+/// Rule selecting messages by upstream, device and type
+///
+/// Unset criteria match everything. Messages are checked against the routes in
+/// order and handled by the first match.
+///
+/// # Examples
+///
+/// ```
+/// use openigtlink_rust::router::{Conversion, Route};
+///
+/// // Rename the probe of the "Tracker" upstream and convert it to RAS
+/// let probe = Route::new("probe")
+///     .from("Tracker:Probe")
+///     .rename("ProbeToTracker")
+///     .convert(Conversion::LpsToRas);
+///
+/// // Do not forward images from any upstream
+/// let no_images = Route::new("no-images").with_types(["IMAGE"]).drop();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Route {
+    /// Name reported in the statistics
+    pub name: String,
+    /// Name of the upstream messages must come from (default any)
+    pub upstream: Option<String>,
+    /// Device name messages must have (default any)
+    pub device: Option<String>,
+    /// Message types to match, e.g. "TRANSFORM" (default any)
+    pub types: Vec<String>,
+    /// What to do with matched messages (default forward unchanged)
+    pub action: RouteAction,
+}
+
+impl Route {
+    /// Create a route matching every message and forwarding it unchanged
+    pub fn new(name: impl Into<String>) -> Self {
+        Route {
+            name: name.into(),
+            upstream: None,
+            device: None,
+            types: Vec::new(),
+            action: RouteAction::Forward {
+                rename: None,
+                conversions: Vec::new(),
+            },
+        }
+    }
+
+    /// Match messages from `source`, written `UPSTREAM` or `UPSTREAM:DEVICE`
+    ///
+    /// Either part may be `*` to match any upstream or device.
+    pub fn from(mut self, source: &str) -> Self {
+        let (upstream, device) = match source.split_once(':') {
+            Some((upstream, device)) => (upstream, Some(device)),
+            None => (source, None),
+        };
+        fn pattern(part: &str) -> Option<String> {
+            (part != "*" && !part.is_empty()).then(|| part.to_string())
+        }
+        self.upstream = pattern(upstream);
+        self.device = device.and_then(pattern);
+        self
+    }
+
+    /// Match only these message types
+    pub fn with_types<I, S>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.types = types.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Forward matched messages under this device name
+    pub fn rename(mut self, device: impl Into<String>) -> Self {
+        if let RouteAction::Forward { rename, .. } = &mut self.action {
+            *rename = Some(device.into());
+        }
+        self
+    }
+
+    /// Convert the coordinates of matched messages, after earlier conversions
+    pub fn convert(mut self, conversion: Conversion) -> Self {
+        if let RouteAction::Forward { conversions, .. } = &mut self.action {
+            conversions.push(conversion);
+        }
+        self
+    }
+
+    /// Discard matched messages
+    pub fn drop(mut self) -> Self {
+        self.action = RouteAction::Drop;
+        self
+    }
+
+    /// Whether a message of `message_type` from `device` on `upstream` matches
+    pub fn matches(&self, upstream: &str, device: &str, message_type: &str) -> bool {
+        self.upstream.as_deref().is_none_or(|u| u == upstream)
+            && self.device.as_deref().is_none_or(|d| d == device)
+            && (self.types.is_empty() || self.types.iter().any(|t| t == message_type))
+    }
+
+    /// Rename and convert a matched message; `None` if the route drops it
+    pub fn apply(&self, mut msg: AnyMessage) -> Result<Option<AnyMessage>> {
+        let RouteAction::Forward {
+            rename,
+            conversions,
+        } = &self.action
+        else {
+            return Ok(None);
+        };
+        if let Some(device) = rename {
+            msg.set_device_name(device)?;
+        }
+        for conversion in conversions {
+            conversion.apply(&mut msg);
+        }
+        Ok(Some(msg))
+    }
+
+    /// Map a message addressed to this route's output back to its upstream
+    ///
+    /// Applies to routes from one upstream device that rename it. Returns the upstream
+    /// name and the message with the original device name and inverse conversions, or
+    /// `None` if `msg` is not addressed to this route's device.
+    pub fn reverse(&self, msg: &AnyMessage) -> Result<Option<(String, AnyMessage)>> {
+        let (
+            Some(upstream),
+            Some(device),
+            RouteAction::Forward {
+                rename: Some(rename),
+                conversions,
+            },
+        ) = (&self.upstream, &self.device, &self.action)
+        else {
+            return Ok(None);
+        };
+        if msg.device_name()? != rename {
+            return Ok(None);
+        }
+        let mut msg = msg.clone();
+        msg.set_device_name(device)?;
+        for conversion in conversions.iter().rev() {
+            conversion.inverse().apply(&mut msg);
+        }
+        Ok(Some((upstream.clone(), msg)))
+    }
+
+    /// Check the route against the configured upstream names
+    pub(crate) fn validate(&self, upstreams: &[&str]) -> Result<()> {
+        let invalid = |reason: String| {
+            Err(IgtlError::Io(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("route \"{}\": {}", self.name, reason),
+            )))
+        };
+        if let Some(upstream) = &self.upstream {
+            if !upstreams.contains(&upstream.as_str()) {
+                return invalid(format!("unknown upstream \"{upstream}\""));
+            }
+        }
+        if let RouteAction::Forward {
+            rename,
+            conversions,
+        } = &self.action
+        {
+            if let Some(rename) = rename.as_ref().filter(|rename| rename.len() > 20) {
+                return invalid(format!("device name \"{rename}\" longer than 20 bytes"));
+            }
+            if conversions
+                .iter()
+                .any(|c| matches!(c, Conversion::Scale(f) if !f.is_normal()))
+            {
+                return invalid("scale factor must be finite and non-zero".to_string());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::message::IgtlMessage;
+
+    fn pose(device: &str) -> AnyMessage {
+        let transform = TransformMessage::translation(10.0, 20.0, 30.0);
+        AnyMessage::Transform(IgtlMessage::new(transform, device).unwrap())
+    }
+
+    #[test]
+    fn test_route_matching() {
+        let route = Route::new("probe")
+            .from("Tracker:Probe")
+            .with_types(["TRANSFORM"]);
+        assert!(route.matches("Tracker", "Probe", "TRANSFORM"));
+        assert!(!route.matches("Tracker", "Needle", "TRANSFORM"));
+        assert!(!route.matches("Robot", "Probe", "TRANSFORM"));
+        assert!(!route.matches("Tracker", "Probe", "IMAGE"));
+
+        let any_device = Route::new("tracker").from("Tracker:*");
+        assert!(any_device.matches("Tracker", "Needle", "IMAGE"));
+        let any_upstream = Route::new("probes").from("*:Probe");
+        assert!(any_upstream.matches("Robot", "Probe", "STATUS"));
+    }
+
+    #[test]
+    fn test_rename_and_convert_round_trip() {
+        let route = Route::new("probe")
+            .from("Tracker:Probe")
+            .rename("ProbeToTracker")
+            .convert(Conversion::LpsToRas)
+            .convert(Conversion::Scale(0.001));
+
+        let routed = route.apply(pose("Probe")).unwrap().unwrap();
+        assert_eq!(routed.device_name().unwrap(), "ProbeToTracker");
+        let matrix = routed.as_transform().unwrap().content.matrix;
+        let translation = [matrix[0][3], matrix[1][3], matrix[2][3]];
+        for (value, expected) in translation.iter().zip([-0.01, -0.02, 0.03]) {
+            assert!((value - expected).abs() < 1e-6, "{translation:?}");
+        }
+        assert_eq!(matrix[0][0], 1.0);
+
+        let (upstream, back) = route.reverse(&routed).unwrap().unwrap();
+        assert_eq!(upstream, "Tracker");
+        assert_eq!(back.device_name().unwrap(), "Probe");
+        let matrix = back.as_transform().unwrap().content.matrix;
+        assert!((matrix[0][3] - 10.0).abs() < 1e-4);
+        assert!((matrix[1][3] - 20.0).abs() < 1e-4);
+
+        assert!(route.reverse(&pose("Other")).unwrap().is_none());
+        assert!(Route::new("drop")
+            .drop()
+            .apply(pose("Probe"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_ras_lps_keeps_rotation_proper() {
+        // 90 degrees about x
+        let mut msg = pose("Probe");
+        if let AnyMessage::Transform(m) = &mut msg {
+            m.content.matrix[1] = [0.0, 0.0, -1.0, 20.0];
+            m.content.matrix[2] = [0.0, 1.0, 0.0, 30.0];
+        }
+        Conversion::RasToLps.apply(&mut msg);
+        let m = msg.as_transform().unwrap().content.matrix;
+        // D R D for D = diag(-1, -1, 1): the rotation axis turns to -x
+        assert_eq!(m[1], [0.0, 0.0, 1.0, -20.0]);
+        assert_eq!(m[2], [0.0, -1.0, 0.0, 30.0]);
+
+        let mut position = AnyMessage::Position(
+            IgtlMessage::new(
+                PositionMessage::with_quaternion([1.0, 2.0, 3.0], [0.5, 0.5, 0.5, 0.5]),
+                "Probe",
+            )
+            .unwrap(),
+        );
+        Conversion::RasToLps.apply(&mut position);
+        let p = &position.as_position().unwrap().content;
+        assert_eq!(p.position, [-1.0, -2.0, 3.0]);
+        assert_eq!(p.quaternion, [-0.5, -0.5, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let upstreams = ["Tracker"];
+        assert!(Route::new("ok")
+            .from("Tracker")
+            .validate(&upstreams)
+            .is_ok());
+        assert!(Route::new("bad")
+            .from("Robot")
+            .validate(&upstreams)
+            .is_err());
+        let long = Route::new("long").rename("ADeviceNameLongerThan20");
+        assert!(long.validate(&upstreams).is_err());
+        let zero = Route::new("zero").convert(Conversion::Scale(0.0));
+        assert!(zero.validate(&upstreams).is_err());
+    }
+}
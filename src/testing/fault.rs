@@ -0,0 +1,763 @@
+//! Fault injection for resilience tests
+//!
+//! [`FaultyStream`] wraps any blocking ([`Read`] + [`Write`]) or Tokio
+//! ([`AsyncRead`] + [`AsyncWrite`]) stream and injects the faults described by a
+//! [`FaultConfig`] into the data written through it: latency, bandwidth limits, short
+//! reads and writes, corrupted message bodies (exercising
+//! [`CrcMismatch`](crate::error::IgtlError::CrcMismatch)), truncated messages and
+//! disconnects after a number of bytes or messages.
+//!
+//! Written data is followed frame by frame using the OpenIGTLink header, so faults hit
+//! message bodies and message boundaries rather than arbitrary bytes. Random decisions
+//! come from a generator seeded by [`FaultConfig::seed`]: the same seed and the same
+//! writes give the same faults.
+//!
+//! # Examples
+//!
+//! ```
+//! use openigtlink_rust::error::IgtlError;
+//! use openigtlink_rust::io::AsyncIgtlStream;
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::StatusMessage;
+//! use openigtlink_rust::testing::{FaultConfig, FaultyStream};
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), IgtlError> {
+//! let (a, b) = tokio::io::duplex(64 * 1024);
+//! let faults = FaultConfig::new().with_seed(7).with_corruption(1.0);
+//! let mut sender = AsyncIgtlStream::new(FaultyStream::new(a, faults));
+//! let mut receiver = AsyncIgtlStream::new(b);
+//!
+//! sender.send(&IgtlMessage::new(StatusMessage::ok("Hello"), "Device")?).await?;
+//! let result = receiver.receive::<StatusMessage>().await;
+//! assert!(matches!(result, Err(IgtlError::CrcMismatch { .. })));
+//! # Ok(())
+//! # }
+//! ```
+
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::Sleep;
+use tracing::debug;
+
+use crate::protocol::header::Header;
+
+/// Offset of the body size field in the OpenIGTLink header
+const BODY_SIZE_OFFSET: usize = 42;
+
+/// Faults injected by a [`FaultyStream`]
+///
+/// Every fault is disabled by default.
+///
+/// # Examples
+///
+/// ```
+/// use openigtlink_rust::testing::FaultConfig;
+/// use std::time::Duration;
+///
+/// // Slow, lossy link that drops after 100 messages
+/// let config = FaultConfig::new()
+///     .with_seed(42)
+///     .with_latency(Duration::from_millis(20))
+///     .with_bandwidth(1_000_000)
+///     .with_corruption(0.01)
+///     .with_disconnect_after_messages(100);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct FaultConfig {
+    /// Seed of the random decisions (default 0)
+    pub seed: u64,
+    /// Delay before each message is written (default zero)
+    pub latency: Duration,
+    /// Random extra delay, up to this value, added to `latency` (default zero)
+    pub latency_jitter: Duration,
+    /// Write throughput limit in bytes per second (default unlimited)
+    pub bandwidth: Option<u64>,
+    /// Each read and write transfers a random part of the requested length (default false)
+    pub short_io: bool,
+    /// Probability that a message gets one bit of its body flipped (default 0)
+    pub corrupt_probability: f64,
+    /// Probability that a message is cut inside its body, followed by a disconnect
+    /// (default 0)
+    pub truncate_probability: f64,
+    /// Disconnect once this many bytes have been written (default never)
+    pub disconnect_after_bytes: Option<u64>,
+    /// Disconnect once this many complete messages have been written (default never)
+    pub disconnect_after_messages: Option<u64>,
+}
+
+impl FaultConfig {
+    /// Create a configuration without faults
+    pub fn new() -> Self {
+        Self {
+            seed: 0,
+            latency: Duration::ZERO,
+            latency_jitter: Duration::ZERO,
+            bandwidth: None,
+            short_io: false,
+            corrupt_probability: 0.0,
+            truncate_probability: 0.0,
+            disconnect_after_bytes: None,
+            disconnect_after_messages: None,
+        }
+    }
+
+    /// Set the seed of the random decisions
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Delay each message by `latency` before writing it
+    pub fn with_latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Add a random delay of up to `jitter` to the latency
+    pub fn with_latency_jitter(mut self, jitter: Duration) -> Self {
+        self.latency_jitter = jitter;
+        self
+    }
+
+    /// Limit write throughput to `bytes_per_sec`
+    pub fn with_bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        self.bandwidth = Some(bytes_per_sec);
+        self
+    }
+
+    /// Make reads and writes transfer a random part of the requested length
+    pub fn with_short_io(mut self, enabled: bool) -> Self {
+        self.short_io = enabled;
+        self
+    }
+
+    /// Flip one body bit of each message with the given probability
+    pub fn with_corruption(mut self, probability: f64) -> Self {
+        self.corrupt_probability = probability;
+        self
+    }
+
+    /// Cut each message inside its body, then disconnect, with the given probability
+    pub fn with_truncation(mut self, probability: f64) -> Self {
+        self.truncate_probability = probability;
+        self
+    }
+
+    /// Disconnect once `bytes` have been written
+    pub fn with_disconnect_after_bytes(mut self, bytes: u64) -> Self {
+        self.disconnect_after_bytes = Some(bytes);
+        self
+    }
+
+    /// Disconnect once `messages` complete messages have been written
+    pub fn with_disconnect_after_messages(mut self, messages: u64) -> Self {
+        self.disconnect_after_messages = Some(messages);
+        self
+    }
+}
+
+impl Default for FaultConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Counters of a [`FaultyStream`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FaultStats {
+    /// Bytes written to the wrapped stream
+    pub bytes_written: u64,
+    /// Complete messages written to the wrapped stream
+    pub messages_written: u64,
+    /// Messages whose body was corrupted
+    pub corrupted_messages: u64,
+    /// Messages cut short
+    pub truncated_messages: u64,
+    /// Whether the stream has been disconnected
+    pub disconnected: bool,
+}
+
+/// SplitMix64 generator, small and reproducible across platforms
+#[derive(Debug, Clone)]
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Random value in `0..n` (0 when `n` is 0)
+    fn below(&mut self, n: u64) -> u64 {
+        if n == 0 {
+            0
+        } else {
+            self.next_u64() % n
+        }
+    }
+
+    /// `true` with the given probability
+    fn chance(&mut self, probability: f64) -> bool {
+        if probability <= 0.0 {
+            return false;
+        }
+        // 53 random bits give a uniform value in [0, 1)
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        unit < probability
+    }
+}
+
+/// What to do with the next write
+enum Plan {
+    /// Retry once this instant has passed
+    Wait(Instant),
+    /// Write `data` to the wrapped stream and report `consumed` bytes as written
+    Write { data: Vec<u8>, consumed: usize },
+    /// The stream has been disconnected
+    Disconnected,
+}
+
+/// Fault decisions shared by the blocking and async implementations
+#[derive(Debug)]
+struct Injector {
+    config: FaultConfig,
+    rng: Rng,
+    stats: FaultStats,
+    /// Header bytes of the current message seen so far
+    header: Vec<u8>,
+    /// Position inside the current message
+    frame_pos: u64,
+    /// Length of the current message, known once its header is complete
+    frame_len: Option<u64>,
+    /// Position of the byte to corrupt in the current message
+    corrupt_at: Option<u64>,
+    /// Position at which the current message is cut
+    truncate_at: Option<u64>,
+    /// When the current message may start, after latency
+    frame_ready_at: Option<Instant>,
+    /// When the next write may start, after bandwidth limiting
+    next_write_at: Option<Instant>,
+}
+
+impl Injector {
+    fn new(config: FaultConfig) -> Self {
+        Injector {
+            rng: Rng(config.seed),
+            config,
+            stats: FaultStats::default(),
+            header: Vec::with_capacity(Header::SIZE),
+            frame_pos: 0,
+            frame_len: None,
+            corrupt_at: None,
+            truncate_at: None,
+            frame_ready_at: None,
+            next_write_at: None,
+        }
+    }
+
+    fn disconnect(&mut self, reason: &str) {
+        if !self.stats.disconnected {
+            debug!(
+                reason = reason,
+                bytes_written = self.stats.bytes_written,
+                messages_written = self.stats.messages_written,
+                "Injected disconnect"
+            );
+            self.stats.disconnected = true;
+        }
+    }
+
+    /// Random part of `len` when short I/O is enabled
+    fn io_len(&mut self, len: usize) -> usize {
+        if self.config.short_io && len > 1 {
+            1 + self.rng.below(len as u64) as usize
+        } else {
+            len
+        }
+    }
+
+    fn plan(&mut self, buf: &[u8], now: Instant) -> Plan {
+        if let Some(max) = self.config.disconnect_after_bytes {
+            if self.stats.bytes_written >= max {
+                self.disconnect("byte limit");
+            }
+        }
+        if let Some(max) = self.config.disconnect_after_messages {
+            if self.stats.messages_written >= max {
+                self.disconnect("message limit");
+            }
+        }
+        if self.stats.disconnected {
+            return Plan::Disconnected;
+        }
+        if buf.is_empty() {
+            return Plan::Write {
+                data: Vec::new(),
+                consumed: 0,
+            };
+        }
+
+        if self.frame_pos == 0 {
+            let ready_at = match self.frame_ready_at {
+                Some(at) => at,
+                None => {
+                    let jitter = self
+                        .rng
+                        .below(self.config.latency_jitter.as_nanos() as u64 + 1);
+                    let at = now + self.config.latency + Duration::from_nanos(jitter);
+                    self.frame_ready_at = Some(at);
+                    at
+                }
+            };
+            if ready_at > now {
+                return Plan::Wait(ready_at);
+            }
+        }
+        if let Some(at) = self.next_write_at {
+            if at > now {
+                return Plan::Wait(at);
+            }
+        }
+
+        // Stop at the end of the header, the message, the cut and the byte limit so
+        // that each of them is handled at its exact position
+        let boundary = self.frame_len.unwrap_or(Header::SIZE as u64);
+        let mut limit = (buf.len() as u64).min(boundary - self.frame_pos);
+        if let Some(cut) = self.truncate_at {
+            limit = limit.min(cut - self.frame_pos);
+        }
+        if let Some(max) = self.config.disconnect_after_bytes {
+            limit = limit.min(max - self.stats.bytes_written);
+        }
+        if let Some(bandwidth) = self.config.bandwidth {
+            // Write in slices of about 50 ms so that the limit applies within messages
+            limit = limit.min((bandwidth / 20).max(1));
+        }
+        let n = self.io_len(limit as usize);
+
+        let mut data = buf[..n].to_vec();
+        if let Some(at) = self.corrupt_at {
+            if (self.frame_pos..self.frame_pos + n as u64).contains(&at) {
+                data[(at - self.frame_pos) as usize] ^= 1 << self.rng.below(8);
+                self.stats.corrupted_messages += 1;
+                debug!(position = at, "Injected message body corruption");
+            }
+        }
+        if let Some(bandwidth) = self.config.bandwidth.filter(|&b| b > 0) {
+            let start = self.next_write_at.map_or(now, |at| at.max(now));
+            self.next_write_at = Some(start + Duration::from_secs_f64(n as f64 / bandwidth as f64));
+        }
+        self.advance(&buf[..n]);
+
+        Plan::Write { data, consumed: n }
+    }
+
+    /// Account for `bytes` written at the current position
+    fn advance(&mut self, bytes: &[u8]) {
+        if self.frame_len.is_none() {
+            self.header.extend_from_slice(bytes);
+            if self.header.len() == Header::SIZE {
+                let mut size = [0u8; 8];
+                size.copy_from_slice(&self.header[BODY_SIZE_OFFSET..BODY_SIZE_OFFSET + 8]);
+                let body_size = u64::from_be_bytes(size);
+                let frame_len = (Header::SIZE as u64).saturating_add(body_size);
+                self.frame_len = Some(frame_len);
+                if body_size > 0 {
+                    if self.rng.chance(self.config.corrupt_probability) {
+                        self.corrupt_at = Some(Header::SIZE as u64 + self.rng.below(body_size));
+                    }
+                    if self.rng.chance(self.config.truncate_probability) {
+                        self.truncate_at = Some(Header::SIZE as u64 + self.rng.below(body_size));
+                    }
+                }
+            }
+        }
+
+        self.frame_pos += bytes.len() as u64;
+        self.stats.bytes_written += bytes.len() as u64;
+
+        if self.truncate_at == Some(self.frame_pos) {
+            debug!(position = self.frame_pos, "Injected message truncation");
+            self.stats.truncated_messages += 1;
+            self.disconnect("truncated message");
+        } else if self.frame_len == Some(self.frame_pos) {
+            self.stats.messages_written += 1;
+            self.header.clear();
+            self.frame_pos = 0;
+            self.frame_len = None;
+            self.corrupt_at = None;
+            self.frame_ready_at = None;
+        }
+    }
+}
+
+fn disconnected_error() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::BrokenPipe,
+        "connection dropped by fault injection",
+    )
+}
+
+/// Stream wrapper injecting the faults of a [`FaultConfig`]
+///
+/// Implements [`Read`] + [`Write`] when the wrapped stream does, and [`AsyncRead`] +
+/// [`AsyncWrite`] for Tokio streams, so it can be wrapped in an
+/// [`IgtlStream`](crate::io::IgtlStream) or [`AsyncIgtlStream`](crate::io::AsyncIgtlStream).
+///
+/// On disconnect the wrapped stream is dropped without a graceful shutdown, so the
+/// peer sees the connection close. Further writes fail with
+/// [`BrokenPipe`](std::io::ErrorKind::BrokenPipe) and reads report end of stream.
+pub struct FaultyStream<S> {
+    inner: Option<S>,
+    injector: Injector,
+    /// Data accepted from the caller but not yet written to the wrapped stream (async)
+    pending: Vec<u8>,
+    /// Timer of the current latency or bandwidth wait (async)
+    delay: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S> FaultyStream<S> {
+    /// Wrap `inner`, injecting the faults of `config`
+    pub fn new(inner: S, config: FaultConfig) -> Self {
+        FaultyStream {
+            inner: Some(inner),
+            injector: Injector::new(config),
+            pending: Vec::new(),
+            delay: None,
+        }
+    }
+
+    /// Fault configuration
+    pub fn config(&self) -> &FaultConfig {
+        &self.injector.config
+    }
+
+    /// Counters of written data and injected faults
+    pub fn stats(&self) -> FaultStats {
+        self.injector.stats
+    }
+
+    /// Whether the stream has been disconnected
+    pub fn is_disconnected(&self) -> bool {
+        self.injector.stats.disconnected
+    }
+
+    /// Disconnect now, dropping the wrapped stream
+    pub fn disconnect(&mut self) {
+        self.injector.disconnect("requested");
+        self.pending.clear();
+        self.inner = None;
+    }
+
+    /// Get a reference to the wrapped stream (`None` after a disconnect)
+    pub fn get_ref(&self) -> Option<&S> {
+        self.inner.as_ref()
+    }
+
+    /// Get a mutable reference to the wrapped stream (`None` after a disconnect)
+    pub fn get_mut(&mut self) -> Option<&mut S> {
+        self.inner.as_mut()
+    }
+
+    /// Unwrap the stream (`None` after a disconnect)
+    pub fn into_inner(self) -> Option<S> {
+        self.inner
+    }
+}
+
+impl<S: Read> Read for FaultyStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let Some(inner) = &mut self.inner else {
+            return Ok(0);
+        };
+        let len = self.injector.io_len(buf.len());
+        inner.read(&mut buf[..len])
+    }
+}
+
+impl<S: Write> Write for FaultyStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        loop {
+            let now = Instant::now();
+            match self.injector.plan(buf, now) {
+                Plan::Wait(at) => std::thread::sleep(at - now),
+                Plan::Disconnected => {
+                    self.inner = None;
+                    return Err(disconnected_error());
+                }
+                Plan::Write { data, consumed } => {
+                    let Some(inner) = &mut self.inner else {
+                        return Err(disconnected_error());
+                    };
+                    inner.write_all(&data)?;
+                    if self.injector.stats.disconnected {
+                        inner.flush()?;
+                        self.inner = None;
+                    }
+                    return Ok(consumed);
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.inner {
+            Some(inner) => inner.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S: AsyncWrite + Unpin> FaultyStream<S> {
+    /// Write out pending data, then drop the wrapped stream if disconnected
+    fn poll_drain(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.pending.is_empty() {
+            let Some(inner) = &mut self.inner else {
+                self.pending.clear();
+                break;
+            };
+            let n = ready!(Pin::new(inner).poll_write(cx, &self.pending))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.pending.drain(..n);
+        }
+        if self.injector.stats.disconnected {
+            if let Some(inner) = &mut self.inner {
+                ready!(Pin::new(inner).poll_flush(cx))?;
+                self.inner = None;
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for FaultyStream<S> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let Some(inner) = &mut this.inner else {
+            return Poll::Ready(Ok(()));
+        };
+        if !this.injector.config.short_io {
+            return Pin::new(inner).poll_read(cx, buf);
+        }
+        let len = this.injector.io_len(buf.remaining());
+        let mut chunk = vec![0u8; len];
+        let mut chunk_buf = ReadBuf::new(&mut chunk);
+        ready!(Pin::new(inner).poll_read(cx, &mut chunk_buf))?;
+        buf.put_slice(chunk_buf.filled());
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for FaultyStream<S> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        loop {
+            match this.injector.plan(buf, Instant::now()) {
+                Plan::Wait(at) => {
+                    let delay = this
+                        .delay
+                        .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(at.into())));
+                    delay.as_mut().reset(at.into());
+                    ready!(delay.as_mut().poll(cx));
+                }
+                Plan::Disconnected => {
+                    this.inner = None;
+                    return Poll::Ready(Err(disconnected_error()));
+                }
+                Plan::Write { data, consumed } => {
+                    this.pending = data;
+                    if let Poll::Ready(Err(e)) = this.poll_drain(cx) {
+                        return Poll::Ready(Err(e));
+                    }
+                    return Poll::Ready(Ok(consumed));
+                }
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        match &mut this.inner {
+            Some(inner) => Pin::new(inner).poll_flush(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_drain(cx))?;
+        match &mut this.inner {
+            Some(inner) => Pin::new(inner).poll_shutdown(cx),
+            None => Poll::Ready(Ok(())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::IgtlError;
+    use crate::io::{AsyncIgtlStream, ClientBuilder, IgtlStream, ReconnectConfig};
+    use crate::protocol::message::IgtlMessage;
+    use crate::protocol::types::{StatusMessage, TransformMessage};
+    use std::io::Cursor;
+    use tokio::io::duplex;
+
+    fn status(text: &str) -> IgtlMessage<StatusMessage> {
+        IgtlMessage::new(StatusMessage::ok(text), "Device").unwrap()
+    }
+
+    fn faulty_pair(
+        config: FaultConfig,
+    ) -> (
+        AsyncIgtlStream<FaultyStream<tokio::io::DuplexStream>>,
+        AsyncIgtlStream<tokio::io::DuplexStream>,
+    ) {
+        let (a, b) = duplex(64 * 1024);
+        (
+            AsyncIgtlStream::new(FaultyStream::new(a, config)),
+            AsyncIgtlStream::new(b),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_corruption_causes_crc_mismatch() {
+        let (mut sender, mut receiver) = faulty_pair(FaultConfig::new().with_corruption(1.0));
+        sender.send(&status("Hello")).await.unwrap();
+        let result = receiver.receive::<StatusMessage>().await;
+        assert!(matches!(result, Err(IgtlError::CrcMismatch { .. })));
+        assert_eq!(sender.get_ref().stats().corrupted_messages, 1);
+    }
+
+    #[test]
+    fn test_same_seed_reproduces_faults() {
+        let messages: Vec<_> = (0..20).map(|i| status(&format!("Message {}", i))).collect();
+        let write = |seed: u64| {
+            let config = FaultConfig::new()
+                .with_seed(seed)
+                .with_short_io(true)
+                .with_corruption(0.5);
+            let mut stream = IgtlStream::new(FaultyStream::new(Cursor::new(Vec::new()), config));
+            for msg in &messages {
+                stream.send(msg).unwrap();
+            }
+            stream.into_inner().into_inner().unwrap().into_inner()
+        };
+        assert_eq!(write(1), write(1));
+        assert_ne!(write(1), write(2));
+    }
+
+    #[tokio::test]
+    async fn test_short_io_keeps_framing() {
+        let config = FaultConfig::new().with_seed(3).with_short_io(true);
+        let (a, b) = duplex(64 * 1024);
+        let mut sender = AsyncIgtlStream::new(FaultyStream::new(a, config.clone()));
+        let mut receiver = AsyncIgtlStream::new(FaultyStream::new(b, config));
+        for i in 0..10 {
+            let msg = IgtlMessage::new(TransformMessage::translation(i as f32, 0.0, 0.0), "Tool")
+                .unwrap();
+            sender.send(&msg).await.unwrap();
+            let received: IgtlMessage<TransformMessage> = receiver.receive().await.unwrap();
+            assert_eq!(received.content, msg.content);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_disconnect_after_messages() {
+        let config = FaultConfig::new().with_disconnect_after_messages(1);
+        let (mut sender, mut receiver) = faulty_pair(config);
+        sender.send(&status("First")).await.unwrap();
+        let err = sender.send(&status("Second")).await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == io::ErrorKind::BrokenPipe));
+
+        receiver.receive::<StatusMessage>().await.unwrap();
+        let err = receiver.receive::<StatusMessage>().await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[tokio::test]
+    async fn test_truncation_disconnects_mid_message() {
+        let (mut sender, mut receiver) = faulty_pair(FaultConfig::new().with_truncation(1.0));
+        assert!(sender.send(&status("Cut")).await.is_err());
+        let stats = sender.get_ref().stats();
+        assert_eq!(stats.truncated_messages, 1);
+        assert!(stats.disconnected);
+
+        let err = receiver.receive::<StatusMessage>().await.unwrap_err();
+        assert!(matches!(err, IgtlError::Io(ref e) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[tokio::test]
+    async fn test_latency_and_bandwidth_delay_writes() {
+        let latency = Duration::from_millis(30);
+        let (mut sender, mut receiver) = faulty_pair(FaultConfig::new().with_latency(latency));
+        let start = Instant::now();
+        sender.send(&status("Late")).await.unwrap();
+        receiver.receive::<StatusMessage>().await.unwrap();
+        assert!(start.elapsed() >= latency);
+
+        // About 1 KB at 10 KB/s
+        let (mut sender, mut receiver) = faulty_pair(FaultConfig::new().with_bandwidth(10_000));
+        let msg = IgtlMessage::new(StatusMessage::ok(&"x".repeat(1000)), "Device").unwrap();
+        let start = Instant::now();
+        sender.send(&msg).await.unwrap();
+        receiver.receive::<StatusMessage>().await.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(80));
+    }
+
+    /// A server dropping each connection after one message drives client reconnection
+    #[tokio::test]
+    async fn test_reconnect_after_injected_disconnect() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            for i in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let config = FaultConfig::new().with_disconnect_after_messages(1);
+                let mut conn = AsyncIgtlStream::new(FaultyStream::new(stream, config));
+                conn.send(&status(&format!("Session {}", i))).await.unwrap();
+                assert!(conn.send(&status("Dropped")).await.is_err());
+            }
+        });
+
+        let reconnect = ReconnectConfig::with_max_attempts(5);
+        let mut client = ClientBuilder::new()
+            .tcp(addr.to_string())
+            .async_mode()
+            .with_reconnect(ReconnectConfig {
+                initial_delay: Duration::from_millis(10),
+                use_jitter: false,
+                ..reconnect
+            })
+            .build()
+            .await
+            .unwrap();
+
+        let first: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(first.content.status_string, "Session 0");
+        let second: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+        assert_eq!(second.content.status_string, "Session 1");
+        assert_eq!(client.reconnect_count(), 1);
+    }
+}
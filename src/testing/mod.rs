@@ -0,0 +1,564 @@
+//! Test harness for OpenIGTLink applications (requires the `testing` feature)
+//!
+//! Protocol interactions can be unit-tested without binding real ports:
+//!
+//! - [`memory_pair`] connects two [`MemoryConnection`]s through an in-memory pipe
+//! - [`MockServer`] runs a [`Script`] of expected and canned messages against a client,
+//!   in memory or on an ephemeral loopback port for clients built with
+//!   [`ClientBuilder`](crate::io::ClientBuilder)
+//! - [`ExpectMessage`] adds assertions such as
+//!   [`expect_message::<T>(device, timeout)`](ExpectMessage::expect_message) to every
+//!   async connection type
+//! - [`FaultyStream`] injects latency, corruption, truncation and disconnects into any
+//!   stream (see [`fault`])
+//!
+//! Assertions panic with a description of what was expected and what arrived, like
+//! `assert!`. Enable the module with:
+//!
+//! ```toml
+//! [dev-dependencies]
+//! openigtlink-rust = { version = "*", features = ["testing"] }
+//! ```
+//!
+//! # Examples
+//!
+//! ```
+//! use openigtlink_rust::protocol::message::IgtlMessage;
+//! use openigtlink_rust::protocol::types::{GetTransformMessage, TransformMessage};
+//! use openigtlink_rust::testing::{ExpectMessage, MockServer, Script};
+//! use std::time::Duration;
+//!
+//! # #[tokio::main(flavor = "current_thread")]
+//! # async fn main() -> Result<(), openigtlink_rust::error::IgtlError> {
+//! let script = Script::new()
+//!     .expect::<GetTransformMessage>("Tool")
+//!     .send(&IgtlMessage::new(TransformMessage::translation(1.0, 2.0, 3.0), "Tool")?);
+//! let (mut client, server) = MockServer::spawn(script);
+//!
+//! client.send(&IgtlMessage::new(GetTransformMessage, "Tool")?).await?;
+//! let reply = client
+//!     .expect_message::<TransformMessage>("Tool", Duration::from_secs(1))
+//!     .await;
+//! assert_eq!(reply.content.matrix[0][3], 1.0);
+//!
+//! let received = server.finish().await;
+//! assert_eq!(received.len(), 1);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod fault;
+
+pub use fault::{FaultConfig, FaultStats, FaultyStream};
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::io::{duplex, AsyncRead, AsyncWrite, AsyncWriteExt, DuplexStream};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::error::Result;
+use crate::io::unified_async_client::UnifiedAsyncClient;
+use crate::io::{
+    AsyncIgtlClient, AsyncIgtlConnection, AsyncIgtlReader, AsyncIgtlStream, WebSocketIgtlConnection,
+};
+use crate::protocol::message::{IgtlMessage, Message};
+use crate::protocol::AnyMessage;
+
+/// Buffer size of each direction of a [`memory_pair`]
+pub const DEFAULT_MEMORY_CAPACITY: usize = 1024 * 1024;
+
+/// Default deadline of each expectation in a [`Script`]
+pub const DEFAULT_SCRIPT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One end of an in-memory connection
+pub type MemoryConnection = AsyncIgtlStream<DuplexStream>;
+
+/// Create two connected in-memory endpoints, e.g. `(client, server)`
+///
+/// Each direction buffers up to [`DEFAULT_MEMORY_CAPACITY`] bytes; a sender blocks
+/// while the buffer is full, like a TCP socket.
+pub fn memory_pair() -> (MemoryConnection, MemoryConnection) {
+    memory_pair_with_capacity(DEFAULT_MEMORY_CAPACITY)
+}
+
+/// Create two connected in-memory endpoints buffering up to `capacity` bytes per direction
+pub fn memory_pair_with_capacity(capacity: usize) -> (MemoryConnection, MemoryConnection) {
+    let (a, b) = duplex(capacity);
+    (AsyncIgtlStream::new(a), AsyncIgtlStream::new(b))
+}
+
+/// Type name as sent on the wire, to compare with [`Message::message_type`]
+fn wire_type_name(msg: &AnyMessage) -> &str {
+    msg.header().type_name.as_str().unwrap_or("")
+}
+
+/// Describe a message for assertion failures, e.g. `TRANSFORM from "Tool"`
+fn describe(msg: &AnyMessage) -> String {
+    format!(
+        "{} from \"{}\"",
+        msg.message_type(),
+        msg.device_name().unwrap_or("")
+    )
+}
+
+// ============================================================================
+// Assertions
+// ============================================================================
+
+/// Assertions on received messages
+///
+/// Implemented for every async connection type of this crate. Each method panics
+/// when the expectation is not met.
+pub trait ExpectMessage {
+    /// Receive the next message of any type
+    fn next_message(&mut self) -> impl Future<Output = Result<AnyMessage>> + Send;
+
+    /// Wait up to `timeout` for the next message of any type
+    ///
+    /// # Panics
+    ///
+    /// If no message arrives in time or the receive fails.
+    fn expect_any(&mut self, timeout: Duration) -> impl Future<Output = AnyMessage> + Send
+    where
+        Self: Send,
+    {
+        async move {
+            match tokio::time::timeout(timeout, self.next_message()).await {
+                Ok(Ok(msg)) => msg,
+                Ok(Err(e)) => panic!("expected a message, but receive failed: {}", e),
+                Err(_) => panic!("expected a message within {:?}, got none", timeout),
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the next message, which must be a `T` from `device`
+    ///
+    /// # Panics
+    ///
+    /// If no message arrives in time, the receive fails, or the next message has
+    /// another type or device name.
+    fn expect_message<T: Message>(
+        &mut self,
+        device: &str,
+        timeout: Duration,
+    ) -> impl Future<Output = IgtlMessage<T>> + Send
+    where
+        Self: Send,
+    {
+        async move {
+            let msg = self.expect_any(timeout).await;
+            let matches =
+                wire_type_name(&msg) == T::message_type() && msg.device_name().ok() == Some(device);
+            assert!(
+                matches,
+                "expected {} from \"{}\", got {}",
+                T::message_type(),
+                device,
+                describe(&msg)
+            );
+            msg.encode()
+                .and_then(|data| IgtlMessage::decode_with_options(&data, false))
+                .unwrap_or_else(|e| panic!("failed to decode {}: {}", describe(&msg), e))
+        }
+    }
+
+    /// Assert that no message arrives for `duration`
+    ///
+    /// # Panics
+    ///
+    /// If a message arrives. A closed or failing connection counts as silent.
+    fn expect_silence(&mut self, duration: Duration) -> impl Future<Output = ()> + Send
+    where
+        Self: Send,
+    {
+        async move {
+            if let Ok(Ok(msg)) = tokio::time::timeout(duration, self.next_message()).await {
+                panic!(
+                    "expected no message for {:?}, got {}",
+                    duration,
+                    describe(&msg)
+                );
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin + Send> ExpectMessage for AsyncIgtlStream<S> {
+    fn next_message(&mut self) -> impl Future<Output = Result<AnyMessage>> + Send {
+        self.receive_any()
+    }
+}
+
+impl<R: AsyncRead + Unpin + Send> ExpectMessage for AsyncIgtlReader<R> {
+    fn next_message(&mut self) -> impl Future<Output = Result<AnyMessage>> + Send {
+        self.receive_any()
+    }
+}
+
+impl ExpectMessage for AsyncIgtlConnection {
+    fn next_message(&mut self) -> impl Future<Output = Result<AnyMessage>> + Send {
+        self.receive_any()
+    }
+}
+
+impl ExpectMessage for AsyncIgtlClient {
+    fn next_message(&mut self) -> impl Future<Output = Result<AnyMessage>> + Send {
+        self.receive_any()
+    }
+}
+
+impl ExpectMessage for UnifiedAsyncClient {
+    fn next_message(&mut self) -> impl Future<Output = Result<AnyMessage>> + Send {
+        self.receive_any()
+    }
+}
+
+impl ExpectMessage for WebSocketIgtlConnection {
+    fn next_message(&mut self) -> impl Future<Output = Result<AnyMessage>> + Send {
+        self.receive_any()
+    }
+}
+
+// ============================================================================
+// Scripted mock server
+// ============================================================================
+
+/// One step of a [`Script`]
+#[derive(Debug, Clone)]
+enum Step {
+    /// Receive a message, optionally checking its type and device name
+    Expect {
+        type_name: Option<&'static str>,
+        device: Option<String>,
+    },
+    /// Send an encoded message
+    Send(Vec<u8>),
+    /// Pause
+    Sleep(Duration),
+    /// Shut down the connection and end the script
+    Close,
+}
+
+/// Sequence of steps played by a [`MockServer`]
+///
+/// Steps run in order. When the last step completes the connection is dropped, which
+/// the client sees as a closed connection.
+///
+/// # Examples
+///
+/// ```
+/// use openigtlink_rust::protocol::message::IgtlMessage;
+/// use openigtlink_rust::protocol::types::{StatusMessage, TransformMessage};
+/// use openigtlink_rust::testing::Script;
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), openigtlink_rust::error::IgtlError> {
+/// let script = Script::new()
+///     .send(&IgtlMessage::new(StatusMessage::ok("Ready"), "Server")?)
+///     .expect::<TransformMessage>("Tool")
+///     .expect_any()
+///     .sleep(Duration::from_millis(10))
+///     .close();
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Script {
+    steps: Vec<Step>,
+    timeout: Duration,
+}
+
+impl Default for Script {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Script {
+    /// Create an empty script with the [`DEFAULT_SCRIPT_TIMEOUT`]
+    pub fn new() -> Self {
+        Script {
+            steps: Vec::new(),
+            timeout: DEFAULT_SCRIPT_TIMEOUT,
+        }
+    }
+
+    /// Set the deadline of each expectation
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Expect a `T` from `device` next
+    pub fn expect<T: Message>(mut self, device: impl Into<String>) -> Self {
+        self.steps.push(Step::Expect {
+            type_name: Some(T::message_type()),
+            device: Some(device.into()),
+        });
+        self
+    }
+
+    /// Expect a message of any type next
+    pub fn expect_any(mut self) -> Self {
+        self.steps.push(Step::Expect {
+            type_name: None,
+            device: None,
+        });
+        self
+    }
+
+    /// Send a message
+    ///
+    /// # Panics
+    ///
+    /// If the message cannot be encoded.
+    pub fn send<T: Message>(mut self, msg: &IgtlMessage<T>) -> Self {
+        let data = msg
+            .encode()
+            .unwrap_or_else(|e| panic!("scripted message cannot be encoded: {}", e));
+        self.steps.push(Step::Send(data));
+        self
+    }
+
+    /// Send a dynamically typed message
+    ///
+    /// # Panics
+    ///
+    /// If the message cannot be encoded.
+    pub fn send_any(mut self, msg: &AnyMessage) -> Self {
+        let data = msg
+            .encode()
+            .unwrap_or_else(|e| panic!("scripted message cannot be encoded: {}", e));
+        self.steps.push(Step::Send(data));
+        self
+    }
+
+    /// Pause before the next step
+    pub fn sleep(mut self, duration: Duration) -> Self {
+        self.steps.push(Step::Sleep(duration));
+        self
+    }
+
+    /// Shut down the connection gracefully and end the script
+    pub fn close(mut self) -> Self {
+        self.steps.push(Step::Close);
+        self
+    }
+
+    /// Play the script on `conn`, returning the messages received by expectations
+    ///
+    /// # Panics
+    ///
+    /// If an expectation is not met or the connection fails.
+    pub async fn run<S>(self, mut conn: AsyncIgtlStream<S>) -> Vec<AnyMessage>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send,
+    {
+        let mut received = Vec::new();
+        for (index, step) in self.steps.into_iter().enumerate() {
+            match step {
+                Step::Expect { type_name, device } => {
+                    let msg = conn.expect_any(self.timeout).await;
+                    if let Some(type_name) = type_name {
+                        assert_eq!(
+                            wire_type_name(&msg),
+                            type_name,
+                            "script step {}: expected {}, got {}",
+                            index,
+                            type_name,
+                            describe(&msg)
+                        );
+                    }
+                    if let Some(device) = device {
+                        assert_eq!(
+                            msg.device_name().unwrap_or(""),
+                            device,
+                            "script step {}: expected device \"{}\", got {}",
+                            index,
+                            device,
+                            describe(&msg)
+                        );
+                    }
+                    received.push(msg);
+                }
+                Step::Send(data) => {
+                    if let Err(e) = conn.write_frame(&data).await {
+                        panic!("script step {}: send failed: {}", index, e);
+                    }
+                }
+                Step::Sleep(duration) => tokio::time::sleep(duration).await,
+                Step::Close => {
+                    let _ = conn.get_mut().shutdown().await;
+                    break;
+                }
+            }
+        }
+        received
+    }
+}
+
+/// Mock OpenIGTLink server playing a [`Script`] against one client
+///
+/// Call [`finish`](Self::finish) at the end of the test: it waits for the script and
+/// re-raises its assertion failures in the test task.
+pub struct MockServer {
+    task: JoinHandle<Vec<AnyMessage>>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl MockServer {
+    /// Play `script` over an in-memory connection, returning the client end
+    pub fn spawn(script: Script) -> (MemoryConnection, MockServer) {
+        let (client, server) = memory_pair();
+        let task = tokio::spawn(script.run(server));
+        (
+            client,
+            MockServer {
+                task,
+                local_addr: None,
+            },
+        )
+    }
+
+    /// Play `script` for the first client connecting to an ephemeral loopback TCP port
+    ///
+    /// For clients that need an address, e.g. built with
+    /// [`ClientBuilder`](crate::io::ClientBuilder). The port is chosen by the OS, so
+    /// parallel tests do not collide.
+    ///
+    /// # Errors
+    ///
+    /// - [`IgtlError::Io`](crate::error::IgtlError::Io) - Failed to bind
+    pub async fn spawn_tcp(script: Script) -> Result<MockServer> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let local_addr = listener.local_addr()?;
+        let task = tokio::spawn(async move {
+            let (stream, _) = listener
+                .accept()
+                .await
+                .unwrap_or_else(|e| panic!("mock server accept failed: {}", e));
+            script.run(AsyncIgtlStream::new(stream)).await
+        });
+        Ok(MockServer {
+            task,
+            local_addr: Some(local_addr),
+        })
+    }
+
+    /// Address to connect to, for servers created with [`spawn_tcp`](Self::spawn_tcp)
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// Wait for the script to complete and return the messages it received
+    ///
+    /// # Panics
+    ///
+    /// Re-raises the panic of a failed expectation in the script.
+    pub async fn finish(self) -> Vec<AnyMessage> {
+        match self.task.await {
+            Ok(received) => received,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            Err(e) => panic!("mock server task failed: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::ClientBuilder;
+    use crate::protocol::types::{GetStatusMessage, StatusMessage, TransformMessage};
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[tokio::test]
+    async fn test_memory_pair_round_trip() {
+        let (mut client, mut server) = memory_pair();
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        client.send(&msg).await.unwrap();
+
+        let received = server
+            .expect_message::<TransformMessage>("Tool", TIMEOUT)
+            .await;
+        assert_eq!(received.content, msg.content);
+        server.expect_silence(Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "expected STATUS from \"Tool\", got TRANSFORM from \"Tool\"")]
+    async fn test_expect_message_wrong_type_panics() {
+        let (mut client, mut server) = memory_pair();
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap();
+        client.send(&msg).await.unwrap();
+        server
+            .expect_message::<StatusMessage>("Tool", TIMEOUT)
+            .await;
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "got none")]
+    async fn test_expect_any_times_out() {
+        let (_client, mut server) = memory_pair();
+        server.expect_any(Duration::from_millis(20)).await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_server_script() {
+        let script = Script::new()
+            .expect::<GetStatusMessage>("Device")
+            .send(&IgtlMessage::new(StatusMessage::ok("Ready"), "Device").unwrap())
+            .close();
+        let (mut client, server) = MockServer::spawn(script);
+
+        client
+            .send(&IgtlMessage::new(GetStatusMessage, "Device").unwrap())
+            .await
+            .unwrap();
+        let status = client
+            .expect_message::<StatusMessage>("Device", TIMEOUT)
+            .await;
+        assert_eq!(status.content.status_string, "Ready");
+        assert!(client.receive_any().await.is_err());
+
+        let received = server.finish().await;
+        assert_eq!(received[0].message_type(), "GET_STATUS");
+    }
+
+    #[tokio::test]
+    #[should_panic(expected = "script step 0: expected device \"Tool\"")]
+    async fn test_mock_server_reports_failed_expectation() {
+        let script = Script::new().expect::<TransformMessage>("Tool");
+        let (mut client, server) = MockServer::spawn(script);
+        let msg = IgtlMessage::new(TransformMessage::identity(), "Other").unwrap();
+        client.send(&msg).await.unwrap();
+        server.finish().await;
+    }
+
+    #[tokio::test]
+    async fn test_mock_server_over_tcp_with_builder_client() {
+        let script = Script::new()
+            .send(&IgtlMessage::new(StatusMessage::ok("Hello"), "Server").unwrap())
+            .expect::<TransformMessage>("Tool");
+        let server = MockServer::spawn_tcp(script).await.unwrap();
+
+        let mut client = ClientBuilder::new()
+            .tcp(server.local_addr().unwrap().to_string())
+            .async_mode()
+            .build()
+            .await
+            .unwrap();
+        client
+            .expect_message::<StatusMessage>("Server", TIMEOUT)
+            .await;
+        client
+            .send(&IgtlMessage::new(TransformMessage::identity(), "Tool").unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(server.finish().await.len(), 1);
+    }
+}
@@ -0,0 +1,89 @@
+//! Helpers shared by the integration tests
+//!
+//! Certificates are generated in-process with `rcgen`, mirroring the set written by
+//! `examples/generate_test_certs.sh`.
+
+// Each test binary uses only some of the helpers
+#![allow(dead_code)]
+
+use rcgen::{
+    BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+    KeyUsagePurpose,
+};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Generate a fresh certificate set (own CA, server and client keys) into `dir`
+///
+/// - `ca.pem`, `ca-key.pem` - test certificate authority
+/// - `cert.pem`, `key.pem` - server certificate (CN=localhost, SAN localhost/127.0.0.1)
+/// - `client.pem`, `client-key.pem` - client certificate (CN=igtl-client, SAN
+///   tracker.local)
+pub fn generate_certs(dir: &Path) {
+    std::fs::create_dir_all(dir).unwrap();
+
+    let ca_key = KeyPair::generate().unwrap();
+    let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    params
+        .distinguished_name
+        .push(DnType::OrganizationName, "OpenIGTLink Test");
+    params
+        .distinguished_name
+        .push(DnType::CommonName, "OpenIGTLink Test CA");
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    let ca = params.self_signed(&ca_key).unwrap();
+    std::fs::write(dir.join("ca.pem"), ca.pem()).unwrap();
+    std::fs::write(dir.join("ca-key.pem"), ca_key.serialize_pem()).unwrap();
+
+    // Issue a certificate signed by the CA
+    let issue = |name: &str, common_name: &str, sans: &[&str], usage| {
+        let key = KeyPair::generate().unwrap();
+        let sans = sans.iter().map(|san| san.to_string()).collect::<Vec<_>>();
+        let mut params = CertificateParams::new(sans).unwrap();
+        params
+            .distinguished_name
+            .push(DnType::OrganizationName, "Organization");
+        params
+            .distinguished_name
+            .push(DnType::CommonName, common_name);
+        params.key_usages = vec![
+            KeyUsagePurpose::DigitalSignature,
+            KeyUsagePurpose::KeyEncipherment,
+        ];
+        params.extended_key_usages = vec![usage];
+        let cert = params.signed_by(&key, &ca, &ca_key).unwrap();
+        std::fs::write(dir.join(format!("{name}.pem")), cert.pem()).unwrap();
+        std::fs::write(dir.join(format!("{name}-key.pem")), key.serialize_pem()).unwrap();
+    };
+    issue(
+        "server",
+        "localhost",
+        &["localhost", "127.0.0.1"],
+        ExtendedKeyUsagePurpose::ServerAuth,
+    );
+    // Kept as cert.pem / key.pem like the TLS examples expect
+    std::fs::rename(dir.join("server.pem"), dir.join("cert.pem")).unwrap();
+    std::fs::rename(dir.join("server-key.pem"), dir.join("key.pem")).unwrap();
+    issue(
+        "client",
+        "igtl-client",
+        &["tracker.local"],
+        ExtendedKeyUsagePurpose::ClientAuth,
+    );
+}
+
+/// Directory with certificates generated once per test binary
+pub fn cert_dir() -> &'static Path {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("igtl-certs-{}", std::process::id()));
+        generate_certs(&dir);
+        dir
+    })
+}
+
+/// Path of a generated certificate or key file
+pub fn cert(name: &str) -> PathBuf {
+    cert_dir().join(name)
+}
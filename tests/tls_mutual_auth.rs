@@ -0,0 +1,201 @@
+//! Integration tests for mutual TLS
+//!
+//! Certificates are generated in-process, see `common::generate_certs`.
+
+mod common;
+
+use common::cert;
+use openigtlink_rust::io::builder::ClientBuilder;
+use openigtlink_rust::io::tls::{ClientTlsConfig, ServerTlsConfig, SubjectAltName};
+use openigtlink_rust::io::{ConnectionEvent, ServerLimits, SyncTlsIgtlServer, TlsIgtlServer};
+use openigtlink_rust::protocol::message::IgtlMessage;
+use openigtlink_rust::protocol::types::StatusMessage;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+
+fn server_config() -> ServerTlsConfig {
+    ServerTlsConfig::new(cert("cert.pem"), cert("key.pem")).with_client_auth(cert("ca.pem"))
+}
+
+fn client_config() -> ClientTlsConfig {
+    ClientTlsConfig::new()
+        .with_ca(cert("ca.pem"))
+        .with_client_cert(cert("client.pem"), cert("client-key.pem"))
+}
+
+/// SHA-256 fingerprint of a PEM certificate, formatted like `openssl x509 -fingerprint`
+fn sha256_fingerprint(path: &Path) -> String {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(path).unwrap());
+    let der = rustls_pemfile::certs(&mut reader).next().unwrap().unwrap();
+    Sha256::digest(der.as_ref())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+#[tokio::test]
+async fn test_async_mutual_tls_exposes_client_identity() {
+    let server = TlsIgtlServer::bind_with_config("127.0.0.1:0", server_config().build().unwrap())
+        .await
+        .unwrap();
+    let port = server.local_addr().unwrap().port();
+
+    let server_task = tokio::spawn(async move {
+        let mut conn = server.accept().await.unwrap();
+        let identity = conn.peer_certificate().cloned().unwrap();
+        let msg: IgtlMessage<StatusMessage> = conn.receive().await.unwrap();
+        (identity, msg)
+    });
+
+    let mut client = ClientBuilder::new()
+        .tcp(format!("localhost:{}", port))
+        .async_mode()
+        .with_tls(client_config().build().unwrap())
+        .build()
+        .await
+        .unwrap();
+    let msg = IgtlMessage::new(StatusMessage::ok("authenticated"), "Tracker").unwrap();
+    client.send(&msg).await.unwrap();
+
+    let (identity, received) = server_task.await.unwrap();
+    assert_eq!(received.content.status_string, "authenticated");
+    assert!(identity.subject().contains("CN=igtl-client"));
+    assert_eq!(
+        identity.subject_alt_names(),
+        &[SubjectAltName::Dns("tracker.local".to_string())]
+    );
+    assert_eq!(
+        identity.fingerprint_hex(),
+        sha256_fingerprint(&cert("client.pem"))
+    );
+}
+
+#[tokio::test]
+async fn test_async_server_rejects_client_without_certificate() {
+    let server = TlsIgtlServer::bind_with_config("127.0.0.1:0", server_config().build().unwrap())
+        .await
+        .unwrap();
+    let port = server.local_addr().unwrap().port();
+    let mut events = server.subscribe_events();
+    let server_task = tokio::spawn(async move { server.accept().await.map(|_| ()) });
+
+    // Trusts the server, but presents no certificate
+    let config = ClientTlsConfig::new().with_ca(cert("ca.pem"));
+    let client = ClientBuilder::new()
+        .tcp(format!("localhost:{}", port))
+        .async_mode()
+        .with_tls(config.build().unwrap())
+        .build()
+        .await;
+
+    // The failed handshake is reported and the server keeps accepting
+    loop {
+        match events.recv().await.unwrap() {
+            ConnectionEvent::TlsHandshakeFailed { .. } => break,
+            _ => continue,
+        }
+    }
+    assert!(!server_task.is_finished());
+    server_task.abort();
+    // With TLS 1.3 the rejection reaches the client after its handshake completed
+    if let Ok(mut client) = client {
+        assert!(client.receive_any().await.is_err());
+    }
+}
+
+#[tokio::test]
+async fn test_async_server_serves_clients_while_a_handshake_stalls() {
+    let server = TlsIgtlServer::bind_with_config("127.0.0.1:0", server_config().build().unwrap())
+        .await
+        .unwrap()
+        .with_limits(ServerLimits::new().with_first_message_timeout(Duration::from_secs(1)));
+    let port = server.local_addr().unwrap().port();
+    let mut events = server.subscribe_events();
+
+    // Connects but never starts the TLS handshake
+    let _stalled = tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    let server = std::sync::Arc::new(server);
+    let server_task = tokio::spawn({
+        let server = server.clone();
+        async move {
+            let mut conn = server.accept().await.unwrap();
+            let msg: IgtlMessage<StatusMessage> = conn.receive().await.unwrap();
+            msg.content.status_string
+        }
+    });
+
+    let mut client = ClientBuilder::new()
+        .tcp(format!("localhost:{}", port))
+        .async_mode()
+        .with_tls(client_config().build().unwrap())
+        .build()
+        .await
+        .unwrap();
+    let msg = IgtlMessage::new(StatusMessage::ok("not blocked"), "Tracker").unwrap();
+    client.send(&msg).await.unwrap();
+    assert_eq!(server_task.await.unwrap(), "not blocked");
+
+    // The stalled handshake times out once accept runs again
+    let accept = tokio::time::timeout(Duration::from_secs(2), server.accept()).await;
+    assert!(accept.is_err());
+    let timed_out = std::iter::from_fn(|| events.try_recv().ok())
+        .any(|event| matches!(event, ConnectionEvent::TlsHandshakeFailed { .. }));
+    assert!(timed_out);
+}
+
+#[test]
+fn test_sync_mutual_tls_exposes_client_identity() {
+    let server =
+        SyncTlsIgtlServer::bind_with_config("127.0.0.1:0", server_config().build().unwrap())
+            .unwrap();
+    let port = server.local_addr().unwrap().port();
+
+    let handle = std::thread::spawn(move || {
+        let mut conn = server.accept().unwrap();
+        let subject = conn.peer_certificate().unwrap().subject().to_string();
+        let msg: IgtlMessage<StatusMessage> = conn.receive().unwrap();
+        conn.send(&IgtlMessage::new(msg.content, "Server").unwrap())
+            .unwrap();
+        subject
+    });
+
+    let mut client = ClientBuilder::new()
+        .tcp(format!("localhost:{}", port))
+        .sync()
+        .with_tls(client_config().build().unwrap())
+        .build()
+        .unwrap();
+    client
+        .send(&IgtlMessage::new(StatusMessage::ok("sync"), "Tracker").unwrap())
+        .unwrap();
+    let reply: IgtlMessage<StatusMessage> = client.receive().unwrap();
+
+    assert_eq!(reply.content.status_string, "sync");
+    assert!(handle.join().unwrap().contains("CN=igtl-client"));
+}
+
+#[test]
+fn test_server_without_client_auth_has_no_peer_certificate() {
+    let config = ServerTlsConfig::new(cert("cert.pem"), cert("key.pem"));
+    let server =
+        SyncTlsIgtlServer::bind_with_config("127.0.0.1:0", config.build().unwrap()).unwrap();
+    let port = server.local_addr().unwrap().port();
+    let handle = std::thread::spawn(move || server.accept().unwrap().peer_certificate().is_none());
+
+    let _client = ClientBuilder::new()
+        .tcp(format!("127.0.0.1:{}", port))
+        .sync()
+        .with_tls(
+            ClientTlsConfig::new()
+                .with_ca(cert("ca.pem"))
+                .build()
+                .unwrap(),
+        )
+        .build()
+        .unwrap();
+    assert!(handle.join().unwrap());
+}
@@ -0,0 +1,139 @@
+//! Integration tests for certificate hot-reload and pinning
+//!
+//! Certificates are generated in-process, see `common::generate_certs`.
+
+mod common;
+
+use common::generate_certs;
+use openigtlink_rust::io::builder::ClientBuilder;
+use openigtlink_rust::io::tls::{
+    CertificatePin, ClientTlsConfig, PeerCertificate, ServerTlsConfig,
+};
+use openigtlink_rust::io::{AsyncIgtlClient, TlsIgtlServer};
+use openigtlink_rust::protocol::message::IgtlMessage;
+use openigtlink_rust::protocol::types::StatusMessage;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::time::Duration;
+
+fn server_cert(dir: &Path) -> PeerCertificate {
+    let mut reader = std::io::BufReader::new(std::fs::File::open(dir.join("cert.pem")).unwrap());
+    let der = rustls_pemfile::certs(&mut reader).next().unwrap().unwrap();
+    PeerCertificate::from_der(der).unwrap()
+}
+
+fn install(from: &Path, to: &Path) {
+    std::fs::copy(from.join("cert.pem"), to.join("cert.pem")).unwrap();
+    std::fs::copy(from.join("key.pem"), to.join("key.pem")).unwrap();
+}
+
+async fn connect(
+    addr: SocketAddr,
+    pin: CertificatePin,
+) -> openigtlink_rust::error::Result<AsyncIgtlClient> {
+    let config = ClientTlsConfig::new().with_pin(pin).build()?;
+    ClientBuilder::new()
+        .tcp(addr.to_string())
+        .async_mode()
+        .with_tls(config)
+        .build()
+        .await
+}
+
+async fn echo(client: &mut AsyncIgtlClient, text: &str) -> String {
+    let msg = IgtlMessage::new(StatusMessage::ok(text), "Client").unwrap();
+    client.send(&msg).await.unwrap();
+    let reply: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+    reply.content.status_string
+}
+
+#[tokio::test]
+async fn test_certificate_rotation_keeps_sessions() {
+    let root = std::env::temp_dir().join(format!("igtl-rotation-{}", std::process::id()));
+    let (old, new, live) = (root.join("old"), root.join("new"), root.join("live"));
+    generate_certs(&old);
+    generate_certs(&new);
+    std::fs::create_dir_all(&live).unwrap();
+    install(&old, &live);
+
+    let config = ServerTlsConfig::new(live.join("cert.pem"), live.join("key.pem"));
+    let server = TlsIgtlServer::bind_reloadable("127.0.0.1:0", &config)
+        .await
+        .unwrap();
+    let addr = server.local_addr().unwrap();
+    let resolver = server.cert_resolver().unwrap().clone();
+    tokio::spawn(async move {
+        loop {
+            // Handshakes rejected by pinned clients are skipped by accept
+            let mut conn = server.accept().await.unwrap();
+            tokio::spawn(async move {
+                while let Ok(msg) = conn.receive::<StatusMessage>().await {
+                    let reply = IgtlMessage::new(msg.content, "Server").unwrap();
+                    conn.send(&reply).await.unwrap();
+                }
+            });
+        }
+    });
+
+    let old_pin = CertificatePin::certificate_hex(&server_cert(&old).fingerprint_hex()).unwrap();
+    let new_pin = CertificatePin::Spki(server_cert(&new).spki_fingerprint());
+
+    let mut session = connect(addr, old_pin).await.unwrap();
+    assert_eq!(echo(&mut session, "before").await, "before");
+    assert!(connect(addr, new_pin).await.is_err());
+
+    // Explicit reload: new handshakes use the new certificate
+    install(&new, &live);
+    resolver.reload().unwrap();
+    assert_eq!(echo(&mut session, "after").await, "after");
+    assert!(connect(addr, old_pin).await.is_err());
+    let mut rotated = connect(addr, new_pin).await.unwrap();
+    assert_eq!(echo(&mut rotated, "rotated").await, "rotated");
+
+    // Reload triggered by file modification
+    let watcher = resolver.watch(Duration::from_millis(20));
+    install(&old, &live);
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+    while connect(addr, old_pin).await.is_err() {
+        assert!(
+            tokio::time::Instant::now() < deadline,
+            "watcher did not reload"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    watcher.abort();
+
+    assert_eq!(echo(&mut session, "still alive").await, "still alive");
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_pin_self_signed_certificate_from_pem_file() {
+    let server = TlsIgtlServer::bind(
+        "127.0.0.1:0",
+        "examples/certs/cert.pem",
+        "examples/certs/key.pem",
+    )
+    .await
+    .unwrap();
+    let addr = server.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let mut conn = server.accept().await.unwrap();
+            tokio::spawn(async move {
+                while let Ok(msg) = conn.receive::<StatusMessage>().await {
+                    let reply = IgtlMessage::new(msg.content, "Server").unwrap();
+                    conn.send(&reply).await.unwrap();
+                }
+            });
+        }
+    });
+
+    let pin = CertificatePin::from_pem_file("examples/certs/cert.pem").unwrap();
+    let mut client = connect(addr, pin).await.unwrap();
+    assert_eq!(echo(&mut client, "pinned").await, "pinned");
+
+    let other = CertificatePin::certificate_hex(&"00".repeat(32)).unwrap();
+    assert!(connect(addr, other).await.is_err());
+    assert!(CertificatePin::from_pem_file("examples/certs/missing.pem").is_err());
+}
@@ -0,0 +1,110 @@
+//! Integration tests for the WebSocket transport
+//!
+//! Certificates are generated in-process, see `common::generate_certs`.
+
+mod common;
+
+use common::cert;
+use openigtlink_rust::io::builder::ClientBuilder;
+use openigtlink_rust::io::tls::{ClientTlsConfig, ServerTlsConfig};
+use openigtlink_rust::io::{AsyncIgtlServer, DeviceRepository, WebSocketIgtlServer};
+use openigtlink_rust::protocol::message::IgtlMessage;
+use openigtlink_rust::protocol::types::{GetTransformMessage, StatusMessage, TransformMessage};
+use openigtlink_rust::protocol::AnyMessage;
+
+#[tokio::test]
+async fn test_secure_websocket_round_trip() {
+    let config = ServerTlsConfig::new(cert("cert.pem"), cert("key.pem"))
+        .build()
+        .unwrap();
+    let server = WebSocketIgtlServer::bind_with_tls("127.0.0.1:0", config)
+        .await
+        .unwrap();
+    let port = server.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut conn = server.accept().await.unwrap();
+        let msg = conn.receive_any().await.unwrap();
+        conn.send_any(&msg).await.unwrap();
+    });
+
+    let tls = ClientTlsConfig::new()
+        .with_ca(cert("ca.pem"))
+        .build()
+        .unwrap();
+    let mut client = ClientBuilder::new()
+        .websocket(format!("wss://localhost:{}/igtl", port))
+        .with_tls(tls)
+        .build()
+        .await
+        .unwrap();
+
+    let msg = IgtlMessage::new(StatusMessage::ok("Secure"), "Browser").unwrap();
+    client.send(&msg).await.unwrap();
+    let reply: IgtlMessage<StatusMessage> = client.receive().await.unwrap();
+    assert_eq!(reply.content.status_string, "Secure");
+}
+
+#[tokio::test]
+async fn test_wss_without_tls_config_is_rejected() {
+    let result = ClientBuilder::new()
+        .websocket("wss://localhost:1/igtl")
+        .build()
+        .await;
+    assert!(result.is_err());
+}
+
+/// The same repository serves a TCP client and a WebSocket client
+#[tokio::test]
+async fn test_tcp_and_websocket_share_repository() {
+    let repository = DeviceRepository::new();
+    let tracked = IgtlMessage::new(TransformMessage::translation(1.0, 2.0, 3.0), "Tool").unwrap();
+    repository
+        .store(AnyMessage::Transform(tracked.clone()))
+        .unwrap();
+
+    let tcp = AsyncIgtlServer::bind("127.0.0.1:0").await.unwrap();
+    let tcp_addr = tcp.local_addr().unwrap();
+    let tcp_repository = repository.clone();
+    tokio::spawn(async move {
+        let mut conn = tcp.accept().await.unwrap();
+        while let Ok(query) = conn.receive_any().await {
+            for reply in tcp_repository.respond(&query).unwrap().unwrap_or_default() {
+                conn.send_any(&reply).await.unwrap();
+            }
+        }
+    });
+
+    let ws = WebSocketIgtlServer::bind("127.0.0.1:0").await.unwrap();
+    let ws_addr = ws.local_addr().unwrap();
+    let ws_repository = repository.clone();
+    tokio::spawn(async move {
+        let mut conn = ws.accept().await.unwrap();
+        while let Ok(query) = conn.receive_any().await {
+            for reply in ws_repository.respond(&query).unwrap().unwrap_or_default() {
+                conn.send_any(&reply).await.unwrap();
+            }
+        }
+    });
+
+    let query = IgtlMessage::new(GetTransformMessage, "Tool").unwrap();
+
+    let mut slicer = ClientBuilder::new()
+        .tcp(tcp_addr.to_string())
+        .async_mode()
+        .build()
+        .await
+        .unwrap();
+    slicer.send(&query).await.unwrap();
+    let over_tcp: IgtlMessage<TransformMessage> = slicer.receive().await.unwrap();
+
+    let mut browser = ClientBuilder::new()
+        .websocket(format!("ws://{}/igtl", ws_addr))
+        .build()
+        .await
+        .unwrap();
+    browser.send(&query).await.unwrap();
+    let over_ws: IgtlMessage<TransformMessage> = browser.receive().await.unwrap();
+
+    assert_eq!(over_tcp.content, tracked.content);
+    assert_eq!(over_ws.content, tracked.content);
+}